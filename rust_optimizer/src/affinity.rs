@@ -0,0 +1,66 @@
+//! Pinning rayon workers to cores, spread evenly over NUMA nodes.
+//!
+//! Pinning is as far as NUMA awareness goes. Linux places a page-cache page
+//! on the node of the thread that first faults it in, so a pinned worker
+//! reading a cold file gets node-local pages for the rest of its hash. Files
+//! already cached stay wherever an earlier reader put them, and nothing steers
+//! files to the worker on their node; local mmap is best-effort only.
+
+use core_affinity::CoreId;
+use std::path::Path;
+
+/// Parse a kernel CPU list such as `0-3,8-11` or `5`.
+pub fn parse_cpulist(list: &str) -> Vec<usize> {
+    let mut cpus = Vec::new();
+    for part in list.trim().split(',').filter(|p| !p.is_empty()) {
+        let (start, end) = part.split_once('-').unwrap_or((part, part));
+        if let (Ok(start), Ok(end)) = (start.trim().parse::<usize>(), end.trim().parse::<usize>()) {
+            cpus.extend(start..=end);
+        }
+    }
+    cpus
+}
+
+/// CPUs of each NUMA node, by node number. Empty where the kernel does not
+/// expose `/sys/devices/system/node`, i.e. anywhere but Linux.
+pub fn numa_nodes() -> Vec<Vec<usize>> {
+    let base = Path::new("/sys/devices/system/node");
+    let Ok(entries) = std::fs::read_dir(base) else {
+        return Vec::new();
+    };
+    let mut nodes: Vec<(usize, Vec<usize>)> = entries
+        .filter_map(|e| {
+            let name = e.ok()?.file_name();
+            let id = name.to_str()?.strip_prefix("node")?.parse().ok()?;
+            let list = std::fs::read_to_string(base.join(&name).join("cpulist")).ok()?;
+            Some((id, parse_cpulist(&list)))
+        })
+        .filter(|(_, cpus)| !cpus.is_empty())
+        .collect();
+    nodes.sort();
+    nodes.into_iter().map(|(_, cpus)| cpus).collect()
+}
+
+/// The cores this process may run on, ordered so that taking the first N
+/// alternates between nodes: node 0's first core, node 1's first core, then
+/// each node's second core, and so on. Fewer workers than cores thus still
+/// use every node's memory bandwidth.
+pub fn worker_cores(nodes: &[Vec<usize>]) -> Vec<CoreId> {
+    let available = core_affinity::get_core_ids().unwrap_or_default();
+    let allowed = |cpu: &usize| available.iter().any(|c| c.id == *cpu);
+    let per_node: Vec<Vec<usize>> =
+        nodes.iter().map(|cpus| cpus.iter().copied().filter(allowed).collect()).collect();
+    let mut cores = Vec::with_capacity(available.len());
+    for round in 0..per_node.iter().map(Vec::len).max().unwrap_or(0) {
+        cores.extend(per_node.iter().filter_map(|cpus| cpus.get(round)).map(|&id| CoreId { id }));
+    }
+    // CPUs in no node, or no node information at all
+    let rest: Vec<CoreId> = available.into_iter().filter(|c| !cores.contains(c)).collect();
+    cores.extend(rest);
+    cores
+}
+
+/// Pin the calling thread, e.g. from a rayon `start_handler`, to one core.
+pub fn pin(core: CoreId) -> bool {
+    core_affinity::set_for_current(core)
+}
@@ -0,0 +1,178 @@
+//! Hashing the members of `.tar`, `.tar.zst` and `.zip` archives without
+//! extracting them.
+
+use crate::errorlog;
+use crate::hashmode::{HashAlgorithm, MultiHasher, Update};
+use crate::process::{encode_digests, ProcessOptions, DEFAULT_DIGEST_LEN};
+use crate::report::{FileReport, FileStatus};
+use anyhow::{Context, Result};
+use memmap2::MmapOptions;
+use std::fs::File;
+use std::io::{self, Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use zip::{CompressionMethod, ZipArchive};
+
+/// Why a zip member that fails its check is reported as errored.
+const CORRUPT: &str =
+    "content does not match its CRC-32 and size in the directory; the archive is corrupt";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Tar,
+    TarZst,
+    Zip,
+}
+
+impl ArchiveKind {
+    /// Recognise an archive by its file name.
+    pub fn detect(path: &Path) -> Option<ArchiveKind> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+            Some(ArchiveKind::TarZst)
+        } else if name.ends_with(".tar") {
+            Some(ArchiveKind::Tar)
+        } else if name.ends_with(".zip") {
+            Some(ArchiveKind::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+/// One report per regular file in the archive, hashed as it streams past.
+/// Each report's `path` is the member path joined onto the archive's, with
+/// the bare member path in `member`. A corrupt archive yields the members
+/// read before the damage plus an `Errored` report for the archive itself;
+/// in a zip, whose directory lists every member up front, a member that
+/// fails its CRC gets the `Errored` report and the rest still hash.
+/// Digest length and hash mode come from `opts`; nothing else there applies.
+pub fn hash_members(path: &Path, kind: ArchiveKind, opts: &ProcessOptions) -> Vec<FileReport> {
+    let mut reports = Vec::new();
+    if let Err(e) = read_members(path, kind, opts, &mut reports) {
+        reports.push(FileReport {
+            status: FileStatus::Errored,
+            error: Some(format!("{:#}", e)),
+            errno: errorlog::errno(&e),
+            ..FileReport::new(path, path.metadata().map(|m| m.len()).unwrap_or(0))
+        });
+    }
+    reports
+}
+
+fn read_members(
+    path: &Path,
+    kind: ArchiveKind,
+    opts: &ProcessOptions,
+    reports: &mut Vec<FileReport>,
+) -> Result<()> {
+    let f = File::open(path)?;
+    let reader: Box<dyn Read> = match kind {
+        ArchiveKind::Tar => Box::new(io::BufReader::new(f)),
+        ArchiveKind::TarZst => Box::new(zstd::Decoder::new(f).context("starting zstd decoder")?),
+        ArchiveKind::Zip => return read_zip_members(path, &f, opts, reports),
+    };
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries().context("reading archive")? {
+        let mut entry = entry.context("reading archive entry")?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let start = Instant::now();
+        let member = entry.path().context("archive member path")?.into_owned();
+        let mut hasher = MultiHasher::new(opts.algorithm, opts.extra_algorithms, &opts.hash_mode);
+        let size = io::copy(&mut entry, &mut hasher)
+            .with_context(|| format!("reading member {:?}", member))?;
+        let digests = hasher.finalize_hex(opts.digest_len.unwrap_or(DEFAULT_DIGEST_LEN));
+        reports.push(member_report(path, member, size, start, Ok(digests), opts));
+    }
+    Ok(())
+}
+
+/// Zip members through a mapping of the archive: stored ones are hashed in
+/// place, deflated ones as they decompress, each checked against its CRC.
+fn read_zip_members(
+    path: &Path,
+    f: &File,
+    opts: &ProcessOptions,
+    reports: &mut Vec<FileReport>,
+) -> Result<()> {
+    let map = unsafe { MmapOptions::new().map(f) }.context("mapping archive")?;
+    let mut zip =
+        ZipArchive::new(Cursor::new(&map[..])).context("reading zip central directory")?;
+    for index in 0..zip.len() {
+        let start = Instant::now();
+        let name = zip.name_for_index(index).unwrap_or_default().to_string();
+        let size = match zip.by_index_raw(index) {
+            Ok(entry) if !entry.is_file() => continue,
+            Ok(entry) => entry.size(),
+            // reported with the error hashing it runs into
+            Err(_) => 0,
+        };
+        let hashed = hash_zip_member(&mut zip, index, &map, opts)
+            .with_context(|| format!("reading member {:?}", name));
+        reports.push(member_report(path, PathBuf::from(name), size, start, hashed, opts));
+    }
+    Ok(())
+}
+
+fn hash_zip_member(
+    zip: &mut ZipArchive<Cursor<&[u8]>>,
+    index: usize,
+    archive: &[u8],
+    opts: &ProcessOptions,
+) -> Result<Vec<(HashAlgorithm, String)>> {
+    let mut hasher = MultiHasher::new(opts.algorithm, opts.extra_algorithms, &opts.hash_mode);
+    let entry = zip.by_index_raw(index)?;
+    anyhow::ensure!(!entry.encrypted(), "member is encrypted");
+    let expected = entry.size();
+    let size = match entry.compression() {
+        CompressionMethod::Stored => {
+            let at = entry.data_start() as usize;
+            let data = at
+                .checked_add(entry.compressed_size() as usize)
+                .and_then(|end| archive.get(at..end))
+                .context("member runs past the end of the archive")?;
+            anyhow::ensure!(crc32fast::hash(data) == entry.crc32(), "{}", CORRUPT);
+            hasher.update_parallel(data);
+            data.len() as u64
+        }
+        CompressionMethod::Deflated => {
+            drop(entry);
+            // the crate checks the CRC-32 once the member is read to its end
+            let mut inflated = zip.by_index(index)?;
+            io::copy(&mut inflated, &mut hasher).context("inflating")?
+        }
+        method => anyhow::bail!("unsupported compression method {}", method),
+    };
+    anyhow::ensure!(size == expected, "{}", CORRUPT);
+    Ok(hasher.finalize_hex(opts.digest_len.unwrap_or(DEFAULT_DIGEST_LEN)))
+}
+
+/// The report of archive member `member`, of `size` bytes uncompressed:
+/// its digests, or why it could not be read.
+fn member_report(
+    path: &Path,
+    member: PathBuf,
+    size: u64,
+    start: Instant,
+    hashed: Result<Vec<(HashAlgorithm, String)>>,
+    opts: &ProcessOptions,
+) -> FileReport {
+    let (digests, error) = match hashed {
+        Ok(digests) => (digests, None),
+        Err(e) => (Vec::new(), Some(e)),
+    };
+    let (hash_str, hashes) = encode_digests(&digests, opts.encoding);
+    let whole = path.join(&member);
+    FileReport {
+        member: Some(member),
+        hash_str,
+        hashes,
+        elapsed_ms: start.elapsed().as_millis(),
+        status: if error.is_some() { FileStatus::Errored } else { FileStatus::Hashed },
+        errno: error.as_ref().and_then(errorlog::errno),
+        error: error.map(|e| format!("{:#}", e)),
+        ..FileReport::new(whole, size)
+    }
+}
@@ -0,0 +1,69 @@
+//! Throughput measurements on synthetic data for the `bench` subcommand, as a
+//! baseline to compare scan rates against.
+
+use crate::report::Units;
+use anyhow::Result;
+use std::time::{Duration, Instant};
+
+/// One timed operation over `bytes` of input.
+#[derive(Debug, Clone)]
+pub struct Measurement {
+    pub name: String,
+    pub bytes: u64,
+    /// Fastest of the rounds run
+    pub elapsed: Duration,
+}
+
+impl Measurement {
+    /// Throughput in `units.mega()` per second.
+    pub fn mega_per_sec(&self, units: Units) -> f64 {
+        self.bytes as f64 / units.mega_bytes() / self.elapsed.as_secs_f64().max(1e-9)
+    }
+}
+
+/// Deterministic incompressible bytes (xorshift64*), so runs are comparable.
+pub fn synthetic_data(len: usize, mut seed: u64) -> Vec<u8> {
+    seed |= 1;
+    let mut out = Vec::with_capacity(len + 8);
+    while out.len() < len {
+        seed ^= seed >> 12;
+        seed ^= seed << 25;
+        seed ^= seed >> 27;
+        out.extend_from_slice(&seed.wrapping_mul(0x2545_f491_4f6c_dd1d).to_le_bytes());
+    }
+    out.truncate(len);
+    out
+}
+
+/// Run `op` `rounds` times and keep the fastest, which is the least disturbed
+/// by other load on the machine.
+pub fn best_of(
+    name: &str,
+    bytes: u64,
+    rounds: usize,
+    op: impl FnMut() -> Result<()>,
+) -> Result<Measurement> {
+    best_of_prepared(name, bytes, rounds, || Ok(()), op)
+}
+
+/// `best_of`, running the untimed `prepare` before every round.
+pub fn best_of_prepared(
+    name: &str,
+    bytes: u64,
+    rounds: usize,
+    mut prepare: impl FnMut() -> Result<()>,
+    mut op: impl FnMut() -> Result<()>,
+) -> Result<Measurement> {
+    let mut best = Duration::MAX;
+    for _ in 0..rounds.max(1) {
+        prepare()?;
+        let start = Instant::now();
+        op()?;
+        best = best.min(start.elapsed());
+    }
+    Ok(Measurement {
+        name: name.to_string(),
+        bytes,
+        elapsed: best,
+    })
+}
@@ -0,0 +1,69 @@
+//! Byte budget bounding how much file data is memory-mapped at once.
+
+use std::sync::{Condvar, Mutex, MutexGuard};
+
+/// Counting semaphore measured in bytes. Workers reserve a file's size before
+/// mapping it and give it back once hashing is done.
+pub struct MemoryBudget {
+    capacity: u64,
+    available: Mutex<u64>,
+    freed: Condvar,
+    // files bigger than the whole budget take turns through this lock
+    oversized: Mutex<()>,
+}
+
+/// Bytes reserved from a `MemoryBudget`, returned on drop.
+pub struct BudgetPermit<'a> {
+    budget: &'a MemoryBudget,
+    bytes: u64,
+}
+
+impl MemoryBudget {
+    pub fn new(capacity: u64) -> Self {
+        Self {
+            capacity,
+            available: Mutex::new(capacity),
+            freed: Condvar::new(),
+            oversized: Mutex::new(()),
+        }
+    }
+
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    /// Whether a file of `size` bytes can ever be mapped within the budget.
+    pub fn fits(&self, size: u64) -> bool {
+        size <= self.capacity
+    }
+
+    /// Reserve `bytes` (clamped to the capacity), blocking until enough is free.
+    pub fn acquire(&self, bytes: u64) -> BudgetPermit<'_> {
+        let bytes = bytes.min(self.capacity);
+        let mut available = self.available.lock().unwrap();
+        while *available < bytes {
+            available = self.freed.wait(available).unwrap();
+        }
+        *available -= bytes;
+        BudgetPermit {
+            budget: self,
+            bytes,
+        }
+    }
+
+    /// Hold this while processing a file that does not fit, so only one
+    /// such file is in flight at a time.
+    pub fn serialize_oversized(&self) -> MutexGuard<'_, ()> {
+        self.oversized.lock().unwrap()
+    }
+}
+
+impl Drop for BudgetPermit<'_> {
+    fn drop(&mut self) {
+        if self.bytes == 0 {
+            return;
+        }
+        *self.budget.available.lock().unwrap() += self.bytes;
+        self.budget.freed.notify_all();
+    }
+}
@@ -0,0 +1,58 @@
+//! `b3sum`/`sha256sum`-compatible checksum lines: `<hex>  <path>`.
+//!
+//! Names containing a backslash, newline or carriage return are escaped the
+//! way coreutils does it: the line gets a leading `\` and those characters
+//! are written as `\\`, `\n` and `\r`.
+
+use std::path::{Path, PathBuf};
+
+/// Render one checksum line (without the trailing newline).
+pub fn format_line(hex: &str, path: &Path) -> String {
+    let name = path.to_string_lossy();
+    if name.contains(['\\', '\n', '\r']) {
+        let escaped = name
+            .replace('\\', "\\\\")
+            .replace('\n', "\\n")
+            .replace('\r', "\\r");
+        format!("\\{}  {}", hex, escaped)
+    } else {
+        format!("{}  {}", hex, name)
+    }
+}
+
+/// Parse a checksum line into `(hex, path)`. Accepts both the text (`  `) and
+/// binary (` *`) separators. Returns `None` for malformed lines.
+pub fn parse_line(line: &str) -> Option<(String, PathBuf)> {
+    let (escaped, line) = match line.strip_prefix('\\') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    let (hex, rest) = line.split_once(' ')?;
+    if hex.is_empty() || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let name = rest.strip_prefix([' ', '*'])?;
+    if name.is_empty() {
+        return None;
+    }
+    let name = if escaped { unescape(name)? } else { name.to_string() };
+    Some((hex.to_ascii_lowercase(), PathBuf::from(name)))
+}
+
+fn unescape(name: &str) -> Option<String> {
+    let mut out = String::with_capacity(name.len());
+    let mut chars = name.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next()? {
+            '\\' => out.push('\\'),
+            'n' => out.push('\n'),
+            'r' => out.push('\r'),
+            _ => return None,
+        }
+    }
+    Some(out)
+}
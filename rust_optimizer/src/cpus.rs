@@ -0,0 +1,103 @@
+//! How many workers a run gets by default: one per physical core, but no
+//! more than the CPU quota of the cgroup the process runs in. Inside a
+//! container `num_cpus` sees every core of the host, and a worker per core
+//! against a quota of two CPUs only adds throttling.
+
+use anyhow::Context;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// `--jobs`: a worker count, or `auto` for `default_jobs`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Jobs {
+    Auto,
+    Count(usize),
+}
+
+impl Jobs {
+    pub fn resolve(self) -> usize {
+        match self {
+            Jobs::Auto => default_jobs(),
+            Jobs::Count(n) => n,
+        }
+    }
+}
+
+impl FromStr for Jobs {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(Jobs::Auto);
+        }
+        match s.parse::<usize>().context("expected a number of threads or `auto`")? {
+            0 => anyhow::bail!("--jobs must be at least 1"),
+            n => Ok(Jobs::Count(n)),
+        }
+    }
+}
+
+/// Physical cores, capped at the cgroup CPU quota rounded up, and at least 1.
+pub fn default_jobs() -> usize {
+    let physical = num_cpus::get_physical().max(1);
+    match cgroup_quota() {
+        Some(quota) => physical.min(quota.ceil() as usize).max(1),
+        None => physical,
+    }
+}
+
+/// CPUs the cgroup may use, e.g. `1.5`; `None` when unlimited or unknown.
+pub fn cgroup_quota() -> Option<f64> {
+    let cgroups = std::fs::read_to_string("/proc/self/cgroup").ok()?;
+    quota_files(&cgroups, Path::new("/sys/fs/cgroup")).into_iter().find_map(|file| {
+        let text = std::fs::read_to_string(&file).ok()?;
+        if file.ends_with("cpu.max") {
+            parse_cpu_max(&text)
+        } else {
+            let period = std::fs::read_to_string(file.with_file_name("cpu.cfs_period_us")).ok()?;
+            parse_cfs(&text, &period)
+        }
+    })
+}
+
+/// Where the quota may be, most specific first: the process's own cgroup
+/// under v2 (`cpu.max`) and v1 (`cpu.cfs_quota_us`), then the hierarchy
+/// root, which is what a container whose cgroup namespace hides its path sees.
+pub fn quota_files(proc_cgroup: &str, mount: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for line in proc_cgroup.lines() {
+        let mut fields = line.splitn(3, ':');
+        let (Some(_), Some(controllers), Some(path)) = (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        let rel = path.trim_start_matches('/');
+        if controllers.is_empty() {
+            files.push(mount.join(rel).join("cpu.max"));
+            files.push(mount.join("cpu.max"));
+        } else if controllers.split(',').any(|c| c == "cpu") {
+            for dir in [mount.join("cpu"), mount.join(controllers)] {
+                files.push(dir.join(rel).join("cpu.cfs_quota_us"));
+                files.push(dir.join("cpu.cfs_quota_us"));
+            }
+        }
+    }
+    files.dedup();
+    files
+}
+
+/// cgroup v2 `cpu.max`: `"<quota> <period>"` in microseconds, or `"max ..."`.
+pub fn parse_cpu_max(text: &str) -> Option<f64> {
+    let mut fields = text.split_whitespace();
+    let quota = fields.next()?;
+    let period = fields.next().unwrap_or("100000");
+    parse_cfs(quota, period)
+}
+
+/// cgroup v1 `cpu.cfs_quota_us` and `cpu.cfs_period_us`; a quota of `-1`
+/// (or `max`) means no limit.
+pub fn parse_cfs(quota: &str, period: &str) -> Option<f64> {
+    let quota: i64 = quota.trim().parse().ok()?;
+    let period: i64 = period.trim().parse().ok()?;
+    (quota > 0 && period > 0).then(|| quota as f64 / period as f64)
+}
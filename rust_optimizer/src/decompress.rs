@@ -0,0 +1,98 @@
+//! `--decompress`: hash the logical content of gzip and zstd files, so a
+//! compressed copy of a model hashes the same as an uncompressed one. The
+//! stream is decoded as it is hashed; nothing is buffered beyond the
+//! decoders' own windows.
+
+use crate::hashmode::{HashAlgorithm, MultiHasher};
+use anyhow::{Context, Result};
+use flate2::read::MultiGzDecoder;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+
+/// Which files to decompress before hashing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Decompress {
+    /// Hash the bytes on disk
+    #[default]
+    None,
+    /// Decode every file as gzip; any other file is an error
+    Gzip,
+    /// Decode every file as zstd; any other file is an error
+    Zstd,
+    /// Decode files that start with a gzip or zstd magic number
+    Auto,
+}
+
+/// A compression format a file was decoded from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Codec {
+    Gzip,
+    Zstd,
+}
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+impl Codec {
+    /// The codec whose magic number `head` starts with.
+    pub fn detect(head: &[u8]) -> Option<Codec> {
+        if head.starts_with(&GZIP_MAGIC) {
+            Some(Codec::Gzip)
+        } else if head.starts_with(&ZSTD_MAGIC) {
+            Some(Codec::Zstd)
+        } else {
+            None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Codec::Gzip => "gzip",
+            Codec::Zstd => "zstd",
+        }
+    }
+}
+
+impl Decompress {
+    /// How to decode a stream starting with `head`; `None` hashes it as it is.
+    pub fn codec(self, head: &[u8]) -> Option<Codec> {
+        match self {
+            Decompress::None => None,
+            Decompress::Gzip => Some(Codec::Gzip),
+            Decompress::Zstd => Some(Codec::Zstd),
+            Decompress::Auto => Codec::detect(head),
+        }
+    }
+
+    /// `codec` for a file, sniffing its first bytes only for `Auto`. Leaves
+    /// `f` at its start.
+    pub fn codec_for(self, f: &mut File) -> io::Result<Option<Codec>> {
+        if self != Decompress::Auto {
+            return Ok(self.codec(&[]));
+        }
+        let mut head = Vec::with_capacity(ZSTD_MAGIC.len());
+        f.by_ref().take(ZSTD_MAGIC.len() as u64).read_to_end(&mut head)?;
+        f.seek(SeekFrom::Start(0))?;
+        Ok(self.codec(&head))
+    }
+}
+
+/// Decode `input` as `codec` straight into `hasher`. Returns its hex digests
+/// and the decompressed length; a corrupt or truncated stream is an error.
+pub fn hash_decoded<R: Read>(
+    input: R,
+    codec: Codec,
+    mut hasher: MultiHasher,
+    digest_len: usize,
+) -> Result<(Vec<(HashAlgorithm, String)>, u64)> {
+    let mut decoder: Box<dyn Read> = match codec {
+        Codec::Gzip => Box::new(MultiGzDecoder::new(BufReader::new(input))),
+        Codec::Zstd => Box::new(zstd::Decoder::new(input).context("starting zstd decoder")?),
+    };
+    let logical = io::copy(&mut decoder, &mut hasher)
+        .with_context(|| format!("decompressing {} stream", codec.name()))?;
+    Ok((hasher.finalize_hex(digest_len), logical))
+}
@@ -0,0 +1,186 @@
+//! Replacing duplicate files with links to one kept copy, or deleting them.
+
+use crate::dupes::DuplicateGroup;
+use crate::symlinks::create_file_symlink;
+use anyhow::{Context, Result};
+use std::fs::{self, File};
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+/// What to do with every copy after the first in a duplicate group.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum DedupAction {
+    /// Only list the duplicates
+    #[default]
+    Report,
+    /// Replace copies with hard links to the kept file
+    Hardlink,
+    /// Replace copies with symlinks to the kept file
+    Symlink,
+    /// Delete the copies
+    Delete,
+}
+
+impl DedupAction {
+    fn verb(self, applied: bool) -> &'static str {
+        match (self, applied) {
+            (DedupAction::Report, _) => "duplicate of",
+            (DedupAction::Hardlink, true) => "hardlinked to",
+            (DedupAction::Hardlink, false) => "would hardlink to",
+            (DedupAction::Symlink, true) => "symlinked to",
+            (DedupAction::Symlink, false) => "would symlink to",
+            (DedupAction::Delete, true) => "deleted, kept",
+            (DedupAction::Delete, false) => "would delete, keeping",
+        }
+    }
+}
+
+/// One copy handled by `apply`.
+pub struct DedupOutcome {
+    pub path: PathBuf,
+    pub kept: PathBuf,
+    pub bytes: u64,
+    pub result: Result<DedupStatus>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DedupStatus {
+    /// Dry run, or `Report`: nothing was touched
+    Planned,
+    Applied,
+    /// Already the same file on disk, nothing to reclaim
+    AlreadyLinked,
+}
+
+impl DedupOutcome {
+    /// `<path>: <what happened> <kept>`, or the error.
+    pub fn describe(&self, action: DedupAction) -> String {
+        match &self.result {
+            Ok(DedupStatus::AlreadyLinked) => {
+                format!("{}: already linked to {}", self.path.display(), self.kept.display())
+            }
+            Ok(status) => format!(
+                "{}: {} {}",
+                self.path.display(),
+                action.verb(*status == DedupStatus::Applied),
+                self.kept.display()
+            ),
+            Err(e) => format!("{}: FAILED: {:#}", self.path.display(), e),
+        }
+    }
+}
+
+/// Handle every extra copy in `groups`. Nothing on disk changes unless
+/// `confirm` is set. Before changing a file its contents are checked against
+/// the kept copy: by size always, byte for byte with `paranoid`.
+pub fn apply(
+    groups: &[DuplicateGroup],
+    action: DedupAction,
+    confirm: bool,
+    paranoid: bool,
+) -> Vec<DedupOutcome> {
+    let mut outcomes = Vec::new();
+    for g in groups {
+        let (kept, copies) = g.paths.split_first().expect("duplicate groups have two or more paths");
+        for path in copies {
+            let result = if same_file(kept, path) {
+                Ok(DedupStatus::AlreadyLinked)
+            } else if action == DedupAction::Report || !confirm {
+                Ok(DedupStatus::Planned)
+            } else {
+                verify(kept, path, g.size, paranoid)
+                    .and_then(|()| replace(kept, path, action))
+                    .map(|()| DedupStatus::Applied)
+            };
+            outcomes.push(DedupOutcome {
+                path: path.clone(),
+                kept: kept.clone(),
+                bytes: g.size,
+                result,
+            });
+        }
+    }
+    outcomes
+}
+
+/// Both paths name the same inode, so there is nothing to reclaim.
+#[cfg(unix)]
+fn same_file(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (fs::metadata(a), fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn same_file(a: &Path, b: &Path) -> bool {
+    matches!((a.canonicalize(), b.canonicalize()), (Ok(a), Ok(b)) if a == b)
+}
+
+/// The scan hashed both files, but they may have changed since.
+fn verify(kept: &Path, copy: &Path, size: u64, paranoid: bool) -> Result<()> {
+    for p in [kept, copy] {
+        let len = fs::metadata(p).with_context(|| format!("reading metadata of {:?}", p))?.len();
+        if len != size {
+            anyhow::bail!("{:?} changed size since it was hashed", p);
+        }
+    }
+    if paranoid && !contents_equal(kept, copy)? {
+        anyhow::bail!("contents differ from {:?} despite equal hashes", kept);
+    }
+    Ok(())
+}
+
+fn contents_equal(a: &Path, b: &Path) -> Result<bool> {
+    const CHUNK: usize = 1 << 20;
+    let open = |p: &Path| -> Result<BufReader<File>> {
+        let f = File::open(p).with_context(|| format!("opening {:?}", p))?;
+        Ok(BufReader::with_capacity(CHUNK, f))
+    };
+    let (mut ra, mut rb) = (open(a)?, open(b)?);
+    let (mut ba, mut bb) = (vec![0u8; CHUNK], vec![0u8; CHUNK]);
+    loop {
+        let na = read_full(&mut ra, &mut ba)?;
+        let nb = read_full(&mut rb, &mut bb)?;
+        if na != nb || ba[..na] != bb[..nb] {
+            return Ok(false);
+        }
+        if na == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+/// Fill `buf` unless the reader ends first; returns how much was read.
+fn read_full(r: &mut impl Read, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match r.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+/// Swap `copy` for a link to `kept` (or remove it). Links are created next
+/// to `copy` and renamed over it, so `copy` is never missing.
+fn replace(kept: &Path, copy: &Path, action: DedupAction) -> Result<()> {
+    if action == DedupAction::Delete {
+        return fs::remove_file(copy).with_context(|| format!("deleting {:?}", copy));
+    }
+    let name = copy.file_name().context("duplicate path has no file name")?;
+    let tmp = copy.with_file_name(format!(".{}.aivista-dedup", name.to_string_lossy()));
+    let _ = fs::remove_file(&tmp);
+    let linked = match action {
+        DedupAction::Symlink => kept.canonicalize().and_then(|target| create_file_symlink(&target, &tmp)),
+        _ => fs::hard_link(kept, &tmp),
+    };
+    linked.with_context(|| format!("linking {:?} to {:?}", tmp, kept))?;
+    if let Err(e) = fs::rename(&tmp, copy) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e).with_context(|| format!("replacing {:?}", copy));
+    }
+    Ok(())
+}
@@ -0,0 +1,84 @@
+//! Free-space queries for the filesystem holding the cache, checked before
+//! anything is written to it.
+
+use crate::report::{human_bytes, SizeFormat};
+use anyhow::{Context, Result};
+use std::io;
+use std::path::Path;
+
+/// Size and free space of one filesystem, in bytes.
+#[derive(Debug, Clone, Copy)]
+pub struct DiskSpace {
+    pub total: u64,
+    /// Space available to this (unprivileged) process
+    pub available: u64,
+}
+
+impl DiskSpace {
+    /// From a `statvfs(3)` result, whose counts are in fragments of
+    /// `f_frsize` bytes, or of `f_bsize` where that is left at 0.
+    #[cfg(unix)]
+    #[allow(clippy::unnecessary_cast)] // the field types differ between platforms
+    pub fn from_statvfs(st: &libc::statvfs) -> Self {
+        let block = if st.f_frsize > 0 { st.f_frsize } else { st.f_bsize } as u64;
+        DiskSpace {
+            total: st.f_blocks as u64 * block,
+            available: st.f_bavail as u64 * block,
+        }
+    }
+
+    /// Fail when fewer than `min_free` bytes are available; `path` names
+    /// the filesystem in the error.
+    pub fn require(self, path: &Path, min_free: u64, sizes: SizeFormat) -> Result<Self> {
+        if self.available < min_free {
+            anyhow::bail!(
+                "only {} free on the filesystem holding {:?}, below the {} minimum",
+                human_bytes(self.available as u128, sizes),
+                path,
+                human_bytes(min_free as u128, sizes)
+            );
+        }
+        Ok(self)
+    }
+}
+
+/// Query the filesystem `path` lives on.
+#[cfg(unix)]
+pub fn disk_space(path: &Path) -> io::Result<DiskSpace> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut st: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut st) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(DiskSpace::from_statvfs(&st))
+}
+
+/// Query the filesystem `path` lives on.
+#[cfg(windows)]
+pub fn disk_space(path: &Path) -> io::Result<DiskSpace> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    // the API wants a directory, not a file
+    let dir = if path.is_dir() {
+        path
+    } else {
+        path.parent().unwrap_or(path)
+    };
+    let wide: Vec<u16> = dir.as_os_str().encode_wide().chain(Some(0)).collect();
+    let (mut available, mut total, mut free) = (0u64, 0u64, 0u64);
+    if unsafe { GetDiskFreeSpaceExW(wide.as_ptr(), &mut available, &mut total, &mut free) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(DiskSpace { total, available })
+}
+
+/// Fail when fewer than `min_free` bytes are available under `path`.
+pub fn ensure_free(path: &Path, min_free: u64, sizes: SizeFormat) -> Result<DiskSpace> {
+    let space = disk_space(path).with_context(|| format!("checking free space for {:?}", path))?;
+    space.require(path, min_free, sizes)
+}
@@ -0,0 +1,139 @@
+//! `--format yaml` and `--format toml`: the JSON report's document written
+//! as YAML or TOML by `serde_yaml` and `toml`. The shared serde types decide
+//! the content, this module only the syntax.
+//!
+//! Both are built whole in memory before a byte is written, several times
+//! the size of the JSON, so for millions of files `--format json` or
+//! `ndjson` are the better choice. TOML has no null: fields without a value
+//! are left out, and a null inside an array is an error.
+
+use anyhow::Result;
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{SerializeMap, Serializer};
+use serde::Serialize;
+use std::fmt;
+
+/// A text syntax for the report document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocFormat {
+    Yaml,
+    Toml,
+}
+
+impl DocFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            DocFormat::Yaml => "YAML",
+            DocFormat::Toml => "TOML",
+        }
+    }
+
+    /// `value` in this syntax, ending with a newline.
+    pub fn render<T: Serialize>(self, value: &T) -> Result<String> {
+        match self {
+            DocFormat::Yaml => Ok(serde_yaml::to_string(value)?),
+            DocFormat::Toml => {
+                // read back into a tree that keeps field order, minus the nulls
+                let node: Node = serde_json::from_slice(&serde_json::to_vec(value)?)?;
+                Ok(toml::to_string(&node)?)
+            }
+        }
+    }
+}
+
+/// A JSON value whose objects keep their keys in document order.
+#[derive(Debug, Clone)]
+enum Node {
+    Null,
+    Bool(bool),
+    Int(i128),
+    Float(f64),
+    Str(String),
+    Seq(Vec<Node>),
+    Map(Vec<(String, Node)>),
+}
+
+impl<'de> Deserialize<'de> for Node {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(NodeVisitor)
+    }
+}
+
+struct NodeVisitor;
+
+impl<'de> Visitor<'de> for NodeVisitor {
+    type Value = Node;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any JSON value")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Node, E> {
+        Ok(Node::Null)
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Node, E> {
+        Ok(Node::Bool(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Node, E> {
+        Ok(Node::Int(v.into()))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Node, E> {
+        Ok(Node::Int(v.into()))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Node, E> {
+        Ok(Node::Float(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Node, E> {
+        Ok(Node::Str(v.to_string()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Node, E> {
+        Ok(Node::Str(v))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Node, A::Error> {
+        let mut items = Vec::new();
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Node::Seq(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Node, A::Error> {
+        let mut entries = Vec::new();
+        while let Some(entry) = map.next_entry()? {
+            entries.push(entry);
+        }
+        Ok(Node::Map(entries))
+    }
+}
+
+impl Serialize for Node {
+    /// Null map values are left out; anywhere else they reach the serializer.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Node::Null => serializer.serialize_unit(),
+            Node::Bool(v) => serializer.serialize_bool(*v),
+            // read from JSON, so at most a u64
+            Node::Int(v) => match i64::try_from(*v) {
+                Ok(v) => serializer.serialize_i64(v),
+                Err(_) => serializer.serialize_u64(*v as u64),
+            },
+            Node::Float(v) => serializer.serialize_f64(*v),
+            Node::Str(v) => serializer.serialize_str(v),
+            Node::Seq(items) => serializer.collect_seq(items),
+            Node::Map(entries) => {
+                let mut map = serializer.serialize_map(None)?;
+                for (k, v) in entries.iter().filter(|(_, v)| !matches!(v, Node::Null)) {
+                    map.serialize_entry(k, v)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
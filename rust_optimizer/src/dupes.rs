@@ -0,0 +1,139 @@
+//! Duplicate detection and the space a cleanup could reclaim.
+
+use crate::layout::OrphanBlob;
+use crate::report::{FileReport, FileStatus};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Files with identical contents. `paths` is sorted, so the first entry is the
+/// one a cleanup would keep.
+#[derive(Debug, Clone, Serialize, JsonSchema, Deserialize)]
+pub struct DuplicateGroup {
+    #[serde(alias = "hash_hex")]
+    pub hash_str: String,
+    pub size: u64,
+    pub paths: Vec<PathBuf>,
+}
+
+impl DuplicateGroup {
+    /// Bytes freed by keeping a single copy.
+    pub fn wasted(&self) -> u64 {
+        self.size * (self.paths.len() as u64 - 1)
+    }
+}
+
+/// Group hashed, non-empty files by digest. Orphaned blobs are left out:
+/// they are reclaimed whole, so counting them here too would count them twice.
+/// Only groups with more than one member are returned, most wasted space first.
+pub fn find_duplicates(reports: &[FileReport], orphans: &[OrphanBlob]) -> Vec<DuplicateGroup> {
+    let orphan_paths: HashSet<&Path> = orphans.iter().map(|o| o.path.as_path()).collect();
+    let mut by_hash: BTreeMap<(&str, u64), Vec<PathBuf>> = BTreeMap::new();
+    for r in reports {
+        let Some(hex) = r.hash_str.as_deref() else {
+            continue;
+        };
+        // archive members cannot be linked or deleted individually, and a
+        // `--range` hash does not show two whole files are equal
+        if r.status != FileStatus::Hashed
+            || r.size == 0
+            || r.member.is_some()
+            || r.range.is_some()
+            || orphan_paths.contains(r.path.as_path())
+        {
+            continue;
+        }
+        by_hash.entry((hex, r.size)).or_default().push(r.path.clone());
+    }
+    let mut groups: Vec<DuplicateGroup> = by_hash
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|((hex, size), mut paths)| {
+            paths.sort();
+            DuplicateGroup {
+                hash_str: hex.to_string(),
+                size,
+                paths,
+            }
+        })
+        .collect();
+    groups.sort_by_key(|g| std::cmp::Reverse(g.wasted()));
+    groups
+}
+
+/// Files of one exact size whose contents differ. Where copies of one file
+/// are expected this often means one of them is silently corrupted.
+#[derive(Debug, Clone, Serialize, JsonSchema, Deserialize)]
+pub struct SizeCollision {
+    pub size: u64,
+    /// Number of different digests among `files`
+    pub distinct_hashes: usize,
+    /// Sorted by hash, then path, so copies that agree sit together
+    pub files: Vec<HashedFile>,
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema, Deserialize)]
+pub struct HashedFile {
+    #[serde(alias = "hash_hex")]
+    pub hash_str: String,
+    pub path: PathBuf,
+}
+
+/// Group hashed, non-empty files by size and keep the sizes at which more
+/// than one distinct hash occurs, largest size first.
+pub fn find_size_collisions(reports: &[FileReport]) -> Vec<SizeCollision> {
+    let mut by_size: BTreeMap<u64, Vec<HashedFile>> = BTreeMap::new();
+    for r in reports {
+        let Some(hex) = &r.hash_str else {
+            continue;
+        };
+        if r.status != FileStatus::Hashed || r.size == 0 {
+            continue;
+        }
+        by_size.entry(r.size).or_default().push(HashedFile {
+            hash_str: hex.clone(),
+            path: r.path.clone(),
+        });
+    }
+    by_size
+        .into_iter()
+        .rev()
+        .filter_map(|(size, mut files)| {
+            files.sort_by(|a, b| (&a.hash_str, &a.path).cmp(&(&b.hash_str, &b.path)));
+            let distinct: HashSet<&str> = files.iter().map(|f| f.hash_str.as_str()).collect();
+            let distinct_hashes = distinct.len();
+            (distinct_hashes > 1).then_some(SizeCollision {
+                size,
+                distinct_hashes,
+                files,
+            })
+        })
+        .collect()
+}
+
+/// Headline figures for `Potentially reclaimable: ...`.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct ReclaimSummary {
+    pub total_bytes: u64,
+    pub duplicate_bytes: u64,
+    /// Extra copies beyond the one kept per group
+    pub duplicate_files: usize,
+    pub orphan_bytes: u64,
+    pub orphan_files: usize,
+}
+
+impl ReclaimSummary {
+    /// `duplicates` must come from `find_duplicates` with the same `orphans`.
+    pub fn new(duplicates: &[DuplicateGroup], orphans: &[OrphanBlob]) -> Self {
+        let duplicate_bytes = duplicates.iter().map(DuplicateGroup::wasted).sum();
+        let orphan_bytes = orphans.iter().map(|o| o.size).sum();
+        Self {
+            total_bytes: duplicate_bytes + orphan_bytes,
+            duplicate_bytes,
+            duplicate_files: duplicates.iter().map(|g| g.paths.len() - 1).sum(),
+            orphan_bytes,
+            orphan_files: orphans.len(),
+        }
+    }
+}
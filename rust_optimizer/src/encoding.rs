@@ -0,0 +1,122 @@
+//! Text encodings for digests. Hashing always yields hex; `--hash-encoding`
+//! re-renders it as unpadded lowercase base32 (RFC 4648, the multibase `b`
+//! alphabet) or unpadded base64url, which are shorter and safe in file names
+//! and URLs.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+const BASE32: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+const BASE64URL: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// How digests are written in reports and manifests.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, JsonSchema, Deserialize, clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum HashEncoding {
+    /// Lowercase hex, as b3sum and sha256sum print it
+    #[default]
+    Hex,
+    /// Unpadded lowercase base32: 52 characters for 32 bytes
+    Base32,
+    /// Unpadded base64url: 43 characters for 32 bytes
+    Base64url,
+}
+
+impl HashEncoding {
+    pub fn name(self) -> &'static str {
+        match self {
+            HashEncoding::Hex => "hex",
+            HashEncoding::Base32 => "base32",
+            HashEncoding::Base64url => "base64url",
+        }
+    }
+
+    pub fn is_hex(&self) -> bool {
+        *self == HashEncoding::Hex
+    }
+
+    pub fn encode(self, bytes: &[u8]) -> String {
+        match self {
+            HashEncoding::Hex => bytes.iter().map(|b| format!("{:02x}", b)).collect(),
+            HashEncoding::Base32 => encode_bits(bytes, 5, BASE32),
+            HashEncoding::Base64url => encode_bits(bytes, 6, BASE64URL),
+        }
+    }
+
+    /// `None` for characters outside the alphabet or a length no byte
+    /// string encodes to. Hex and base32 also accept upper case.
+    pub fn decode(self, text: &str) -> Option<Vec<u8>> {
+        match self {
+            HashEncoding::Hex => {
+                if !text.len().is_multiple_of(2) || !text.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    return None;
+                }
+                (0..text.len())
+                    .step_by(2)
+                    .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+                    .collect()
+            }
+            HashEncoding::Base32 => decode_bits(&text.to_ascii_lowercase(), 5, BASE32),
+            HashEncoding::Base64url => decode_bits(text, 6, BASE64URL),
+        }
+    }
+
+    /// Re-render a hex digest; text that is not hex is passed through.
+    pub fn from_hex(self, hex: &str) -> String {
+        match (self, HashEncoding::Hex.decode(hex)) {
+            (HashEncoding::Hex, _) | (_, None) => hex.to_string(),
+            (_, Some(bytes)) => self.encode(&bytes),
+        }
+    }
+
+    /// A digest in this encoding as lowercase hex; text that does not
+    /// decode is passed through, so it simply fails to match.
+    pub fn to_hex(self, text: &str) -> String {
+        match self.decode(text) {
+            Some(bytes) => HashEncoding::Hex.encode(&bytes),
+            None => text.to_string(),
+        }
+    }
+}
+
+/// Big-endian groups of `bits` bits, the last one zero-padded, no `=`.
+fn encode_bits(bytes: &[u8], bits: u32, alphabet: &[u8]) -> String {
+    let mask = (1u32 << bits) - 1;
+    let mut out = String::with_capacity((bytes.len() * 8).div_ceil(bits as usize));
+    let (mut acc, mut held) = (0u32, 0u32);
+    for &b in bytes {
+        acc = (acc << 8) | b as u32;
+        held += 8;
+        while held >= bits {
+            held -= bits;
+            out.push(alphabet[((acc >> held) & mask) as usize] as char);
+        }
+        acc &= (1 << held) - 1;
+    }
+    if held > 0 {
+        out.push(alphabet[((acc << (bits - held)) & mask) as usize] as char);
+    }
+    out
+}
+
+fn decode_bits(text: &str, bits: u32, alphabet: &[u8]) -> Option<Vec<u8>> {
+    // a trailing group must hold less than a byte of padding, all zero
+    if (text.len() * bits as usize) % 8 >= bits as usize {
+        return None;
+    }
+    let mut out = Vec::with_capacity(text.len() * bits as usize / 8);
+    let (mut acc, mut held) = (0u32, 0u32);
+    for c in text.bytes() {
+        let value = alphabet.iter().position(|&a| a == c)? as u32;
+        acc = (acc << bits) | value;
+        held += bits;
+        if held >= 8 {
+            held -= 8;
+            out.push((acc >> held) as u8);
+        }
+        acc &= (1 << held) - 1;
+    }
+    (acc == 0).then_some(out)
+}
@@ -0,0 +1,69 @@
+//! `--error-log`: every file that could not be processed, one JSON object
+//! per line, so a long list of failures survives the terminal scrolling.
+
+use crate::report::{FileReport, FileStatus};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// One line of the log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorEntry {
+    pub path: PathBuf,
+    /// Path inside the archive, for `--archives` members
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub member: Option<PathBuf>,
+    pub error: String,
+    /// OS error number, when the failure came from a system call
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub errno: Option<i32>,
+}
+
+/// The innermost OS error number in `err`'s chain, if any.
+pub fn errno(err: &anyhow::Error) -> Option<i32> {
+    err.chain()
+        .filter_map(|e| e.downcast_ref::<std::io::Error>())
+        .find_map(std::io::Error::raw_os_error)
+}
+
+/// The log file, created before the scan so a bad path fails fast instead
+/// of after hours of hashing.
+pub struct ErrorLog {
+    path: PathBuf,
+    file: File,
+}
+
+impl ErrorLog {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path).with_context(|| format!("creating error log {:?}", path))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+        })
+    }
+
+    /// Write an entry for every `Errored` or `PartiallyHashed` report and
+    /// return how many there were. No errors leaves the file empty.
+    pub fn write(self, reports: &[FileReport]) -> Result<usize> {
+        let mut out = BufWriter::new(self.file);
+        let mut count = 0;
+        let failed = |r: &&FileReport| {
+            matches!(r.status, FileStatus::Errored | FileStatus::PartiallyHashed)
+        };
+        for r in reports.iter().filter(failed) {
+            let entry = ErrorEntry {
+                path: r.path.clone(),
+                member: r.member.clone(),
+                error: r.error.clone().unwrap_or_default(),
+                errno: r.errno,
+            };
+            serde_json::to_writer(&mut out, &entry)?;
+            out.write_all(b"\n")?;
+            count += 1;
+        }
+        out.flush().with_context(|| format!("writing error log {:?}", self.path))?;
+        Ok(count)
+    }
+}
@@ -0,0 +1,177 @@
+//! `--exec`: run a command on every hashed file, for uploads, logging or
+//! conversion. The template is split into words once, the way a shell would
+//! split it, and each word has `{path}`, `{hash}` and `{size}` substituted;
+//! the command is then spawned directly, never through a shell, so a file
+//! name full of quotes or `;` is just an argument.
+
+use crate::report::{FileReport, FileStatus};
+use anyhow::{Context, Result};
+use crossbeam_channel::{unbounded, Sender};
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+/// Commands run at once by default.
+pub const DEFAULT_JOBS: usize = 4;
+
+/// Failures printed before the rest are only counted.
+const SHOWN_FAILURES: usize = 20;
+
+/// A parsed `--exec` command line.
+#[derive(Debug, Clone)]
+pub struct ExecTemplate {
+    words: Vec<String>,
+}
+
+impl ExecTemplate {
+    /// Split `template` with shell quoting rules; `'{path}'` and `{path}`
+    /// are the same word, since nothing is ever re-split after substitution.
+    pub fn parse(template: &str) -> Result<Self> {
+        let words = shlex::split(template)
+            .with_context(|| format!("unbalanced quotes in --exec {:?}", template))?;
+        if words.is_empty() {
+            anyhow::bail!("--exec needs a command");
+        }
+        Ok(Self { words })
+    }
+
+    /// The command for one file, stdin closed and stdout discarded.
+    pub fn command(&self, path: &Path, hash: &str, size: u64) -> Command {
+        let size = size.to_string();
+        let mut words = self.words.iter().map(|w| expand(w, path, hash, &size));
+        let mut cmd = Command::new(words.next().unwrap_or_default());
+        cmd.args(words).stdin(Stdio::null()).stdout(Stdio::null());
+        cmd
+    }
+}
+
+/// `word` with every placeholder replaced; a path is substituted as it is,
+/// even when it is not UTF-8.
+fn expand(word: &str, path: &Path, hash: &str, size: &str) -> OsString {
+    let values = [
+        ("{path}", path.as_os_str()),
+        ("{hash}", OsStr::new(hash)),
+        ("{size}", OsStr::new(size)),
+    ];
+    let mut out = OsString::new();
+    let mut rest = word;
+    while let Some(at) = rest.find('{') {
+        out.push(&rest[..at]);
+        rest = &rest[at..];
+        match values.iter().find(|(placeholder, _)| rest.starts_with(placeholder)) {
+            Some((placeholder, value)) => {
+                out.push(value);
+                rest = &rest[placeholder.len()..];
+            }
+            None => {
+                out.push("{");
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push(rest);
+    out
+}
+
+/// How the commands went.
+#[derive(Debug, Clone, Default)]
+pub struct ExecOutcome {
+    pub ran: usize,
+    /// Commands that could not be started or exited nonzero
+    pub failed: usize,
+}
+
+struct Job {
+    path: PathBuf,
+    hash: String,
+    size: u64,
+}
+
+/// A fixed pool of workers running the command for each submitted file, so
+/// a slow upload never holds up hashing.
+pub struct ExecHook {
+    jobs: Sender<Job>,
+    workers: Vec<JoinHandle<()>>,
+    outcome: Arc<Mutex<ExecOutcome>>,
+}
+
+impl ExecHook {
+    /// Start `jobs` workers (at least one).
+    pub fn start(template: ExecTemplate, jobs: usize) -> Self {
+        let (tx, rx) = unbounded::<Job>();
+        let outcome = Arc::new(Mutex::new(ExecOutcome::default()));
+        let workers = (0..jobs.max(1))
+            .map(|_| {
+                let rx = rx.clone();
+                let template = template.clone();
+                let outcome = Arc::clone(&outcome);
+                std::thread::spawn(move || {
+                    for job in rx {
+                        let result = run(&template, &job);
+                        let mut outcome = outcome.lock().unwrap();
+                        outcome.ran += 1;
+                        if let Err(e) = result {
+                            outcome.failed += 1;
+                            if outcome.failed <= SHOWN_FAILURES {
+                                eprintln!("[WARN] --exec failed for {:?}: {:#}", job.path, e);
+                            }
+                        }
+                    }
+                })
+            })
+            .collect();
+        Self {
+            jobs: tx,
+            workers,
+            outcome,
+        }
+    }
+
+    /// Queue the command for `r` if it was hashed; archive members, errors
+    /// and files that were only sized, sampled or warmed are passed over.
+    pub fn submit(&self, r: &FileReport) {
+        let Some(hash) = r.hash_str.as_ref() else {
+            return;
+        };
+        if r.status != FileStatus::Hashed || r.member.is_some() {
+            return;
+        }
+        let job = Job {
+            path: r.path.clone(),
+            hash: hash.clone(),
+            size: r.size,
+        };
+        // the workers outlive every sender, so this cannot fail
+        let _ = self.jobs.send(job);
+    }
+
+    /// Wait for every queued command to finish.
+    pub fn finish(self) -> ExecOutcome {
+        drop(self.jobs);
+        for worker in self.workers {
+            let _ = worker.join();
+        }
+        let outcome = self.outcome.lock().unwrap().clone();
+        if outcome.failed > SHOWN_FAILURES {
+            eprintln!("[WARN] {} more --exec failures not shown.", outcome.failed - SHOWN_FAILURES);
+        }
+        outcome
+    }
+}
+
+fn run(template: &ExecTemplate, job: &Job) -> Result<()> {
+    let output = template
+        .command(&job.path, &job.hash, job.size)
+        .output()
+        .with_context(|| format!("starting {:?}", template.words[0]))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        match stderr.lines().rev().find(|l| !l.trim().is_empty()) {
+            Some(last) => anyhow::bail!("{}: {}", output.status, last.trim()),
+            None => anyhow::bail!("{}", output.status),
+        }
+    }
+    Ok(())
+}
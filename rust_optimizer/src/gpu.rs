@@ -0,0 +1,181 @@
+//! Optional OpenCL XOR64 checksum, enabled with the `gpu` feature.
+
+#[cfg(feature = "gpu")]
+pub use self::opencl::GpuContext;
+#[cfg(not(feature = "gpu"))]
+pub use self::stub::GpuContext;
+
+#[cfg(feature = "gpu")]
+mod opencl {
+    use crate::xor64::pack_u64_le;
+    use anyhow::{Context, Result};
+    use ocl::{flags, Buffer, Kernel, Platform, ProQue, Queue};
+
+    // Small non-cryptographic GPU XOR kernel that reduces u64 chunks to a single u64.
+    // NOTE: This is just to stress GPU memory transfer and compute.
+    const KERNEL_SRC: &str = r#"
+        __kernel void xor_reduce(__global const ulong* data, __global ulong* out, uint n) {
+            uint gid = get_global_id(0);
+            ulong acc = 0;
+            // stride loop for safety
+            for (uint i = gid; i < n; i += get_global_size(0)) {
+                acc ^= data[i];
+            }
+            // each work-item writes its partial result into out[gid], host will reduce it
+            out[gid] = acc;
+        }
+    "#;
+
+    /// One program shared by every worker, plus a command queue per worker.
+    ///
+    /// `ProQue`, `Program` and `Queue` are refcounted OpenCL handles that `ocl`
+    /// marks `Send + Sync`, and since OpenCL 1.1 every API call is thread-safe
+    /// except `clSetKernelArg`, so sharing the context itself is sound. What
+    /// hurts is a single in-order queue: enqueues from all threads serialize on
+    /// it, so each rayon worker gets its own. A `Kernel` holds its arguments and
+    /// is not `Sync`, which is why one is built per call rather than shared.
+    pub struct GpuContext {
+        pro_que: ProQue,
+        queues: Vec<Queue>,
+        max_work_items: usize,
+        /// Words of input one launch copies to the device
+        chunk_words: usize,
+    }
+
+    impl GpuContext {
+        /// One queue per thread of the current rayon pool.
+        pub fn try_new() -> Result<Self> {
+            Self::with_queues(rayon::current_num_threads())
+        }
+
+        pub fn with_queues(count: usize) -> Result<Self> {
+            // Create a ProQue on the first available platform/device
+            let platform = Platform::default();
+            let pro_que = ProQue::builder()
+                .platform(platform)
+                .src(KERNEL_SRC)
+                .build()
+                .context("Failed to build OpenCL ProQue")?;
+            // max work items = device max compute units * some multiplier, clamp
+            let device = pro_que.device();
+            let max_wi = device.max_work_group_size()? as usize;
+            let max_items = (device.max_compute_units()? as usize) * max_wi;
+            let queues = (0..count.max(1))
+                .map(|_| Queue::new(pro_que.context(), device, None))
+                .collect::<Result<Vec<_>, _>>()
+                .context("Failed to create OpenCL command queues")?;
+            // a quarter of global memory is the largest single allocation
+            // OpenCL guarantees, and every queue may hold one at once
+            let global_mem = device.global_mem_size()?;
+            let budget = global_mem / 4 / queues.len() as u64;
+            Ok(Self {
+                pro_que,
+                queues,
+                max_work_items: max_items.clamp(64, 4096),
+                chunk_words: (budget / 8).clamp(1024, u32::MAX as u64) as usize,
+            })
+        }
+
+        /// Copy at most `bytes` of input to the device per launch, below
+        /// the share of device memory chosen by default.
+        pub fn with_max_chunk(mut self, bytes: usize) -> Self {
+            self.chunk_words = self.chunk_words.min(bytes / 8).max(1);
+            self
+        }
+
+        /// The calling rayon worker's own queue; other threads share the first.
+        fn queue(&self) -> &Queue {
+            let index = rayon::current_thread_index().unwrap_or(0);
+            &self.queues[index % self.queues.len()]
+        }
+
+        /// Compute an XOR64 reduction on the provided bytes using the GPU.
+        /// Input larger than the device's share of memory goes over in
+        /// chunks, one launch each, with the partials reduced on the host.
+        pub fn xor64_for_file(&self, bytes: &[u8]) -> Result<u64> {
+            // OpenCL rejects zero-length buffers; the XOR of nothing is 0
+            if bytes.is_empty() {
+                return Ok(0);
+            }
+            let words = bytes.len().div_ceil(8);
+            let capacity = words.min(self.chunk_words);
+            let wg = std::cmp::min(self.max_work_items, capacity);
+            let queue = self.queue();
+            let in_buf = Buffer::<u64>::builder()
+                .queue(queue.clone())
+                .flags(flags::MEM_READ_ONLY)
+                .len(capacity)
+                .build()
+                .context("Failed to build input buffer")?;
+            let out_buf = Buffer::<u64>::builder()
+                .queue(queue.clone())
+                .flags(flags::MEM_WRITE_ONLY)
+                .len(wg)
+                .build()
+                .context("Failed to build output buffer")?;
+
+            let mut partials = vec![0u64; wg];
+            let mut acc = 0u64;
+            // every chunk but the last is whole words, so only it is padded
+            for chunk in bytes.chunks(capacity * 8) {
+                let u64buf = pack_u64_le(chunk);
+                let n = u64buf.len();
+                in_buf
+                    .write(&u64buf)
+                    .enq()
+                    .context("Failed to copy input to the device")?;
+                let kernel = Kernel::builder()
+                    .program(self.pro_que.program())
+                    .name("xor_reduce")
+                    .global_work_size(wg)
+                    .arg(&in_buf)
+                    .arg(&out_buf)
+                    .arg(n as u32)
+                    .queue(queue.clone())
+                    .build()
+                    .context("Failed to build kernel")?;
+
+                unsafe {
+                    kernel.enq().context("Failed to enqueue kernel")?;
+                }
+
+                // Read partial results and reduce on host
+                out_buf
+                    .read(&mut partials)
+                    .enq()
+                    .context("Failed to read partials")?;
+                for &v in &partials {
+                    acc ^= v;
+                }
+            }
+            Ok(acc)
+        }
+    }
+}
+
+// Without the `gpu` feature there is no context to hand out; an uninhabited
+// stand-in keeps `process_file`'s signature identical across both builds.
+#[cfg(not(feature = "gpu"))]
+mod stub {
+    use anyhow::Result;
+
+    pub enum GpuContext {}
+
+    impl GpuContext {
+        pub fn try_new() -> Result<Self> {
+            anyhow::bail!("built without the `gpu` feature")
+        }
+
+        pub fn with_queues(_count: usize) -> Result<Self> {
+            Self::try_new()
+        }
+
+        pub fn with_max_chunk(self, _bytes: usize) -> Self {
+            match self {}
+        }
+
+        pub fn xor64_for_file(&self, _bytes: &[u8]) -> Result<u64> {
+            match *self {}
+        }
+    }
+}
@@ -0,0 +1,75 @@
+//! `--hardlink-report`: which scanned paths are hard links to one inode,
+//! and how much disk the cache really uses once each inode is counted once.
+//! Summing file sizes overcounts a cache whose blobs are hardlinked into
+//! several snapshots or environments.
+
+use crate::report::{FileReport, FileStatus};
+use rayon::prelude::*;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Scanned paths sharing one inode.
+#[derive(Debug, Clone, Serialize, JsonSchema, Deserialize)]
+pub struct HardlinkGroup {
+    pub size: u64,
+    /// Links to the inode on the whole filesystem, scanned or not
+    pub nlink: u64,
+    /// The scanned ones, sorted
+    pub paths: Vec<PathBuf>,
+}
+
+impl HardlinkGroup {
+    /// Bytes a plain sum over the scanned paths counts more than once.
+    pub fn overcounted(&self) -> u64 {
+        self.size * (self.paths.len() as u64).saturating_sub(1)
+    }
+}
+
+/// Hard link groups among `reports`, most overcounted bytes first, and the
+/// bytes the files occupy with each inode counted once. Archive members and
+/// recorded symlinks are left out; files that can no longer be examined
+/// count as their own inode.
+pub fn find_hardlinks(reports: &[FileReport]) -> (Vec<HardlinkGroup>, u64) {
+    let files: Vec<&FileReport> = reports
+        .iter()
+        .filter(|r| r.member.is_none() && r.status != FileStatus::Symlink)
+        .collect();
+    let inodes: Vec<Option<(u64, u64, u64)>> = files.par_iter().map(|r| inode(&r.path)).collect();
+    let mut shared: BTreeMap<(u64, u64), HardlinkGroup> = BTreeMap::new();
+    let mut real_bytes = 0;
+    for (r, inode) in files.iter().zip(inodes) {
+        let Some((dev, ino, nlink)) = inode.filter(|&(_, _, nlink)| nlink > 1) else {
+            real_bytes += r.size;
+            continue;
+        };
+        let group = shared.entry((dev, ino)).or_insert_with(|| {
+            real_bytes += r.size;
+            HardlinkGroup {
+                size: r.size,
+                nlink,
+                paths: Vec::new(),
+            }
+        });
+        group.paths.push(r.path.clone());
+    }
+    let mut groups: Vec<HardlinkGroup> = shared.into_values().collect();
+    groups.iter_mut().for_each(|g| g.paths.sort());
+    groups.sort_by(|a, b| b.overcounted().cmp(&a.overcounted()).then_with(|| a.paths.cmp(&b.paths)));
+    (groups, real_bytes)
+}
+
+/// Device, inode and link count of what `path` resolves to.
+#[cfg(unix)]
+fn inode(path: &Path) -> Option<(u64, u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    let meta = path.metadata().ok()?;
+    Some((meta.dev(), meta.ino(), meta.nlink()))
+}
+
+/// Link counts are not exposed here; every file is its own inode.
+#[cfg(not(unix))]
+fn inode(_path: &Path) -> Option<(u64, u64, u64)> {
+    None
+}
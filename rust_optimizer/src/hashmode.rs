@@ -0,0 +1,246 @@
+//! Plain, keyed or context-derived BLAKE3, so fingerprints of separate
+//! caches can live in separate keyspaces, and the non-cryptographic
+//! algorithms `--hash` offers for cheap change detection.
+
+use anyhow::Result;
+use blake3::hazmat::{hash_derive_key_context, ContextKey, HasherExt, Mode};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::io;
+use xxhash_rust::xxh3::Xxh3;
+
+/// Digest algorithm for file contents.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize, JsonSchema,
+    Deserialize,
+    clap::ValueEnum,
+)]
+pub enum HashAlgorithm {
+    /// BLAKE3: cryptographic, and the only one keys, contexts and --hash-length apply to
+    #[default]
+    #[serde(rename = "blake3")]
+    Blake3,
+    /// CRC-32 (IEEE): 8 hex digits, change detection only
+    #[serde(rename = "crc32")]
+    Crc32,
+    /// XXH3 128-bit: 32 hex digits, change detection only
+    #[value(name = "xxh3-128", alias = "xxh3")]
+    #[serde(rename = "xxh3-128")]
+    Xxh3_128,
+}
+
+impl HashAlgorithm {
+    pub fn name(self) -> &'static str {
+        match self {
+            HashAlgorithm::Blake3 => "blake3",
+            HashAlgorithm::Crc32 => "crc32",
+            HashAlgorithm::Xxh3_128 => "xxh3-128",
+        }
+    }
+
+    pub fn is_blake3(&self) -> bool {
+        *self == HashAlgorithm::Blake3
+    }
+
+    /// Whether equal digests can be trusted to mean equal contents, even
+    /// across a whole cache; the quick algorithms only flag change.
+    pub fn is_cryptographic(self) -> bool {
+        self.is_blake3()
+    }
+}
+
+/// Anything file bytes are fed into: a BLAKE3 hasher or a `FileHasher`.
+pub trait Update {
+    fn update(&mut self, data: &[u8]);
+
+    /// `update` spread over the rayon pool where the algorithm allows it.
+    /// BLAKE3 hashes subtrees of `data` on idle workers and merges them, for
+    /// the same digest as a serial update.
+    fn update_parallel(&mut self, data: &[u8]) {
+        self.update(data);
+    }
+}
+
+impl Update for blake3::Hasher {
+    fn update(&mut self, data: &[u8]) {
+        blake3::Hasher::update(self, data);
+    }
+
+    fn update_parallel(&mut self, data: &[u8]) {
+        self.update_rayon(data);
+    }
+}
+
+/// A running digest in any `HashAlgorithm`.
+pub enum FileHasher {
+    Blake3(Box<blake3::Hasher>),
+    Crc32(crc32fast::Hasher),
+    Xxh3(Box<Xxh3>),
+}
+
+impl FileHasher {
+    /// `mode` only applies to BLAKE3.
+    pub fn new(algorithm: HashAlgorithm, mode: &HashMode) -> Self {
+        match algorithm {
+            HashAlgorithm::Blake3 => FileHasher::Blake3(Box::new(mode.hasher())),
+            HashAlgorithm::Crc32 => FileHasher::Crc32(crc32fast::Hasher::new()),
+            HashAlgorithm::Xxh3_128 => FileHasher::Xxh3(Box::default()),
+        }
+    }
+
+    /// Lowercase hex of the digest: `blake3_len` bytes read from BLAKE3's
+    /// XOF, or the fixed big-endian width of the other algorithms.
+    pub fn finalize_hex(&self, blake3_len: usize) -> String {
+        match self {
+            FileHasher::Blake3(h) => {
+                let mut digest = vec![0u8; blake3_len];
+                h.finalize_xof().fill(&mut digest);
+                digest.iter().map(|b| format!("{:02x}", b)).collect()
+            }
+            FileHasher::Crc32(h) => format!("{:08x}", h.clone().finalize()),
+            FileHasher::Xxh3(h) => format!("{:032x}", h.digest128()),
+        }
+    }
+}
+
+impl Update for FileHasher {
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            FileHasher::Blake3(h) => {
+                h.update(data);
+            }
+            FileHasher::Crc32(h) => h.update(data),
+            FileHasher::Xxh3(h) => h.update(data),
+        }
+    }
+
+    fn update_parallel(&mut self, data: &[u8]) {
+        match self {
+            FileHasher::Blake3(h) => {
+                h.update_rayon(data);
+            }
+            _ => self.update(data),
+        }
+    }
+}
+
+/// Several digests of the same bytes, `--hash blake3,xxh3-128`: every
+/// update goes to each hasher, so the extra algorithms cost CPU but no I/O.
+pub struct MultiHasher(Vec<(HashAlgorithm, FileHasher)>);
+
+impl MultiHasher {
+    /// `primary` first, then `extra`; `mode` only applies to BLAKE3.
+    pub fn new(primary: HashAlgorithm, extra: &[HashAlgorithm], mode: &HashMode) -> Self {
+        let algorithms = std::iter::once(primary).chain(extra.iter().copied());
+        Self(algorithms.map(|a| (a, FileHasher::new(a, mode))).collect())
+    }
+
+    /// Each algorithm's `finalize_hex`, in the order given to `new`.
+    pub fn finalize_hex(&self, blake3_len: usize) -> Vec<(HashAlgorithm, String)> {
+        self.0.iter().map(|(a, h)| (*a, h.finalize_hex(blake3_len))).collect()
+    }
+}
+
+impl Update for MultiHasher {
+    fn update(&mut self, data: &[u8]) {
+        self.0.iter_mut().for_each(|(_, h)| h.update(data));
+    }
+
+    fn update_parallel(&mut self, data: &[u8]) {
+        self.0.iter_mut().for_each(|(_, h)| h.update_parallel(data));
+    }
+}
+
+impl io::Write for MultiHasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Update::update(self, buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl io::Write for FileHasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Update::update(self, buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Which BLAKE3 mode file contents are hashed in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HashMode {
+    #[default]
+    Plain,
+    /// `--hash-key`: a 32-byte key
+    Keyed([u8; blake3::KEY_LEN]),
+    /// `--hash-context`: `derive_key` mode, stored as the already-hashed context
+    DeriveKey(ContextKey),
+}
+
+impl HashMode {
+    /// Parse `--hash-key`: exactly 64 hex digits.
+    pub fn keyed_from_hex(hex: &str) -> Result<Self> {
+        let hex = hex.trim();
+        if hex.len() != 2 * blake3::KEY_LEN {
+            anyhow::bail!(
+                "hash key must be {} hex digits (32 bytes), got {} characters",
+                2 * blake3::KEY_LEN,
+                hex.len()
+            );
+        }
+        let mut key = [0u8; blake3::KEY_LEN];
+        for (i, byte) in key.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16)
+                .map_err(|_| anyhow::anyhow!("hash key is not valid hex near position {}", 2 * i))?;
+        }
+        Ok(HashMode::Keyed(key))
+    }
+
+    pub fn derive_key(context: &str) -> Self {
+        HashMode::DeriveKey(hash_derive_key_context(context))
+    }
+
+    pub fn hasher(&self) -> blake3::Hasher {
+        match self {
+            HashMode::Plain => blake3::Hasher::new(),
+            HashMode::Keyed(key) => blake3::Hasher::new_keyed(key),
+            HashMode::DeriveKey(context_key) => blake3::Hasher::new_from_context_key(context_key),
+        }
+    }
+
+    /// The mode for merging subtree chaining values by hand.
+    pub fn tree_mode(&self) -> Mode<'_> {
+        match self {
+            HashMode::Plain => Mode::Hash,
+            HashMode::Keyed(key) => Mode::KeyedHash(key),
+            HashMode::DeriveKey(context_key) => Mode::DeriveKeyMaterial(context_key),
+        }
+    }
+
+    /// Short fingerprint of the key, recorded with resumable state so it is
+    /// never resumed under another key. Empty for the plain mode, matching
+    /// checkpoints written before keyed hashing existed.
+    pub fn id(&self) -> String {
+        let (kind, key) = match self {
+            HashMode::Plain => return String::new(),
+            HashMode::Keyed(key) => ("keyed", key),
+            HashMode::DeriveKey(context_key) => ("derive", context_key),
+        };
+        format!("{}:{}", kind, &blake3::hash(key).to_hex()[..16])
+    }
+}
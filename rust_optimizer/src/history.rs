@@ -0,0 +1,161 @@
+//! `--sqlite`: one row per scan and one per file in a SQLite database, so
+//! daily scans can be compared with plain SQL, e.g. which models grew:
+//!
+//! ```sql
+//! SELECT f.path, f.size - p.size AS growth
+//! FROM files f JOIN files p ON p.path = f.path
+//! WHERE f.scan_id = (SELECT max(id) FROM scans)
+//!   AND p.scan_id = (SELECT max(id) FROM scans WHERE started_at < unixepoch() - 7 * 86400)
+//! ORDER BY growth DESC;
+//! ```
+//!
+//! The schema is versioned with `PRAGMA user_version`; opening a database
+//! applies whatever migrations it is missing, and refuses one written by a
+//! newer version rather than guess at its layout.
+
+use crate::encoding::HashEncoding;
+use crate::hashmode::{HashAlgorithm, HashMode};
+use crate::manifest::Manifest;
+use crate::sink::{ReportSink, ScanSummary};
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection};
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Schema changes in order; `user_version` counts how many have been applied.
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE scans (
+        id INTEGER PRIMARY KEY,
+        started_at INTEGER NOT NULL,
+        cache TEXT NOT NULL,
+        total_files INTEGER NOT NULL,
+        total_bytes INTEGER NOT NULL,
+        fingerprint TEXT NOT NULL,
+        duration_ms INTEGER NOT NULL,
+        hash_algorithm TEXT NOT NULL,
+        hash_encoding TEXT NOT NULL,
+        cancelled INTEGER NOT NULL
+    );
+    CREATE TABLE files (
+        scan_id INTEGER NOT NULL REFERENCES scans(id) ON DELETE CASCADE,
+        path TEXT NOT NULL,
+        member TEXT,
+        size INTEGER NOT NULL,
+        hash TEXT,
+        status TEXT NOT NULL,
+        elapsed_ms INTEGER NOT NULL
+    );
+    CREATE INDEX files_scan ON files(scan_id);
+    CREATE INDEX files_path ON files(path);",
+];
+
+/// Open `path`, creating it and bringing its schema up to date.
+pub fn open(path: &Path) -> Result<Connection> {
+    let mut conn =
+        Connection::open(path).with_context(|| format!("opening SQLite database {:?}", path))?;
+    migrate(&mut conn).with_context(|| format!("updating the schema of {:?}", path))?;
+    Ok(conn)
+}
+
+fn migrate(conn: &mut Connection) -> Result<()> {
+    let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version > MIGRATIONS.len() {
+        anyhow::bail!(
+            "schema version {} is newer than this build knows ({}); use a newer aivista",
+            version,
+            MIGRATIONS.len()
+        );
+    }
+    let tx = conn.transaction()?;
+    for (i, sql) in MIGRATIONS.iter().enumerate().skip(version) {
+        tx.execute_batch(sql).with_context(|| format!("migration {}", i + 1))?;
+    }
+    tx.pragma_update(None, "user_version", MIGRATIONS.len())?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Records the scan once it is done. The database is opened up front, so a
+/// bad path or a schema from the future fails before the scan starts.
+pub struct SqliteSink {
+    conn: Connection,
+    started_at: u64,
+    start: Instant,
+    /// Manifest paths are relative to this, as in `--emit-manifest`
+    root: PathBuf,
+    algorithm: HashAlgorithm,
+    mode: HashMode,
+    encoding: HashEncoding,
+}
+
+impl SqliteSink {
+    pub fn open(
+        path: &Path,
+        root: &Path,
+        algorithm: HashAlgorithm,
+        mode: HashMode,
+        encoding: HashEncoding,
+    ) -> Result<Self> {
+        let started_at = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        Ok(Self {
+            conn: open(path)?,
+            started_at,
+            start: Instant::now(),
+            root: root.to_path_buf(),
+            algorithm,
+            mode,
+            encoding,
+        })
+    }
+
+    /// Insert the scan and its files in one transaction; returns the scan id.
+    pub fn record(&mut self, summary: &ScanSummary) -> Result<i64> {
+        let report = &summary.report;
+        let manifest =
+            Manifest::build(&self.root, &report.files, self.algorithm, &self.mode, self.encoding);
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "INSERT INTO scans (started_at, cache, total_files, total_bytes, fingerprint,
+                duration_ms, hash_algorithm, hash_encoding, cancelled)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                self.started_at as i64,
+                report.cache.to_string_lossy(),
+                report.total_files as i64,
+                report.total_bytes as i64,
+                manifest.fingerprint,
+                self.start.elapsed().as_millis() as i64,
+                self.algorithm.name(),
+                self.encoding.name(),
+                summary.cancelled,
+            ],
+        )?;
+        let scan_id = tx.last_insert_rowid();
+        {
+            let mut insert = tx.prepare(
+                "INSERT INTO files (scan_id, path, member, size, hash, status, elapsed_ms)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )?;
+            for r in &report.files {
+                insert.execute(params![
+                    scan_id,
+                    r.path.to_string_lossy(),
+                    r.member.as_ref().map(|m| m.to_string_lossy()),
+                    r.size as i64,
+                    r.hash_str,
+                    r.status.as_str(),
+                    r.elapsed_ms as i64,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(scan_id)
+    }
+}
+
+impl ReportSink for SqliteSink {
+    fn finish(&mut self, summary: &ScanSummary) -> Result<()> {
+        self.record(summary).context("recording the scan in the SQLite database")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,153 @@
+//! A manifest kept in memory and updated file by file, for embedders that
+//! watch a cache and need its fingerprint after every change.
+//!
+//! The fingerprint is the manifest's own, so an index and a fresh
+//! `--emit-manifest` of the same files always agree. Entries stay sorted by
+//! path, so inserting or removing one is O(log n); the fingerprint is then
+//! refolded from the stored digests, a few dozen bytes per file, without
+//! reading any file again, and cached until the next change.
+
+use crate::encoding::HashEncoding;
+use crate::hashmode::{HashAlgorithm, HashMode};
+use crate::manifest::{self, algorithm_name, Manifest, ManifestEntry, MANIFEST_VERSION};
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Mutex, RwLock};
+
+#[derive(Debug, Default)]
+struct Entries {
+    /// `/`-separated relative path to size and digest, in `encoding`
+    files: BTreeMap<String, (u64, String)>,
+    /// Bumped by every change, so a cached fingerprint can tell it is stale
+    generation: u64,
+}
+
+/// Shareable between threads: updates take a write lock, and the
+/// fingerprint is computed at most once per generation of entries.
+#[derive(Debug)]
+pub struct IndexState {
+    algorithm: HashAlgorithm,
+    mode: HashMode,
+    encoding: HashEncoding,
+    entries: RwLock<Entries>,
+    fingerprint: Mutex<Option<(u64, String)>>,
+}
+
+impl IndexState {
+    /// An empty index whose digests are made with `algorithm` and `mode`.
+    pub fn new(algorithm: HashAlgorithm, mode: HashMode, encoding: HashEncoding) -> Self {
+        Self {
+            algorithm,
+            mode,
+            encoding,
+            entries: RwLock::new(Entries::default()),
+            fingerprint: Mutex::new(None),
+        }
+    }
+
+    /// Seed from a manifest; `mode` must be the one its fingerprint used.
+    pub fn from_manifest(manifest: &Manifest, algorithm: HashAlgorithm, mode: HashMode) -> Self {
+        let index = Self::new(algorithm, mode, manifest.encoding);
+        {
+            let mut entries = index.entries.write().unwrap();
+            entries.files = manifest
+                .files
+                .iter()
+                .map(|e| (e.path.clone(), (e.size, e.hash.clone())))
+                .collect();
+        }
+        index
+    }
+
+    /// Resume an index saved by `save`, checking it was made with
+    /// `algorithm` and `mode` and not edited since.
+    pub fn load(path: &Path, algorithm: HashAlgorithm, mode: HashMode) -> Result<Self> {
+        let manifest = Manifest::load(path)?;
+        manifest.check(algorithm, &mode)?;
+        Ok(Self::from_manifest(&manifest, algorithm, mode))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        self.to_manifest().write(path)
+    }
+
+    /// Set the size and digest of `path`, given as in the manifest (relative,
+    /// `/`-separated) with `hash` in this index's encoding. Returns whether
+    /// the entry changed.
+    pub fn update_index(&self, path: &str, size: u64, hash: &str) -> bool {
+        let mut entries = self.entries.write().unwrap();
+        let new = (size, hash.to_string());
+        if entries.files.get(path) == Some(&new) {
+            return false;
+        }
+        entries.files.insert(path.to_string(), new);
+        entries.generation += 1;
+        true
+    }
+
+    /// Drop `path`; returns whether it was indexed.
+    pub fn remove(&self, path: &str) -> bool {
+        let mut entries = self.entries.write().unwrap();
+        if entries.files.remove(path).is_none() {
+            return false;
+        }
+        entries.generation += 1;
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.read().unwrap().files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The digest of `path`, if indexed.
+    pub fn hash(&self, path: &str) -> Option<String> {
+        self.entries.read().unwrap().files.get(path).map(|(_, hash)| hash.clone())
+    }
+
+    /// The manifest fingerprint of the current entries.
+    pub fn fingerprint(&self) -> String {
+        self.fingerprint_of(&self.entries.read().unwrap())
+    }
+
+    fn fingerprint_of(&self, entries: &Entries) -> String {
+        let mut cached = self.fingerprint.lock().unwrap();
+        if let Some((generation, fingerprint)) = cached.as_ref() {
+            if *generation == entries.generation {
+                return fingerprint.clone();
+            }
+        }
+        let fingerprint = manifest::fingerprint(&to_entries(entries), &self.mode, self.encoding);
+        *cached = Some((entries.generation, fingerprint.clone()));
+        fingerprint
+    }
+
+    /// A snapshot of the index as a manifest, as `--emit-manifest` writes it.
+    pub fn to_manifest(&self) -> Manifest {
+        // one lock for both, so the fingerprint is of exactly these entries
+        let entries = self.entries.read().unwrap();
+        Manifest {
+            version: MANIFEST_VERSION,
+            algorithm: algorithm_name(self.algorithm, &self.mode).to_string(),
+            encoding: self.encoding,
+            fingerprint: self.fingerprint_of(&entries),
+            files: to_entries(&entries),
+        }
+    }
+}
+
+fn to_entries(entries: &Entries) -> Vec<ManifestEntry> {
+    entries
+        .files
+        .iter()
+        .map(|(path, (size, hash))| ManifestEntry {
+            path: path.clone(),
+            size: *size,
+            hash: hash.clone(),
+        })
+        .collect()
+}
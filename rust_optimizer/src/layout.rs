@@ -0,0 +1,324 @@
+//! Known model-cache layouts and per-model grouping of scan results.
+
+use crate::report::FileReport;
+use crate::walk::Symlink;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory prefixes of HuggingFace hub repos, and the name prefix each gets.
+const HF_REPO_KINDS: &[(&str, &str)] = &[("models--", ""), ("datasets--", "datasets/"), ("spaces--", "spaces/")];
+
+/// Registry and namespace Ollama omits when displaying a model name.
+const OLLAMA_DEFAULT_PREFIX: &[&str] = &["registry.ollama.ai", "library"];
+
+/// How the cache directory is organised.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Layout {
+    /// Guess from the directory shape
+    #[default]
+    Auto,
+    /// HuggingFace hub: `models--org--name/{blobs,refs,snapshots/<rev>}`
+    Hf,
+    /// Ollama: `manifests/<registry>/<namespace>/<model>/<tag>` plus `blobs/`
+    Ollama,
+    /// No known structure; files are reported individually
+    Raw,
+}
+
+impl Layout {
+    /// Replace `Auto` with whatever `root` looks like.
+    pub fn resolve(self, root: &Path) -> Layout {
+        if self != Layout::Auto {
+            return self;
+        }
+        if root.join("manifests").is_dir() && root.join("blobs").is_dir() {
+            return Layout::Ollama;
+        }
+        let has_hf_repo = fs::read_dir(root).into_iter().flatten().flatten().any(|e| {
+            let name = e.file_name();
+            let name = name.to_string_lossy();
+            e.path().is_dir() && HF_REPO_KINDS.iter().any(|(p, _)| name.starts_with(p))
+        });
+        if has_hf_repo {
+            Layout::Hf
+        } else {
+            Layout::Raw
+        }
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            Layout::Auto => "auto",
+            Layout::Hf => "huggingface",
+            Layout::Ollama => "ollama",
+            Layout::Raw => "raw",
+        }
+    }
+}
+
+/// Scanned files belonging to one model.
+#[derive(Debug, Clone, Serialize, JsonSchema, Deserialize)]
+pub struct ModelGroup {
+    pub name: String,
+    /// HF snapshot revisions (the one `refs/main` points at first) or the Ollama tag
+    pub revisions: Vec<String>,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// Group `reports` by model according to `layout`, largest first. `Raw`
+/// (and an unresolved `Auto`) yields no groups.
+pub fn group_reports(layout: Layout, root: &Path, reports: &[FileReport]) -> Vec<ModelGroup> {
+    let mut tally = ModelTally::new(layout, root);
+    reports.iter().for_each(|r| tally.add(r));
+    tally.finish()
+}
+
+/// `group_reports` one report at a time, holding one entry per model (and,
+/// for Ollama, per file a manifest names) rather than every report.
+#[derive(Clone)]
+pub struct ModelTally {
+    layout: Layout,
+    root: PathBuf,
+    /// HF repo directory -> its group, revisions filled in by `finish`
+    hf: BTreeMap<String, ModelGroup>,
+    ollama: Vec<OllamaModel>,
+    /// Sizes of scanned files some Ollama manifest names
+    ollama_sizes: HashMap<PathBuf, Option<u64>>,
+}
+
+impl ModelTally {
+    /// Ollama manifests are read here, before any report arrives.
+    pub fn new(layout: Layout, root: &Path) -> Self {
+        let ollama = match layout {
+            Layout::Ollama => ollama_models(root),
+            _ => Vec::new(),
+        };
+        let ollama_sizes = ollama
+            .iter()
+            .flat_map(|m| std::iter::once(&m.manifest).chain(&m.blobs))
+            .map(|p| (p.clone(), None))
+            .collect();
+        Self {
+            layout,
+            root: root.to_path_buf(),
+            hf: BTreeMap::new(),
+            ollama,
+            ollama_sizes,
+        }
+    }
+
+    pub fn add(&mut self, r: &FileReport) {
+        match self.layout {
+            Layout::Hf => {
+                let Some(dir) = r
+                    .path
+                    .strip_prefix(&self.root)
+                    .ok()
+                    .and_then(|rel| rel.components().next())
+                    .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                else {
+                    return;
+                };
+                let Some(name) = hf_repo_name(&dir) else {
+                    return;
+                };
+                let group = self.hf.entry(dir).or_insert_with(|| ModelGroup {
+                    name,
+                    revisions: Vec::new(),
+                    files: 0,
+                    bytes: 0,
+                });
+                group.files += 1;
+                group.bytes += r.size;
+            }
+            Layout::Ollama => {
+                if let Some(size) = self.ollama_sizes.get_mut(&r.path) {
+                    *size = Some(r.size);
+                }
+            }
+            Layout::Auto | Layout::Raw => {}
+        }
+    }
+
+    /// Fold in `other`, a clone of this tally fed other reports.
+    pub fn merge(&mut self, other: ModelTally) {
+        for (dir, group) in other.hf {
+            let mine = self.hf.entry(dir).or_insert_with(|| ModelGroup {
+                files: 0,
+                bytes: 0,
+                ..group.clone()
+            });
+            mine.files += group.files;
+            mine.bytes += group.bytes;
+        }
+        for (path, size) in other.ollama_sizes.into_iter().filter(|(_, size)| size.is_some()) {
+            self.ollama_sizes.insert(path, size);
+        }
+    }
+
+    pub fn finish(self) -> Vec<ModelGroup> {
+        let mut groups = match self.layout {
+            Layout::Hf => {
+                let root = self.root;
+                self.hf
+                    .into_iter()
+                    .map(|(dir, group)| ModelGroup {
+                        revisions: hf_revisions(&root.join(dir)),
+                        ..group
+                    })
+                    .collect()
+            }
+            Layout::Ollama => self
+                .ollama
+                .into_iter()
+                .map(|model| {
+                    // a blob shared by several tags counts towards each of them
+                    let scanned: Vec<u64> = std::iter::once(&model.manifest)
+                        .chain(&model.blobs)
+                        .filter_map(|p| self.ollama_sizes.get(p).copied().flatten())
+                        .collect();
+                    ModelGroup {
+                        name: model.name,
+                        revisions: vec![model.tag],
+                        files: scanned.len(),
+                        bytes: scanned.iter().sum(),
+                    }
+                })
+                .collect(),
+            Layout::Auto | Layout::Raw => Vec::new(),
+        };
+        groups.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.name.cmp(&b.name)));
+        groups
+    }
+}
+
+/// `models--org--name` -> `org/name`, `datasets--org--name` -> `datasets/org/name`.
+pub fn hf_repo_name(dir_name: &str) -> Option<String> {
+    HF_REPO_KINDS.iter().find_map(|(prefix, shown)| {
+        dir_name
+            .strip_prefix(prefix)
+            .map(|rest| format!("{}{}", shown, rest.replace("--", "/")))
+    })
+}
+
+/// Snapshot revisions of one HF repo, the one `refs/main` names first.
+fn hf_revisions(repo_dir: &Path) -> Vec<String> {
+    let mut revisions: Vec<String> = fs::read_dir(repo_dir.join("snapshots"))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        .collect();
+    revisions.sort();
+    if let Ok(main) = fs::read_to_string(repo_dir.join("refs").join("main")) {
+        let main = main.trim();
+        if let Some(i) = revisions.iter().position(|r| r == main) {
+            let main = revisions.remove(i);
+            revisions.insert(0, main);
+        }
+    }
+    revisions
+}
+
+/// A blob in an HF repo that no snapshot links to any more.
+#[derive(Debug, Clone, Serialize, JsonSchema, Deserialize)]
+pub struct OrphanBlob {
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+/// Scanned files under `<repo>/blobs/` that none of `symlinks` resolves to,
+/// i.e. space that deleting them would reclaim.
+pub fn hf_orphans(root: &Path, symlinks: &[Symlink], reports: &[FileReport]) -> Vec<OrphanBlob> {
+    let referenced: HashSet<&Path> = symlinks.iter().filter_map(|s| s.target.as_deref()).collect();
+    reports
+        .iter()
+        .filter(|r| {
+            let Ok(rel) = r.path.strip_prefix(root) else {
+                return false;
+            };
+            let mut parts = rel.components().map(|c| c.as_os_str().to_string_lossy());
+            let repo = parts.next();
+            repo.is_some_and(|d| hf_repo_name(&d).is_some())
+                && parts.next().is_some_and(|d| d == "blobs")
+        })
+        .filter(|r| {
+            r.path
+                .canonicalize()
+                .is_ok_and(|abs| !referenced.contains(abs.as_path()))
+        })
+        .map(|r| OrphanBlob {
+            path: r.path.clone(),
+            size: r.size,
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+struct OllamaLayer {
+    digest: String,
+}
+
+#[derive(Deserialize)]
+struct OllamaManifest {
+    config: Option<OllamaLayer>,
+    #[serde(default)]
+    layers: Vec<OllamaLayer>,
+}
+
+/// An Ollama model tag and the files its manifest pulls in.
+#[derive(Clone)]
+pub struct OllamaModel {
+    pub name: String,
+    pub tag: String,
+    pub manifest: PathBuf,
+    pub blobs: Vec<PathBuf>,
+}
+
+/// Parse every manifest under `<root>/manifests`. Unreadable manifests are skipped.
+pub fn ollama_models(root: &Path) -> Vec<OllamaModel> {
+    let manifests = root.join("manifests");
+    let mut models = Vec::new();
+    for entry in walkdir::WalkDir::new(&manifests).into_iter().flatten() {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let Ok(text) = fs::read_to_string(entry.path()) else {
+            continue;
+        };
+        let Ok(manifest) = serde_json::from_str::<OllamaManifest>(&text) else {
+            continue;
+        };
+        let Ok(rel) = entry.path().strip_prefix(&manifests) else {
+            continue;
+        };
+        let mut parts: Vec<String> = rel
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        let Some(tag) = parts.pop() else {
+            continue;
+        };
+        if parts.len() > OLLAMA_DEFAULT_PREFIX.len() && parts[..2] == OLLAMA_DEFAULT_PREFIX[..] {
+            parts.drain(..2);
+        }
+        let blobs = manifest
+            .config
+            .iter()
+            .chain(&manifest.layers)
+            // blobs are stored as `sha256-<hex>` for digest `sha256:<hex>`
+            .map(|layer| root.join("blobs").join(layer.digest.replace(':', "-")))
+            .collect();
+        models.push(OllamaModel {
+            name: parts.join("/"),
+            tag,
+            manifest: entry.into_path(),
+            blobs,
+        });
+    }
+    models
+}
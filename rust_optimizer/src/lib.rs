@@ -0,0 +1,51 @@
+//! Model-cache scanner: walks a cache, maps and hashes every file, and
+//! reports sizes, timings and checksums.
+
+pub mod affinity;
+pub mod archive;
+pub mod bench;
+pub mod budget;
+pub mod checksums;
+pub mod chunks;
+pub mod cpus;
+pub mod dedup;
+pub mod decompress;
+pub mod diskspace;
+pub mod docformat;
+pub mod dupes;
+pub mod encoding;
+pub mod errorlog;
+pub mod exec;
+pub mod gpu;
+pub mod hardlinks;
+pub mod hashmode;
+pub mod history;
+pub mod index;
+pub mod layout;
+pub mod manifest;
+pub mod mismatch;
+pub mod mounts;
+pub mod openfiles;
+pub mod process;
+pub mod progress;
+pub mod provenance;
+pub mod pytorch;
+pub mod remote;
+pub mod residency;
+pub mod report;
+pub mod resume;
+pub mod scan;
+pub mod selftest;
+pub mod sink;
+pub mod sparse;
+pub mod suspicious;
+pub mod symlinks;
+pub mod table;
+pub mod tally;
+pub mod timing;
+pub mod tree;
+pub mod verify;
+pub mod walk;
+pub mod watch;
+pub mod webhook;
+pub mod xor64;
@@ -1,316 +1,1682 @@
+use aivista_cache_scan::affinity;
+use aivista_cache_scan::archive::ArchiveKind;
+use aivista_cache_scan::bench;
+use aivista_cache_scan::budget::MemoryBudget;
+use aivista_cache_scan::checksums;
+use aivista_cache_scan::chunks::{self, Chunking};
+use aivista_cache_scan::cpus::Jobs;
+use aivista_cache_scan::decompress::{self, Decompress};
+use aivista_cache_scan::dedup::{self, DedupAction, DedupStatus};
+use aivista_cache_scan::diskspace;
+use aivista_cache_scan::docformat::DocFormat;
+use aivista_cache_scan::dupes::{self, ReclaimSummary};
+use aivista_cache_scan::encoding::HashEncoding;
+use aivista_cache_scan::errorlog::ErrorLog;
+use aivista_cache_scan::exec::{self, ExecHook, ExecTemplate};
+use aivista_cache_scan::gpu;
+use aivista_cache_scan::hardlinks;
+use aivista_cache_scan::hashmode::{HashAlgorithm, HashMode, MultiHasher};
+use aivista_cache_scan::history::SqliteSink;
+use aivista_cache_scan::layout::{self, Layout, ModelTally};
+use aivista_cache_scan::manifest::{self, Manifest};
+use aivista_cache_scan::mounts::MountTable;
+use aivista_cache_scan::openfiles::OpenForWrite;
+use aivista_cache_scan::process::{ByteRange, DEFAULT_DIGEST_LEN, encode_digests, hash_reader, process_file, ProcessOptions, ReaderMode};
+use aivista_cache_scan::progress::{self, SmoothedRate};
+use aivista_cache_scan::provenance::Provenance;
+use aivista_cache_scan::remote;
+use aivista_cache_scan::report::{
+    human_bytes, report_schema, FileReport, FileStatus, ReportOrder, SampleInfo, ScanReport,
+    SizeFormat, SortKey, Units,
+};
+use aivista_cache_scan::residency;
+use aivista_cache_scan::resume::HashCheckpoint;
+use aivista_cache_scan::scan::{self, CancellationToken, WorkerOptions};
+use aivista_cache_scan::selftest;
+use aivista_cache_scan::sink::{
+    self, ChecksumsSink, CsvSink, DocumentSink, HashPathSink, HumanSummary, JsonSink, NdjsonSink,
+    NoopSink, ReclaimJsonSink, ReportSink, ScanSummary, Tee,
+};
+use aivista_cache_scan::symlinks;
+use aivista_cache_scan::table::{new_table, use_color};
+use aivista_cache_scan::tally::{Shards, Tally};
+use aivista_cache_scan::timing::PhaseTimings;
+use aivista_cache_scan::tree;
+use aivista_cache_scan::verify::{self, Expected, Verdict};
+use aivista_cache_scan::webhook::{self, WebhookSink};
+use aivista_cache_scan::walk::{self, SymlinkPolicy};
+use aivista_cache_scan::watch::{self, Change, WatchIndex, WatchOptions};
+use aivista_cache_scan::xor64::xor64_cpu;
 use anyhow::{Context, Result};
-use clap::Parser;
-use crossbeam_channel::{bounded, Receiver, Sender};
-use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
-use memmap2::MmapOptions;
-use rayon::prelude::*;
-use std::cmp::Reverse;
-use std::collections::HashMap;
+use clap::{Parser, Subcommand, ValueEnum};
+use crossbeam_channel::{bounded, unbounded, Receiver, Sender, TrySendError};
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget};
+use std::collections::{BTreeMap, HashSet};
 use std::fs::File;
-use std::io::{Read, Seek, SeekFrom};
+use std::io::{BufRead, IsTerminal};
 use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use walkdir::WalkDir;
-
-#[cfg(unix)]
-use libc;
-
-#[cfg(feature = "gpu")]
-mod gpu {
-    use anyhow::{Context, Result};
-    use ocl::{flags, Buffer, Kernel, Platform, ProQue};
-    use std::path::Path;
-
-    // Small non-cryptographic GPU XOR kernel that reduces u64 chunks to a single u64.
-    // NOTE: This is just to stress GPU memory transfer and compute.
-    const KERNEL_SRC: &str = r#"
-        __kernel void xor_reduce(__global const ulong* data, __global ulong* out, uint n) {
-            uint gid = get_global_id(0);
-            ulong acc = 0;
-            // stride loop for safety
-            for (uint i = gid; i < n; i += get_global_size(0)) {
-                acc ^= data[i];
-            }
-            // each work-item writes its partial result into out[gid], host will reduce it
-            out[gid] = acc;
-        }
-    "#;
-
-    pub struct GpuContext {
-        pro_que: ProQue,
-        max_work_items: usize,
-    }
-
-    impl GpuContext {
-        pub fn try_new() -> Result<Self> {
-            // Create a ProQue on the first available platform/device
-            let platform = Platform::default();
-            let pro_que = ProQue::builder()
-                .platform(platform)
-                .src(KERNEL_SRC)
-                .build()
-                .context("Failed to build OpenCL ProQue")?;
-            // max work items = device max compute units * some multiplier, clamp
-            let device = pro_que.device();
-            let max_wi = device.max_work_group_size()? as usize;
-            let max_items = (device.max_compute_units()? as usize) * max_wi;
-            Ok(Self {
-                pro_que,
-                max_work_items: max_items.clamp(64, 4096),
-            })
-        }
 
-        /// Compute an XOR64 reduction on the provided bytes using the GPU.
-        /// We will pad/truncate to u64 multiples and copy to GPU in chunks to avoid OOM.
-        pub fn xor64_for_file(&self, bytes: &[u8]) -> Result<u64> {
-            // Build a u64 slice view (pad if necessary)
-            let mut len_u64 = bytes.len() / 8;
-            if bytes.len() % 8 != 0 {
-                len_u64 += 1;
-            }
-            // Prepare a Vec<u64> with zero padding
-            let mut u64buf = vec![0u64; len_u64];
-            let mut rdr = bytes;
-            for i in 0..len_u64 {
-                let mut chunk = [0u8; 8];
-                let take = std::cmp::min(8, rdr.len());
-                chunk[..take].copy_from_slice(&rdr[..take]);
-                u64buf[i] = u64::from_le_bytes(chunk);
-                if rdr.len() <= take {
-                    break;
-                }
-                rdr = &rdr[take..];
-            }
+/// Shortest digest, in bytes, trusted to replace one file with another
+/// without a byte-for-byte comparison.
+const MIN_DEDUP_DIGEST_LEN: usize = 16;
 
-            // Create buffers and run kernel in one shot
-            let n = u64buf.len();
-            let wg = std::cmp::min(self.max_work_items, n);
-            let in_buf = Buffer::<u64>::builder()
-                .queue(self.pro_que.queue().clone())
-                .flags(flags::MEM_READ_ONLY)
-                .len(n)
-                .copy_host_slice(&u64buf)
-                .build()
-                .context("Failed to build input buffer")?;
-            let out_buf = Buffer::<u64>::builder()
-                .queue(self.pro_que.queue().clone())
-                .flags(flags::MEM_WRITE_ONLY)
-                .len(wg)
-                .build()
-                .context("Failed to build output buffer")?;
-
-            let kernel = Kernel::builder()
-                .program(self.pro_que.program())
-                .name("xor_reduce")
-                .global_work_size(wg)
-                .arg(&in_buf)
-                .arg(&out_buf)
-                .arg(n as u32)
-                .queue(self.pro_que.queue().clone())
-                .build()
-                .context("Failed to build kernel")?;
-
-            unsafe {
-                kernel.enq().context("Failed to enqueue kernel")?;
-            }
+const EXIT_STATUS_HELP: &str = "\
+Exit status (the most severe wins when several apply):
+  0  everything was read and, with --check/--verify, matched
+  1  a file's size or hash did not match
+  2  an expected file is missing, or with --strict one was deleted mid-scan
+  3  a file, or with --strict-walk a directory, could not be read, a change to the cache
+     failed, or the run itself failed
+  4  --verify-url could not fetch the manifest
+Invalid arguments also exit with 2.";
 
-            // Read partial results and reduce on host
-            let mut partials = vec![0u64; wg];
-            out_buf
-                .read(&mut partials)
-                .enq()
-                .context("Failed to read partials")?;
-            let mut acc = 0u64;
-            for v in partials {
-                acc ^= v;
-            }
-            Ok(acc)
-        }
-    }
+#[derive(Parser)]
+#[clap(args_conflicts_with_subcommands = true, after_help = EXIT_STATUS_HELP)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Option<Command>,
+
+    // `scan` is the default when no subcommand is given
+    #[clap(flatten)]
+    scan: ScanArgs,
 }
 
-#[derive(Parser)]
-struct Args {
-    /// Path to cache directory
-    #[clap(short, long, default_value = "model_cache")]
-    cache: PathBuf,
+#[derive(Subcommand)]
+enum Command {
+    /// Scan a cache directory (default)
+    Scan(Box<ScanArgs>),
+    /// Compare two JSON scan reports written with `--output`
+    Diff(DiffArgs),
+    /// Measure hashing, disk and GPU throughput on synthetic data
+    Bench(BenchArgs),
+    /// Scan a generated fixture cache and check counts, hashes, duplicates and the fingerprint
+    Selftest(SelftestArgs),
+    /// Print the JSON Schema of the --output document
+    Schema,
+}
 
-    /// Number of parallel worker threads (defaults to number of physical cores)
-    #[clap(short = 'j', long)]
-    jobs: Option<usize>,
+#[derive(clap::Args)]
+struct ScanArgs {
+    /// Path to cache directory, or a single file to hash. Repeat it or give a
+    /// comma-separated list to scan several roots into one report
+    #[clap(short, long, value_delimiter = ',', default_value = "model_cache")]
+    cache: Vec<PathBuf>,
 
-    /// Enable GPU XOR warmup/checksum (requires --features gpu)
+    /// Hash standard input instead of scanning a cache
     #[clap(long)]
-    gpu: bool,
+    stdin: bool,
 
-    /// Only warm files (mmap + advise + optional hashing), don't compute heavy stats
+    /// Process the files listed here (`-` for stdin) instead of walking the cache;
+    /// one path per line, or NDJSON `{"path": ...}` objects
+    #[clap(long, value_name = "FILE", conflicts_with_all = ["stdin", "check", "find_orphans"])]
+    file_list: Option<PathBuf>,
+
+    /// Number of parallel worker threads, or `auto` (the default): one per physical core,
+    /// capped at the CPU quota of the container's cgroup (cpu.max or cpu.cfs_quota_us)
+    #[clap(short = 'j', long, value_name = "N|auto")]
+    jobs: Option<Jobs>,
+
+    /// Pin each worker thread to its own core, spreading workers across NUMA nodes so
+    /// files they read first are cached on their node (best-effort; Linux for NUMA)
+    #[clap(long)]
+    numa_aware: bool,
+
+    /// Files per unit of parallel work; by default chunks are sized by bytes so large
+    /// files are spread across threads instead of queueing behind each other
+    #[clap(
+        long,
+        value_name = "N",
+        value_parser = clap::builder::RangedI64ValueParser::<usize>::new().range(1..)
+    )]
+    work_chunk: Option<usize>,
+
+    /// Queue files in path order instead of largest first; slower on caches of mixed sizes,
+    /// but deterministic in which file each worker gets
+    #[clap(long)]
+    no_size_sort: bool,
+
+    /// What to do with symlinks inside the cache: `skip` them, `follow` them and hash the
+    /// target's content, or `record` each with its target without hashing
+    #[clap(long, value_enum, default_value_t = SymlinkPolicy::Skip)]
+    symlinks: SymlinkPolicy,
+
+    /// Skip resolving each path to drop ones that reach the same file through overlapping
+    /// roots or symlinked roots; faster, but such files are hashed and counted twice
     #[clap(long)]
+    no_canonicalize: bool,
+
+    /// Read only the allocated extents of sparse files (SEEK_DATA/SEEK_HOLE, Linux) and hash
+    /// their holes as zeros, so digests match a dense read; other files are read as usual
+    #[clap(long, conflicts_with_all = ["sample_hash", "warm_only"])]
+    sparse_aware: bool,
+
+    /// Hash each memory-mapped file of at least this many bytes with all workers, splitting
+    /// it into BLAKE3 subtrees; the digest is the same as a serial hash. Other hashes and
+    /// files read with --reader read stay on one worker
+    #[clap(long, value_name = "BYTES")]
+    parallel_file_threshold: Option<u64>,
+
+    /// Record how much of each file was already in the page cache before it was read
+    /// (mincore, Linux) and print the overall hit ratio; run after --warm-only to check it
+    #[clap(long, conflicts_with = "sample_hash")]
+    measure_residency: bool,
+
+    /// Compute the XOR64 checksum, on the GPU when available (requires --features gpu), else on CPU
+    #[clap(long)]
+    gpu: bool,
+
+    /// Hash each member of .tar, .tar.zst and .zip archives, streaming, instead of the archive
+    /// file
+    #[clap(long, conflicts_with_all = ["sample_hash", "warm_only"])]
+    archives: bool,
+
+    /// Scan the members of this .zip archive instead of a --cache directory, one report each,
+    /// without extracting it: stored members are hashed in place, deflated ones as they inflate
+    #[clap(
+        long,
+        value_name = "ARCHIVE",
+        conflicts_with_all = ["cache", "stdin", "file_list", "sample_hash", "warm_only", "range"]
+    )]
+    zip: Option<PathBuf>,
+
+    /// Hash the decompressed content of gzip or zstd files, streaming, so a compressed copy
+    /// hashes like an uncompressed one; `auto` decodes only files with either magic number
+    #[clap(
+        long,
+        value_enum,
+        default_value_t = Decompress::None,
+        conflicts_with_all = ["sample_hash", "warm_only", "range", "resumable_hash"]
+    )]
+    decompress: Decompress,
+
+    /// When a read fails partway through a file (a bad sector, say), report the hash of the
+    /// bytes read before it and how many there were, as status partially_hashed, instead of
+    /// only an error; files are then always read, never mapped
+    #[clap(long, conflicts_with_all = ["sample_hash", "warm_only", "resumable_hash"])]
+    partial_on_error: bool,
+
+    /// Only pull files into the page cache (mmap + advise + touch, or a plain read); no hashing
+    #[clap(long, conflicts_with = "sample_hash")]
     warm_only: bool,
 
     /// Limit processing to files larger than this many bytes (default 0)
     #[clap(long, default_value_t = 0)]
     min_bytes: u64,
+
+    /// Leave out files larger than this many bytes; with --min-bytes, an inclusive size window
+    #[clap(long, value_name = "BYTES")]
+    max_bytes: Option<u64>,
+
+    /// Hash only LEN bytes from byte START of each file, e.g. 0:1048576 for a stable
+    /// header region; files ending at or before START are skipped
+    #[clap(
+        long,
+        value_name = "START:LEN",
+        value_parser = ByteRange::parse,
+        conflicts_with_all = [
+            "stdin", "check", "verify", "emit_manifest", "archives", "sample_hash", "warm_only"
+        ]
+    )]
+    range: Option<ByteRange>,
+
+    /// Also list the N files that took longest to process (0 disables)
+    #[clap(long, default_value_t = 0)]
+    slowest: usize,
+
+    /// Add mean and p50/p90/p99 file sizes to the summary
+    #[clap(long)]
+    stats: bool,
+
+    /// Add directory sizes to the summary as a tree, largest first, with each directory's
+    /// share of its parent; files outside every --cache root are left out
+    #[clap(long)]
+    tree: bool,
+
+    /// Directory levels below each root the --tree shows; deeper ones count in their ancestor
+    #[clap(long, value_name = "N", default_value_t = tree::DEFAULT_DEPTH, requires = "tree")]
+    depth: usize,
+
+    /// Also write a JSON report of every processed file to this path, whatever --format
+    /// prints on stdout; its versioned layout is printed by the `schema` subcommand
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+
+    /// Write every file that could not be processed to this path as NDJSON (path, error,
+    /// errno), and print only their count instead of one warning each
+    #[clap(long, value_name = "PATH")]
+    error_log: Option<PathBuf>,
+
+    /// Record this scan (time, totals, fingerprint, duration) and every file in a SQLite
+    /// database, created if absent, for tracking the cache over time
+    #[clap(long, value_name = "PATH")]
+    sqlite: Option<PathBuf>,
+
+    /// After the scan, keep running and re-hash files as they are created or modified,
+    /// printing each addition, change and removal; Ctrl-C stops
+    #[clap(long)]
+    watch: bool,
+
+    /// Milliseconds a file must go without events before --watch re-hashes it
+    #[clap(long, value_name = "MS", default_value_t = watch::DEFAULT_DEBOUNCE_MS)]
+    watch_debounce_ms: u64,
+
+    /// Hash large files in windows and checkpoint progress so an interrupted run resumes
+    #[clap(long)]
+    resumable_hash: bool,
+
+    /// Checkpoint file used by --resumable-hash
+    #[clap(long, default_value = "aivista-checkpoint.json")]
+    checkpoint: PathBuf,
+
+    /// What to print on stdout
+    #[clap(long, value_enum, default_value = "human")]
+    format: OutputFormat,
+
+    /// Print only `<hash><TAB><path>` lines on stdout, each as soon as its file is hashed,
+    /// for sort, uniq or a database loader; shorthand for --format hash-path
+    #[clap(long, conflicts_with_all = ["format", "reclaim_report", "fingerprint_only"])]
+    only_hash: bool,
+
+    /// Verify the files listed in a checksums file instead of scanning
+    #[clap(long, value_name = "FILE")]
+    check: Option<PathBuf>,
+
+    /// Re-hash the files recorded in a JSON report (from --output) or a manifest (from
+    /// --emit-manifest, resolved against --cache) and compare sizes and hashes
+    #[clap(long, value_name = "FILE", conflicts_with_all = ["check", "stdin", "file_list"])]
+    verify: Option<PathBuf>,
+
+    /// Like --verify, with the manifest or report fetched from this URL. Unchanged manifests
+    /// are revalidated by ETag against a local copy; a failed fetch exits with 4
+    #[clap(
+        long,
+        value_name = "URL",
+        conflicts_with_all = ["check", "verify", "stdin", "file_list"]
+    )]
+    verify_url: Option<String>,
+
+    /// Extra request header for --verify-url, as `Name: value` (e.g. `Authorization: Bearer
+    /// ...`); repeatable
+    #[clap(long, value_name = "HEADER", requires = "verify_url")]
+    header: Vec<String>,
+
+    /// Where --verify-url keeps the last manifest and its ETag [default:
+    /// $XDG_CACHE_HOME/aivista/manifests or ~/.cache/aivista/manifests]
+    #[clap(long, value_name = "DIR", requires = "verify_url")]
+    manifest_cache: Option<PathBuf>,
+
+    /// Always download the whole manifest for --verify-url, and keep no copy
+    #[clap(long, requires = "verify_url", conflicts_with = "manifest_cache")]
+    no_manifest_cache: bool,
+
+    /// Write a canonical manifest: sorted relative paths, sizes and hashes plus a combined
+    /// fingerprint. Identical caches give byte-identical manifests; check one with --verify
+    #[clap(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["stdin", "file_list", "sample_hash", "sample_fraction", "warm_only"]
+    )]
+    emit_manifest: Option<PathBuf>,
+
+    /// Hash everything, then print only the manifest fingerprint on stdout, in full or as
+    /// its 7-digit short id; no summary, banners or progress
+    #[clap(
+        long,
+        value_enum,
+        value_name = "STYLE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "full",
+        conflicts_with_all = [
+            "format", "reclaim_report", "stdin", "file_list", "sample_hash", "sample_fraction",
+            "warm_only", "range"
+        ]
+    )]
+    fingerprint_only: Option<FingerprintStyle>,
+
+    /// Gitignore-style exclusion rules (defaults to `<cache>/.aivista-ignore` if present)
+    #[clap(long, value_name = "FILE")]
+    ignore_file: Option<PathBuf>,
+
+    /// Skip partial downloads: files with an incomplete suffix or a very recent mtime (default)
+    #[clap(long, overrides_with = "no_skip_incomplete")]
+    skip_incomplete: bool,
+
+    /// Hash partial downloads too
+    #[clap(long)]
+    no_skip_incomplete: bool,
+
+    /// File suffix marking a partial download; repeat to replace the default set
+    #[clap(long = "incomplete-suffix", value_name = "SUFFIX")]
+    incomplete_suffixes: Vec<String>,
+
+    /// Treat files modified within this many seconds as still being written
+    #[clap(long, default_value_t = walk::DEFAULT_SETTLE_SECS)]
+    settle_secs: u64,
+
+    /// Skip files another process has open for writing, read from /proc (Linux; other
+    /// users' processes only as root). Without /proc the mtime window is used instead
+    #[clap(long)]
+    skip_open_for_write: bool,
+
+    /// Order of the summary listing and of every output format (default: size, largest first)
+    #[clap(long, value_enum)]
+    sort: Option<SortKey>,
+
+    /// Sort in descending order
+    #[clap(long)]
+    sort_desc: bool,
+
+    /// How file contents are read: mmap, buffered read, or auto (read on network mounts)
+    #[clap(long, value_enum, default_value = "auto")]
+    reader: ReaderMode,
+
+    /// Map files with MAP_POPULATE (Linux), reading every page in before hashing starts,
+    /// instead of asking for readahead with MADV_WILLNEED. Tends to win on cold caches on
+    /// fast local disks, where hashing otherwise stalls on page faults; tends to lose on
+    /// files larger than free memory, whose first pages can be evicted again before they
+    /// are hashed. `bench --cold` times both on this machine
+    #[clap(long)]
+    populate: bool,
+
+    /// Cap on bytes memory-mapped at once across all workers; larger files are read in chunks, one at a time
+    #[clap(long, value_name = "BYTES", value_parser = clap::value_parser!(u64).range(1..))]
+    max_mem: Option<u64>,
+
+    /// Cache layout used to group the summary by model
+    #[clap(long, value_enum, default_value = "auto")]
+    layout: Layout,
+
+    /// List HF blobs no snapshot links to, with their reclaimable size
+    #[clap(long)]
+    find_orphans: bool,
+
+    /// How to print the reclaimable-space summary; `json` writes it to stdout for scripts
+    #[clap(long, value_enum, value_name = "FORMAT", default_value = "human")]
+    reclaim_report: ReclaimFormat,
+
+    /// What to do with duplicate files: keep the first of each group and link or delete the rest
+    #[clap(long, value_enum, default_value = "report")]
+    dedup_action: DedupAction,
+
+    /// Actually apply --dedup-action and --fix-symlinks; without it only a preview is printed
+    #[clap(long)]
+    confirm: bool,
+
+    /// Refuse to modify the cache when its filesystem has less free space than this
+    #[clap(long, value_name = "BYTES")]
+    min_free_bytes: Option<u64>,
+
+    /// Compare duplicates byte for byte before touching them, not just by hash
+    #[clap(long)]
+    paranoid: bool,
+
+    /// POST the JSON report (same schema as --output) to this URL when the scan ends
+    #[clap(long, value_name = "URL")]
+    webhook: Option<String>,
+
+    /// Extra request header for --webhook, as `Name: value`; repeatable
+    #[clap(long, value_name = "HEADER", requires = "webhook")]
+    webhook_header: Vec<String>,
+
+    /// Fail the scan when the webhook cannot be delivered
+    #[clap(long, requires = "webhook")]
+    webhook_required: bool,
+
+    /// Run this command on each hashed file, e.g. "upload.sh {path} {hash} {size}". It is
+    /// split like a shell line but run without a shell; placeholders fill whole arguments
+    #[clap(
+        long,
+        value_name = "COMMAND",
+        conflicts_with_all = ["stdin", "check", "verify", "sample_hash", "warm_only"]
+    )]
+    exec: Option<String>,
+
+    /// How many --exec commands may run at once
+    #[clap(long, value_name = "N", default_value_t = exec::DEFAULT_JOBS, requires = "exec")]
+    exec_jobs: usize,
+
+    /// Fail the scan when an --exec command cannot be started or exits nonzero
+    #[clap(long, requires = "exec")]
+    exec_required: bool,
+
+    /// Content hash. crc32 and xxh3-128 are far cheaper but only detect change: never use
+    /// them to verify downloads, and destructive --dedup-action needs --paranoid with them.
+    /// A comma list such as blake3,xxh3 computes each in the same pass and adds them all
+    /// to the report; the first is the one compared, deduplicated and verified against
+    #[clap(long, value_enum, value_delimiter = ',', default_value = "blake3")]
+    hash: Vec<HashAlgorithm>,
+
+    /// How hashes and the manifest fingerprint are written: hex, or the shorter base32 and
+    /// base64url, safe in file names and URLs. --format checksums stays hex
+    #[clap(long, value_enum, default_value_t = HashEncoding::Hex)]
+    hash_encoding: HashEncoding,
+
+    /// Digest length in bytes (BLAKE3 XOF). Below 16 collision resistance is
+    /// noticeably weakened, so --dedup-action then needs --paranoid; the first
+    /// 32 bytes always equal the standard hash
+    #[clap(
+        long,
+        value_name = "N",
+        default_value_t = DEFAULT_DIGEST_LEN,
+        value_parser = clap::builder::RangedI64ValueParser::<usize>::new().range(4..=64)
+    )]
+    hash_length: usize,
+
+    /// Hash in BLAKE3 keyed mode with this 32-byte key (64 hex digits), so the
+    /// same file gets a different digest under each key
+    #[clap(long, value_name = "HEX", conflicts_with = "hash_context")]
+    hash_key: Option<String>,
+
+    /// Hash in BLAKE3 derive-key mode with this context string, e.g. "acme models 2026 v1"
+    #[clap(long, value_name = "CONTEXT")]
+    hash_context: Option<String>,
+
+    /// Record a quick change-detection signature (size plus the first and last
+    /// --sample-bytes) instead of a full hash. Not collision-resistant: never use it to verify downloads
+    #[clap(long)]
+    sample_hash: bool,
+
+    /// Bytes read from each end of a file by --sample-hash
+    #[clap(long, value_name = "BYTES", default_value_t = 1 << 20)]
+    sample_bytes: u64,
+
+    /// Process only this fraction of the files (0 < F <= 1), picked reproducibly by --sample-seed
+    #[clap(long, value_name = "F", value_parser = parse_fraction)]
+    sample_fraction: Option<f64>,
+
+    /// Seed for --sample-fraction; the same seed selects the same files
+    #[clap(long, value_name = "S", default_value_t = 0, requires = "sample_fraction")]
+    sample_seed: u64,
+
+    /// Process only the first N files, in processing order (largest first unless
+    /// --no-size-sort) and after every filter: a deterministic cut, unlike --sample-fraction
+    #[clap(
+        long,
+        value_name = "N",
+        conflicts_with_all = ["stdin", "emit_manifest", "fingerprint_only", "watch"]
+    )]
+    limit: Option<usize>,
+
+    /// Estimate how well each file compresses (zstd level 1 on its first
+    /// --compress-probe-bytes) and summarise the savings per extension
+    #[clap(long, conflicts_with_all = ["sample_hash", "warm_only"])]
+    compressibility_estimate: bool,
+
+    /// Leading bytes of each file compressed by --compressibility-estimate
+    #[clap(long, value_name = "BYTES", default_value_t = 4 << 20)]
+    compress_probe_bytes: u64,
+
+    /// Capacity of the worker-to-aggregator result channel, used only when
+    /// reports stream to stdout or --exec; 0 means unbounded
+    #[clap(long, value_name = "N", default_value_t = 1024)]
+    channel_cap: usize,
+
+    /// Print pipeline diagnostics, such as how often workers waited on a full result channel
+    #[clap(short, long)]
+    verbose: bool,
+
+    /// Record each file's mount point and filesystem type, and break totals and throughput
+    /// down by them (Linux)
+    #[clap(long)]
+    by_mount: bool,
+
+    /// Flag empty files, model files too small for their extension and truncated safetensors
+    #[clap(long)]
+    flag_suspicious: bool,
+
+    /// Flag files whose first bytes contradict their extension, e.g. an HTML error page or a
+    /// Git LFS pointer saved as .safetensors, or a .gguf without the GGUF magic
+    #[clap(long)]
+    detect_mismatch: bool,
+
+    /// List tensor names, dtypes and shapes of PyTorch checkpoints (zip or legacy .bin/.pt),
+    /// by walking their pickle index without executing it
+    #[clap(long)]
+    inspect_pytorch: bool,
+
+    /// List files that share an exact size but differ in hash, a hint that one copy is corrupt
+    #[clap(long)]
+    size_collisions: bool,
+
+    /// List paths that are hard links to one inode, with link counts, and add the real disk
+    /// usage, counting each inode once, to the summary (Unix)
+    #[clap(long)]
+    hardlink_report: bool,
+
+    /// Report symlinks that are broken or point outside the cache
+    #[clap(long)]
+    check_symlinks: bool,
+
+    /// Add this machine's hostname to the report's provenance, which otherwise leaves it out
+    #[clap(long)]
+    include_hostname: bool,
+
+    /// Repoint such links at the same-named blob in their repo (implies --check-symlinks; needs --confirm)
+    #[clap(long)]
+    fix_symlinks: bool,
+
+    /// Print how long each phase took: walk, size estimate, GPU init, hashing, aggregation
+    #[clap(long)]
+    timing: bool,
+
+    /// Make the JSON, NDJSON and CSV output byte-identical across runs over unchanged files:
+    /// every elapsed_ms is written as 0 and NDJSON comes in report order at the end of the scan
+    #[clap(long)]
+    reproducible: bool,
+
+    /// Count files deleted between the walk and hashing as missing (exit 2), not as noise
+    #[clap(long)]
+    strict: bool,
+
+    /// List every directory the walk could not read, e.g. for lack of permission; without
+    /// it only their number is printed
+    #[clap(long)]
+    report_walk_errors: bool,
+
+    /// Fail the run (exit 3) when any directory could not be read, since the scan then
+    /// covers only part of the cache
+    #[clap(long)]
+    strict_walk: bool,
+
+    /// Size units: `iec` (1024, KiB/MiB) or `si` (1000, kB/MB)
+    #[clap(long, value_enum, default_value_t = Units::Iec)]
+    units: Units,
+
+    /// Decimal places in printed sizes; exact values drop theirs, e.g. `4 GiB`
+    #[clap(
+        long,
+        value_name = "N",
+        default_value_t = SizeFormat::DEFAULT_PRECISION,
+        value_parser = clap::builder::RangedI64ValueParser::<usize>::new().range(0..=9)
+    )]
+    size_precision: usize,
+
+    /// Disable coloured output (also honours the NO_COLOR environment variable)
+    #[clap(long)]
+    no_color: bool,
+
+    /// Write a JSON status line (files and bytes done and total, MB/s) to this named pipe
+    /// or file at a fixed interval, for a separate monitoring process
+    #[clap(long, value_name = "PATH")]
+    progress_pipe: Option<PathBuf>,
+
+    /// Milliseconds between --progress-pipe frames
+    #[clap(
+        long,
+        default_value_t = progress::DEFAULT_PIPE_INTERVAL_MS,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    progress_pipe_interval_ms: u64,
+
+    /// When stderr is not a terminal (cron, CI), print a status line with counts, rate and
+    /// ETA every S seconds instead of progress bars; 0 disables
+    #[clap(long, value_name = "S", default_value_t = progress::DEFAULT_STATUS_INTERVAL_SECS)]
+    progress_interval: u64,
+
+    /// Minimum milliseconds between progress-bar redraws
+    #[clap(long, default_value_t = progress::DEFAULT_REFRESH_MS)]
+    progress_refresh_ms: u64,
+
+    /// indicatif template for the file bar, e.g. "{pos}/{len} {wide_msg}"; there `{msg}` is
+    /// the file just finished. An invalid template warns and keeps the default
+    #[clap(long, value_name = "TEMPLATE")]
+    progress_template: Option<String>,
+
+    /// indicatif template for the byte bar; besides the indicatif keys it may use
+    /// `{smoothed_rate}` and `{smoothed_eta}`. An invalid template warns and keeps the default
+    #[clap(long, value_name = "TEMPLATE")]
+    bytes_template: Option<String>,
 }
 
-#[derive(Debug)]
-struct FileReport {
-    path: PathBuf,
+impl ScanArgs {
+    /// The first `--hash`: the digest in `hash_str`.
+    fn hash(&self) -> HashAlgorithm {
+        self.hash[0]
+    }
+
+    /// The rest of `--hash`, computed alongside the first.
+    fn extra_hashes(&self) -> &[HashAlgorithm] {
+        &self.hash[1..]
+    }
+
+    /// `--units` and `--size-precision`.
+    fn sizes(&self) -> SizeFormat {
+        SizeFormat::new(self.units, self.size_precision)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Progress banners and a summary
+    Human,
+    /// The `--output` JSON report
+    Json,
+    /// `<hex>  <path>` lines, interchangeable with b3sum/sha256sum
+    Checksums,
+    /// One row per file with a header line
+    Csv,
+    /// One JSON object per file, streamed as files complete
+    Ndjson,
+    /// `<hash><TAB><path>` lines, streamed as files complete; nothing else
+    HashPath,
+    /// The JSON report as YAML, built whole in memory at the end
+    Yaml,
+    /// The JSON report as TOML, built whole in memory at the end; fields
+    /// without a value are left out, as TOML has no null
+    Toml,
+    /// Nothing; pair with --output
+    None,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum FingerprintStyle {
+    /// All 64 hex digits
+    Full,
+    /// The first 7, like an abbreviated git commit
+    Short,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ReclaimFormat {
+    /// A headline in the summary
+    Human,
+    /// A JSON object on stdout, in place of the human output
+    Json,
+}
+
+#[derive(clap::Args)]
+struct DiffArgs {
+    /// Report from the earlier scan
+    old: PathBuf,
+    /// Report from the later scan
+    new: PathBuf,
+
+    /// Size units: `iec` (1024, KiB/MiB) or `si` (1000, kB/MB)
+    #[clap(long, value_enum, default_value_t = Units::Iec)]
+    units: Units,
+
+    /// Decimal places in printed sizes; exact values drop theirs, e.g. `4 GiB`
+    #[clap(
+        long,
+        value_name = "N",
+        default_value_t = SizeFormat::DEFAULT_PRECISION,
+        value_parser = clap::builder::RangedI64ValueParser::<usize>::new().range(0..=9)
+    )]
+    size_precision: usize,
+}
+
+#[derive(clap::Args)]
+struct BenchArgs {
+    /// Bytes of synthetic data per test
+    #[clap(
+        long,
+        value_name = "BYTES",
+        default_value_t = 256 << 20,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
     size: u64,
-    blake3_hex: Option<String>,
-    xor64_gpu: Option<u64>,
-    elapsed_ms: u128,
+
+    /// Time mmap+hash on this existing file instead of a freshly written one, which
+    /// is likely still in the page cache
+    #[clap(long, value_name = "FILE")]
+    file: Option<PathBuf>,
+
+    /// Directory for the scratch file (defaults to the system temp directory)
+    #[clap(long, value_name = "DIR", conflicts_with = "file")]
+    dir: Option<PathBuf>,
+
+    /// Repetitions of each test; the fastest is reported
+    #[clap(long, default_value_t = 3)]
+    rounds: usize,
+
+    /// Drop the file from the page cache before every round (Linux), so the mmap tests read
+    /// from disk; compares --populate with the default readahead advice on a cold cache
+    #[clap(long)]
+    cold: bool,
+
+    /// Also time the XOR64 reduction on the GPU (requires --features gpu) and on CPU
+    #[clap(long)]
+    gpu: bool,
+
+    /// Size and rate units: `iec` (1024, KiB/MiB) or `si` (1000, kB/MB)
+    #[clap(long, value_enum, default_value_t = Units::Iec)]
+    units: Units,
+
+    /// Decimal places in printed sizes; exact values drop theirs, e.g. `4 GiB`
+    #[clap(
+        long,
+        value_name = "N",
+        default_value_t = SizeFormat::DEFAULT_PRECISION,
+        value_parser = clap::builder::RangedI64ValueParser::<usize>::new().range(0..=9)
+    )]
+    size_precision: usize,
+
+    /// Disable coloured output
+    #[clap(long)]
+    no_color: bool,
 }
 
-fn physical_cpus() -> usize {
-    num_cpus::get_physical().max(1)
+#[derive(clap::Args)]
+struct SelftestArgs {
+    /// Directory for the fixture (defaults to the system temp directory)
+    #[clap(long, value_name = "DIR")]
+    dir: Option<PathBuf>,
+
+    /// Leave the fixture in place afterwards, to scan it by hand
+    #[clap(long)]
+    keep: bool,
+}
+
+fn parse_fraction(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(f) if f > 0.0 && f <= 1.0 => Ok(f),
+        Ok(_) => Err("must be greater than 0 and at most 1".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
 }
 
-/// Try to advise OS to prefetch the mapped region (POSIX madvise MADV_WILLNEED where supported)
-#[inline]
-fn advise_willneed(ptr: *const u8, len: usize) {
-    #[cfg(unix)]
-    unsafe {
-        // madvise tends to be available on Linux/BSD/macOS (value MADV_WILLNEED)
-        let res = libc::madvise(ptr as *mut _, len, libc::MADV_WILLNEED);
-        if res != 0 {
-            // ignore errors (best-effort)
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match cli.command.unwrap_or(Command::Scan(Box::new(cli.scan))) {
+        Command::Scan(args) => run_scan(*args),
+        Command::Diff(args) => run_diff(args).map(|()| Verdict::Ok),
+        Command::Bench(args) => run_bench(args).map(|()| Verdict::Ok),
+        Command::Selftest(args) => run_selftest(args),
+        Command::Schema => serde_json::to_string_pretty(&report_schema())
+            .map(|schema| {
+                println!("{}", schema);
+                Verdict::Ok
+            })
+            .map_err(Into::into),
+    };
+    match result {
+        Ok(verdict) => verdict.exit_code(),
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            Verdict::IoError.exit_code()
         }
     }
-    // On Windows and others we do nothing (memmap still helps).
 }
 
-/// Process a single file: mmap, advise, compute blake3, optional gpu xor.
-/// Returns a FileReport.
-fn process_file(
-    path: &Path,
-    min_bytes: u64,
-    use_gpu: bool,
-    gpu_ctx: Option<&gpu::GpuContext>,
-) -> anyhow::Result<FileReport> {
-    let start = Instant::now();
-    let meta = path.metadata()?;
-    let size = meta.len();
-    if size < min_bytes {
-        let elapsed = start.elapsed().as_millis();
-        return Ok(FileReport {
-            path: path.to_path_buf(),
-            size,
-            blake3_hex: None,
-            xor64_gpu: None,
-            elapsed_ms: elapsed,
-        });
+fn load_report(path: &Path) -> Result<ScanReport> {
+    let f = File::open(path).with_context(|| format!("opening report {:?}", path))?;
+    ScanReport::from_reader(std::io::BufReader::new(f))
+        .with_context(|| format!("parsing report {:?}", path))
+}
+
+fn run_diff(args: DiffArgs) -> Result<()> {
+    let sizes = SizeFormat::new(args.units, args.size_precision);
+    let old = load_report(&args.old)?;
+    let new = load_report(&args.new)?;
+
+    let old_by_path: BTreeMap<&Path, &FileReport> =
+        old.files.iter().map(|r| (r.path.as_path(), r)).collect();
+    let new_by_path: BTreeMap<&Path, &FileReport> =
+        new.files.iter().map(|r| (r.path.as_path(), r)).collect();
+
+    let added: Vec<&FileReport> = new_by_path
+        .iter()
+        .filter(|(p, _)| !old_by_path.contains_key(*p))
+        .map(|(_, r)| *r)
+        .collect();
+    let removed: Vec<&FileReport> = old_by_path
+        .iter()
+        .filter(|(p, _)| !new_by_path.contains_key(*p))
+        .map(|(_, r)| *r)
+        .collect();
+    let changed: Vec<(&FileReport, &FileReport)> = old_by_path
+        .iter()
+        .filter_map(|(p, o)| new_by_path.get(p).map(|n| (*o, *n)))
+        // reports written with different --hash-encoding still compare equal
+        .filter(|(o, n)| {
+            let old_hash = o.hash_str.as_deref().map(|h| old.hash_encoding.to_hex(h));
+            old_hash != n.hash_str.as_deref().map(|h| new.hash_encoding.to_hex(h))
+        })
+        .collect();
+
+    let old_bytes: u64 = old.files.iter().map(|r| r.size).sum();
+    let new_bytes: u64 = new.files.iter().map(|r| r.size).sum();
+    let delta = new_bytes as i128 - old_bytes as i128;
+
+    println!("Added ({}):", added.len());
+    for r in &added {
+        println!("  + {:>10}  {}", human_bytes(r.size as u128, sizes), r.path.display());
+    }
+    println!("Removed ({}):", removed.len());
+    for r in &removed {
+        println!("  - {:>10}  {}", human_bytes(r.size as u128, sizes), r.path.display());
+    }
+    println!("Changed ({}):", changed.len());
+    for (o, n) in &changed {
+        println!(
+            "  ~ {:>10} -> {:>10}  {}",
+            human_bytes(o.size as u128, sizes),
+            human_bytes(n.size as u128, sizes),
+            n.path.display()
+        );
     }
+    let sign = if delta < 0 { "-" } else { "+" };
+    println!(
+        "\nNet change: {}{} ({} -> {})",
+        sign,
+        human_bytes(delta.unsigned_abs(), sizes),
+        human_bytes(old_bytes as u128, sizes),
+        human_bytes(new_bytes as u128, sizes)
+    );
+    Ok(())
+}
 
-    // open file readonly
-    let f = File::open(path)?;
-    // memory-map entire file read-only (safe cross-platform)
-    let mmap = unsafe { MmapOptions::new().map(&f) }?;
-    let data = &mmap[..];
+fn run_selftest(args: SelftestArgs) -> Result<Verdict> {
+    let dir = args
+        .dir
+        .unwrap_or_else(std::env::temp_dir)
+        .join(format!("aivista-selftest-{}", std::process::id()));
+    selftest::create_fixture(&dir)?;
+    let checks = selftest::run(&dir);
+    if args.keep {
+        eprintln!("Fixture left in {:?}", dir);
+    } else {
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+    let mut verdict = Verdict::Ok;
+    for check in checks? {
+        if check.passed() {
+            println!("ok      {}", check.name);
+        } else {
+            verdict = Verdict::Mismatch;
+            println!(
+                "FAILED  {}: expected {:?}, got {:?}",
+                check.name, check.expected, check.actual
+            );
+        }
+    }
+    if verdict != Verdict::Ok {
+        eprintln!("WARNING: the self-test failed");
+    }
+    Ok(verdict)
+}
 
-    // advise OS to prefetch (best-effort)
-    advise_willneed(data.as_ptr(), data.len());
+fn run_bench(args: BenchArgs) -> Result<()> {
+    let units = args.units;
+    let sizes = SizeFormat::new(units, args.size_precision);
+    let len = usize::try_from(args.size).context("--size does not fit in memory")?;
+    eprintln!("Generating {} of test data...", human_bytes(args.size as u128, sizes));
+    let data = bench::synthetic_data(len, 0x9e37_79b9_7f4a_7c15);
+    let in_memory = bench::best_of("BLAKE3, in memory, 1 thread", args.size, args.rounds, || {
+        std::hint::black_box(blake3::hash(&data));
+        Ok(())
+    })?;
+    let mut results = vec![in_memory];
 
-    // Compute blake3 hash (super-fast, SIMD, streaming)
-    // For large maps, hashing the slice directly is fine.
-    let blake3_hex = {
-        // Use streaming hasher for consistency and small memory overhead
-        let mut hasher = blake3::Hasher::new();
-        hasher.update(data);
-        let hash = hasher.finalize();
-        Some(hash.to_hex().to_string())
+    // the same mmap path a scan takes, minus the walk and the channel
+    let scratch = match &args.file {
+        Some(_) => None,
+        None => {
+            let dir = args.dir.clone().unwrap_or_else(std::env::temp_dir);
+            let path = dir.join(format!("aivista-bench-{}.bin", std::process::id()));
+            std::fs::write(&path, &data).with_context(|| format!("writing {:?}", path))?;
+            Some(path)
+        }
+    };
+    let path = args.file.as_deref().or(scratch.as_deref()).expect("file or scratch");
+    let file_bytes = path.metadata().with_context(|| format!("reading {:?}", path))?.len();
+    let evict = || -> Result<()> {
+        if args.cold {
+            let f = File::open(path).with_context(|| format!("opening {:?}", path))?;
+            residency::evict(&f).context("dropping the file from the page cache")?;
+        }
+        Ok(())
+    };
+    let mmap = |name: &str, populate: bool| {
+        let opts = ProcessOptions {
+            reader: ReaderMode::Mmap,
+            populate,
+            ..Default::default()
+        };
+        bench::best_of_prepared(name, file_bytes, args.rounds, evict, || {
+            process_file(path, &opts).map(|_| ())
+        })
     };
+    let willneed = mmap("mmap + WILLNEED + BLAKE3 from disk", false);
+    let populate = mmap("mmap + POPULATE + BLAKE3 from disk", true);
+    if let Some(scratch) = &scratch {
+        let _ = std::fs::remove_file(scratch);
+    }
+    results.push(willneed?);
+    results.push(populate?);
 
-    // GPU optional quick XOR checksum (non-cryptographic)
-    let xor64_gpu = if use_gpu {
-        match gpu_ctx {
-            Some(ctx) => match ctx.xor64_for_file(data) {
-                Ok(v) => Some(v),
-                Err(_) => None,
-            },
-            None => None,
+    if args.gpu {
+        results.push(bench::best_of("XOR64, CPU", args.size, args.rounds, || {
+            std::hint::black_box(xor64_cpu(&data));
+            Ok(())
+        })?);
+        match gpu::GpuContext::with_queues(1) {
+            Ok(ctx) => results.push(bench::best_of("XOR64, GPU", args.size, args.rounds, || {
+                ctx.xor64_for_file(&data).map(|_| ())
+            })?),
+            Err(e) => eprintln!("[GPU] Skipping the GPU test: {:#}", e),
         }
+    }
+
+    let color = use_color(args.no_color);
+    let rate = format!("{}/s", units.mega());
+    let mut table = new_table(&["Test", "Size", "Time", &rate], &[1, 2, 3], color);
+    for r in &results {
+        table.add_row(vec![
+            r.name.clone(),
+            human_bytes(r.bytes as u128, sizes),
+            format!("{:.3} s", r.elapsed.as_secs_f64()),
+            format!("{:.1}", r.mega_per_sec(units)),
+        ]);
+    }
+    println!("{table}");
+    Ok(())
+}
+
+/// The mount table is only needed to resolve `--reader auto`.
+fn load_mounts(reader: ReaderMode) -> Option<MountTable> {
+    if reader == ReaderMode::Auto {
+        MountTable::load()
+    } else {
+        None
+    }
+}
+
+/// Print a single `<hash>  <name>` line, in the same layout as `sha256sum`; with
+/// several `--hash` algorithms, one tagged `<ALGO> (<name>) = <hash>` line each.
+fn hash_single(args: &ScanArgs, path: &Path) -> Result<()> {
+    let sizes = args.sizes();
+    if args.stdin {
+        let mut input = std::io::BufReader::new(std::io::stdin().lock());
+        let codec = args.decompress.codec(input.fill_buf().context("reading standard input")?);
+        let hasher = MultiHasher::new(args.hash(), args.extra_hashes(), &hash_mode(args)?);
+        let digests = match codec {
+            Some(codec) => decompress::hash_decoded(input, codec, hasher, args.hash_length)
+                .map(|(digests, _)| digests),
+            None => hash_reader(input, hasher).map(|h| h.finalize_hex(args.hash_length)),
+        }
+        .context("reading standard input")?;
+        let (hash, hashes) = encode_digests(&digests, args.hash_encoding);
+        print_digests(hash.as_deref().unwrap_or_default(), hashes.as_ref(), "-");
+        return Ok(());
+    }
+    let checkpoint = if args.resumable_hash {
+        Some(HashCheckpoint::load(&args.checkpoint)?)
     } else {
         None
     };
+    let mounts = load_mounts(args.reader);
+    let opts = ProcessOptions {
+        reader: args.reader,
+        checkpoint: checkpoint.as_ref(),
+        mounts: mounts.as_ref(),
+        sample: args.sample_hash.then_some(args.sample_bytes),
+        warm_only: args.warm_only,
+        digest_len: Some(args.hash_length),
+        hash_mode: hash_mode(args)?,
+        algorithm: args.hash(),
+        extra_algorithms: args.extra_hashes(),
+        range: args.range,
+        parallel_threshold: args.parallel_file_threshold,
+        encoding: args.hash_encoding,
+        decompress: args.decompress,
+        partial_on_error: args.partial_on_error,
+        ..Default::default()
+    };
+    let report = process_file(path, &opts).with_context(|| format!("processing file {:?}", path))?;
+    if let Some(reason) = report.skip_reason {
+        anyhow::bail!("{}: {}", path.display(), reason);
+    }
+    if let Some(partial) = report.partial_hash {
+        anyhow::bail!(
+            "{}: {}; partial hash of the bytes before it: {}",
+            path.display(),
+            report.error.unwrap_or_default(),
+            partial
+        );
+    }
+    if report.status == FileStatus::Warmed {
+        eprintln!("Warmed {} ({})", path.display(), human_bytes(report.size as u128, sizes));
+        return Ok(());
+    }
+    let hash = report.hash_str.or(report.signature).context("file was not hashed")?;
+    print_digests(&hash, report.hashes.as_ref(), &path.display().to_string());
+    Ok(())
+}
 
-    let elapsed = start.elapsed().as_millis();
-    Ok(FileReport {
-        path: path.to_path_buf(),
-        size,
-        blake3_hex,
-        xor64_gpu,
-        elapsed_ms: elapsed,
-    })
+/// `hash_single`'s output lines for `name`.
+fn print_digests(hash: &str, hashes: Option<&BTreeMap<HashAlgorithm, String>>, name: &str) {
+    match hashes {
+        Some(hashes) => {
+            for (algorithm, hash) in hashes {
+                println!("{} ({}) = {}", algorithm.name().to_uppercase(), name, hash);
+            }
+        }
+        None => println!("{}  {}", hash, name),
+    }
+}
+
+/// `--hash-key` / `--hash-context`, or plain BLAKE3.
+fn hash_mode(args: &ScanArgs) -> Result<HashMode> {
+    let keyed = args.hash_key.is_some() || args.hash_context.is_some();
+    if keyed && !args.hash.contains(&HashAlgorithm::Blake3) {
+        anyhow::bail!("--hash-key and --hash-context only apply to --hash blake3");
+    }
+    match (&args.hash_key, &args.hash_context) {
+        (Some(hex), _) => HashMode::keyed_from_hex(hex).context("invalid --hash-key"),
+        (None, Some(context)) => Ok(HashMode::derive_key(context)),
+        (None, None) => Ok(HashMode::Plain),
+    }
+}
+
+/// Re-hash `expected`, printing `OK`/`FAILED` per file like `sha256sum --check`.
+fn report_verification(
+    expected: &[Expected],
+    reader: ReaderMode,
+    algorithm: HashAlgorithm,
+    hash_mode: HashMode,
+) -> Verdict {
+    let mounts = load_mounts(reader);
+    let opts = ProcessOptions {
+        reader,
+        mounts: mounts.as_ref(),
+        hash_mode,
+        algorithm,
+        ..Default::default()
+    };
+    let results = verify::verify_all(expected, &opts);
+    let (mut failed, mut missing, mut unreadable) = (0usize, 0usize, 0usize);
+    for (e, checked) in expected.iter().zip(&results) {
+        match checked.verdict {
+            Verdict::Ok => println!("{}: OK", e.path.display()),
+            Verdict::Mismatch => {
+                failed += 1;
+                println!("{}: FAILED", e.path.display());
+            }
+            verdict => {
+                if verdict == Verdict::Missing {
+                    missing += 1;
+                } else {
+                    unreadable += 1;
+                }
+                eprintln!("{}: {}", e.path.display(), checked.error.as_deref().unwrap_or_default());
+                println!("{}: FAILED open or read", e.path.display());
+            }
+        }
+    }
+    if missing > 0 {
+        eprintln!("WARNING: {} listed file(s) are missing", missing);
+    }
+    if unreadable > 0 {
+        eprintln!("WARNING: {} listed file(s) could not be read", unreadable);
+    }
+    if failed > 0 {
+        eprintln!("WARNING: {} computed checksum(s) did NOT match", failed);
+    }
+    results.iter().map(|c| c.verdict).max().unwrap_or_default()
 }
 
-fn human_bytes(bytes: u128) -> String {
-    const UNITS: [&str; 6] = ["B", "KB", "MB", "GB", "TB", "PB"];
-    let mut b = bytes as f64;
-    let mut i = 0;
-    while b >= 1024.0 && i < UNITS.len() - 1 {
-        b /= 1024.0;
-        i += 1;
+/// Verify every entry of a checksums file.
+fn run_check(
+    list: &Path,
+    reader: ReaderMode,
+    algorithm: HashAlgorithm,
+    hash_mode: HashMode,
+) -> Result<Verdict> {
+    let text = std::fs::read_to_string(list).with_context(|| format!("reading {:?}", list))?;
+    let mut malformed = 0usize;
+    let expected: Vec<Expected> = text
+        .lines()
+        .filter(|l| !l.is_empty())
+        .filter_map(|line| {
+            let parsed = checksums::parse_line(line);
+            if parsed.is_none() {
+                malformed += 1;
+            }
+            parsed
+        })
+        .map(|(hash_hex, path)| Expected {
+            path,
+            size: None,
+            hash_hex,
+        })
+        .collect();
+    let mut verdict = report_verification(&expected, reader, algorithm, hash_mode);
+    if malformed > 0 {
+        eprintln!("WARNING: {} line(s) are improperly formatted", malformed);
+        // an entry that cannot be parsed cannot be shown to match
+        verdict = verdict.max(Verdict::Mismatch);
     }
-    format!("{:.2} {}", b, UNITS[i])
+    Ok(verdict)
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
+/// Verify every hashed file recorded in a JSON scan report, or every entry of
+/// a manifest, whose relative paths are taken from `root`.
+fn run_verify(
+    path: &Path,
+    root: &Path,
+    reader: ReaderMode,
+    algorithm: HashAlgorithm,
+    hash_mode: HashMode,
+) -> Result<Verdict> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("reading {:?}", path))?;
+    verify_document(&text, &format!("{:?}", path), root, reader, algorithm, hash_mode)
+}
 
+/// `run_verify` with the document fetched from a URL. Only a failed fetch
+/// gives `Verdict::Network`; a malformed document is an error like any other.
+fn run_verify_url(
+    url: &str,
+    args: &ScanArgs,
+    algorithm: HashAlgorithm,
+    hash_mode: HashMode,
+) -> Result<Verdict> {
+    let headers = webhook::header_map(&args.header).context("invalid --header")?;
+    let cache_dir = match (&args.manifest_cache, args.no_manifest_cache) {
+        (_, true) => None,
+        (Some(dir), false) => Some(dir.clone()),
+        (None, false) => remote::default_cache_dir(),
+    };
+    let fetched = match remote::fetch(url, headers, cache_dir.as_deref()) {
+        Ok(fetched) => fetched,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            return Ok(Verdict::Network);
+        }
+    };
+    if fetched.not_modified {
+        eprintln!("Manifest at {} is unchanged; using the local copy.", url);
+    }
+    verify_document(&fetched.text, url, &args.cache[0], args.reader, algorithm, hash_mode)
+}
+
+/// Verify against a manifest or JSON report; `source` names it in errors.
+fn verify_document(
+    text: &str,
+    source: &str,
+    root: &Path,
+    reader: ReaderMode,
+    algorithm: HashAlgorithm,
+    hash_mode: HashMode,
+) -> Result<Verdict> {
+    if let Ok(manifest) = serde_json::from_str::<Manifest>(text) {
+        manifest
+            .check(algorithm, &hash_mode)
+            .with_context(|| format!("checking manifest {}", source))?;
+        return Ok(report_verification(&manifest.expected(root), reader, algorithm, hash_mode));
+    }
+    let report = serde_json::from_str(text)
+        .map_err(anyhow::Error::from)
+        .and_then(ScanReport::from_json)
+        .with_context(|| format!("parsing report {}", source))?;
+    if report.hash_algorithm != algorithm {
+        anyhow::bail!(
+            "report {} was hashed with {}; pass --hash {}",
+            source,
+            report.hash_algorithm.name(),
+            report.hash_algorithm.name()
+        );
+    }
+    let expected: Vec<Expected> = report
+        .files
+        .iter()
+        // archive members have no path of their own to re-read
+        .filter(|r| r.member.is_none())
+        .filter_map(|r| {
+            r.hash_str.as_ref().map(|hash| Expected {
+                path: r.path.clone(),
+                size: Some(r.size),
+                hash_hex: report.hash_encoding.to_hex(hash),
+            })
+        })
+        .collect();
+    let unhashed = report.files.len() - expected.len();
+    if unhashed > 0 {
+        eprintln!("Skipping {} report entries without a hash or outside the filesystem.", unhashed);
+    }
+    Ok(report_verification(&expected, reader, algorithm, hash_mode))
+}
+
+/// What manifest paths are relative to: the root itself, or for a lone file
+/// the directory holding it, so the file is listed by name.
+fn manifest_root(root: &Path) -> &Path {
+    match root.parent() {
+        Some(parent) if root.is_file() => parent,
+        _ => root,
+    }
+}
+
+/// One `--watch` line: the file's size, the start of its hash, and its path.
+fn print_change(change: &Change, sizes: SizeFormat) {
+    let short =
+        |hash: &Option<String>| manifest::short_id(hash.as_deref().unwrap_or("-")).to_owned();
+    let size = |bytes: &u64| human_bytes(*bytes as u128, sizes);
+    match change {
+        Change::Added { path, size: bytes, hash } => {
+            println!("+ {:>10}  {:<7}  {}", size(bytes), short(hash), path.display())
+        }
+        Change::Changed { path, old_size, size: bytes, old_hash, hash } => println!(
+            "~ {:>10}  {:<7}  {} (was {}, {})",
+            size(bytes),
+            short(hash),
+            path.display(),
+            size(old_size),
+            short(old_hash)
+        ),
+        Change::Removed { path, size: bytes } => {
+            println!("- {:>10}  {:<7}  {}", size(bytes), "", path.display())
+        }
+        Change::Failed { path, error } => {
+            eprintln!("[WARN] Error processing {:?}: {}", path, error)
+        }
+    }
+}
+
+/// Every destination the flags ask for, fed by the aggregator when it streams
+/// and given the merged reports in `finish`.
+fn build_sink(args: &ScanArgs, order: ReportOrder, human: bool) -> Result<Box<dyn ReportSink>> {
+    let mut sinks: Vec<Box<dyn ReportSink>> = Vec::new();
+    if human {
+        sinks.push(Box::new(HumanSummary {
+            order,
+            slowest: args.slowest,
+            show_orphans: args.find_orphans,
+            show_symlinks: args.check_symlinks || args.fix_symlinks,
+            show_size_collisions: args.size_collisions,
+            show_hardlinks: args.hardlink_report,
+            show_suspicious: args.flag_suspicious,
+            show_mismatches: args.detect_mismatch,
+            show_pytorch: args.inspect_pytorch,
+            stats: args.stats,
+            tree_depth: args.tree.then_some(args.depth),
+            sizes: args.sizes(),
+            color: use_color(args.no_color),
+        }));
+    }
+    match args.format {
+        // the human summary is only pushed when nothing else claims stdout
+        OutputFormat::Human => {}
+        OutputFormat::Json => sinks.push(Box::new(JsonSink::stdout())),
+        OutputFormat::Checksums => sinks.push(Box::new(ChecksumsSink::stdout())),
+        OutputFormat::Csv => sinks.push(Box::new(CsvSink::stdout())),
+        OutputFormat::Ndjson if args.reproducible => {
+            sinks.push(Box::new(NdjsonSink::stdout().in_report_order()))
+        }
+        OutputFormat::Ndjson => sinks.push(Box::new(NdjsonSink::stdout())),
+        OutputFormat::HashPath => sinks.push(Box::new(HashPathSink::stdout())),
+        OutputFormat::Yaml => sinks.push(Box::new(DocumentSink::stdout(DocFormat::Yaml))),
+        OutputFormat::Toml => sinks.push(Box::new(DocumentSink::stdout(DocFormat::Toml))),
+        OutputFormat::None => sinks.push(Box::new(NoopSink)),
+    }
+    if args.reclaim_report == ReclaimFormat::Json {
+        sinks.push(Box::new(ReclaimJsonSink));
+    }
+    if let Some(path) = &args.output {
+        sinks.push(Box::new(JsonSink::create(path)?));
+    }
+    if let Some(url) = &args.webhook {
+        sinks.push(Box::new(WebhookSink::new(url, &args.webhook_header, args.webhook_required)?));
+    }
+    if let Some(path) = &args.sqlite {
+        sinks.push(Box::new(SqliteSink::open(
+            path,
+            manifest_root(&args.cache[0]),
+            args.hash(),
+            hash_mode(args)?,
+            args.hash_encoding,
+        )?));
+    }
+    Ok(Box::new(Tee(sinks)))
+}
+
+fn run_scan(mut args: ScanArgs) -> Result<Verdict> {
+    let units = args.units;
+    let sizes = args.sizes();
     let start_all = Instant::now();
 
-    if !args.cache.exists() {
-        anyhow::bail!("Cache path {:?} does not exist", args.cache);
+    if let Some(twice) = args.hash.iter().enumerate().find(|(i, a)| args.hash[..*i].contains(a)) {
+        anyhow::bail!("--hash lists {} twice", twice.1.name());
+    }
+    // a zip is scanned as a one-file cache whose only file is opened up
+    if let Some(zip) = args.zip.take() {
+        if ArchiveKind::detect(&zip) != Some(ArchiveKind::Zip) {
+            anyhow::bail!("--zip {:?}: the archive's name must end in .zip", zip);
+        }
+        args.cache = vec![zip];
+        args.archives = true;
+    }
+    if args.only_hash {
+        args.format = OutputFormat::HashPath;
+    }
+    if let Some(list) = &args.check {
+        return run_check(list, args.reader, args.hash(), hash_mode(&args)?);
+    }
+    if let Some(report) = &args.verify {
+        return run_verify(report, &args.cache[0], args.reader, args.hash(), hash_mode(&args)?);
+    }
+    if let Some(url) = &args.verify_url {
+        return run_verify_url(url, &args, args.hash(), hash_mode(&args)?);
+    }
+    if args.reclaim_report == ReclaimFormat::Json && args.format != OutputFormat::Human {
+        anyhow::bail!("--reclaim-report json cannot be combined with another --format on stdout");
+    }
+    // checked before the report file is created, which would truncate stdout's file
+    if let Some(path) = args.output.as_deref().filter(|p| sink::is_stdout(p)) {
+        if args.format != OutputFormat::None {
+            anyhow::bail!(
+                "--output {:?} is this run's stdout, where --format also writes; pass \
+                 --format none or write the report elsewhere",
+                path
+            );
+        }
+    }
+    // --fingerprint-only leaves stdout and stderr to its one line, and warnings
+    let quiet = args.fingerprint_only.is_some();
+    // only the human format may print banners; other formats are pure data on stdout
+    let human = args.format == OutputFormat::Human
+        && args.reclaim_report == ReclaimFormat::Human
+        && !quiet;
+
+    if args.max_bytes.is_some_and(|max| max < args.min_bytes) {
+        anyhow::bail!("--max-bytes must not be below --min-bytes");
+    }
+    let roots = &args.cache;
+    if args.emit_manifest.is_some() && roots.len() > 1 {
+        anyhow::bail!("--emit-manifest paths are relative to one root; pass a single --cache");
+    }
+    if args.fingerprint_only.is_some() && roots.len() > 1 {
+        anyhow::bail!("--fingerprint-only hashes paths relative to one root; pass one --cache");
+    }
+    if args.watch && (args.stdin || args.file_list.is_some() || quiet) {
+        anyhow::bail!("--watch follows the --cache roots; it cannot follow --stdin, a --file-list \
+                       or --fingerprint-only");
+    }
+    if !args.stdin && args.file_list.is_none() {
+        if let Some(missing) = roots.iter().find(|r| !r.exists()) {
+            anyhow::bail!("Cache path {:?} does not exist", missing);
+        }
+    }
+    // a lone file or stream skips the walk, progress bars and aggregator entirely;
+    // a file whose report, manifest or fingerprint was asked for is scanned as a
+    // one-file cache instead, so none of those are silently dropped
+    let wants_report = !human
+        || args.output.is_some()
+        || args.emit_manifest.is_some()
+        || args.webhook.is_some()
+        || args.exec.is_some()
+        || args.watch;
+    if args.file_list.is_none() {
+        match roots.as_slice() {
+            _ if args.stdin => return hash_single(&args, Path::new("-")).map(|()| Verdict::Ok),
+            [only] if only.is_file()
+                && !wants_report
+                && !(args.archives && ArchiveKind::detect(only).is_some()) =>
+            {
+                return hash_single(&args, only).map(|()| Verdict::Ok)
+            }
+            _ => {}
+        }
     }
 
     // Determine number of threads
-    let num_workers = args
-        .jobs
-        .unwrap_or_else(|| physical_cpus().saturating_mul(1)); // 1x physical cores
-    rayon::ThreadPoolBuilder::new()
-        .num_threads(num_workers)
-        .build_global()
-        .context("Failed to initialize rayon thread pool")?;
+    let num_workers = args.jobs.unwrap_or(Jobs::Auto).resolve();
+    let mut pool = rayon::ThreadPoolBuilder::new().num_threads(num_workers);
+    if args.numa_aware {
+        let nodes = affinity::numa_nodes();
+        let cores = affinity::worker_cores(&nodes);
+        if cores.is_empty() {
+            eprintln!("[WARN] Cannot list CPU cores here; --numa-aware leaves threads unpinned.");
+        } else {
+            if args.verbose {
+                eprintln!(
+                    "[INFO] Pinning {} workers over {} core(s) on {} NUMA node(s).",
+                    num_workers,
+                    cores.len(),
+                    nodes.len().max(1)
+                );
+            }
+            // more workers than cores share them round-robin
+            pool = pool.start_handler(move |i| {
+                affinity::pin(cores[i % cores.len()]);
+            });
+        }
+    }
+    pool.build_global().context("Failed to initialize rayon thread pool")?;
 
-    println!(
-        "Scanning cache: {:?}  (workers={})",
-        args.cache, num_workers
-    );
+    // stdout carries only the requested format; banners and logs go to stderr
+    match &args.file_list {
+        _ if quiet => {}
+        Some(list) => eprintln!("Reading file list: {:?}  (workers={})", list, num_workers),
+        None => {
+            for root in roots {
+                eprintln!("Scanning cache: {:?}  (workers={})", root, num_workers);
+            }
+        }
+    }
 
-    // Gather files first (cheap), then parallel process with progress bar
-    let mut files: Vec<PathBuf> = Vec::new();
-    for entry in WalkDir::new(&args.cache).into_iter().filter_map(|e| e.ok()) {
-        if entry.file_type().is_file() {
-            files.push(entry.into_path());
+    let error_log = args.error_log.as_deref().map(ErrorLog::create).transpose()?;
+
+    // writes need the headroom; fail before spending time on the scan
+    let mutating = args.confirm && (args.dedup_action != DedupAction::Report || args.fix_symlinks);
+    // a quick hash match is only a hint, so files are compared before being replaced
+    let dedups = mutating && args.dedup_action != DedupAction::Report;
+    if dedups && !args.hash().is_cryptographic() && !args.paranoid {
+        anyhow::bail!(
+            "--hash {} cannot prove two files equal; add --paranoid to compare them byte \
+             for byte before --dedup-action changes anything",
+            args.hash().name()
+        );
+    }
+    if dedups && args.hash_length < MIN_DEDUP_DIGEST_LEN && !args.paranoid {
+        anyhow::bail!(
+            "a {}-byte digest is too short to prove two files equal; add --paranoid or use \
+             --hash-length {} or more before --dedup-action changes anything",
+            args.hash_length,
+            MIN_DEDUP_DIGEST_LEN
+        );
+    }
+    if let (true, Some(min)) = (mutating, args.min_free_bytes) {
+        for root in roots {
+            diskspace::ensure_free(root, min, sizes)?;
+        }
+    }
+
+    let layouts: Vec<Layout> = roots.iter().map(|r| args.layout.resolve(r)).collect();
+    if args.find_orphans {
+        if let Some((root, layout)) = roots.iter().zip(&layouts).find(|(_, l)| **l != Layout::Hf) {
+            anyhow::bail!(
+                "--find-orphans needs a HuggingFace cache, but {:?} has the {} layout",
+                root,
+                layout.describe()
+            );
         }
     }
+    // shown in the summary; roots that disagree leave it unresolved
+    let layout = match layouts.split_first() {
+        Some((first, rest)) if rest.iter().all(|l| l == first) => *first,
+        _ => Layout::Auto,
+    };
 
-    files.sort(); // deterministic order
+    // Gather files first (cheap), then parallel process with progress bar
+    let open_for_write = args.skip_open_for_write.then(OpenForWrite::scan);
+    let no_proc = matches!(open_for_write, Some(None));
+    if no_proc {
+        eprintln!("[WARN] --skip-open-for-write needs /proc; relying on the mtime window.");
+    }
+    let incomplete = (!args.no_skip_incomplete || no_proc).then(|| walk::IncompleteFilter {
+        suffixes: if args.incomplete_suffixes.is_empty() {
+            walk::DEFAULT_INCOMPLETE_SUFFIXES.iter().map(|s| s.to_string()).collect()
+        } else {
+            args.incomplete_suffixes.clone()
+        },
+        settle: Duration::from_secs(args.settle_secs),
+    });
+    let resolve_symlinks = args.find_orphans || args.check_symlinks || args.fix_symlinks;
+    let mut timings = PhaseTimings::default();
+    let walked = timings.time("walk", || -> Result<walk::WalkOutcome> {
+        let mut walked = walk::WalkOutcome::default();
+        match &args.file_list {
+            // an explicit list bypasses the walk and its filters entirely
+            Some(list) => walked.files = walk::read_file_list(list)?,
+            None => {
+                for root in roots {
+                    let walk_opts = walk::WalkOptions {
+                        ignore: walk::load_ignore(root, args.ignore_file.as_deref())?,
+                        incomplete: incomplete.clone(),
+                        resolve_symlinks,
+                        symlinks: args.symlinks,
+                    };
+                    walked.merge(walk::collect_files(root, &walk_opts));
+                }
+            }
+        }
+        if !args.no_canonicalize {
+            walked.dedup_canonical(args.symlinks);
+        }
+        Ok(walked)
+    })?;
+    let symlinks = walked.symlinks;
+    let walk_errors = walked.errors;
+    let mut files = walked.files;
+    let mut skipped_open = 0;
+    if let Some(Some(open)) = &open_for_write {
+        let before = files.len();
+        files.retain(|p| {
+            let Some(writer) = open.writer(p) else {
+                return true;
+            };
+            if args.verbose {
+                eprintln!(
+                    "[INFO] Skipping {:?}: open for writing by {} (pid {}).",
+                    p, writer.command, writer.pid
+                );
+            }
+            false
+        });
+        skipped_open = before - files.len();
+    }
+    let (files, sample) = match args.sample_fraction {
+        Some(fraction) => {
+            let population_files = files.len();
+            let files = walk::sample_files(files, fraction, args.sample_seed);
+            eprintln!(
+                "Sampling {} of {} files ({:.1}%, seed {}).",
+                files.len(),
+                population_files,
+                fraction * 100.0,
+                args.sample_seed
+            );
+            let info = SampleInfo {
+                fraction,
+                seed: args.sample_seed,
+                population_files,
+            };
+            (files, Some(info))
+        }
+        None => (files, None),
+    };
 
+    let sized: Vec<(PathBuf, u64)> = timings.time("size estimation", || {
+        let sized = |p: PathBuf| {
+            let size = args.symlinks.size_of(&p);
+            (p, size)
+        };
+        files.into_iter().map(sized).collect()
+    });
+    // files over --max-bytes are never queued, so the estimates cover only the window
+    let (mut sized, too_large): (Vec<_>, Vec<_>) = sized
+        .into_iter()
+        .partition(|(_, size)| args.max_bytes.is_none_or(|max| *size <= max));
+    if !args.no_size_sort {
+        chunks::sort_largest_first(&mut sized);
+    }
+    let limited_from = args.limit.filter(|&n| n < sized.len()).map(|n| {
+        let eligible = sized.len();
+        sized.truncate(n);
+        eligible
+    });
+    let (files, file_sizes): (Vec<PathBuf>, Vec<u64>) = sized.into_iter().unzip();
     let total_files = files.len();
-    let total_bytes_est: u128 = files
-        .iter()
-        .filter_map(|p| p.metadata().ok().map(|m| m.len() as u128))
-        .sum();
+    let total_bytes_est: u128 = file_sizes.iter().map(|&s| s as u128).sum();
 
-    println!(
-        "Found {} files, ~{} total.",
-        total_files,
-        human_bytes(total_bytes_est)
-    );
+    if !quiet {
+        match limited_from {
+            Some(eligible) => eprintln!(
+                "Found {} files; processing the first {} (--limit), ~{} total.",
+                eligible,
+                total_files,
+                human_bytes(total_bytes_est, sizes)
+            ),
+            None => eprintln!(
+                "Found {} files, ~{} total.",
+                total_files,
+                human_bytes(total_bytes_est, sizes)
+            ),
+        }
+    }
+    if walked.collapsed > 0 && !quiet {
+        eprintln!(
+            "Collapsed {} paths that reach a file already queued under another path.",
+            walked.collapsed
+        );
+    }
+    if walked.skipped_incomplete > 0 && !quiet {
+        eprintln!(
+            "Skipped {} incomplete or still-downloading files.",
+            walked.skipped_incomplete
+        );
+    }
+    if skipped_open > 0 && !quiet {
+        eprintln!(
+            "Skipped {} files open for writing by another process{}",
+            skipped_open,
+            if args.verbose { "." } else { " (list them with --verbose)." }
+        );
+    }
+    if !walk_errors.is_empty() {
+        eprintln!(
+            "[WARN] {} path(s) could not be walked; the files under them are not in the scan{}",
+            walk_errors.len(),
+            if args.report_walk_errors { ":" } else { " (list them with --report-walk-errors)." }
+        );
+        if args.report_walk_errors {
+            for e in &walk_errors {
+                eprintln!("  {}: {}", e.path.display(), e.error);
+            }
+        }
+    }
+    if !too_large.is_empty() && !quiet {
+        eprintln!(
+            "Left out {} files larger than --max-bytes ({} in all).",
+            too_large.len(),
+            human_bytes(too_large.iter().map(|(_, size)| *size as u128).sum(), sizes)
+        );
+    }
 
-    // Possibly initialize GPU context
+    // Possibly initialize GPU context, only when some file will get a full hash and XOR
+    let xor_work = files.iter().zip(&file_sizes).any(|(p, &size)| {
+        size > 0 && size >= args.min_bytes && !(args.archives && ArchiveKind::detect(p).is_some())
+    });
+    let want_gpu = args.gpu && !args.warm_only && !args.sample_hash && xor_work;
+    if args.gpu && !want_gpu {
+        eprintln!("[GPU] No file needs an XOR checksum; skipping OpenCL init.");
+    }
+    let gpu_start = Instant::now();
     #[cfg(feature = "gpu")]
-    let gpu_ctx = if args.gpu {
-        match gpu::GpuContext::try_new() {
+    let gpu_ctx = if want_gpu {
+        match gpu::GpuContext::with_queues(num_workers) {
             Ok(ctx) => {
-                println!("[GPU] OpenCL GPU context available. GPU warmup enabled.");
+                eprintln!("[GPU] OpenCL GPU context available. GPU warmup enabled.");
                 Some(Arc::new(ctx))
             }
             Err(e) => {
-                println!("[GPU] OpenCL init failed (falling back to CPU only): {:?}", e);
+                eprintln!("[GPU] OpenCL init failed (falling back to CPU only): {:?}", e);
                 None
             }
         }
@@ -318,134 +1684,264 @@ fn main() -> Result<()> {
         None
     };
     #[cfg(not(feature = "gpu"))]
-    let gpu_ctx: Option<Arc<()>> = None;
+    let gpu_ctx: Option<Arc<gpu::GpuContext>> = {
+        if want_gpu {
+            eprintln!("[GPU] Built without the `gpu` feature; computing XOR checksums on CPU.");
+        }
+        None
+    };
+    timings.record("gpu init", gpu_start.elapsed());
+    let provenance = Provenance::collect(num_workers, gpu_ctx.is_some(), args.include_hostname);
 
     // Prepare multi-progress bars
-    let m = MultiProgress::new();
+    let m = MultiProgress::with_draw_target(if quiet {
+        ProgressDrawTarget::hidden()
+    } else {
+        progress::draw_target(args.progress_refresh_ms)
+    });
     let pb_files = m.add(ProgressBar::new(total_files as u64));
     pb_files.set_style(
-        ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} files")
-            .unwrap()
-            .progress_chars("#>-"),
+        progress::bar_style(
+            args.progress_template.as_deref(),
+            progress::FILES_TEMPLATE,
+            "--progress-template",
+        )
+        .progress_chars("#>-"),
     );
+    // only a custom template can show the file just finished; skip the work otherwise
+    let show_current = args.progress_template.as_deref().is_some_and(|t| t.contains("msg"));
 
     let pb_bytes = m.add(ProgressBar::new(total_bytes_est as u64));
     pb_bytes.set_style(
-        ProgressStyle::with_template("{msg} {bytes:>7}/{total_bytes:7} {eta_precise}")
-            .unwrap()
+        progress::bar_style(
+            args.bytes_template.as_deref(),
+            progress::bytes_template(units),
+            "--bytes-template",
+        )
+        .with_key("smoothed_rate", SmoothedRate::rate(units))
+            .with_key("smoothed_eta", SmoothedRate::eta())
             .progress_chars("=>-"),
     );
     pb_bytes.set_message("scanned bytes:");
+    // steady ticks keep the spinner and ETA moving while one large file hashes
+    pb_files.enable_steady_tick(Duration::from_millis(args.progress_refresh_ms.max(1)));
+    pb_bytes.enable_steady_tick(Duration::from_millis(args.progress_refresh_ms.max(1)));
 
     // Channels for results aggregation
-    let (tx, rx): (Sender<FileReport>, Receiver<FileReport>) = bounded(1024);
+    let (tx, rx): (Sender<FileReport>, Receiver<FileReport>) = match args.channel_cap {
+        0 => unbounded(),
+        cap => bounded(cap),
+    };
+    // sends that found the channel full, i.e. workers stalled on the aggregator
+    let send_blocked = AtomicU64::new(0);
 
     // Atomic counters
-    let total_processed = Arc::new(AtomicU64::new(0));
-    let total_bytes_processed = Arc::new(AtomicU64::new(0));
+    let counters = progress::Counters::default();
+    let total_processed = Arc::clone(&counters.files);
+    let total_bytes_processed = Arc::clone(&counters.bytes);
+    let pipe_reporter = args.progress_pipe.as_deref().map(|path| {
+        progress::PipeReporter::start(
+            path,
+            Duration::from_millis(args.progress_pipe_interval_ms),
+            counters.clone(),
+            total_files as u64,
+            total_bytes_est as u64,
+        )
+    });
+
+    let status_reporter = (args.progress_interval > 0
+        && !quiet
+        && !std::io::stderr().is_terminal())
+    .then(|| {
+        progress::StatusReporter::start(
+            Duration::from_secs(args.progress_interval),
+            counters.clone(),
+            total_files as u64,
+            total_bytes_est as u64,
+            units,
+        )
+    });
 
-    // Start a background aggregator thread to collect results and update progress bars
+    // Start a background aggregator thread when reports stream to a sink or --exec
     let agg_total_files = total_files;
-    let agg_total_bytes = total_bytes_est as u64;
-    let agg_handle = {
-        let pb_files = pb_files.clone();
-        let pb_bytes = pb_bytes.clone();
-        let total_processed = Arc::clone(&total_processed);
-        let total_bytes_processed = Arc::clone(&total_bytes_processed);
+    let bytes_estimate = total_bytes_est as u64;
+    let order = ReportOrder::new(args.sort, args.sort_desc);
+    let archives = args.archives;
+    let reproducible = args.reproducible;
+    let sink = build_sink(&args, order, human)?;
+    let exec_hook = match &args.exec {
+        Some(template) => Some(ExecHook::start(ExecTemplate::parse(template)?, args.exec_jobs)),
+        None => None,
+    };
+    // every report is kept only when some output lists them all; otherwise the
+    // workers fold them into the totals and keep just what the post-passes read:
+    // problem files, and files sharing a size with another (the only ones that
+    // can be duplicates or size collisions)
+    let retain_all =
+        !matches!(args.format, OutputFormat::Human | OutputFormat::None | OutputFormat::HashPath)
+        || args.output.is_some()
+        || args.emit_manifest.is_some()
+        || quiet
+        || args.webhook.is_some()
+        || args.sqlite.is_some()
+        || args.find_orphans
+        || args.hardlink_report
+        || args.min_free_bytes.is_some()
+        || args.watch
+        // members are sized only once read, so none is known to share a size
+        || (args.size_collisions && archives);
+    let shared_sizes: Arc<HashSet<u64>> = Arc::new(if retain_all {
+        HashSet::new()
+    } else {
+        let mut seen = HashSet::with_capacity(file_sizes.len());
+        file_sizes.iter().filter(|&&size| !seen.insert(size)).copied().collect()
+    });
+    let mut tally = Tally::new(order, args.slowest, args.stats);
+    if args.tree {
+        tally = tally.with_tree(roots, args.depth);
+    }
+    let model_tallies: Vec<ModelTally> =
+        roots.iter().zip(&layouts).map(|(root, layout)| ModelTally::new(*layout, root)).collect();
+    // each worker folds its reports into its own shard of the totals and of the
+    // retained reports, merged once hashing ends; only a sink that writes reports
+    // as they arrive, or --exec, needs them funnelled through one aggregator
+    let shards = Shards::new((tally, model_tallies, Vec::new()), num_workers);
+    let keep = move |rep: &FileReport| {
+        retain_all
+            || matches!(
+                rep.status,
+                FileStatus::Errored | FileStatus::Vanished | FileStatus::PartiallyHashed
+            )
+            || rep.skip_reason.is_some()
+            || shared_sizes.contains(&rep.size)
+    };
+    let streams = sink.streams() || exec_hook.is_some();
+    let mut outputs = Some((sink, exec_hook));
+    let agg_handle = streams.then(|| {
+        let (mut sink, exec_hook) = outputs.take().expect("outputs are taken once");
+        let keep = keep.clone();
         std::thread::spawn(move || {
             let mut reports: Vec<FileReport> = Vec::with_capacity(agg_total_files.min(1000));
-            let mut largest: Vec<(u64, PathBuf)> = Vec::new();
+            let mut sink_error: Option<anyhow::Error> = None;
+            let mut busy = Duration::ZERO;
             while let Ok(rep) = rx.recv() {
-                // update counters
-                total_processed.fetch_add(1, Ordering::Relaxed);
-                total_bytes_processed.fetch_add(rep.size as u64, Ordering::Relaxed);
-
-                // update PBs
-                pb_files.inc(1);
-                pb_bytes.inc(rep.size);
-
-                // keep some aggregated info
-                largest.push((rep.size, rep.path.clone()));
-                if largest.len() > 256 {
-                    // keep top 256 largest
-                    largest.sort_by_key(|(s, _p)| Reverse(*s));
-                    largest.truncate(256);
+                let handled = Instant::now();
+                // keep draining after a failed write so workers never block on the channel
+                if sink_error.is_none() {
+                    sink_error = sink.emit(&rep).err();
                 }
-
-                reports.push(rep);
-            }
-
-            // finalize
-            pb_files.finish_with_message("files processed");
-            pb_bytes.finish_with_message("bytes processed");
-            reports.sort_by_key(|r| Reverse(r.size));
-            // assemble a short summary
-            let total_files = reports.len();
-            let total_bytes: u128 = reports.iter().map(|r| r.size as u128).sum();
-            println!("\n--- Summary ---");
-            println!("Processed files: {}", total_files);
-            println!("Total bytes processed: {}", human_bytes(total_bytes));
-            if !reports.is_empty() {
-                println!("\nTop 10 largest files:");
-                for r in reports.iter().take(10) {
-                    println!(
-                        "  {:>8}  {}",
-                        human_bytes(r.size as u128),
-                        r.path.display()
-                    );
+                if let Some(hook) = &exec_hook {
+                    hook.submit(&rep);
                 }
+                if keep(&rep) {
+                    reports.push(rep);
+                }
+                busy += handled.elapsed();
             }
-            // return reports via thread results? we'll just print summary here.
+            (reports, sink, sink_error, busy, exec_hook)
         })
-    };
+    });
 
     // Kick off parallel processing using rayon parallel iterator but send results to aggregator channel
     let tx_arc = Arc::new(tx);
-    let use_gpu_flag = args.gpu;
-    let min_bytes = args.min_bytes;
-    let warm_only = args.warm_only;
+    let checkpoint = if args.resumable_hash {
+        Some(HashCheckpoint::load(&args.checkpoint)?)
+    } else {
+        None
+    };
+    let mounts = if args.by_mount {
+        let table = MountTable::load();
+        if table.is_none() {
+            eprintln!("[WARN] No /proc/mounts here; --by-mount has nothing to report.");
+        }
+        table
+    } else {
+        load_mounts(args.reader)
+    };
+    let budget = args.max_mem.map(MemoryBudget::new);
+    let base_opts = ProcessOptions {
+        min_bytes: args.min_bytes,
+        max_bytes: args.max_bytes,
+        use_gpu: args.gpu,
+        reader: args.reader,
+        // the shared context hands each thread its own command queue
+        gpu: gpu_ctx.as_deref(),
+        checkpoint: checkpoint.as_ref(),
+        mounts: mounts.as_ref(),
+        budget: budget.as_ref(),
+        sample: args.sample_hash.then_some(args.sample_bytes),
+        warm_only: args.warm_only,
+        compress_probe: args.compressibility_estimate.then_some(args.compress_probe_bytes),
+        digest_len: Some(args.hash_length),
+        hash_mode: hash_mode(&args)?,
+        algorithm: args.hash(),
+        extra_algorithms: args.extra_hashes(),
+        range: args.range,
+        sparse_aware: args.sparse_aware,
+        parallel_threshold: args.parallel_file_threshold,
+        measure_residency: args.measure_residency,
+        encoding: args.hash_encoding,
+        record_symlinks: args.symlinks == SymlinkPolicy::Record,
+        decompress: args.decompress,
+        partial_on_error: args.partial_on_error,
+        populate: args.populate,
+    };
 
     // Parallel iterate over files in chunks to avoid overwhelming rayon with channel ops
-    files.par_chunks(128).for_each(|chunk| {
-        // chunk processed on this thread
-        // Prepare optional gpu context clone for this thread
-        #[cfg(feature = "gpu")]
-        let local_gpu = gpu_ctx.clone();
-
-        for p in chunk {
-            // process file with best-effort error handling
-            match (|| -> Result<FileReport> {
-                #[cfg(feature = "gpu")]
-                {
-                    let gpu_ref = local_gpu.as_ref().and_then(|a| a.as_ref());
-                    // convert Arc<gpu::GpuContext> to Option<&gpu::GpuContext> for passing
-                    let gpu_ctx_ref = gpu_ref.map(|arc_ctx| &**arc_ctx);
-                    process_file(p, min_bytes, use_gpu_flag, gpu_ctx_ref)
-                        .with_context(|| format!("processing file {:?}", p))
-                }
-                #[cfg(not(feature = "gpu"))]
-                {
-                    let _ = &use_gpu_flag; // unused
-                    process_file(p, min_bytes, false, None)
-                        .with_context(|| format!("processing file {:?}", p))
-                }
-            }) {
-                Ok(report) => {
-                    let _ = tx_arc.send(report);
-                }
-                Err(e) => {
-                    // send a minimal report for error, still count file as processed
-                    let err_report = FileReport {
-                        path: p.clone(),
-                        size: p.metadata().map(|m| m.len()).unwrap_or(0),
-                        blake3_hex: None,
-                        xor64_gpu: None,
-                        elapsed_ms: 0,
-                    };
-                    let _ = tx_arc.send(err_report);
-                    eprintln!("[WARN] Error processing {:?}: {:?}", p, e);
-                }
+    let chunking = args.work_chunk.map_or(Chunking::SizeAware, Chunking::Fixed);
+    let work = chunks::plan(&file_sizes, chunking, num_workers);
+    if args.verbose {
+        eprintln!("[INFO] {} files in {} chunks ({:?}).", files.len(), work.len(), chunking);
+    }
+    let worker = WorkerOptions {
+        process: base_opts,
+        archives,
+        roots,
+        tag_mounts: mounts.as_ref().filter(|_| args.by_mount),
+        flag_suspicious: args.flag_suspicious,
+        detect_mismatch: args.detect_mismatch,
+        inspect_pytorch: args.inspect_pytorch,
+        progress: None,
+    };
+    let hashing_start = Instant::now();
+    scan::process_files(&files, work, &worker, &CancellationToken::new(), |report| {
+        if let Some(e) = report.error.as_ref().filter(|_| error_log.is_none()) {
+            eprintln!("[WARN] Error processing {:?}: {}", report.path, e);
+        }
+        let finished = Instant::now();
+        total_processed.fetch_add(1, Ordering::Relaxed);
+        let done = total_bytes_processed.fetch_add(report.size, Ordering::Relaxed) + report.size;
+
+        // update PBs; files that grew since the walk stretch the byte bar,
+        // archive members both bars
+        pb_files.inc(1);
+        if pb_files.length().is_some_and(|len| pb_files.position() > len) {
+            pb_files.set_length(pb_files.position());
+        }
+        if show_current {
+            pb_files.set_message(report.path.display().to_string());
+        }
+        pb_bytes.inc(report.size);
+        if pb_bytes.length().is_some_and(|len| done > len) {
+            pb_bytes.set_length(done);
+        }
+
+        shards.with(|(tally, models, _)| {
+            tally.add_finished(&report, finished);
+            models.iter_mut().for_each(|t| t.add(&report));
+        });
+        // totals and --timing saw the real elapsed time, the output does not
+        let mut report = report;
+        if reproducible {
+            report.elapsed_ms = 0;
+        }
+        if !streams {
+            if keep(&report) {
+                shards.with(|(_, _, kept)| kept.push(report));
             }
+        } else if let Err(TrySendError::Full(report)) = tx_arc.try_send(report) {
+            send_blocked.fetch_add(1, Ordering::Relaxed);
+            let _ = tx_arc.send(report);
         }
     });
 
@@ -453,12 +1949,336 @@ fn main() -> Result<()> {
     drop(tx_arc);
 
     // Wait for aggregator to finish. In this design, aggregator thread listens until rx closed.
-    agg_handle.join().unwrap();
+    let (mut reports, mut sink, sink_error, aggregator_busy, exec_hook) = match agg_handle {
+        Some(handle) => handle.join().unwrap(),
+        None => {
+            let (sink, exec_hook) = outputs.take().expect("outputs stay here without a stream");
+            (Vec::new(), sink, None, Duration::ZERO, exec_hook)
+        }
+    };
+    let mut shards = shards.into_inner().into_iter();
+    let (mut tally, mut model_tallies, kept) = shards.next().expect("at least one shard");
+    reports.extend(kept);
+    for (part, models, kept) in shards {
+        tally.merge(part);
+        model_tallies.iter_mut().zip(models).for_each(|(t, other)| t.merge(other));
+        reports.extend(kept);
+    }
+
+    // finalize, reconciling the byte bar with what was really read
+    let actual_bytes = total_bytes_processed.load(Ordering::Relaxed);
+    pb_bytes.set_length(actual_bytes);
+    pb_bytes.set_position(actual_bytes);
+    pb_files.finish_with_message("files processed");
+    pb_bytes.finish_with_message("bytes processed");
+    let drift = progress::estimate_drift(bytes_estimate, actual_bytes);
+    // archive members add up to their uncompressed size, not the walk's estimate
+    if !archives && drift > progress::ESTIMATE_DRIFT_WARN {
+        eprintln!(
+            "[WARN] Cache changed during the scan: estimated {}, processed {} ({:.1}% off).",
+            human_bytes(bytes_estimate as u128, sizes),
+            human_bytes(actual_bytes as u128, sizes),
+            drift * 100.0
+        );
+    }
+    order.sort(&mut reports);
+    let exec = exec_hook.map(ExecHook::finish);
+    if let Some(reporter) = pipe_reporter {
+        reporter.finish();
+    }
+    if let Some(reporter) = status_reporter {
+        reporter.finish();
+    }
+    timings.record("hashing", hashing_start.elapsed());
+    if args.verbose && streams {
+        let cap = match args.channel_cap {
+            0 => "unbounded".to_string(),
+            cap => cap.to_string(),
+        };
+        eprintln!(
+            "[INFO] Result channel (capacity {}): workers blocked on a full channel {} time(s) over {} sends.",
+            cap,
+            send_blocked.load(Ordering::Relaxed),
+            tally.files
+        );
+    }
+    if let Some(e) = sink_error {
+        return Err(e.context("writing scan output"));
+    }
+    if let Some(range) = args.range.filter(|_| !quiet) {
+        let short = reports.iter().filter(|r| r.skip_reason.is_some()).count();
+        if short > 0 {
+            eprintln!("Skipped {} files ending at or before --range start {}.", short, range.start);
+        }
+    }
+
+    let summarise_start = Instant::now();
+    let models = model_tallies.into_iter().flat_map(ModelTally::finish).collect();
+    let orphans = if args.find_orphans {
+        roots
+            .iter()
+            .flat_map(|root| layout::hf_orphans(root, &symlinks, &reports))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let duplicates = dupes::find_duplicates(&reports, &orphans);
+    let reclaim = ReclaimSummary::new(&duplicates, &orphans);
+    let size_collisions = if args.size_collisions {
+        dupes::find_size_collisions(&reports)
+    } else {
+        Vec::new()
+    };
+    let (hardlinks, real_bytes) = if args.hardlink_report {
+        let (groups, real) = hardlinks::find_hardlinks(&reports);
+        (groups, Some(real))
+    } else {
+        (Vec::new(), None)
+    };
+    let symlink_issues = if args.check_symlinks || args.fix_symlinks {
+        roots
+            .iter()
+            .flat_map(|root| {
+                let under: Vec<_> =
+                    symlinks.iter().filter(|s| s.link.starts_with(root)).cloned().collect();
+                symlinks::check_symlinks(root, &under)
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let summary = ScanSummary {
+        report: ScanReport {
+            cache: roots[0].clone(),
+            roots: if roots.len() > 1 { roots.clone() } else { Vec::new() },
+            total_files: tally.files,
+            total_bytes: tally.bytes as u64,
+            hash_algorithm: args.hash(),
+            hash_encoding: args.hash_encoding,
+            files: reports,
+            models,
+            orphans,
+            duplicates,
+            symlink_issues,
+            size_collisions,
+            walk_errors,
+            hardlinks,
+            real_bytes,
+            sample,
+            limited_from,
+            provenance: Some(provenance),
+        },
+        reclaim,
+        layout,
+        cancelled: false,
+        tally: Some(tally),
+    };
+    timings.record("summarise", summarise_start.elapsed());
+    timings.time("output", || sink.finish(&summary))?;
+    if let Some(out_path) = args.output.as_ref().filter(|_| !quiet) {
+        eprintln!("Wrote JSON report to {:?}", out_path);
+    }
+    if let (Some(log), Some(path)) = (error_log, &args.error_log) {
+        let errored = log.write(&summary.report.files)?;
+        if errored > 0 {
+            eprintln!("[WARN] {} file(s) could not be processed; see {:?}", errored, path);
+        }
+    }
+    if args.emit_manifest.is_some() || quiet {
+        let manifest = Manifest::build(
+            manifest_root(&roots[0]),
+            &summary.report.files,
+            args.hash(),
+            &hash_mode(&args)?,
+            args.hash_encoding,
+        );
+        if let Some(path) = &args.emit_manifest {
+            manifest.write(path)?;
+            if !quiet {
+                eprintln!(
+                    "Wrote manifest of {} files to {:?} (fingerprint {}).",
+                    manifest.files.len(),
+                    path,
+                    manifest::short_id(&manifest.fingerprint)
+                );
+            }
+        }
+        // recorded symlinks were never meant to be hashed
+        let links = summary.report.files.iter().filter(|r| r.status == FileStatus::Symlink);
+        let left_out = summary.report.files.len() - links.count() - manifest.files.len();
+        if left_out > 0 {
+            eprintln!("[WARN] {} file(s) were not fully hashed and are left out.", left_out);
+        }
+        match args.fingerprint_only {
+            Some(FingerprintStyle::Full) => println!("{}", manifest.fingerprint),
+            Some(FingerprintStyle::Short) => {
+                println!("{}", manifest::short_id(&manifest.fingerprint))
+            }
+            None => {}
+        }
+    }
+
+    if let Some(min) = args.min_free_bytes {
+        for root in roots {
+            let space = diskspace::disk_space(root)
+                .with_context(|| format!("checking free space for {:?}", root))?;
+            let bytes: u64 = summary
+                .report
+                .files
+                .iter()
+                .filter(|r| r.path.starts_with(root))
+                .map(|r| r.size)
+                .sum();
+            eprintln!(
+                "Cache {:?} is {:.1}% of its filesystem ({} of {}, {} free).",
+                root,
+                100.0 * bytes as f64 / space.total.max(1) as f64,
+                human_bytes(bytes as u128, sizes),
+                human_bytes(space.total as u128, sizes),
+                human_bytes(space.available as u128, sizes)
+            );
+            if mutating {
+                // re-check: the disk may have filled up while the scan ran
+                diskspace::ensure_free(root, min, sizes)?;
+            } else if space.available < min {
+                eprintln!(
+                    "[WARN] Free space is below --min-free-bytes ({}).",
+                    human_bytes(min as u128, sizes)
+                );
+            }
+        }
+    }
+
+    let mut verdict = summary
+        .report
+        .files
+        .iter()
+        .filter_map(|r| match r.status {
+            FileStatus::Errored | FileStatus::PartiallyHashed => {
+                Some(Verdict::for_unreadable(&r.path))
+            }
+            FileStatus::Vanished if args.strict => Some(Verdict::Missing),
+            _ => None,
+        })
+        .max()
+        .unwrap_or_default();
+    if args.strict_walk && !summary.report.walk_errors.is_empty() {
+        verdict = verdict.max(Verdict::IoError);
+    }
+
+    if let Some(exec) = exec.filter(|e| e.failed > 0) {
+        eprintln!("[WARN] --exec failed for {} of {} file(s).", exec.failed, exec.ran);
+        if args.exec_required {
+            verdict = verdict.max(Verdict::IoError);
+        }
+    }
+
+    if args.fix_symlinks {
+        let (mut fixed, mut failed) = (0usize, 0usize);
+        for issue in summary.report.symlink_issues.iter().filter(|i| i.fix.is_some()) {
+            let fix = issue.fix.as_deref().unwrap_or(Path::new(""));
+            if !args.confirm {
+                eprintln!("{}: would repoint to {}", issue.link.display(), fix.display());
+                continue;
+            }
+            match symlinks::apply_fix(issue) {
+                Ok(()) => {
+                    fixed += 1;
+                    eprintln!("{}: repointed to {}", issue.link.display(), fix.display());
+                }
+                Err(e) => {
+                    failed += 1;
+                    eprintln!("{}: FAILED: {:#}", issue.link.display(), e);
+                }
+            }
+        }
+        if args.confirm {
+            eprintln!("Symlinks: {} repointed, {} failed.", fixed, failed);
+            if failed > 0 {
+                verdict = verdict.max(Verdict::IoError);
+            }
+        } else {
+            eprintln!("Dry run: pass --confirm to repoint symlinks.");
+        }
+    }
+
+    if args.dedup_action != DedupAction::Report {
+        let outcomes = dedup::apply(
+            &summary.report.duplicates,
+            args.dedup_action,
+            args.confirm,
+            args.paranoid,
+        );
+        if !outcomes.is_empty() {
+            eprintln!();
+        }
+        let (mut applied, mut reclaimed, mut linked, mut failed) = (0usize, 0u64, 0usize, 0usize);
+        for o in &outcomes {
+            eprintln!("{}", o.describe(args.dedup_action));
+            match o.result {
+                Ok(DedupStatus::Planned) => reclaimed += o.bytes,
+                Ok(DedupStatus::Applied) => {
+                    applied += 1;
+                    reclaimed += o.bytes;
+                }
+                Ok(DedupStatus::AlreadyLinked) => linked += 1,
+                Err(_) => failed += 1,
+            }
+        }
+        if args.confirm {
+            eprintln!(
+                "Dedup: {} file(s) replaced, {} reclaimed; {} already linked, {} failed.",
+                applied,
+                human_bytes(reclaimed as u128, sizes),
+                linked,
+                failed
+            );
+            if failed > 0 {
+                verdict = verdict.max(Verdict::IoError);
+            }
+        } else {
+            eprintln!(
+                "Dry run: would reclaim {}. Pass --confirm to apply.",
+                human_bytes(reclaimed as u128, sizes)
+            );
+        }
+    }
 
     let elapsed = start_all.elapsed();
-    println!(
-        "\nAll done in {:.2}s (wall).",
-        elapsed.as_secs_f64()
-    );
-    Ok(())
-}
\ No newline at end of file
+    if args.timing {
+        let busy_ms = summary.tally.as_ref().map_or(0, |t| t.busy_ms);
+        let busy = Duration::from_millis(busy_ms.try_into().unwrap_or(u64::MAX));
+        timings.print(elapsed, "hashing", busy, aggregator_busy, num_workers);
+    }
+    if !quiet {
+        eprintln!(
+            "\nAll done in {:.2}s (wall).",
+            elapsed.as_secs_f64()
+        );
+    }
+    if args.watch {
+        let mut index = WatchIndex::from_reports(&summary.report.files);
+        drop(summary);
+        let mut ignores = Vec::new();
+        for root in roots {
+            if let Some(rules) = walk::load_ignore(root, args.ignore_file.as_deref())? {
+                ignores.push((root.clone(), rules));
+            }
+        }
+        let opts = WatchOptions {
+            debounce: Duration::from_millis(args.watch_debounce_ms),
+            ignores,
+            incomplete_suffixes: incomplete.map(|f| f.suffixes).unwrap_or_default(),
+        };
+        let cancel = CancellationToken::new();
+        let stop = cancel.clone();
+        ctrlc::set_handler(move || stop.cancel()).context("installing the Ctrl-C handler")?;
+        eprintln!("Watching {} file(s) for changes; press Ctrl-C to stop.", index.len());
+        watch::watch(roots, &opts, &worker.process, &mut index, &cancel, |change| {
+            print_change(change, sizes)
+        })?;
+        eprintln!("Stopped watching.");
+    }
+    Ok(verdict)
+}
+
@@ -0,0 +1,166 @@
+//! Content-addressed manifest of a cache, for verifying a copy elsewhere.
+//!
+//! Unlike the `--output` report, the manifest holds nothing about the scan
+//! itself: no absolute paths, timings or statuses. Entries are sorted by
+//! their `/`-separated path relative to the cache root, so two identical
+//! caches produce byte-identical manifests wherever they live.
+//!
+//! The fingerprint hashes, in entry order, one `<hash>  <size>  <path>\n`
+//! line per file, in the same BLAKE3 mode as the file hashes themselves. It
+//! is BLAKE3 even when the files were hashed with a quick `--hash`. The
+//! lines always carry hex digests, so with `--hash-encoding` the entries and
+//! the fingerprint change only in how they are written.
+
+use crate::encoding::HashEncoding;
+use crate::hashmode::{HashAlgorithm, HashMode};
+use crate::report::{FileReport, FileStatus};
+use crate::verify::Expected;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path};
+
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// Characters in a fingerprint's short id, as in an abbreviated git commit.
+pub const SHORT_ID_LEN: usize = 7;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    /// `blake3`, `blake3-keyed`, `blake3-derive-key`, `crc32` or `xxh3-128`; a
+    /// key itself is never written
+    pub algorithm: String,
+    /// How `fingerprint` and every entry's `hash` are written; absent means hex
+    #[serde(default, skip_serializing_if = "HashEncoding::is_hex")]
+    pub encoding: HashEncoding,
+    pub fingerprint: String,
+    pub files: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Relative to the cache root, `/`-separated on every platform
+    pub path: String,
+    pub size: u64,
+    pub hash: String,
+}
+
+pub fn algorithm_name(algorithm: HashAlgorithm, mode: &HashMode) -> &'static str {
+    if algorithm != HashAlgorithm::Blake3 {
+        return algorithm.name();
+    }
+    match mode {
+        HashMode::Plain => "blake3",
+        HashMode::Keyed(_) => "blake3-keyed",
+        HashMode::DeriveKey(_) => "blake3-derive-key",
+    }
+}
+
+impl Manifest {
+    /// Every fully hashed file under `root`, whose hashes are in `encoding`.
+    /// Archive members and files that were skipped, sampled or unreadable
+    /// are left out.
+    pub fn build(
+        root: &Path,
+        reports: &[FileReport],
+        algorithm: HashAlgorithm,
+        mode: &HashMode,
+        encoding: HashEncoding,
+    ) -> Manifest {
+        let mut files: Vec<ManifestEntry> = reports
+            .iter()
+            .filter(|r| r.status == FileStatus::Hashed && r.member.is_none())
+            .filter_map(|r| {
+                Some(ManifestEntry {
+                    path: relative_path(root, &r.path)?,
+                    size: r.size,
+                    hash: r.hash_str.clone()?,
+                })
+            })
+            .collect();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        Manifest {
+            version: MANIFEST_VERSION,
+            algorithm: algorithm_name(algorithm, mode).to_string(),
+            encoding,
+            fingerprint: fingerprint(&files, mode, encoding),
+            files,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Manifest> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("reading {:?}", path))?;
+        serde_json::from_str(&text).with_context(|| format!("parsing manifest {:?}", path))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let mut text = serde_json::to_string_pretty(self)?;
+        text.push('\n');
+        std::fs::write(path, text).with_context(|| format!("writing manifest {:?}", path))
+    }
+
+    /// Check the manifest was made with `algorithm` and `mode` and has not
+    /// been edited since.
+    pub fn check(&self, algorithm: HashAlgorithm, mode: &HashMode) -> Result<()> {
+        if self.version != MANIFEST_VERSION {
+            anyhow::bail!("unsupported manifest version {}", self.version);
+        }
+        let ours = algorithm_name(algorithm, mode);
+        if self.algorithm != ours {
+            anyhow::bail!(
+                "manifest was hashed with {}, but this run uses {}; pass the same \
+                 --hash, --hash-key or --hash-context",
+                self.algorithm,
+                ours
+            );
+        }
+        if fingerprint(&self.files, mode, self.encoding) != self.fingerprint {
+            anyhow::bail!(
+                "manifest fingerprint does not match its entries: it was edited, or made with \
+                 a different key or context"
+            );
+        }
+        Ok(())
+    }
+
+    /// The entries as files under `root` to re-hash.
+    pub fn expected(&self, root: &Path) -> Vec<Expected> {
+        self.files
+            .iter()
+            .map(|e| Expected {
+                path: e.path.split('/').fold(root.to_path_buf(), |p, part| p.join(part)),
+                size: Some(e.size),
+                hash_hex: self.encoding.to_hex(&e.hash),
+            })
+            .collect()
+    }
+}
+
+pub fn fingerprint(files: &[ManifestEntry], mode: &HashMode, encoding: HashEncoding) -> String {
+    let mut hasher = mode.hasher();
+    for e in files {
+        let hash = encoding.to_hex(&e.hash);
+        hasher.update(format!("{}  {}  {}\n", hash, e.size, e.path).as_bytes());
+    }
+    encoding.encode(hasher.finalize().as_bytes())
+}
+
+/// The first `SHORT_ID_LEN` characters of `fingerprint`, for prompts and
+/// cache keys where the whole of it is unwieldy.
+pub fn short_id(fingerprint: &str) -> &str {
+    fingerprint.get(..SHORT_ID_LEN).unwrap_or(fingerprint)
+}
+
+/// `path` under `root` as `a/b/c`; `None` outside the root or for names
+/// that are not valid UTF-8, which another machine could not match.
+fn relative_path(root: &Path, path: &Path) -> Option<String> {
+    let rel = path.strip_prefix(root).ok()?;
+    let parts = rel
+        .components()
+        .map(|c| match c {
+            Component::Normal(part) => part.to_str(),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(parts.join("/"))
+}
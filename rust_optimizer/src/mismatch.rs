@@ -0,0 +1,125 @@
+//! `--detect-mismatch`: catch files whose content contradicts their
+//! extension, such as an HTML error page or a JSON body saved as
+//! `.safetensors`. Only the first bytes are read, and right after hashing
+//! they are still in the page cache.
+
+use crate::decompress::Codec;
+use crate::suspicious::MAX_SAFETENSORS_HEADER;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Enough for every signature below, after leading whitespace.
+const PREFIX_BYTES: u64 = 64;
+
+/// What a file's first bytes say it holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Content {
+    Safetensors,
+    Gguf,
+    Gzip,
+    Zstd,
+    /// A zip archive, as PyTorch checkpoints since 1.6 are
+    Zip,
+    /// A bare pickle, as legacy PyTorch checkpoints are
+    Pickle,
+    Json,
+    /// An error page from a proxy or download server
+    Html,
+    /// A Git LFS pointer checked out instead of the object
+    LfsPointer,
+}
+
+impl Content {
+    pub fn name(self) -> &'static str {
+        match self {
+            Content::Safetensors => "safetensors",
+            Content::Gguf => "GGUF",
+            Content::Gzip => "gzip",
+            Content::Zstd => "zstd",
+            Content::Zip => "zip",
+            Content::Pickle => "pickle",
+            Content::Json => "JSON",
+            Content::Html => "HTML",
+            Content::LfsPointer => "a Git LFS pointer",
+        }
+    }
+
+    /// Recognise `head`, the first bytes of a file of `size` bytes.
+    pub fn sniff(head: &[u8], size: u64) -> Option<Content> {
+        if let Some(codec) = Codec::detect(head) {
+            return Some(match codec {
+                Codec::Gzip => Content::Gzip,
+                Codec::Zstd => Content::Zstd,
+            });
+        }
+        if head.starts_with(b"GGUF") {
+            return Some(Content::Gguf);
+        }
+        if head.starts_with(b"PK\x03\x04") {
+            return Some(Content::Zip);
+        }
+        // pickle protocol 2 and up open with PROTO and the version
+        if head.len() >= 2 && head[0] == 0x80 && (2..=5).contains(&head[1]) {
+            return Some(Content::Pickle);
+        }
+        if head.len() > 8 && head[8] == b'{' {
+            let header_len = u64::from_le_bytes(head[..8].try_into().unwrap());
+            if header_len <= MAX_SAFETENSORS_HEADER && 8 + header_len <= size {
+                return Some(Content::Safetensors);
+            }
+        }
+        let text = head.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(head).trim_ascii_start();
+        if text.starts_with(b"version https://git-lfs") {
+            return Some(Content::LfsPointer);
+        }
+        let lower = text.to_ascii_lowercase();
+        if lower.starts_with(b"<!doctype html") || lower.starts_with(b"<html") {
+            return Some(Content::Html);
+        }
+        if text.starts_with(b"{") || text.starts_with(b"[") {
+            return Some(Content::Json);
+        }
+        None
+    }
+}
+
+/// What files with each extension may hold. Extensions used for anything,
+/// like `.bin`, are not checked.
+const EXPECTED: &[(&str, &[Content])] = &[
+    ("safetensors", &[Content::Safetensors]),
+    ("gguf", &[Content::Gguf]),
+    ("gz", &[Content::Gzip]),
+    ("tgz", &[Content::Gzip]),
+    ("zst", &[Content::Zstd]),
+    ("zstd", &[Content::Zstd]),
+    ("json", &[Content::Json]),
+    ("pt", &[Content::Zip, Content::Pickle]),
+    ("pth", &[Content::Zip, Content::Pickle]),
+    ("zip", &[Content::Zip]),
+];
+
+/// Why the content of `path` contradicts its extension, if it does. Empty
+/// files are left to `--flag-suspicious`.
+pub fn check(path: &Path, size: u64) -> Option<String> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    let &(_, expected) = EXPECTED.iter().find(|(e, _)| *e == ext)?;
+    if size == 0 {
+        return None;
+    }
+    let head = match read_prefix(path) {
+        Ok(head) => head,
+        Err(e) => return Some(format!("could not read its first bytes: {}", e)),
+    };
+    match Content::sniff(&head, size) {
+        Some(found) if expected.contains(&found) => None,
+        Some(found) => Some(format!(".{} file holds {}", ext, found.name())),
+        None => Some(format!(".{} file has no {} signature", ext, expected[0].name())),
+    }
+}
+
+fn read_prefix(path: &Path) -> io::Result<Vec<u8>> {
+    let mut head = Vec::with_capacity(PREFIX_BYTES as usize);
+    File::open(path)?.take(PREFIX_BYTES).read_to_end(&mut head)?;
+    Ok(head)
+}
@@ -0,0 +1,97 @@
+//! Mount-table lookups: which filesystem a path lives on.
+
+use std::path::{Path, PathBuf};
+
+/// Filesystem types served over the network, where page-faulting through
+/// mmap tends to be far slower than sequential reads.
+const NETWORK_FS_TYPES: &[&str] = &[
+    "nfs", "nfs4", "cifs", "smb3", "smbfs", "9p", "afs", "ceph", "glusterfs", "lustre", "gpfs",
+    "davfs", "sshfs", "fuse.sshfs", "fuse.glusterfs", "fuse.rclone", "fuse.s3fs", "fuse.gcsfuse",
+];
+
+#[derive(Debug, Clone)]
+pub struct MountEntry {
+    pub device: String,
+    pub mount_point: PathBuf,
+    pub fs_type: String,
+}
+
+impl MountEntry {
+    pub fn is_network(&self) -> bool {
+        NETWORK_FS_TYPES.contains(&self.fs_type.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MountTable {
+    entries: Vec<MountEntry>,
+}
+
+impl MountTable {
+    /// Read `/proc/mounts`. Returns `None` where that isn't available.
+    pub fn load() -> Option<Self> {
+        std::fs::read_to_string("/proc/mounts")
+            .ok()
+            .map(|text| Self::parse(&text))
+    }
+
+    /// Parse `/proc/mounts` / `fstab` formatted text.
+    pub fn parse(text: &str) -> Self {
+        let entries = text
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                let device = unescape_octal(fields.next()?);
+                let mount_point = PathBuf::from(unescape_octal(fields.next()?));
+                let fs_type = fields.next()?.to_string();
+                Some(MountEntry {
+                    device,
+                    mount_point,
+                    fs_type,
+                })
+            })
+            .collect();
+        Self { entries }
+    }
+
+    /// The mount containing `path`: the longest matching mount point. Later
+    /// entries win ties, since they are mounted over earlier ones. `path`
+    /// should be absolute and canonical.
+    pub fn lookup(&self, path: &Path) -> Option<&MountEntry> {
+        self.entries
+            .iter()
+            .filter(|e| path.starts_with(&e.mount_point))
+            .max_by_key(|e| e.mount_point.as_os_str().len())
+    }
+
+    /// `lookup` for a path that may not exist as such, like an archive member
+    /// or a file deleted mid-scan: the nearest ancestor that resolves decides.
+    pub fn lookup_file(&self, path: &Path) -> Option<&MountEntry> {
+        let abs = path.ancestors().find_map(|p| p.canonicalize().ok())?;
+        self.lookup(&abs)
+    }
+}
+
+/// Undo the `\040`-style octal escapes the kernel uses for spaces and tabs.
+fn unescape_octal(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = bytes.get(i + 1..i + 4).filter(|d| {
+            bytes[i] == b'\\' && d.iter().all(|b| (b'0'..=b'7').contains(b))
+        });
+        match escape {
+            Some(d) => {
+                let code = d.iter().fold(0u32, |acc, b| acc * 8 + u32::from(b - b'0'));
+                out.push(code as u8);
+                i += 4;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
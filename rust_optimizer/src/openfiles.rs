@@ -0,0 +1,101 @@
+//! `--skip-open-for-write`: leave out files another process holds open for
+//! writing, such as a model still downloading, found through every
+//! process's descriptors under `/proc`. Unlike the mtime window this catches
+//! a stalled download and lets a file that was merely touched through.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+/// A process with a file open for writing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Writer {
+    pub pid: u32,
+    /// Its command name, from `/proc/<pid>/comm`
+    pub command: String,
+}
+
+/// Files open for writing, by device and inode.
+#[derive(Debug, Default)]
+pub struct OpenForWrite(HashMap<(u64, u64), Writer>);
+
+impl OpenForWrite {
+    /// Every regular file another process has open for writing, among the
+    /// processes this one may inspect: its own user's, or all when run as
+    /// root. `None` where there is no `/proc` to read.
+    #[cfg(target_os = "linux")]
+    pub fn scan() -> Option<Self> {
+        use std::os::unix::fs::MetadataExt;
+        let me = std::process::id();
+        let mut files = HashMap::new();
+        for entry in std::fs::read_dir("/proc").ok()?.flatten() {
+            let Some(pid) = entry.file_name().to_str().and_then(|s| s.parse::<u32>().ok()) else {
+                continue;
+            };
+            if pid == me {
+                continue;
+            }
+            let dir = entry.path();
+            // unreadable for other users' processes, and gone if it just exited
+            let Ok(fds) = std::fs::read_dir(dir.join("fd")) else {
+                continue;
+            };
+            let mut command = None;
+            for fd in fds.flatten() {
+                if !writable(&dir.join("fdinfo").join(fd.file_name())) {
+                    continue;
+                }
+                // the link resolves to the open file itself, even once renamed
+                let Ok(meta) = fd.path().metadata() else {
+                    continue;
+                };
+                if !meta.is_file() {
+                    continue;
+                }
+                let command = command.get_or_insert_with(|| {
+                    let comm = std::fs::read_to_string(dir.join("comm")).unwrap_or_default();
+                    comm.trim_end().to_string()
+                });
+                files.entry((meta.dev(), meta.ino())).or_insert_with(|| Writer {
+                    pid,
+                    command: command.clone(),
+                });
+            }
+        }
+        Some(Self(files))
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn scan() -> Option<Self> {
+        None
+    }
+
+    /// Who has `path` open for writing, if anyone.
+    pub fn writer(&self, path: &Path) -> Option<&Writer> {
+        self.0.get(&inode(path)?)
+    }
+}
+
+/// Whether the descriptor described by the `fdinfo` file at `path` was
+/// opened write-only or read-write.
+#[cfg(target_os = "linux")]
+fn writable(path: &Path) -> bool {
+    let Ok(info) = std::fs::read_to_string(path) else {
+        return false;
+    };
+    info.lines()
+        .find_map(|line| line.strip_prefix("flags:"))
+        .and_then(|flags| u32::from_str_radix(flags.trim(), 8).ok())
+        .is_some_and(|flags| flags & libc::O_ACCMODE as u32 != libc::O_RDONLY as u32)
+}
+
+#[cfg(unix)]
+fn inode(path: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    let meta = path.metadata().ok()?;
+    Some((meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+fn inode(_path: &Path) -> Option<(u64, u64)> {
+    None
+}
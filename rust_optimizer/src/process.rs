@@ -0,0 +1,678 @@
+//! Per-file work: map or read, prefetch, hash and checksum.
+
+use crate::budget::MemoryBudget;
+use crate::decompress::{self, Decompress};
+use crate::encoding::HashEncoding;
+use crate::errorlog;
+use crate::gpu::GpuContext;
+use crate::hashmode::{HashAlgorithm, HashMode, MultiHasher, Update};
+use crate::mounts::MountTable;
+use crate::report::{FileReport, FileStatus, XorSource};
+use crate::residency;
+use crate::resume::{
+    cv_from_hex, cv_to_hex, hash_windowed, mtime_ns, HashCheckpoint, HashProgress, HASH_WINDOW,
+};
+use crate::sparse;
+use crate::xor64::{xor64_cpu, Xor64Stream};
+use blake3::hazmat::ChainingValue;
+use memmap2::MmapOptions;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs::{File, Metadata};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::Path;
+use std::time::Instant;
+
+/// How file contents are fetched for hashing. Every mode yields the same digest.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReaderMode {
+    /// Memory-map the whole file
+    Mmap,
+    /// Sequential buffered reads into a reusable buffer
+    Read,
+    /// `read` for files on network filesystems, `mmap` everywhere else
+    #[default]
+    Auto,
+}
+
+/// `--range START:LEN`: hash only this span of each file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub len: u64,
+}
+
+impl ByteRange {
+    /// Parse `START:LEN`, both in bytes; `LEN` must be positive.
+    pub fn parse(s: &str) -> Result<ByteRange, String> {
+        let (start, len) = s.split_once(':').ok_or("expected START:LEN")?;
+        let start = start.trim().parse().map_err(|e| format!("START: {}", e))?;
+        let len = len.trim().parse().map_err(|e| format!("LEN: {}", e))?;
+        if len == 0 {
+            return Err("LEN must be at least 1".to_string());
+        }
+        Ok(ByteRange { start, len })
+    }
+
+    /// The part of a `size`-byte file covered, cut short at its end; `None`
+    /// when the file ends at or before `start`.
+    pub fn within(&self, size: u64) -> Option<Range<u64>> {
+        (self.start < size).then(|| self.start..self.start.saturating_add(self.len).min(size))
+    }
+}
+
+/// Size of each worker's reusable buffer for `ReaderMode::Read`.
+const READ_BUF_LEN: usize = 1 << 20;
+
+thread_local! {
+    static READ_BUF: RefCell<Vec<u8>> = RefCell::new(vec![0u8; READ_BUF_LEN]);
+}
+
+/// Everything `process_file` needs besides the path.
+#[derive(Default, Clone, Copy)]
+pub struct ProcessOptions<'a> {
+    /// Files smaller than this are reported but not hashed
+    pub min_bytes: u64,
+    /// Files larger than this are reported but not hashed
+    pub max_bytes: Option<u64>,
+    /// Compute the XOR64 checksum (GPU if `gpu` is set, else CPU)
+    pub use_gpu: bool,
+    pub reader: ReaderMode,
+    pub gpu: Option<&'a GpuContext>,
+    /// Checkpoint for resumable hashing of files larger than one window
+    pub checkpoint: Option<&'a HashCheckpoint>,
+    /// Mount table consulted by `ReaderMode::Auto`
+    pub mounts: Option<&'a MountTable>,
+    /// Caps the bytes mapped at once across all workers
+    pub budget: Option<&'a MemoryBudget>,
+    /// Compute a `sample_signature` over this many bytes at each end instead of a full hash
+    pub sample: Option<u64>,
+    /// Only pull the file into the page cache; no hash or checksum
+    pub warm_only: bool,
+    /// Estimate compressibility from this many leading bytes
+    pub compress_probe: Option<u64>,
+    /// Digest length in bytes, read from BLAKE3's XOF; `None` is the standard 32
+    pub digest_len: Option<usize>,
+    /// Plain, keyed or derive-key hashing
+    pub hash_mode: HashMode,
+    /// Digest algorithm; `hash_mode`, `digest_len` and `checkpoint` only apply to BLAKE3
+    pub algorithm: HashAlgorithm,
+    /// Further digests computed in the same pass, recorded in `FileReport::hashes`;
+    /// a file hashed with any is never resumed from `checkpoint`
+    pub extra_algorithms: &'a [HashAlgorithm],
+    /// Hash (and checksum) only this span; ignored by `sample` and `warm_only`
+    pub range: Option<ByteRange>,
+    /// Read only the data extents of sparse files, hashing their holes as zeros
+    pub sparse_aware: bool,
+    /// Hash mapped BLAKE3 spans of at least this many bytes with the whole
+    /// rayon pool instead of one worker
+    pub parallel_threshold: Option<u64>,
+    /// Record how much of each file was already in the page cache
+    pub measure_residency: bool,
+    /// How `FileReport::hash_str` is written
+    pub encoding: HashEncoding,
+    /// Report a symlink and its target instead of hashing through it
+    pub record_symlinks: bool,
+    /// Hash gzip or zstd files' decompressed content; a decoded file gets no
+    /// `range`, checkpoint, XOR checksum or compressibility probe
+    pub decompress: Decompress,
+    /// On a read error partway through, report the digest of the bytes read so
+    /// far as `PartiallyHashed` instead of failing. Files are then read, never
+    /// mapped, since a mapped read error cannot be recovered from, and are
+    /// never resumed from `checkpoint`
+    pub partial_on_error: bool,
+    /// Map with `MAP_POPULATE`, faulting every page in before hashing starts,
+    /// instead of `MADV_WILLNEED`, which only starts readahead (Linux only)
+    pub populate: bool,
+}
+
+/// The error context of a read that failed after `bytes_ok` bytes were
+/// hashed, with the digests of those bytes.
+#[derive(Debug)]
+pub struct PartialHash {
+    pub bytes_ok: u64,
+    pub digests: Digests,
+}
+
+impl std::fmt::Display for PartialHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "read failed after {} bytes", self.bytes_ok)
+    }
+}
+
+/// Passes everything on to `inner`, counting the bytes.
+struct Counted<'a> {
+    inner: &'a mut dyn Update,
+    bytes: u64,
+}
+
+impl Update for Counted<'_> {
+    fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
+        self.bytes += data.len() as u64;
+    }
+
+    fn update_parallel(&mut self, data: &[u8]) {
+        self.inner.update_parallel(data);
+        self.bytes += data.len() as u64;
+    }
+}
+
+/// Length of a standard BLAKE3 digest.
+pub const DEFAULT_DIGEST_LEN: usize = blake3::OUT_LEN;
+
+/// Hex of the first `len` bytes of `output`. The first 32 are exactly the
+/// standard BLAKE3 hash, so any length is a prefix of any longer one.
+pub fn digest_hex(mut output: blake3::OutputReader, len: usize) -> String {
+    let mut digest = vec![0u8; len];
+    output.fill(&mut digest);
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Hex digests from one pass over the bytes, `ProcessOptions::algorithm`'s first.
+type Digests = Vec<(HashAlgorithm, String)>;
+
+/// A report's `hash_str` and, with more than one digest, `hashes`, in `encoding`.
+pub fn encode_digests(
+    digests: &[(HashAlgorithm, String)],
+    encoding: HashEncoding,
+) -> (Option<String>, Option<BTreeMap<HashAlgorithm, String>>) {
+    let hash_str = digests.first().map(|(_, hex)| encoding.from_hex(hex));
+    let hashes = (digests.len() > 1)
+        .then(|| digests.iter().map(|(a, hex)| (*a, encoding.from_hex(hex))).collect());
+    (hash_str, hashes)
+}
+
+/// Try to advise OS to prefetch the mapped region (POSIX madvise MADV_WILLNEED,
+/// PrefetchVirtualMemory on Windows 8+)
+#[inline]
+pub fn advise_willneed(ptr: *const u8, len: usize) {
+    #[cfg(unix)]
+    unsafe {
+        // madvise tends to be available on Linux/BSD/macOS (value MADV_WILLNEED)
+        let res = libc::madvise(ptr as *mut _, len, libc::MADV_WILLNEED);
+        if res != 0 {
+            // ignore errors (best-effort)
+        }
+    }
+    #[cfg(windows)]
+    unsafe {
+        use windows_sys::Win32::System::Memory::{PrefetchVirtualMemory, WIN32_MEMORY_RANGE_ENTRY};
+        use windows_sys::Win32::System::Threading::GetCurrentProcess;
+        let range = WIN32_MEMORY_RANGE_ENTRY {
+            VirtualAddress: ptr as *mut _,
+            NumberOfBytes: len,
+        };
+        // a zero return only means no readahead happened (best-effort)
+        let _ = PrefetchVirtualMemory(GetCurrentProcess(), 1, &range, 0);
+    }
+    // Elsewhere we do nothing (memmap still helps).
+}
+
+/// Assumed page size when touching mapped memory; on larger pages some
+/// touches are redundant, never missing.
+const TOUCH_STRIDE: usize = 4096;
+
+/// Fault in every page of `data` by reading one byte from each.
+fn touch_pages(data: &[u8]) {
+    let acc = data.iter().step_by(TOUCH_STRIDE).fold(0u8, |acc, b| acc ^ b);
+    std::hint::black_box(acc);
+}
+
+/// zstd level 1 output size over input size; `None` for empty input. Level 1
+/// is fast enough to run on every file and tracks higher levels' ordering.
+pub fn compress_ratio(sample: &[u8]) -> Option<f64> {
+    if sample.is_empty() {
+        return None;
+    }
+    let compressed = zstd::bulk::compress(sample, 1).ok()?;
+    Some(compressed.len() as f64 / sample.len() as f64)
+}
+
+/// Feed a stream that cannot be mapped (e.g. stdin) through `hasher`.
+pub fn hash_reader<R: Read>(mut reader: R, mut hasher: MultiHasher) -> anyhow::Result<MultiHasher> {
+    std::io::copy(&mut reader, &mut hasher)?;
+    Ok(hasher)
+}
+
+/// Pick the concrete reader for `path`; `Auto` never comes back out.
+fn resolve_reader(mode: ReaderMode, path: &Path, mounts: Option<&MountTable>) -> ReaderMode {
+    if mode != ReaderMode::Auto {
+        return mode;
+    }
+    let on_network = mounts
+        .zip(path.canonicalize().ok())
+        .and_then(|(table, abs)| table.lookup(&abs).map(|m| m.is_network()))
+        .unwrap_or(false);
+    if on_network {
+        ReaderMode::Read
+    } else {
+        ReaderMode::Mmap
+    }
+}
+
+/// The error for a file found shorter than it was when sized.
+fn shrank() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "file shrank while being read")
+}
+
+/// Stream bytes `start..end` of `f` through this thread's reusable buffer.
+fn read_range(f: &mut File, start: u64, end: u64, mut sink: impl FnMut(&[u8])) -> io::Result<()> {
+    f.seek(SeekFrom::Start(start))?;
+    READ_BUF.with(|buf| {
+        let mut buf = buf.borrow_mut();
+        let mut remaining = end - start;
+        while remaining > 0 {
+            let want = remaining.min(buf.len() as u64) as usize;
+            let n = match f.read(&mut buf[..want]) {
+                Ok(0) => return Err(shrank()),
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            sink(&buf[..n]);
+            remaining -= n as u64;
+        }
+        Ok(())
+    })
+}
+
+/// Stands in for a sparse file's holes.
+static ZEROS: [u8; 64 << 10] = [0; 64 << 10];
+
+fn feed_zeros(mut len: u64, sink: &mut impl FnMut(&[u8])) {
+    while len > 0 {
+        let n = len.min(ZEROS.len() as u64) as usize;
+        sink(&ZEROS[..n]);
+        len -= n as u64;
+    }
+}
+
+/// `read_range`, except that with `extents` only the data inside them is
+/// read and the holes between are passed to `sink` as zeros.
+fn read_extents(
+    f: &mut File,
+    start: u64,
+    end: u64,
+    extents: Option<&[Range<u64>]>,
+    mut sink: impl FnMut(&[u8]),
+) -> io::Result<()> {
+    let Some(extents) = extents else {
+        return read_range(f, start, end, sink);
+    };
+    let mut pos = start;
+    for extent in extents {
+        let (from, to) = (extent.start.max(pos), extent.end.min(end));
+        if from >= to {
+            continue;
+        }
+        feed_zeros(from - pos, &mut sink);
+        read_range(f, from, to, &mut sink)?;
+        pos = to;
+    }
+    feed_zeros(end.saturating_sub(pos), &mut sink);
+    Ok(())
+}
+
+/// Domain separation for `sample_signature`; bump the version if the layout changes.
+const SAMPLE_CONTEXT: &str = "aivista-cache-scan 2026-10-16 sample signature v1";
+
+/// Quick change-detection fingerprint: BLAKE3 over the size, `edge` and the
+/// first and last `edge` bytes. Edits confined to the middle of a large file
+/// go unnoticed, so this is not a substitute for the full hash and must not
+/// be used to verify downloads.
+pub fn sample_signature(f: &mut File, size: u64, edge: u64) -> io::Result<String> {
+    let mut hasher = blake3::Hasher::new_derive_key(SAMPLE_CONTEXT);
+    hasher.update(&size.to_le_bytes());
+    hasher.update(&edge.to_le_bytes());
+    let head_end = edge.min(size);
+    read_range(f, 0, head_end, |b| {
+        hasher.update(b);
+    })?;
+    // the tail never re-reads bytes already covered by the head
+    let tail_start = size.saturating_sub(edge).max(head_end);
+    read_range(f, tail_start, size, |b| {
+        hasher.update(b);
+    })?;
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// Hex digests of the file's `meta.len()` bytes, or of `range` only, as
+/// produced by `feed`. Whole files hashed with BLAKE3 alone go through the
+/// resumable windowed path when a checkpoint applies.
+fn hash_contents(
+    path: &Path,
+    meta: &Metadata,
+    range: Option<&Range<u64>>,
+    opts: &ProcessOptions,
+    mut feed: impl FnMut(&mut dyn Update, u64, u64) -> anyhow::Result<()>,
+) -> anyhow::Result<Digests> {
+    let size = meta.len();
+    let digest_len = opts.digest_len.unwrap_or(DEFAULT_DIGEST_LEN);
+    let mode = &opts.hash_mode;
+    let resumable = opts.checkpoint.filter(|_| {
+        range.is_none()
+            && opts.algorithm == HashAlgorithm::Blake3
+            && opts.extra_algorithms.is_empty()
+            && !opts.partial_on_error
+            && size > HASH_WINDOW
+    });
+    let Some(ckpt) = resumable else {
+        let mut hasher = MultiHasher::new(opts.algorithm, opts.extra_algorithms, mode);
+        let (from, to) = range.map_or((0, size), |r| (r.start, r.end));
+        let mut counted = Counted { inner: &mut hasher, bytes: 0 };
+        if let Err(e) = feed(&mut counted, from, to) {
+            let bytes_ok = counted.bytes;
+            if !opts.partial_on_error {
+                return Err(e);
+            }
+            let digests = hasher.finalize_hex(digest_len);
+            return Err(e.context(PartialHash { bytes_ok, digests }));
+        }
+        return Ok(hasher.finalize_hex(digest_len));
+    };
+    let mtime = mtime_ns(meta);
+    let mode_id = mode.id();
+    // only resume from state recorded for this exact file version and key
+    let (start_at, stack) = match ckpt.get(path) {
+        Some(p) if p.size == size && p.mtime_ns == mtime && p.mode == mode_id => {
+            let stack: Option<Vec<ChainingValue>> =
+                p.cv_stack.iter().map(|h| cv_from_hex(h)).collect();
+            match stack {
+                Some(stack) => (p.bytes_hashed, stack),
+                None => (0, Vec::new()),
+            }
+        }
+        _ => (0, Vec::new()),
+    };
+    let feed_blake3 = |hasher: &mut blake3::Hasher, from, to| feed(hasher, from, to);
+    let hash = hash_windowed(mode, size, HASH_WINDOW, start_at, stack, feed_blake3, |done, stack| {
+        ckpt.update(
+            path,
+            Some(HashProgress {
+                size,
+                mtime_ns: mtime,
+                bytes_hashed: done,
+                cv_stack: stack.iter().map(cv_to_hex).collect(),
+                mode: mode_id.clone(),
+            }),
+        )
+    })?;
+    ckpt.update(path, None)?;
+    Ok(vec![(HashAlgorithm::Blake3, digest_hex(hash, digest_len))])
+}
+
+/// Report for a file that is sized but not hashed.
+fn skipped(path: &Path, size: u64, elapsed_ms: u128) -> FileReport {
+    FileReport {
+        elapsed_ms,
+        status: FileStatus::Skipped,
+        ..FileReport::new(path, size)
+    }
+}
+
+/// Report for a file whose read failed after part of it was hashed; `err`
+/// carries a `PartialHash`.
+fn partially_hashed(
+    path: &Path,
+    size: u64,
+    err: anyhow::Error,
+    opts: &ProcessOptions,
+    start: Instant,
+) -> FileReport {
+    let partial = err.downcast_ref::<PartialHash>().expect("a PartialHash error");
+    let (partial_hash, _) = encode_digests(&partial.digests, opts.encoding);
+    FileReport {
+        status: FileStatus::PartiallyHashed,
+        partial_hash,
+        bytes_ok: Some(partial.bytes_ok),
+        errno: errorlog::errno(&err),
+        error: Some(format!("{:#}", err)),
+        ..skipped(path, size, start.elapsed().as_millis())
+    }
+}
+
+/// Process a single file: map or read it, compute blake3, optional xor.
+/// Returns a FileReport. A symlink is hashed through to its target, or only
+/// recorded with `record_symlinks`; either way its report names the target.
+pub fn process_file(path: &Path, opts: &ProcessOptions) -> anyhow::Result<FileReport> {
+    let start = Instant::now();
+    let link = path.symlink_metadata()?;
+    let target = if link.file_type().is_symlink() {
+        Some(std::fs::read_link(path)?)
+    } else {
+        None
+    };
+    if target.is_some() && opts.record_symlinks {
+        return Ok(FileReport {
+            status: FileStatus::Symlink,
+            symlink_target: target,
+            ..skipped(path, link.len(), start.elapsed().as_millis())
+        });
+    }
+    let report = process_target(path, opts, start)?;
+    Ok(FileReport {
+        symlink_target: target,
+        ..report
+    })
+}
+
+fn process_target(
+    path: &Path,
+    opts: &ProcessOptions,
+    start: Instant,
+) -> anyhow::Result<FileReport> {
+    let meta = path.metadata()?;
+    if !meta.is_file() {
+        anyhow::bail!("not a regular file");
+    }
+    let size = meta.len();
+    let outside_window = size < opts.min_bytes || opts.max_bytes.is_some_and(|max| size > max);
+    let range = match opts.range {
+        Some(r) if !outside_window => match r.within(size) {
+            Some(range) => Some(range),
+            None => {
+                return Ok(FileReport {
+                    skip_reason: Some(format!(
+                        "only {} bytes, but --range starts at byte {}",
+                        size, r.start
+                    )),
+                    ..skipped(path, size, start.elapsed().as_millis())
+                })
+            }
+        },
+        _ => None,
+    };
+    if outside_window {
+        return Ok(skipped(path, size, start.elapsed().as_millis()));
+    }
+    // bytes this file contributes to the hash, the checksum and the memory budget
+    let (span_start, span_len) = range.as_ref().map_or((0, size), |r| (r.start, r.end - r.start));
+
+    // open file readonly
+    let mut f = File::open(path)?;
+
+    let codec = if opts.warm_only { None } else { opts.decompress.codec_for(&mut f)? };
+    if let Some(codec) = codec {
+        let digest_len = opts.digest_len.unwrap_or(DEFAULT_DIGEST_LEN);
+        let hasher = MultiHasher::new(opts.algorithm, opts.extra_algorithms, &opts.hash_mode);
+        let (digests, logical) = decompress::hash_decoded(f, codec, hasher, digest_len)?;
+        let (hash_str, hashes) = encode_digests(&digests, opts.encoding);
+        return Ok(FileReport {
+            hash_str,
+            hashes,
+            elapsed_ms: start.elapsed().as_millis(),
+            decompressed: Some(codec),
+            logical_size: Some(logical),
+            ..FileReport::new(path, size)
+        });
+    }
+
+    if let Some(edge) = opts.sample {
+        let signature = sample_signature(&mut f, size, edge)?;
+        return Ok(FileReport {
+            signature: Some(signature),
+            elapsed_ms: start.elapsed().as_millis(),
+            status: FileStatus::Sampled,
+            ..FileReport::new(path, size)
+        });
+    }
+
+    let mut reader = resolve_reader(opts.reader, path, opts.mounts);
+    // a sparse file is read extent by extent, so its holes never leave the disk;
+    // where extents cannot be listed it is read densely as usual
+    let extents = (opts.sparse_aware && !opts.warm_only && sparse::is_sparse(&meta))
+        .then(|| sparse::data_extents(&f, span_start, span_start + span_len).ok())
+        .flatten();
+    if extents.is_some() || opts.partial_on_error {
+        reader = ReaderMode::Read;
+    }
+    // mapped files count against the budget until hashing is done; a file that
+    // could never fit is read in chunks instead, one such file at a time
+    let (_permit, _oversized) = match opts.budget {
+        Some(budget) if reader == ReaderMode::Mmap && budget.fits(span_len) => {
+            (Some(budget.acquire(span_len)), None)
+        }
+        Some(budget) => {
+            let guard = (reader == ReaderMode::Mmap).then(|| budget.serialize_oversized());
+            reader = ReaderMode::Read;
+            (None, guard)
+        }
+        None => (None, None),
+    };
+
+    // before anything below faults pages in
+    let resident = opts
+        .measure_residency
+        .then(|| residency::resident_fraction(&f, span_start, span_len))
+        .flatten();
+
+    if opts.warm_only {
+        match reader {
+            ReaderMode::Read => read_range(&mut f, 0, size, |_| {})?,
+            _ => {
+                let mmap = unsafe { MmapOptions::new().map(&f) }?;
+                advise_willneed(mmap.as_ptr(), mmap.len());
+                touch_pages(&mmap);
+            }
+        }
+        return Ok(FileReport {
+            elapsed_ms: start.elapsed().as_millis(),
+            status: FileStatus::Warmed,
+            resident,
+            ..FileReport::new(path, size)
+        });
+    }
+
+    let (digests, xor64, xor64_source, compress_ratio) = match reader {
+        ReaderMode::Read => {
+            // one sequential pass feeds both the hasher and the XOR accumulator;
+            // the GPU needs the whole file resident, so XOR stays on the CPU here
+            let mut xor = opts.use_gpu.then(Xor64Stream::default);
+            let mut xor_pos = span_start;
+            let hashed = hash_contents(path, &meta, range.as_ref(), opts, |hasher, from, to| {
+                if let Some(x) = xor.as_mut() {
+                    // a resumed hash skips its prefix, which the XOR still needs
+                    if from > xor_pos {
+                        read_extents(&mut f, xor_pos, from, extents.as_deref(), |b| x.update(b))?;
+                    }
+                    xor_pos = to;
+                }
+                read_extents(&mut f, from, to, extents.as_deref(), |b| {
+                    hasher.update(b);
+                    if let Some(x) = xor.as_mut() {
+                        x.update(b);
+                    }
+                })?;
+                Ok(())
+            });
+            let hash = match hashed {
+                Err(e) if e.downcast_ref::<PartialHash>().is_some() => {
+                    return Ok(FileReport {
+                        range: range.map(|r| [r.start, r.end]),
+                        ..partially_hashed(path, size, e, opts, start)
+                    });
+                }
+                hashed => hashed?,
+            };
+            let source = xor.is_some().then_some(XorSource::Cpu);
+            let ratio = match opts.compress_probe {
+                Some(probe) => {
+                    let head_len = probe.min(span_len);
+                    let mut head = Vec::with_capacity(head_len as usize);
+                    read_range(&mut f, span_start, span_start + head_len, |b| {
+                        head.extend_from_slice(b)
+                    })?;
+                    compress_ratio(&head)
+                }
+                None => None,
+            };
+            (hash, xor.map(|x| x.finish()), source, ratio)
+        }
+        _ => {
+            // the file was sized before it was opened and the budget waited on;
+            // a mapping past its current end would fault rather than read short
+            if f.metadata()?.len() < span_start + span_len {
+                return Err(shrank().into());
+            }
+            // memory-map the entire file, or just the --range span, read-only
+            let mut map_opts = MmapOptions::new();
+            if range.is_some() {
+                map_opts.offset(span_start).len(span_len as usize);
+            }
+            if opts.populate {
+                map_opts.populate();
+            }
+            let mmap = unsafe { map_opts.map(&f) }?;
+            let data = &mmap[..];
+
+            // advise OS to prefetch (best-effort); a populated mapping is already in
+            if !opts.populate {
+                advise_willneed(data.as_ptr(), data.len());
+            }
+
+            // Compute blake3 hash (super-fast, SIMD, streaming)
+            let parallel = opts.parallel_threshold.is_some_and(|min| span_len >= min);
+            let hash = hash_contents(path, &meta, range.as_ref(), opts, |hasher, from, to| {
+                let bytes = &data[(from - span_start) as usize..(to - span_start) as usize];
+                if parallel {
+                    hasher.update_parallel(bytes);
+                } else {
+                    hasher.update(bytes);
+                }
+                Ok(())
+            })?;
+
+            // Optional quick XOR checksum (non-cryptographic); the CPU computes the
+            // same reduction whenever no GPU context exists or the kernel fails
+            let (xor64, xor64_source) = if opts.use_gpu {
+                match opts.gpu.and_then(|ctx| ctx.xor64_for_file(data).ok()) {
+                    Some(v) => (Some(v), Some(XorSource::Gpu)),
+                    None => (Some(xor64_cpu(data)), Some(XorSource::Cpu)),
+                }
+            } else {
+                (None, None)
+            };
+            let ratio = opts
+                .compress_probe
+                .and_then(|probe| compress_ratio(&data[..probe.min(span_len) as usize]));
+            (hash, xor64, xor64_source, ratio)
+        }
+    };
+
+    let elapsed = start.elapsed().as_millis();
+    let (hash_str, hashes) = encode_digests(&digests, opts.encoding);
+    Ok(FileReport {
+        hash_str,
+        hashes,
+        xor64,
+        xor64_source,
+        elapsed_ms: elapsed,
+        compress_ratio,
+        range: range.map(|r| [r.start, r.end]),
+        hole_bytes: extents.map(|e| sparse::hole_bytes(&e, span_start, span_start + span_len)),
+        resident,
+        ..FileReport::new(path, size)
+    })
+}
@@ -0,0 +1,362 @@
+//! Progress-bar plumbing: a rate-limited draw target and a smoothed
+//! throughput/ETA estimate for the byte bar, JSON status frames for a
+//! monitoring process reading a pipe, and plain status lines for logs.
+
+use crate::report::Units;
+use indicatif::style::ProgressTracker;
+use indicatif::{DecimalBytes, HumanBytes, ProgressDrawTarget, ProgressState, ProgressStyle};
+use serde::Serialize;
+use std::fmt::Write;
+use std::fs::{File, OpenOptions};
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Default redraw interval (~10 Hz).
+pub const DEFAULT_REFRESH_MS: u64 = 100;
+
+/// Time constant of the rate average. Long enough that one multi-GB file
+/// finishing does not swing the ETA, short enough to follow real slowdowns.
+const SMOOTHING_SECS: f64 = 10.0;
+
+/// Warn when bytes actually processed differ from the walk-time estimate by
+/// more than this fraction, e.g. because files grew while downloading.
+pub const ESTIMATE_DRIFT_WARN: f64 = 0.01;
+
+/// Relative difference between the estimate and what was processed.
+pub fn estimate_drift(estimate: u64, actual: u64) -> f64 {
+    if estimate == actual {
+        return 0.0;
+    }
+    estimate.abs_diff(actual) as f64 / estimate.max(1) as f64
+}
+
+/// stderr draw target that redraws at most once per `refresh_ms`.
+pub fn draw_target(refresh_ms: u64) -> ProgressDrawTarget {
+    let hz = (1000 / refresh_ms.max(1)).clamp(1, u8::MAX as u64) as u8;
+    ProgressDrawTarget::stderr_with_hz(hz)
+}
+
+/// The file bar's default template.
+pub const FILES_TEMPLATE: &str =
+    "{spinner:.green} [{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} files";
+
+/// The byte bar's default template; `smoothed_rate` and `smoothed_eta` are
+/// `SmoothedRate` keys.
+pub fn bytes_template(units: Units) -> &'static str {
+    match units {
+        Units::Iec => "{msg} {bytes:>7}/{total_bytes:7} {smoothed_rate} ETA {smoothed_eta}",
+        Units::Si => {
+            "{msg} {decimal_bytes:>7}/{decimal_total_bytes:7} {smoothed_rate} ETA {smoothed_eta}"
+        }
+    }
+}
+
+/// A style from the user's `custom` template, or from `default` when there
+/// is none or it does not parse; `flag` names the option in the warning.
+/// Never panics: a default that fails too gives indicatif's plain bar.
+pub fn bar_style(custom: Option<&str>, default: &str, flag: &str) -> ProgressStyle {
+    if let Some(template) = custom {
+        match ProgressStyle::with_template(template) {
+            Ok(style) => return style,
+            Err(e) => eprintln!("[WARN] Ignoring {} {:?}: {}", flag, template, e),
+        }
+    }
+    ProgressStyle::with_template(default).unwrap_or_else(|_| ProgressStyle::default_bar())
+}
+
+#[derive(Clone, Copy)]
+enum Show {
+    Rate,
+    Eta,
+}
+
+/// Exponentially weighted bytes/sec, exposed to templates as a custom key.
+#[derive(Clone)]
+pub struct SmoothedRate {
+    show: Show,
+    units: Units,
+    rate: Option<f64>,
+    last: Option<(Instant, u64)>,
+}
+
+impl SmoothedRate {
+    /// Tracker rendering the smoothed rate, e.g. `812.40 MiB/s` or `851.87 MB/s`.
+    pub fn rate(units: Units) -> Self {
+        Self::new(Show::Rate, units)
+    }
+
+    /// Tracker rendering the remaining time at the smoothed rate as `HH:MM:SS`.
+    pub fn eta() -> Self {
+        Self::new(Show::Eta, Units::default())
+    }
+
+    fn new(show: Show, units: Units) -> Self {
+        Self {
+            show,
+            units,
+            rate: None,
+            last: None,
+        }
+    }
+}
+
+impl ProgressTracker for SmoothedRate {
+    fn clone_box(&self) -> Box<dyn ProgressTracker> {
+        Box::new(self.clone())
+    }
+
+    fn tick(&mut self, state: &ProgressState, now: Instant) {
+        let pos = state.pos();
+        let Some((then, prev)) = self.last else {
+            self.last = Some((now, pos));
+            return;
+        };
+        let dt = now.duration_since(then).as_secs_f64();
+        if dt < 0.05 {
+            return;
+        }
+        let sample = pos.saturating_sub(prev) as f64 / dt;
+        // weight by elapsed time so irregular tick spacing doesn't bias the average
+        let alpha = 1.0 - (-dt / SMOOTHING_SECS).exp();
+        self.rate = Some(match self.rate {
+            Some(rate) => rate + alpha * (sample - rate),
+            None => sample,
+        });
+        self.last = Some((now, pos));
+    }
+
+    fn reset(&mut self, state: &ProgressState, now: Instant) {
+        self.rate = None;
+        self.last = Some((now, state.pos()));
+    }
+
+    fn write(&self, state: &ProgressState, w: &mut dyn Write) {
+        let rate = self.rate.filter(|r| *r > 0.0);
+        let _ = match (self.show, rate) {
+            (Show::Rate, Some(rate)) => match self.units {
+                Units::Iec => write!(w, "{}/s", HumanBytes(rate as u64)),
+                Units::Si => write!(w, "{}/s", DecimalBytes(rate as u64)),
+            },
+            (Show::Rate, None) => write!(w, "-/s"),
+            (Show::Eta, Some(rate)) => {
+                let remaining = state.len().unwrap_or(0).saturating_sub(state.pos());
+                let secs = (remaining as f64 / rate) as u64;
+                write!(w, "{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+            }
+            (Show::Eta, None) => write!(w, "--:--:--"),
+        };
+    }
+}
+
+/// Default interval between `--progress-pipe` frames.
+pub const DEFAULT_PIPE_INTERVAL_MS: u64 = 1000;
+
+/// One line written to `--progress-pipe`.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ProgressFrame {
+    pub files_done: u64,
+    pub files_total: u64,
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+    /// Megabytes (10^6 bytes) per second over the last interval
+    pub rate_mbps: f64,
+}
+
+/// Running totals the aggregator updates and the frame writer reads.
+#[derive(Clone, Default)]
+pub struct Counters {
+    pub files: Arc<AtomicU64>,
+    pub bytes: Arc<AtomicU64>,
+}
+
+/// Background thread writing a `ProgressFrame` per interval, as NDJSON, to a
+/// FIFO or file. A FIFO is opened without blocking: frames are dropped while
+/// no reader is attached or the pipe is full, and a reader that goes away is
+/// simply waited for again, so monitoring never stalls or kills the scan.
+pub struct PipeReporter {
+    stop: mpsc::Sender<()>,
+    handle: JoinHandle<()>,
+}
+
+impl PipeReporter {
+    pub fn start(
+        path: &Path,
+        interval: Duration,
+        counters: Counters,
+        files_total: u64,
+        bytes_total: u64,
+    ) -> Self {
+        let (stop, stopped) = mpsc::channel();
+        let path = path.to_path_buf();
+        let handle = std::thread::spawn(move || {
+            let mut pipe = PipeWriter { path, out: None };
+            let mut last = (Instant::now(), 0u64);
+            loop {
+                let done = stopped.recv_timeout(interval) != Err(RecvTimeoutError::Timeout);
+                let bytes_done = counters.bytes.load(Ordering::Relaxed);
+                let now = Instant::now();
+                let secs = now.duration_since(last.0).as_secs_f64().max(1e-3);
+                let frame = ProgressFrame {
+                    files_done: counters.files.load(Ordering::Relaxed),
+                    files_total,
+                    bytes_done,
+                    // archive members and growing files can outrun the estimate
+                    bytes_total: bytes_total.max(bytes_done),
+                    rate_mbps: bytes_done.saturating_sub(last.1) as f64 / 1e6 / secs,
+                };
+                last = (now, bytes_done);
+                pipe.send(&frame);
+                if done {
+                    break;
+                }
+            }
+        });
+        Self { stop, handle }
+    }
+
+    /// Write a last frame with the final counts and stop the thread.
+    pub fn finish(self) {
+        let _ = self.stop.send(());
+        let _ = self.handle.join();
+    }
+}
+
+/// Default seconds between `--progress-interval` status lines.
+pub const DEFAULT_STATUS_INTERVAL_SECS: u64 = 30;
+
+/// One status line, e.g.
+/// `[07:12] 4210/10000 files, 12.30 GiB/80.00 GiB, 45.00 MiB/s, ETA 12m`.
+/// `rate` is bytes/sec over the last interval; the ETA uses the average since
+/// the start, which a single slow file does not swing.
+pub fn status_line(
+    elapsed: Duration,
+    (files_done, files_total): (u64, u64),
+    (bytes_done, bytes_total): (u64, u64),
+    rate: f64,
+    units: Units,
+) -> String {
+    let bytes = |n: u64| match units {
+        Units::Iec => HumanBytes(n).to_string(),
+        Units::Si => DecimalBytes(n).to_string(),
+    };
+    let secs = elapsed.as_secs();
+    let average = bytes_done as f64 / elapsed.as_secs_f64().max(1e-3);
+    let eta = match bytes_total.saturating_sub(bytes_done) {
+        0 => "0s".to_string(),
+        _ if average <= 0.0 => "?".to_string(),
+        left => short_duration((left as f64 / average) as u64),
+    };
+    format!(
+        "[{:02}:{:02}] {}/{} files, {}/{}, {}/s, ETA {}",
+        secs / 60,
+        secs % 60,
+        files_done,
+        files_total,
+        bytes(bytes_done),
+        bytes(bytes_total.max(bytes_done)),
+        bytes(rate as u64),
+        eta
+    )
+}
+
+/// Coarse duration for an ETA: `45s`, `12m`, `3h05m`.
+fn short_duration(secs: u64) -> String {
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3600 => format!("{}m", secs / 60),
+        _ => format!("{}h{:02}m", secs / 3600, secs / 60 % 60),
+    }
+}
+
+/// Background thread printing a `status_line` to stderr per interval, for
+/// runs where stderr is not a terminal and the bars are not drawn.
+pub struct StatusReporter {
+    stop: mpsc::Sender<()>,
+    handle: JoinHandle<()>,
+}
+
+impl StatusReporter {
+    pub fn start(
+        interval: Duration,
+        counters: Counters,
+        files_total: u64,
+        bytes_total: u64,
+        units: Units,
+    ) -> Self {
+        let (stop, stopped) = mpsc::channel();
+        let handle = std::thread::spawn(move || {
+            let start = Instant::now();
+            let mut last = (start, 0u64);
+            while stopped.recv_timeout(interval) == Err(RecvTimeoutError::Timeout) {
+                let bytes_done = counters.bytes.load(Ordering::Relaxed);
+                let now = Instant::now();
+                let secs = now.duration_since(last.0).as_secs_f64().max(1e-3);
+                let rate = bytes_done.saturating_sub(last.1) as f64 / secs;
+                last = (now, bytes_done);
+                eprintln!(
+                    "{}",
+                    status_line(
+                        now.duration_since(start),
+                        (counters.files.load(Ordering::Relaxed), files_total),
+                        (bytes_done, bytes_total),
+                        rate,
+                        units,
+                    )
+                );
+            }
+        });
+        Self { stop, handle }
+    }
+
+    /// Stop the thread; the summary follows, so no last line is printed.
+    pub fn finish(self) {
+        let _ = self.stop.send(());
+        let _ = self.handle.join();
+    }
+}
+
+struct PipeWriter {
+    path: PathBuf,
+    out: Option<File>,
+}
+
+impl PipeWriter {
+    fn send(&mut self, frame: &ProgressFrame) {
+        if self.out.is_none() {
+            self.out = open_nonblocking(&self.path).ok();
+        }
+        let Some(out) = self.out.as_mut() else {
+            return;
+        };
+        let mut line = serde_json::to_vec(frame).unwrap_or_default();
+        line.push(b'\n');
+        match out.write(&line) {
+            Ok(_) => {}
+            // a full pipe just loses this frame
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+            // reader gone (EPIPE): reopen once another one attaches
+            Err(_) => self.out = None,
+        }
+    }
+}
+
+#[cfg(unix)]
+fn open_nonblocking(path: &Path) -> std::io::Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    // on a FIFO with no reader this fails with ENXIO instead of blocking
+    OpenOptions::new()
+        .append(true)
+        .create(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path)
+}
+
+#[cfg(not(unix))]
+fn open_nonblocking(path: &Path) -> std::io::Result<File> {
+    OpenOptions::new().append(true).create(true).open(path)
+}
@@ -0,0 +1,92 @@
+//! The report's `provenance`: which build scanned on what machine, with
+//! how many threads and which arguments, so reports from different hosts
+//! can be told apart and their timings explained. The hostname is left out
+//! unless asked for with `--include-hostname`.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema, Deserialize)]
+pub struct Provenance {
+    pub tool_version: String,
+    /// Only with `--include-hostname`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    /// `linux`, `macos`, `windows`, ...
+    pub os: String,
+    pub arch: String,
+    /// Kernel release, where the OS reports one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub os_release: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_model: Option<String>,
+    /// Logical CPUs of the machine
+    pub cpus: usize,
+    /// Worker threads the scan used
+    pub threads: usize,
+    /// Whether XOR64 checksums ran on an OpenCL device
+    pub gpu: bool,
+    /// The command line, program name included
+    pub args: Vec<String>,
+}
+
+impl Provenance {
+    /// This process and machine, for a scan on `threads` workers.
+    pub fn collect(threads: usize, gpu: bool, include_hostname: bool) -> Self {
+        Self {
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            hostname: include_hostname.then(hostname).flatten(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            os_release: os_release(),
+            cpu_model: cpu_model(),
+            cpus: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            threads,
+            gpu,
+            args: std::env::args_os().map(|a| a.to_string_lossy().into_owned()).collect(),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn hostname() -> Option<String> {
+    let mut buf = [0u8; 256];
+    // SAFETY: the buffer outlives the call and its length is passed along
+    let rc = unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) };
+    if rc != 0 {
+        return None;
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    Some(String::from_utf8_lossy(&buf[..len]).into_owned()).filter(|h| !h.is_empty())
+}
+
+#[cfg(not(unix))]
+fn hostname() -> Option<String> {
+    std::env::var("COMPUTERNAME").ok()
+}
+
+#[cfg(target_os = "linux")]
+fn os_release() -> Option<String> {
+    let release = std::fs::read_to_string("/proc/sys/kernel/osrelease").ok()?;
+    Some(release.trim().to_string())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn os_release() -> Option<String> {
+    None
+}
+
+/// The first `model name` in `/proc/cpuinfo`; ARM kernels may not list one.
+#[cfg(target_os = "linux")]
+fn cpu_model() -> Option<String> {
+    let info = std::fs::read_to_string("/proc/cpuinfo").ok()?;
+    info.lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| key.trim() == "model name")
+        .map(|(_, value)| value.trim().to_string())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn cpu_model() -> Option<String> {
+    None
+}
@@ -0,0 +1,529 @@
+//! `--inspect-pytorch`: tensor names, dtypes and shapes of PyTorch
+//! checkpoints (`pytorch_model.bin`, `.pt`, `.pth`), read from the pickle
+//! that indexes them.
+//!
+//! The pickle is walked opcode by opcode into a plain object graph. Nothing
+//! is imported, called or executed: `GLOBAL` only records a name and
+//! `REDUCE` only records the call it would have made. Any opcode outside
+//! the subset `torch.save` writes stops the walk with an error.
+//!
+//! Two layouts are read. The zip archive `torch.save` has written since
+//! PyTorch 1.6 keeps the index in `<name>/data.pkl`, stored uncompressed;
+//! only the central directory and that entry are read. The legacy format is
+//! a stream of pickles whose tensor data follows the index, so reading
+//! stops at the index's `STOP`.
+
+use anyhow::{Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use zip::{CompressionMethod, ZipArchive};
+
+/// Largest index read; checkpoints with millions of tensors stay well below
+const MAX_PICKLE_BYTES: u64 = 64 << 20;
+/// Containers nested deeper than this are not searched for tensors
+const MAX_DEPTH: usize = 64;
+/// Objects visited while searching for tensors. Memoized objects can be
+/// shared many times over, so a few hundred bytes of pickle can otherwise
+/// name more key paths than any scan could walk
+const MAX_VISITS: usize = 1 << 20;
+
+const ZIP_LOCAL_SIG: [u8; 4] = *b"PK\x03\x04";
+/// PROTO 2, LONG1 of 10 bytes, then torch's magic number 0x1950a86a20f9469cfc6c
+const LEGACY_MAGIC: [u8; 14] = [
+    0x80, 0x02, 0x8a, 0x0a, 0x6c, 0xfc, 0x9c, 0x46, 0xf9, 0x20, 0x6a, 0xa8, 0x50, 0x19,
+];
+
+/// How the checkpoint was saved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PytorchFormat {
+    /// `torch.save` since PyTorch 1.6
+    Zip,
+    /// `torch.save(..., _use_new_zipfile_serialization=False)` and older
+    Legacy,
+}
+
+/// One stored tensor.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema, Deserialize)]
+pub struct TensorInfo {
+    /// Dotted path through the saved dicts, e.g. `model.layers.0.weight`
+    pub name: String,
+    /// `float32`, `float16`, `bfloat16`, ...; the storage class for others
+    pub dtype: String,
+    pub shape: Vec<u64>,
+}
+
+/// What `--inspect-pytorch` found in one checkpoint.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema, Deserialize)]
+pub struct PytorchInfo {
+    pub format: PytorchFormat,
+    pub tensors: Vec<TensorInfo>,
+    /// Why the index could not be read; `tensors` is then empty
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl PytorchInfo {
+    /// Elements over all tensors.
+    pub fn parameters(&self) -> u64 {
+        self.tensors
+            .iter()
+            .map(|t| t.shape.iter().fold(1u64, |n, &d| n.saturating_mul(d)))
+            .fold(0, u64::saturating_add)
+    }
+}
+
+/// Inspect `path` if it is a PyTorch checkpoint. `None` for any other
+/// file; a checkpoint whose index cannot be read gets an `error`.
+pub fn inspect(path: &Path) -> Option<PytorchInfo> {
+    let mut f = File::open(path).ok()?;
+    let mut head = [0u8; LEGACY_MAGIC.len()];
+    f.read_exact(&mut head).ok()?;
+    let (format, tensors) = if head.starts_with(&ZIP_LOCAL_SIG) {
+        // other zip files (npz, docx, ...) have no data.pkl and are not checkpoints,
+        // and neither is one whose central directory cannot be read to tell
+        let mut zip = ZipArchive::new(f).ok()?;
+        let index = find_data_pkl(&zip)?;
+        let tensors = read_stored(&mut zip, index).and_then(|data| {
+            let mut pickle = Unpickler::new(data.as_slice());
+            let root = pickle.load()?;
+            pickle.tensors(root)
+        });
+        (PytorchFormat::Zip, tensors)
+    } else if head == LEGACY_MAGIC {
+        let tensors = (|| {
+            f.seek(SeekFrom::Start(0))?;
+            let mut pickle = Unpickler::new(BufReader::new(f.take(MAX_PICKLE_BYTES)));
+            // magic number, protocol version and system info precede the index
+            for _ in 0..3 {
+                pickle.load()?;
+            }
+            let root = pickle.load()?;
+            pickle.tensors(root)
+        })();
+        (PytorchFormat::Legacy, tensors)
+    } else {
+        return None;
+    };
+    Some(match tensors {
+        Ok(tensors) => PytorchInfo {
+            format,
+            tensors,
+            error: None,
+        },
+        Err(e) => failed(format, e),
+    })
+}
+
+fn failed(format: PytorchFormat, e: anyhow::Error) -> PytorchInfo {
+    PytorchInfo {
+        format,
+        tensors: Vec::new(),
+        error: Some(format!("{:#}", e)),
+    }
+}
+
+/// Index of `data.pkl`, at the top level or one directory down.
+fn find_data_pkl<R: Read + Seek>(zip: &ZipArchive<R>) -> Option<usize> {
+    (0..zip.len()).find(|&i| {
+        zip.name_for_index(i).is_some_and(|name| {
+            name == "data.pkl"
+                || (name.ends_with("/data.pkl") && name.matches('/').count() == 1)
+        })
+    })
+}
+
+/// The data of a stored (uncompressed) entry.
+fn read_stored<R: Read + Seek>(zip: &mut ZipArchive<R>, index: usize) -> Result<Vec<u8>> {
+    let entry = zip.by_index(index)?;
+    if entry.compression() != CompressionMethod::Stored {
+        anyhow::bail!("data.pkl is compressed; torch.save stores it as is");
+    }
+    if entry.size() > MAX_PICKLE_BYTES {
+        anyhow::bail!("data.pkl is {} bytes", entry.size());
+    }
+    let mut data = Vec::with_capacity(entry.size() as usize);
+    entry.take(MAX_PICKLE_BYTES).read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// A pickle object. Children are indices into `Unpickler::objects`, so
+/// memoized objects stay shared the way the pickle meant them to be.
+#[derive(Debug, Clone)]
+enum Obj {
+    None,
+    Int(i64),
+    Str(String),
+    /// Anything whose value tensor lookup never needs: floats, bytes,
+    /// booleans, integers beyond 64 bits
+    Opaque,
+    Tuple(Vec<usize>),
+    List(Vec<usize>),
+    Dict(Vec<(usize, usize)>),
+    Global(String, String),
+    /// `REDUCE` or `NEWOBJ`: the callable and argument tuple, never called
+    Call(usize, usize),
+    /// `BINPERSID`: a reference the loader would resolve, e.g. a storage
+    PersId(usize),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Item {
+    Obj(usize),
+    Mark,
+}
+
+struct Unpickler<R> {
+    input: R,
+    objects: Vec<Obj>,
+    memo: HashMap<u64, usize>,
+}
+
+impl<R: Read> Unpickler<R> {
+    fn new(input: R) -> Self {
+        Self {
+            input,
+            objects: Vec::new(),
+            memo: HashMap::new(),
+        }
+    }
+
+    fn bytes(&mut self, n: u64) -> Result<Vec<u8>> {
+        if n > MAX_PICKLE_BYTES {
+            anyhow::bail!("pickle argument of {} bytes", n);
+        }
+        let mut buf = Vec::new();
+        (&mut self.input).take(n).read_to_end(&mut buf)?;
+        if (buf.len() as u64) < n {
+            anyhow::bail!("pickle ends mid-opcode");
+        }
+        Ok(buf)
+    }
+
+    fn uint(&mut self, n: u64) -> Result<u64> {
+        Ok(self.bytes(n)?.iter().rev().fold(0, |acc, &b| acc << 8 | b as u64))
+    }
+
+    fn line(&mut self) -> Result<String> {
+        let mut line = Vec::new();
+        loop {
+            match self.bytes(1)?[0] {
+                b'\n' => return Ok(String::from_utf8_lossy(&line).into_owned()),
+                b => line.push(b),
+            }
+            if line.len() > 4096 {
+                anyhow::bail!("GLOBAL name too long");
+            }
+        }
+    }
+
+    fn push(&mut self, stack: &mut Vec<Item>, obj: Obj) {
+        self.objects.push(obj);
+        stack.push(Item::Obj(self.objects.len() - 1));
+    }
+
+    /// Read one pickle, up to its `STOP`, and return its top object.
+    fn load(&mut self) -> Result<usize> {
+        let mut stack: Vec<Item> = Vec::new();
+        self.memo.clear();
+        loop {
+            let op = self.bytes(1)?[0];
+            match op {
+                // PROTO, FRAME: framing only
+                0x80 => drop(self.bytes(1)?),
+                0x95 => drop(self.bytes(8)?),
+                b'.' => return top(&mut stack),
+                b'(' => stack.push(Item::Mark),
+                b'N' => self.push(&mut stack, Obj::None),
+                0x88 | 0x89 | b'G' => {
+                    if op == b'G' {
+                        self.bytes(8)?;
+                    }
+                    self.push(&mut stack, Obj::Opaque)
+                }
+                b'K' => {
+                    let v = self.uint(1)? as i64;
+                    self.push(&mut stack, Obj::Int(v))
+                }
+                b'M' => {
+                    let v = self.uint(2)? as i64;
+                    self.push(&mut stack, Obj::Int(v))
+                }
+                b'J' => {
+                    let v = self.uint(4)? as u32 as i32 as i64;
+                    self.push(&mut stack, Obj::Int(v))
+                }
+                0x8a => {
+                    let n = self.uint(1)?;
+                    let raw = self.bytes(n)?;
+                    let obj = if raw.len() <= 8 {
+                        // little-endian two's complement, sign-extended
+                        let fill = if raw.last().is_some_and(|b| b & 0x80 != 0) { 0xff } else { 0 };
+                        let mut le = [fill; 8];
+                        le[..raw.len()].copy_from_slice(&raw);
+                        Obj::Int(i64::from_le_bytes(le))
+                    } else {
+                        Obj::Opaque
+                    };
+                    self.push(&mut stack, obj)
+                }
+                b'X' | 0x8c | 0x8d | b'U' | b'T' => {
+                    let n = match op {
+                        0x8c | b'U' => self.uint(1)?,
+                        0x8d => self.uint(8)?,
+                        _ => self.uint(4)?,
+                    };
+                    let text = String::from_utf8_lossy(&self.bytes(n)?).into_owned();
+                    self.push(&mut stack, Obj::Str(text))
+                }
+                b'C' | b'B' | 0x8e => {
+                    let n = match op {
+                        b'C' => self.uint(1)?,
+                        b'B' => self.uint(4)?,
+                        _ => self.uint(8)?,
+                    };
+                    self.bytes(n)?;
+                    self.push(&mut stack, Obj::Opaque)
+                }
+                b'}' => self.push(&mut stack, Obj::Dict(Vec::new())),
+                b']' => self.push(&mut stack, Obj::List(Vec::new())),
+                b')' => self.push(&mut stack, Obj::Tuple(Vec::new())),
+                b't' => {
+                    let items = pop_mark(&mut stack)?;
+                    self.push(&mut stack, Obj::Tuple(items))
+                }
+                0x85..=0x87 => {
+                    let n = (op - 0x84) as usize;
+                    let at = stack.len().checked_sub(n).context("TUPLE on a short stack")?;
+                    let items = objs(stack.split_off(at))?;
+                    self.push(&mut stack, Obj::Tuple(items))
+                }
+                b'q' | b'r' | 0x94 => {
+                    let key = match op {
+                        b'q' => self.uint(1)?,
+                        b'r' => self.uint(4)?,
+                        _ => self.memo.len() as u64,
+                    };
+                    let Some(&Item::Obj(id)) = stack.last() else {
+                        anyhow::bail!("memoizing an empty stack");
+                    };
+                    self.memo.insert(key, id);
+                }
+                b'h' | b'j' => {
+                    let key = if op == b'h' { self.uint(1)? } else { self.uint(4)? };
+                    let id = *self.memo.get(&key).context("BINGET of an unset memo key")?;
+                    stack.push(Item::Obj(id));
+                }
+                b'c' => {
+                    let (module, name) = (self.line()?, self.line()?);
+                    self.push(&mut stack, Obj::Global(module, name))
+                }
+                0x93 => {
+                    let name = top(&mut stack)?;
+                    let module = top(&mut stack)?;
+                    let global = match (&self.objects[module], &self.objects[name]) {
+                        (Obj::Str(m), Obj::Str(n)) => Obj::Global(m.clone(), n.clone()),
+                        _ => anyhow::bail!("STACK_GLOBAL of non-strings"),
+                    };
+                    self.push(&mut stack, global)
+                }
+                b'R' | 0x81 => {
+                    let args = top(&mut stack)?;
+                    let func = top(&mut stack)?;
+                    let obj = match &self.objects[func] {
+                        // what OrderedDict() would give, ready for SETITEMS
+                        Obj::Global(m, n) if m == "collections" && n == "OrderedDict" => {
+                            Obj::Dict(Vec::new())
+                        }
+                        _ => Obj::Call(func, args),
+                    };
+                    self.push(&mut stack, obj)
+                }
+                b'Q' => {
+                    let pid = top(&mut stack)?;
+                    self.push(&mut stack, Obj::PersId(pid))
+                }
+                // BUILD sets state (e.g. a state dict's `_metadata`) that tensor lookup ignores
+                b'b' => drop(top(&mut stack)?),
+                b's' | b'u' => {
+                    let items = if op == b's' {
+                        let value = top(&mut stack)?;
+                        vec![top(&mut stack)?, value]
+                    } else {
+                        pop_mark(&mut stack)?
+                    };
+                    if items.len() % 2 != 0 {
+                        anyhow::bail!("SETITEMS with an odd number of items");
+                    }
+                    let target = *stack.last().context("SETITEMS on an empty stack")?;
+                    let Item::Obj(target) = target else {
+                        anyhow::bail!("SETITEMS on a mark");
+                    };
+                    let Obj::Dict(entries) = &mut self.objects[target] else {
+                        anyhow::bail!("SETITEMS on something other than a dict");
+                    };
+                    entries.extend(items.chunks_exact(2).map(|kv| (kv[0], kv[1])));
+                }
+                b'a' | b'e' => {
+                    let items = if op == b'a' {
+                        vec![top(&mut stack)?]
+                    } else {
+                        pop_mark(&mut stack)?
+                    };
+                    let Some(&Item::Obj(target)) = stack.last() else {
+                        anyhow::bail!("APPENDS without a list");
+                    };
+                    let Obj::List(list) = &mut self.objects[target] else {
+                        anyhow::bail!("APPENDS on something other than a list");
+                    };
+                    list.extend(items);
+                }
+                b'0' => drop(stack.pop().context("POP on an empty stack")?),
+                b'1' => drop(pop_mark(&mut stack)?),
+                _ => anyhow::bail!("unsupported pickle opcode 0x{:02x}", op),
+            }
+        }
+    }
+
+    /// Every tensor reachable from `root` through dicts, named by key path.
+    fn tensors(&self, root: usize) -> Result<Vec<TensorInfo>> {
+        let mut out = Vec::new();
+        let mut visits = 0;
+        self.collect(root, String::new(), 0, &mut visits, &mut out)?;
+        Ok(out)
+    }
+
+    fn collect(
+        &self,
+        id: usize,
+        name: String,
+        depth: usize,
+        visits: &mut usize,
+        out: &mut Vec<TensorInfo>,
+    ) -> Result<()> {
+        if depth > MAX_DEPTH {
+            return Ok(());
+        }
+        *visits += 1;
+        if *visits > MAX_VISITS {
+            anyhow::bail!("index reaches more than {} objects through shared ones", MAX_VISITS);
+        }
+        match &self.objects[id] {
+            Obj::Dict(entries) => {
+                for &(key, value) in entries {
+                    let key = match &self.objects[key] {
+                        Obj::Str(s) => s.clone(),
+                        Obj::Int(i) => i.to_string(),
+                        _ => continue,
+                    };
+                    let name = if name.is_empty() { key } else { format!("{}.{}", name, key) };
+                    self.collect(value, name, depth + 1, visits, out)?;
+                }
+            }
+            Obj::Call(func, args) => {
+                let (Obj::Global(module, func), Obj::Tuple(args)) =
+                    (&self.objects[*func], &self.objects[*args])
+                else {
+                    return Ok(());
+                };
+                match (module.as_str(), func.as_str()) {
+                    ("torch._utils", "_rebuild_tensor" | "_rebuild_tensor_v2") => {
+                        if let Some(tensor) = self.tensor(name, args) {
+                            out.push(tensor);
+                        }
+                    }
+                    ("torch._utils", "_rebuild_parameter" | "_rebuild_parameter_with_state") => {
+                        if let Some(&tensor) = args.first() {
+                            self.collect(tensor, name, depth + 1, visits, out)?;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// `_rebuild_tensor_v2(storage, storage_offset, size, stride, ...)`, where
+    /// storage is `PersId(("storage", torch.<Type>Storage, key, location, numel))`.
+    fn tensor(&self, name: String, args: &[usize]) -> Option<TensorInfo> {
+        let Obj::PersId(pid) = &self.objects[*args.first()?] else {
+            return None;
+        };
+        let Obj::Tuple(pid) = &self.objects[*pid] else {
+            return None;
+        };
+        let Obj::Global(_, storage) = &self.objects[*pid.get(1)?] else {
+            return None;
+        };
+        let Obj::Tuple(dims) = &self.objects[*args.get(2)?] else {
+            return None;
+        };
+        let shape = dims
+            .iter()
+            .map(|&d| match self.objects[d] {
+                Obj::Int(n) => u64::try_from(n).ok(),
+                _ => None,
+            })
+            .collect::<Option<Vec<u64>>>()?;
+        Some(TensorInfo {
+            name,
+            dtype: dtype_name(storage),
+            shape,
+        })
+    }
+}
+
+fn top(stack: &mut Vec<Item>) -> Result<usize> {
+    match stack.pop() {
+        Some(Item::Obj(id)) => Ok(id),
+        Some(Item::Mark) => anyhow::bail!("unexpected mark on the pickle stack"),
+        None => anyhow::bail!("pickle stack underflow"),
+    }
+}
+
+/// Objects above the topmost mark, which is removed.
+fn pop_mark(stack: &mut Vec<Item>) -> Result<Vec<usize>> {
+    let mark = stack
+        .iter()
+        .rposition(|i| matches!(i, Item::Mark))
+        .context("no mark on the pickle stack")?;
+    let items = stack.split_off(mark + 1);
+    stack.pop();
+    objs(items)
+}
+
+fn objs(items: Vec<Item>) -> Result<Vec<usize>> {
+    items
+        .into_iter()
+        .map(|i| match i {
+            Item::Obj(id) => Ok(id),
+            Item::Mark => anyhow::bail!("unexpected mark on the pickle stack"),
+        })
+        .collect()
+}
+
+/// `FloatStorage` -> `float32`, and so on; unknown classes are kept as is.
+fn dtype_name(storage: &str) -> String {
+    let dtype = match storage {
+        "FloatStorage" => "float32",
+        "DoubleStorage" => "float64",
+        "HalfStorage" => "float16",
+        "BFloat16Storage" => "bfloat16",
+        "LongStorage" => "int64",
+        "IntStorage" => "int32",
+        "ShortStorage" => "int16",
+        "CharStorage" => "int8",
+        "ByteStorage" => "uint8",
+        "BoolStorage" => "bool",
+        "ComplexFloatStorage" => "complex64",
+        "ComplexDoubleStorage" => "complex128",
+        other => other,
+    };
+    dtype.to_string()
+}
@@ -0,0 +1,106 @@
+//! Fetching a manifest (or JSON report) from a URL for `--verify-url`.
+//!
+//! Each URL's last body is kept on disk with its `ETag`, and sent back as
+//! `If-None-Match`; a `304 Not Modified` then costs one round trip and no
+//! download. A failed fetch is never answered from that copy, so a fleet
+//! cannot quietly verify against a manifest the server no longer serves.
+
+use anyhow::{Context, Result};
+use reqwest::blocking::Client;
+use reqwest::header::{HeaderMap, ETAG, IF_NONE_MATCH};
+use reqwest::StatusCode;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Limit covering connect and download.
+pub const FETCH_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// `$XDG_CACHE_HOME/aivista/manifests`, else `~/.cache/aivista/manifests`.
+pub fn default_cache_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CACHE_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| Path::new(&h).join(".cache")))?;
+    Some(base.join("aivista").join("manifests"))
+}
+
+/// A fetched document and whether it came from the local copy.
+#[derive(Debug)]
+pub struct Fetched {
+    pub text: String,
+    pub not_modified: bool,
+}
+
+/// Where one URL's body and ETag are kept under `dir`.
+struct CacheEntry {
+    body: PathBuf,
+    etag: PathBuf,
+}
+
+impl CacheEntry {
+    fn new(dir: &Path, url: &str) -> Self {
+        let key = &blake3::hash(url.as_bytes()).to_hex()[..16];
+        Self {
+            body: dir.join(format!("{}.json", key)),
+            etag: dir.join(format!("{}.etag", key)),
+        }
+    }
+
+    /// The stored ETag, only when its body is there too.
+    fn etag(&self) -> Option<String> {
+        let etag = std::fs::read_to_string(&self.etag).ok()?;
+        self.body.is_file().then(|| etag.trim().to_string())
+    }
+
+    fn store(&self, text: &str, etag: Option<&str>) -> std::io::Result<()> {
+        if let Some(dir) = self.body.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        // body first: an ETag without the body it names is never sent
+        let _ = std::fs::remove_file(&self.etag);
+        std::fs::write(&self.body, text)?;
+        match etag {
+            Some(etag) => std::fs::write(&self.etag, etag),
+            None => Ok(()),
+        }
+    }
+}
+
+/// GET `url` with `headers`, revalidating against the copy in `cache_dir`
+/// when there is one. Any error here is a fetch failure, not a verdict on
+/// the cache.
+pub fn fetch(url: &str, headers: HeaderMap, cache_dir: Option<&Path>) -> Result<Fetched> {
+    let entry = cache_dir.map(|dir| CacheEntry::new(dir, url));
+    let etag = entry.as_ref().and_then(CacheEntry::etag);
+    let client = Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .build()
+        .context("building HTTP client")?;
+    let mut request = client.get(url).headers(headers);
+    if let Some(etag) = &etag {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+    let resp = request.send().with_context(|| format!("fetching {}", url))?;
+    if let (StatusCode::NOT_MODIFIED, Some(entry)) = (resp.status(), &entry) {
+        let text = std::fs::read_to_string(&entry.body)
+            .with_context(|| format!("reading cached copy {:?}", entry.body))?;
+        return Ok(Fetched {
+            text,
+            not_modified: true,
+        });
+    }
+    if !resp.status().is_success() {
+        anyhow::bail!("{} answered {}", url, resp.status());
+    }
+    let new_etag = resp.headers().get(ETAG).and_then(|v| v.to_str().ok()).map(str::to_string);
+    let text = resp.text().with_context(|| format!("reading response from {}", url))?;
+    if let Some(entry) = &entry {
+        if let Err(e) = entry.store(&text, new_etag.as_deref()) {
+            eprintln!("[WARN] Could not cache the manifest in {:?}: {}", entry.body, e);
+        }
+    }
+    Ok(Fetched {
+        text,
+        not_modified: false,
+    })
+}
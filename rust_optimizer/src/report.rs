@@ -0,0 +1,632 @@
+//! Per-file results and the serialized scan report.
+
+use crate::decompress::Codec;
+use crate::dupes::{DuplicateGroup, SizeCollision};
+use crate::encoding::HashEncoding;
+use crate::hardlinks::HardlinkGroup;
+use crate::hashmode::HashAlgorithm;
+use crate::layout::{ModelGroup, OrphanBlob};
+use crate::provenance::Provenance;
+use crate::pytorch::PytorchInfo;
+use crate::symlinks::SymlinkIssue;
+use crate::walk::WalkError;
+use anyhow::{Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::PathBuf;
+
+/// Version of the `--output` document, bumped whenever its shape changes.
+/// Version 1 was the bare `ScanReport`, files included, without an envelope;
+/// version 3 added `hashes`, `provenance`, `partial_hash` and `bytes_ok`.
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// JSON Schema of the `--output` document at `SCHEMA_VERSION`, derived from
+/// the report types and printed by `aivista_cache_scan schema`;
+/// `schema/report.schema.json` is a copy of it.
+pub fn report_schema() -> serde_json::Value {
+    let mut schema = schemars::schema_for!(ReportDocument<'static>).to_value();
+    schema["title"] = "aivista_cache_scan --output document".into();
+    schema["description"] = format!(
+        "Schema version {}. Optional fields are left out when empty; readers should ignore \
+         fields they do not know.",
+        SCHEMA_VERSION
+    )
+    .into();
+    schema["properties"]["schema_version"] = serde_json::json!({ "const": SCHEMA_VERSION });
+    schema
+}
+
+/// A scan's summary and files. Serialized on its own it is the `summary`
+/// of the `--output` document; `document` wraps it with the files and the
+/// versions.
+#[derive(Debug, Serialize, JsonSchema, Deserialize)]
+pub struct ScanReport {
+    /// The first `--cache` root
+    pub cache: PathBuf,
+    /// Every root, when more than one was scanned
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roots: Vec<PathBuf>,
+    pub total_files: usize,
+    pub total_bytes: u64,
+    /// Algorithm of every `hash_str`; absent means BLAKE3
+    #[serde(default, skip_serializing_if = "HashAlgorithm::is_blake3")]
+    pub hash_algorithm: HashAlgorithm,
+    /// Text encoding of every `hash_str`; absent means hex
+    #[serde(default, skip_serializing_if = "HashEncoding::is_hex")]
+    pub hash_encoding: HashEncoding,
+    /// Written beside the summary by `document`, not inside it
+    #[serde(skip)]
+    pub files: Vec<FileReport>,
+    /// Per-model totals when the cache has a known layout
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub models: Vec<ModelGroup>,
+    /// HF blobs no snapshot refers to, from `--find-orphans`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub orphans: Vec<OrphanBlob>,
+    /// Groups of files with identical contents, orphans excluded
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub duplicates: Vec<DuplicateGroup>,
+    /// Dangling or escaping symlinks, from `--check-symlinks`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub symlink_issues: Vec<SymlinkIssue>,
+    /// Equal sizes with differing contents, from `--size-collisions`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub size_collisions: Vec<SizeCollision>,
+    /// Directories the walk could not read; the scan is missing their files
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub walk_errors: Vec<WalkError>,
+    /// Inodes reached through more than one link, from `--hardlink-report`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hardlinks: Vec<HardlinkGroup>,
+    /// Bytes on disk with each inode counted once, from `--hardlink-report`;
+    /// `total_bytes` counts every path
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub real_bytes: Option<u64>,
+    /// Set when only a `--sample-fraction` of the files was processed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample: Option<SampleInfo>,
+    /// Files eligible before `--limit` cut the scan short; `files` holds
+    /// only the first of them in processing order
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limited_from: Option<usize>,
+    /// Machine, build and arguments of the run; set by the command-line tool
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+}
+
+/// The versioned `--output` document, borrowing its report.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ReportDocument<'a> {
+    pub schema_version: u32,
+    /// Version of the aivista that wrote the document
+    pub tool_version: &'static str,
+    pub summary: &'a ScanReport,
+    pub files: &'a [FileReport],
+}
+
+#[derive(Deserialize)]
+struct StoredDocument {
+    summary: ScanReport,
+    files: Vec<FileReport>,
+}
+
+/// A schema version 1 report: the summary's fields and the files side by side.
+#[derive(Deserialize)]
+struct FlatReport {
+    #[serde(flatten)]
+    summary: ScanReport,
+    files: Vec<FileReport>,
+}
+
+impl ScanReport {
+    pub fn document(&self) -> ReportDocument<'_> {
+        ReportDocument {
+            schema_version: SCHEMA_VERSION,
+            tool_version: env!("CARGO_PKG_VERSION"),
+            summary: self,
+            files: &self.files,
+        }
+    }
+
+    /// Parse an `--output` document of this or any earlier schema version;
+    /// one from a newer aivista is refused rather than half understood.
+    pub fn from_json(value: serde_json::Value) -> Result<Self> {
+        let version = match value.get("schema_version") {
+            None => 1,
+            Some(v) => v.as_u64().context("schema_version is not a number")?,
+        };
+        if version > SCHEMA_VERSION as u64 {
+            anyhow::bail!(
+                "report schema version {} is newer than this build reads ({}); use a newer aivista",
+                version,
+                SCHEMA_VERSION
+            );
+        }
+        let (mut report, files) = if version == 1 {
+            let flat: FlatReport = serde_json::from_value(value)?;
+            (flat.summary, flat.files)
+        } else {
+            let doc: StoredDocument = serde_json::from_value(value)?;
+            (doc.summary, doc.files)
+        };
+        report.files = files;
+        Ok(report)
+    }
+
+    pub fn from_reader(reader: impl Read) -> Result<Self> {
+        Self::from_json(serde_json::from_reader(reader)?)
+    }
+}
+
+/// How a sampled scan's files were chosen, and from how many.
+#[derive(Debug, Clone, Copy, Serialize, JsonSchema, Deserialize)]
+pub struct SampleInfo {
+    pub fraction: f64,
+    pub seed: u64,
+    /// Files eligible before sampling
+    pub population_files: usize,
+}
+
+impl SampleInfo {
+    /// Extrapolate the bytes of the whole population from the sampled files.
+    pub fn estimated_bytes(&self, sampled_files: usize, sampled_bytes: u64) -> u64 {
+        if sampled_files == 0 {
+            return 0;
+        }
+        (sampled_bytes as u128 * self.population_files as u128 / sampled_files as u128) as u64
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema, Deserialize)]
+pub struct FileReport {
+    pub path: PathBuf,
+    pub size: u64,
+    /// Content digest in the scan's `--hash-encoding`; reports from before
+    /// the encoding could be chosen call it `hash_hex`
+    #[serde(alias = "hash_hex")]
+    pub hash_str: Option<String>,
+    /// Every digest by algorithm, `hash_str`'s included, when `--hash`
+    /// names more than one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(extend("propertyNames" = { "$ref": "#/$defs/HashAlgorithm" }))]
+    pub hashes: Option<BTreeMap<HashAlgorithm, String>>,
+    /// `--sample-hash` content signature; see `process::sample_signature`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    pub xor64: Option<u64>,
+    pub xor64_source: Option<XorSource>,
+    pub elapsed_ms: u128,
+    #[serde(default)]
+    pub status: FileStatus,
+    /// Why the file could not be processed, for `Errored`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// OS error number behind `error`, when there is one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub errno: Option<i32>,
+    /// The `--cache` root the file was found under, when several were scanned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root: Option<PathBuf>,
+    /// Path inside the archive, for `--archives` members; `path` then
+    /// names no real file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub member: Option<PathBuf>,
+    /// zstd level-1 size over original size for the file's first bytes,
+    /// from `--compressibility-estimate`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compress_ratio: Option<f64>,
+    /// Mount point of the filesystem holding the file, from `--by-mount`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mount: Option<PathBuf>,
+    /// That filesystem's type, e.g. `ext4` or `nfs4`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fs_type: Option<String>,
+    /// Why the file looks like a failed download, from `--flag-suspicious`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suspicious: Option<String>,
+    /// How the content contradicts the extension, from `--detect-mismatch`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_mismatch: Option<String>,
+    /// Why a `Skipped` file was not hashed, when it is not simply its size
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skip_reason: Option<String>,
+    /// `[start, end)` byte offsets `hash_str` covers, from `--range`; the
+    /// hash then says nothing about the rest of the file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub range: Option<[u64; 2]>,
+    /// Bytes of sparse-file holes hashed as zeros instead of read, from `--sparse-aware`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hole_bytes: Option<u64>,
+    /// Fraction of the hashed span already in the page cache, from `--measure-residency`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resident: Option<f64>,
+    /// Tensors of a PyTorch checkpoint, from `--inspect-pytorch`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pytorch: Option<PytorchInfo>,
+    /// Where the symlink at `path` points, as written in the link
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symlink_target: Option<PathBuf>,
+    /// What `--decompress` decoded the file from before hashing it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decompressed: Option<Codec>,
+    /// Length of the decompressed content the hash covers; `size` stays the
+    /// length on disk
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logical_size: Option<u64>,
+    /// Digest of the bytes read before a read error, for `PartiallyHashed`;
+    /// never a hash of the whole file, so kept apart from `hash_str`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub partial_hash: Option<String>,
+    /// Bytes `partial_hash` covers, counted from the start of the hashed span
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes_ok: Option<u64>,
+}
+
+impl FileReport {
+    /// A `Hashed` report of `path` and its `size` with nothing else set;
+    /// callers override the fields they know.
+    pub fn new(path: impl Into<PathBuf>, size: u64) -> Self {
+        FileReport {
+            path: path.into(),
+            size,
+            hash_str: None,
+            hashes: None,
+            signature: None,
+            xor64: None,
+            xor64_source: None,
+            elapsed_ms: 0,
+            status: FileStatus::Hashed,
+            error: None,
+            errno: None,
+            root: None,
+            member: None,
+            compress_ratio: None,
+            mount: None,
+            fs_type: None,
+            suspicious: None,
+            content_mismatch: None,
+            skip_reason: None,
+            range: None,
+            hole_bytes: None,
+            resident: None,
+            pytorch: None,
+            symlink_target: None,
+            decompressed: None,
+            logical_size: None,
+            partial_hash: None,
+            bytes_ok: None,
+        }
+    }
+}
+
+/// Outcome of processing one file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, JsonSchema, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileStatus {
+    #[default]
+    Hashed,
+    /// Outside the `--min-bytes`/`--max-bytes` window, or shorter than the
+    /// `--range` start: sized but not hashed
+    Skipped,
+    /// Only a `--sample-hash` signature was computed
+    Sampled,
+    /// Read into the page cache by `--warm-only`, not hashed
+    Warmed,
+    /// Missing or unreadable
+    Errored,
+    /// Deleted between the walk and being opened, as on a cache cleaned
+    /// while it is scanned; not an error unless `--strict`
+    Vanished,
+    /// A symlink listed by `--symlinks record`; `size` is the link's own
+    Symlink,
+    /// A read failed partway with `--partial-on-error`: `error` says why and
+    /// `partial_hash` covers the `bytes_ok` bytes before it
+    #[serde(rename = "partially_hashed")]
+    PartiallyHashed,
+}
+
+impl FileStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FileStatus::Hashed => "hashed",
+            FileStatus::Skipped => "skipped",
+            FileStatus::Sampled => "sampled",
+            FileStatus::Warmed => "warmed",
+            FileStatus::Errored => "errored",
+            FileStatus::Vanished => "vanished",
+            FileStatus::Symlink => "symlink",
+            FileStatus::PartiallyHashed => "partially_hashed",
+        }
+    }
+}
+
+/// Where a report's XOR64 checksum was computed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum XorSource {
+    Gpu,
+    Cpu,
+}
+
+impl XorSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            XorSource::Gpu => "gpu",
+            XorSource::Cpu => "cpu",
+        }
+    }
+}
+
+/// Field the summary and every output format are ordered by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SortKey {
+    Size,
+    Path,
+    Elapsed,
+    Hash,
+}
+
+/// Ordering shared by all outputs. `None` keeps the historical default of
+/// largest first. Ties fall back to path so the order never depends on which
+/// worker finished first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReportOrder {
+    pub key: SortKey,
+    pub descending: bool,
+}
+
+impl ReportOrder {
+    pub fn new(key: Option<SortKey>, descending: bool) -> Self {
+        match key {
+            Some(key) => Self { key, descending },
+            None => Self {
+                key: SortKey::Size,
+                descending: true,
+            },
+        }
+    }
+
+    /// A total order: ties on the key fall back to path, then archive member,
+    /// so output never depends on which worker finished first.
+    pub fn compare(&self, a: &FileReport, b: &FileReport) -> Ordering {
+        let primary = match self.key {
+            SortKey::Size => a.size.cmp(&b.size),
+            SortKey::Path => Ordering::Equal,
+            SortKey::Elapsed => a.elapsed_ms.cmp(&b.elapsed_ms),
+            SortKey::Hash => a.hash_str.cmp(&b.hash_str),
+        };
+        let primary = if self.descending { primary.reverse() } else { primary };
+        let by_path = a.path.cmp(&b.path);
+        let by_path = if self.key == SortKey::Path && self.descending {
+            by_path.reverse()
+        } else {
+            by_path
+        };
+        primary.then(by_path).then_with(|| a.member.cmp(&b.member))
+    }
+
+    pub fn sort(&self, reports: &mut [FileReport]) {
+        reports.sort_by(|a, b| self.compare(a, b));
+    }
+
+    /// Human-readable description for summary headings, e.g. `size, descending`.
+    pub fn describe(&self) -> String {
+        let key = match self.key {
+            SortKey::Size => "size",
+            SortKey::Path => "path",
+            SortKey::Elapsed => "elapsed",
+            SortKey::Hash => "hash",
+        };
+        let dir = if self.descending { "descending" } else { "ascending" };
+        format!("{}, {}", key, dir)
+    }
+}
+
+/// Files and bytes sharing one extension.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtensionTotal {
+    pub extension: String,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// Lowercased extension, `(none)` for files without one.
+pub(crate) fn extension_of(r: &FileReport) -> String {
+    r.path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_else(|| "(none)".to_string())
+}
+
+/// Estimated savings from compressing one extension's files.
+#[derive(Debug, Clone)]
+pub struct CompressibilityTotal {
+    pub extension: String,
+    pub files: usize,
+    pub bytes: u64,
+    /// Bytes left after compression, extrapolated from each file's probe
+    pub estimated_compressed: u64,
+}
+
+impl CompressibilityTotal {
+    pub fn estimated_savings(&self) -> u64 {
+        self.bytes.saturating_sub(self.estimated_compressed)
+    }
+}
+
+/// Files and bytes found under one cache root.
+#[derive(Debug, Clone)]
+pub struct RootTotal {
+    pub root: PathBuf,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// Files and bytes on one mounted filesystem.
+#[derive(Debug, Clone)]
+pub struct MountTotal {
+    pub mount: PathBuf,
+    pub fs_type: String,
+    pub files: usize,
+    pub bytes: u64,
+    /// Milliseconds at least one file on the mount was being processed;
+    /// `None` when finish times were not recorded
+    pub busy_ms: Option<u64>,
+}
+
+impl MountTotal {
+    /// Bytes per second while the mount was busy: what its device sustained,
+    /// however many workers shared it.
+    pub fn rate(&self) -> Option<u128> {
+        (self.bytes as u128 * 1000).checked_div(self.busy_ms? as u128)
+    }
+}
+
+/// Files and bytes across every mount of one filesystem type.
+#[derive(Debug, Clone)]
+pub struct FsTypeTotal {
+    pub fs_type: String,
+    pub mounts: usize,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// `mount_totals` folded by filesystem type, largest first.
+pub fn fs_type_totals(mounts: &[MountTotal]) -> Vec<FsTypeTotal> {
+    let mut by_type: BTreeMap<&str, FsTypeTotal> = BTreeMap::new();
+    for m in mounts {
+        let entry = by_type.entry(&m.fs_type).or_insert_with(|| FsTypeTotal {
+            fs_type: m.fs_type.clone(),
+            mounts: 0,
+            files: 0,
+            bytes: 0,
+        });
+        entry.mounts += 1;
+        entry.files += m.files;
+        entry.bytes += m.bytes;
+    }
+    let mut totals: Vec<FsTypeTotal> = by_type.into_values().collect();
+    totals.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.fs_type.cmp(&b.fs_type)));
+    totals
+}
+
+/// Distribution of file sizes for `--stats`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SizeStats {
+    pub files: usize,
+    pub mean: f64,
+    pub min: u64,
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub max: u64,
+}
+
+impl SizeStats {
+    /// Nearest-rank percentiles, so each one is a size some file actually
+    /// has; a single file is every percentile. `None` without files.
+    pub fn of(mut sizes: Vec<u64>) -> Option<Self> {
+        if sizes.is_empty() {
+            return None;
+        }
+        sizes.sort_unstable();
+        let n = sizes.len();
+        let rank = |p: usize| sizes[(p * n).div_ceil(100).max(1) - 1];
+        Some(SizeStats {
+            files: n,
+            mean: sizes.iter().map(|&s| s as u128).sum::<u128>() as f64 / n as f64,
+            min: sizes[0],
+            p50: rank(50),
+            p90: rank(90),
+            p99: rank(99),
+            max: sizes[n - 1],
+        })
+    }
+}
+
+/// Unit base for human-readable sizes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Units {
+    /// Powers of 1024, labelled KiB, MiB, GiB
+    #[default]
+    Iec,
+    /// Powers of 1000, labelled kB, MB, GB, as `ls --si` and cloud consoles show them
+    Si,
+}
+
+impl Units {
+    fn base(self) -> f64 {
+        match self {
+            Units::Iec => 1024.0,
+            Units::Si => 1000.0,
+        }
+    }
+
+    fn labels(self) -> [&'static str; 6] {
+        match self {
+            Units::Iec => ["B", "KiB", "MiB", "GiB", "TiB", "PiB"],
+            Units::Si => ["B", "kB", "MB", "GB", "TB", "PB"],
+        }
+    }
+
+    /// Label for one step up from bytes, e.g. `MiB` for a per-second rate.
+    pub fn mega(self) -> &'static str {
+        self.labels()[2]
+    }
+
+    /// Bytes in one `mega()`.
+    pub fn mega_bytes(self) -> f64 {
+        self.base() * self.base()
+    }
+}
+
+/// How `human_bytes` prints a size: unit base and decimal places.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeFormat {
+    pub units: Units,
+    pub precision: usize,
+}
+
+impl SizeFormat {
+    pub const DEFAULT_PRECISION: usize = 2;
+
+    pub fn new(units: Units, precision: usize) -> Self {
+        Self { units, precision }
+    }
+}
+
+impl Default for SizeFormat {
+    fn default() -> Self {
+        Self::new(Units::default(), Self::DEFAULT_PRECISION)
+    }
+}
+
+impl From<Units> for SizeFormat {
+    fn from(units: Units) -> Self {
+        Self::new(units, Self::DEFAULT_PRECISION)
+    }
+}
+
+/// `bytes` in the largest unit that keeps the number at least 1. Decimals
+/// are dropped when the value is exact, so 4 GiB prints as `4 GiB` and
+/// 1536 bytes as `1.5 KiB`, while a rounded value keeps all of them.
+pub fn human_bytes(bytes: u128, sizes: SizeFormat) -> String {
+    let units = sizes.units;
+    let labels = units.labels();
+    let mut b = bytes as f64;
+    let mut i = 0;
+    while b >= units.base() && i < labels.len() - 1 {
+        b /= units.base();
+        i += 1;
+    }
+    let mut number = format!("{:.*}", sizes.precision, b);
+    // nothing was rounded away, so the trailing zeros say nothing
+    if number.contains('.') && number.parse::<f64>() == Ok(b) {
+        number = number.trim_end_matches('0').trim_end_matches('.').to_string();
+    }
+    format!("{} {}", number, labels[i])
+}
+
@@ -0,0 +1,66 @@
+//! How much of a file is already in the page cache, from `mincore(2)` on a
+//! mapping that is never touched. Measured before hashing or warming reads
+//! anything, so a `--warm-only` run followed by a scan shows the hit ratio
+//! the warm-up bought.
+
+use std::fs::File;
+use std::io;
+
+/// Fraction of the pages of `offset..offset + len` that are resident, in
+/// `0.0..=1.0`. `None` for empty spans, files that cannot be mapped, and
+/// platforms other than Linux.
+#[cfg(target_os = "linux")]
+pub fn resident_fraction(f: &File, offset: u64, len: u64) -> Option<f64> {
+    if len == 0 {
+        return None;
+    }
+    let len = usize::try_from(len).ok()?;
+    // SAFETY: the mapping is only passed to mincore, never read
+    let mmap = unsafe { memmap2::MmapOptions::new().offset(offset).len(len).map(f) }.ok()?;
+    // SAFETY: sysconf has no preconditions
+    let page = usize::try_from(unsafe { libc::sysconf(libc::_SC_PAGESIZE) }).ok()?;
+    // mincore wants a page-aligned start; memmap2 maps from the page holding `offset`
+    let start = mmap.as_ptr() as usize & !(page - 1);
+    let span = mmap.as_ptr() as usize + mmap.len() - start;
+    let mut vec = vec![0u8; span.div_ceil(page)];
+    // SAFETY: start..start + span lies within the mapping, and vec has one
+    // byte per page of it
+    if unsafe { libc::mincore(start as *mut libc::c_void, span, vec.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    let resident = vec.iter().filter(|&&b| b & 1 != 0).count();
+    Some(resident as f64 / vec.len() as f64)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn resident_fraction(_f: &File, _offset: u64, _len: u64) -> Option<f64> {
+    None
+}
+
+/// Drop `f`'s pages from the page cache, after writing back any dirty ones,
+/// so the next read comes from disk. Cached pages still mapped elsewhere
+/// may stay. Only on Linux.
+#[cfg(target_os = "linux")]
+pub fn evict(f: &File) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+    f.sync_data()?;
+    // SAFETY: posix_fadvise only takes the descriptor and a range
+    match unsafe { libc::posix_fadvise(f.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED) } {
+        0 => Ok(()),
+        errno => Err(io::Error::from_raw_os_error(errno)),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn evict(_f: &File) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "page cache eviction needs Linux"))
+}
+
+/// Byte-weighted resident fraction over `(len, fraction)` pairs; `None`
+/// when nothing was measured.
+pub fn hit_ratio(measured: impl IntoIterator<Item = (u64, f64)>) -> Option<f64> {
+    let (resident, total) = measured
+        .into_iter()
+        .fold((0.0, 0u128), |(r, t), (len, fraction)| (r + len as f64 * fraction, t + len as u128));
+    (total > 0).then(|| resident / total as f64)
+}
@@ -0,0 +1,141 @@
+//! Windowed BLAKE3 hashing whose progress can be checkpointed and resumed.
+
+use anyhow::{Context, Result};
+use crate::hashmode::HashMode;
+use blake3::hazmat::{merge_subtrees_non_root, merge_subtrees_root_xof, ChainingValue, HasherExt};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Size of one resumable hashing window. Must be a power-of-two multiple of
+/// `blake3::CHUNK_LEN` so every full window is a complete BLAKE3 subtree.
+pub const HASH_WINDOW: u64 = 1 << 30;
+
+/// Partial hashing state for one file, as persisted in the checkpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HashProgress {
+    pub size: u64,
+    pub mtime_ns: u128,
+    pub bytes_hashed: u64,
+    /// Chaining values of completed subtrees, hex encoded, oldest first.
+    pub cv_stack: Vec<String>,
+    /// `HashMode::id` the chaining values were computed under
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub mode: String,
+}
+
+/// In-progress hashes keyed by path, shared by all workers and rewritten after every window.
+pub struct HashCheckpoint {
+    path: PathBuf,
+    entries: Mutex<BTreeMap<PathBuf, HashProgress>>,
+}
+
+impl HashCheckpoint {
+    pub fn load(path: &Path) -> Result<Self> {
+        let entries = if path.exists() {
+            let f = File::open(path).with_context(|| format!("opening checkpoint {:?}", path))?;
+            serde_json::from_reader(std::io::BufReader::new(f))
+                .with_context(|| format!("parsing checkpoint {:?}", path))?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            entries: Mutex::new(entries),
+        })
+    }
+
+    pub fn get(&self, file: &Path) -> Option<HashProgress> {
+        self.entries.lock().unwrap().get(file).cloned()
+    }
+
+    pub fn update(&self, file: &Path, progress: Option<HashProgress>) -> Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+        match progress {
+            Some(p) => entries.insert(file.to_path_buf(), p),
+            None => entries.remove(file),
+        };
+        // write-then-rename so a crash mid-write never leaves a torn checkpoint
+        let tmp = self.path.with_extension("tmp");
+        let f = File::create(&tmp).with_context(|| format!("creating {:?}", tmp))?;
+        serde_json::to_writer(std::io::BufWriter::new(f), &*entries)?;
+        std::fs::rename(&tmp, &self.path)
+            .with_context(|| format!("replacing checkpoint {:?}", self.path))?;
+        Ok(())
+    }
+}
+
+pub fn mtime_ns(meta: &std::fs::Metadata) -> u128 {
+    meta.modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos())
+        .unwrap_or(0)
+}
+
+pub fn cv_to_hex(cv: &ChainingValue) -> String {
+    cv.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn cv_from_hex(s: &str) -> Option<ChainingValue> {
+    if s.len() != 64 {
+        return None;
+    }
+    let mut cv = [0u8; 32];
+    for (i, byte) in cv.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(s.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(cv)
+}
+
+/// Hash `len` bytes one `window`-sized subtree at a time, starting after
+/// `bytes_hashed` bytes whose subtree chaining values are already in `stack`.
+/// `feed(hasher, start, end)` must update `hasher` with input bytes
+/// `start..end`. `on_window` is called after each completed (non-final)
+/// window with the new state, so the caller can persist it. The result
+/// reads the same output as a plain BLAKE3 hash of the whole input.
+pub fn hash_windowed(
+    mode: &HashMode,
+    len: u64,
+    window: u64,
+    mut bytes_hashed: u64,
+    mut stack: Vec<ChainingValue>,
+    mut feed: impl FnMut(&mut blake3::Hasher, u64, u64) -> Result<()>,
+    mut on_window: impl FnMut(u64, &[ChainingValue]) -> Result<()>,
+) -> Result<blake3::OutputReader> {
+    if len <= window {
+        let mut hasher = mode.hasher();
+        feed(&mut hasher, 0, len)?;
+        return Ok(hasher.finalize_xof());
+    }
+    loop {
+        let end = (bytes_hashed + window).min(len);
+        let mut hasher = mode.hasher();
+        hasher.set_input_offset(bytes_hashed);
+        feed(&mut hasher, bytes_hashed, end)?;
+        let cv = hasher.finalize_non_root();
+        if end == len {
+            // fold the stack right-to-left; only the topmost merge is the root
+            let mut right = cv;
+            while stack.len() > 1 {
+                let left = stack.pop().unwrap();
+                right = merge_subtrees_non_root(&left, &right, mode.tree_mode());
+            }
+            let left = stack.pop().context("resumable hash state has no left subtree")?;
+            return Ok(merge_subtrees_root_xof(&left, &right, mode.tree_mode()));
+        }
+        // merge completed sibling subtrees eagerly, as BLAKE3's own CV stack does
+        let mut cv = cv;
+        let mut windows_done = end / window;
+        while windows_done & 1 == 0 {
+            let left = stack.pop().context("resumable hash state is inconsistent")?;
+            cv = merge_subtrees_non_root(&left, &cv, mode.tree_mode());
+            windows_done >>= 1;
+        }
+        stack.push(cv);
+        bytes_hashed = end;
+        on_window(bytes_hashed, &stack)?;
+    }
+}
@@ -0,0 +1,634 @@
+//! Scanning as a library call, for embedders such as a GUI that need the
+//! summary without the CLI's progress bars, and need to stop a scan midway;
+//! `scan_cache_iter` streams the reports instead for callers that would
+//! rather not hold them all.
+
+use crate::archive::{self, ArchiveKind};
+use crate::chunks::{self, Chunking};
+use crate::decompress::Decompress;
+use crate::dupes::{self, ReclaimSummary};
+use crate::encoding::HashEncoding;
+use crate::errorlog;
+use crate::hardlinks;
+use crate::hashmode::{HashAlgorithm, HashMode};
+use crate::layout::{self, Layout};
+use crate::mounts::MountTable;
+use crate::process::{process_file, ByteRange, ProcessOptions, ReaderMode, DEFAULT_DIGEST_LEN};
+use crate::mismatch;
+use crate::pytorch;
+use crate::report::{FileReport, FileStatus, ReportOrder, ScanReport};
+use crate::sink::ScanSummary;
+use crate::suspicious;
+use crate::walk::{self, IncompleteFilter, SymlinkPolicy, WalkError, WalkOptions};
+use anyhow::{Context, Result};
+use crossbeam_channel::Receiver;
+use rayon::prelude::*;
+use std::ops::Range;
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Cooperative stop signal shared between a caller and a running scan.
+/// Workers check it before each file: a file already being hashed is
+/// finished, nothing new is started, and the scan returns what it has.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask the scan to stop; callable from any thread, any number of times.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Stage of a `scan_cache` run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanPhase {
+    Walking,
+    /// Hashing starts, with what the walk found
+    Hashing { files: usize, bytes: u64 },
+    /// Grouping models and finding duplicates
+    Summarising,
+    Done,
+}
+
+/// Progress of a scan as it happens. Borrowed paths keep events free to build.
+#[derive(Debug, Clone, Copy)]
+pub enum ProgressEvent<'a> {
+    PhaseChanged(ScanPhase),
+    /// Sent from the worker thread about to open the file
+    FileStarted { path: &'a Path },
+    /// Sent from the thread collecting results, in completion order
+    FileCompleted {
+        path: &'a Path,
+        size: u64,
+        elapsed: Duration,
+        status: FileStatus,
+    },
+}
+
+/// Receiver of `ProgressEvent`s, e.g. to drive a GUI. It runs on the scan's
+/// own threads, so it should hand events off (a channel, an atomic) rather
+/// than do slow work; `scan_cache` draws no progress bars of its own.
+#[derive(Clone)]
+pub struct ProgressCallback(Arc<dyn Fn(ProgressEvent<'_>) + Send + Sync>);
+
+impl ProgressCallback {
+    pub fn new(f: impl Fn(ProgressEvent<'_>) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(f))
+    }
+
+    pub fn emit(&self, event: ProgressEvent<'_>) {
+        (self.0)(event)
+    }
+}
+
+impl std::fmt::Debug for ProgressCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ProgressCallback")
+    }
+}
+
+/// What `scan_cache` scans and how; `ScanConfig::new` gives the CLI's defaults.
+#[derive(Debug, Clone)]
+pub struct ScanConfig {
+    pub roots: Vec<PathBuf>,
+    /// Resolved separately for each root when `Auto`
+    pub layout: Layout,
+    /// Gitignore-style rules applied to every root, instead of `<root>/.aivista-ignore`
+    pub ignore_file: Option<PathBuf>,
+    /// `None` hashes partial downloads too
+    pub incomplete: Option<IncompleteFilter>,
+    /// Files smaller than this are reported but not hashed
+    pub min_bytes: u64,
+    /// Files larger than this are left out of the scan entirely
+    pub max_bytes: Option<u64>,
+    /// Hash only this span of each file; shorter files are skipped
+    pub range: Option<ByteRange>,
+    pub reader: ReaderMode,
+    pub hash_mode: HashMode,
+    pub algorithm: HashAlgorithm,
+    /// Further digests computed in the same pass, into `FileReport::hashes`
+    pub extra_algorithms: Vec<HashAlgorithm>,
+    /// Digest length in bytes
+    pub digest_len: usize,
+    /// Hash the members of .tar, .tar.zst and .zip archives instead of the archive
+    pub archives: bool,
+    /// Look for unreferenced blobs; every root must use the HuggingFace layout
+    pub find_orphans: bool,
+    /// Group hard-linked files and total the bytes with each inode counted once
+    pub hardlinks: bool,
+    pub chunking: Chunking,
+    /// Hash the largest files first; `false` keeps walk (path) order
+    pub size_sort: bool,
+    /// Process only this many files, the first in that order
+    pub limit: Option<usize>,
+    /// Hash mapped files of at least this many bytes with the whole pool
+    pub parallel_threshold: Option<u64>,
+    /// Record each file's page-cache residency before reading it
+    pub measure_residency: bool,
+    /// How hashes are written in the report
+    pub encoding: HashEncoding,
+    /// Drop paths that resolve to a file already queued under another path
+    pub canonicalize: bool,
+    /// Follow, skip or only list the symlinks inside each root
+    pub symlinks: SymlinkPolicy,
+    /// Hash the decompressed content of gzip and zstd files
+    pub decompress: Decompress,
+    /// Report the hash of what was read before a read error instead of
+    /// only the error; see `ProcessOptions::partial_on_error`
+    pub partial_on_error: bool,
+    /// Prefault mapped files instead of advising readahead; see
+    /// `ProcessOptions::populate`
+    pub populate: bool,
+    /// Report every `elapsed_ms` as 0, so two scans of unchanged files
+    /// serialize to the same bytes
+    pub reproducible: bool,
+    pub order: ReportOrder,
+    pub progress_callback: Option<ProgressCallback>,
+}
+
+impl ScanConfig {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            roots: vec![root.into()],
+            layout: Layout::Auto,
+            ignore_file: None,
+            incomplete: Some(IncompleteFilter::default()),
+            min_bytes: 0,
+            max_bytes: None,
+            range: None,
+            reader: ReaderMode::Auto,
+            hash_mode: HashMode::Plain,
+            algorithm: HashAlgorithm::Blake3,
+            extra_algorithms: Vec::new(),
+            digest_len: DEFAULT_DIGEST_LEN,
+            archives: false,
+            find_orphans: false,
+            hardlinks: false,
+            chunking: Chunking::SizeAware,
+            size_sort: true,
+            limit: None,
+            parallel_threshold: None,
+            measure_residency: false,
+            encoding: HashEncoding::Hex,
+            canonicalize: true,
+            symlinks: SymlinkPolicy::Skip,
+            decompress: Decompress::None,
+            partial_on_error: false,
+            populate: false,
+            reproducible: false,
+            order: ReportOrder::new(None, false),
+            progress_callback: None,
+        }
+    }
+}
+
+/// How workers turn paths into reports, beyond `process_file` itself.
+#[derive(Clone, Copy)]
+pub struct WorkerOptions<'a> {
+    pub process: ProcessOptions<'a>,
+    /// Hash archive members instead of archives
+    pub archives: bool,
+    /// Every root scanned; with two or more each report records its own
+    pub roots: &'a [PathBuf],
+    /// Tag each report with its mount point and filesystem type
+    pub tag_mounts: Option<&'a MountTable>,
+    /// Annotate empty, implausibly small and truncated model files
+    pub flag_suspicious: bool,
+    /// Annotate files whose first bytes contradict their extension
+    pub detect_mismatch: bool,
+    /// List the tensors of PyTorch checkpoints
+    pub inspect_pytorch: bool,
+    /// Told of each file as a worker picks it up
+    pub progress: Option<&'a ProgressCallback>,
+}
+
+/// Process `files` on the rayon pool, one unit of work per range in `work`,
+/// handing each report to `send` as it completes. A file that cannot be read
+/// still yields an `Errored` report, or a `Vanished` one if it was deleted
+/// after the walk found it. Returns early, without error, once `cancel` is set.
+pub fn process_files(
+    files: &[PathBuf],
+    work: Vec<Range<usize>>,
+    opts: &WorkerOptions,
+    cancel: &CancellationToken,
+    send: impl Fn(FileReport) + Sync,
+) {
+    // provenance only matters when several roots feed one report
+    let root_of = |p: &Path| {
+        if opts.roots.len() < 2 {
+            return None;
+        }
+        opts.roots
+            .iter()
+            .filter(|r| p.starts_with(r))
+            .max_by_key(|r| r.as_os_str().len())
+            .cloned()
+    };
+    let finish = |mut report: FileReport| {
+        report.root = root_of(&report.path);
+        // archive members cannot be reopened by path, so they go unchecked
+        if opts.flag_suspicious && report.member.is_none() && report.error.is_none() {
+            report.suspicious = suspicious::check(&report.path, report.size);
+        }
+        if opts.detect_mismatch && report.member.is_none() && report.error.is_none() {
+            report.content_mismatch = mismatch::check(&report.path, report.size);
+        }
+        if opts.inspect_pytorch && report.member.is_none() && report.error.is_none() {
+            report.pytorch = pytorch::inspect(&report.path);
+        }
+        if let Some(entry) = opts.tag_mounts.and_then(|t| t.lookup_file(&report.path)) {
+            report.mount = Some(entry.mount_point.clone());
+            report.fs_type = Some(entry.fs_type.clone());
+        }
+        send(report);
+    };
+
+    // `par_bridge` hands out chunks in list order as workers free up
+    work.into_iter().par_bridge().for_each(|range| {
+        for p in &files[range] {
+            if cancel.is_cancelled() {
+                return;
+            }
+            if let Some(progress) = opts.progress {
+                progress.emit(ProgressEvent::FileStarted { path: p });
+            }
+            if let Some(kind) = opts.archives.then(|| ArchiveKind::detect(p)).flatten() {
+                archive::hash_members(p, kind, &opts.process).into_iter().for_each(&finish);
+                continue;
+            }
+            let report = process_file(p, &opts.process)
+                .with_context(|| format!("processing file {:?}", p))
+                .unwrap_or_else(|e| {
+                    let gone = vanished(p, &e);
+                    FileReport {
+                        status: if gone { FileStatus::Vanished } else { FileStatus::Errored },
+                        error: (!gone).then(|| format!("{:#}", e)),
+                        errno: if gone { None } else { errorlog::errno(&e) },
+                        ..FileReport::new(p, p.metadata().map(|m| m.len()).unwrap_or(0))
+                    }
+                });
+            finish(report);
+        }
+    });
+}
+
+/// `err` is a not-found error and nothing is at `path` any more: the file
+/// was deleted after the walk listed it, not merely unreadable.
+fn vanished(path: &Path, err: &anyhow::Error) -> bool {
+    let not_found = err
+        .chain()
+        .filter_map(|e| e.downcast_ref::<std::io::Error>())
+        .any(|e| e.kind() == std::io::ErrorKind::NotFound);
+    not_found && path.symlink_metadata().is_err()
+}
+
+/// What the walk leaves for the workers.
+struct Planned {
+    layouts: Vec<Layout>,
+    /// Symlinks and walk errors; its files have moved to `files`
+    walked: walk::WalkOutcome,
+    files: Vec<PathBuf>,
+    work: Vec<Range<usize>>,
+    limited_from: Option<usize>,
+    mounts: Option<MountTable>,
+}
+
+fn emit_phase(config: &ScanConfig, phase: ScanPhase) {
+    if let Some(progress) = &config.progress_callback {
+        progress.emit(ProgressEvent::PhaseChanged(phase));
+    }
+}
+
+/// Check the roots, walk them and split the files found into units of work,
+/// announcing the walking and hashing phases.
+fn plan(config: &ScanConfig, cancel: &CancellationToken) -> Result<Planned> {
+    let roots = &config.roots;
+    if roots.is_empty() {
+        anyhow::bail!("no cache root to scan");
+    }
+    if let Some(missing) = roots.iter().find(|r| !r.exists()) {
+        anyhow::bail!("Cache path {:?} does not exist", missing);
+    }
+    let layouts: Vec<Layout> = roots.iter().map(|r| config.layout.resolve(r)).collect();
+    if config.find_orphans {
+        if let Some((root, layout)) = roots.iter().zip(&layouts).find(|(_, l)| **l != Layout::Hf) {
+            anyhow::bail!(
+                "finding orphans needs a HuggingFace cache, but {:?} has the {} layout",
+                root,
+                layout.describe()
+            );
+        }
+    }
+
+    emit_phase(config, ScanPhase::Walking);
+    let mut walked = walk::WalkOutcome::default();
+    for root in roots {
+        let walk_opts = WalkOptions {
+            ignore: walk::load_ignore(root, config.ignore_file.as_deref())?,
+            incomplete: config.incomplete.clone(),
+            resolve_symlinks: config.find_orphans,
+            symlinks: config.symlinks,
+        };
+        walked.merge(walk::collect_files(root, &walk_opts));
+        if cancel.is_cancelled() {
+            break;
+        }
+    }
+    if config.canonicalize {
+        walked.dedup_canonical(config.symlinks);
+    }
+    let mut sized: Vec<(PathBuf, u64)> = std::mem::take(&mut walked.files)
+        .into_iter()
+        .map(|p| {
+            let size = config.symlinks.size_of(&p);
+            (p, size)
+        })
+        .filter(|(_, size)| config.max_bytes.is_none_or(|max| *size <= max))
+        .collect();
+    if config.size_sort {
+        chunks::sort_largest_first(&mut sized);
+    }
+    let limited_from = config.limit.filter(|&n| n < sized.len()).map(|n| {
+        let eligible = sized.len();
+        sized.truncate(n);
+        eligible
+    });
+    let (files, sizes): (Vec<PathBuf>, Vec<u64>) = sized.into_iter().unzip();
+    let work = chunks::plan(&sizes, config.chunking, rayon::current_num_threads());
+
+    let mounts = (config.reader == ReaderMode::Auto).then(MountTable::load).flatten();
+    emit_phase(
+        config,
+        ScanPhase::Hashing {
+            files: files.len(),
+            bytes: sizes.iter().sum(),
+        },
+    );
+    Ok(Planned {
+        layouts,
+        walked,
+        files,
+        work,
+        limited_from,
+        mounts,
+    })
+}
+
+/// How the library's workers process files under `config`.
+fn worker_options<'a>(config: &'a ScanConfig, mounts: Option<&'a MountTable>) -> WorkerOptions<'a> {
+    WorkerOptions {
+        process: ProcessOptions {
+            min_bytes: config.min_bytes,
+            max_bytes: config.max_bytes,
+            range: config.range,
+            reader: config.reader,
+            mounts,
+            digest_len: Some(config.digest_len),
+            hash_mode: config.hash_mode,
+            algorithm: config.algorithm,
+            extra_algorithms: &config.extra_algorithms,
+            parallel_threshold: config.parallel_threshold,
+            measure_residency: config.measure_residency,
+            encoding: config.encoding,
+            record_symlinks: config.symlinks == SymlinkPolicy::Record,
+            decompress: config.decompress,
+            partial_on_error: config.partial_on_error,
+            populate: config.populate,
+            ..Default::default()
+        },
+        archives: config.archives,
+        roots: &config.roots,
+        tag_mounts: None,
+        flag_suspicious: false,
+        detect_mismatch: false,
+        inspect_pytorch: false,
+        progress: config.progress_callback.as_ref(),
+    }
+}
+
+/// Tell the progress callback `r` is done and apply `reproducible`, as each
+/// report reaches the caller.
+fn complete(config: &ScanConfig, r: &mut FileReport) {
+    if let Some(progress) = &config.progress_callback {
+        progress.emit(ProgressEvent::FileCompleted {
+            path: &r.path,
+            size: r.size,
+            elapsed: Duration::from_millis(r.elapsed_ms.try_into().unwrap_or(u64::MAX)),
+            status: r.status,
+        });
+    }
+    if config.reproducible {
+        r.elapsed_ms = 0;
+    }
+}
+
+/// Walk, hash and summarise the caches in `config` on the current rayon
+/// pool. Cancelling returns `Ok` with the files finished so far and
+/// `cancelled` set; the post-passes then only cover those files.
+pub fn scan_cache(config: &ScanConfig, cancel: &CancellationToken) -> Result<ScanSummary> {
+    let Planned {
+        layouts,
+        walked,
+        files,
+        work,
+        limited_from,
+        mounts,
+    } = plan(config, cancel)?;
+    let roots = &config.roots;
+    let first = &roots[0];
+    let worker = worker_options(config, mounts.as_ref());
+
+    // hashing stays on this thread, so it runs in the caller's pool, while a
+    // helper drains; no worker waits on a full buffer
+    let (tx, rx) = crossbeam_channel::unbounded::<FileReport>();
+    let mut reports = std::thread::scope(|s| {
+        let drained = s.spawn(move || {
+            let mut reports = Vec::new();
+            for mut r in rx {
+                complete(config, &mut r);
+                reports.push(r);
+            }
+            reports
+        });
+        process_files(&files, work, &worker, cancel, |r| {
+            let _ = tx.send(r);
+        });
+        drop(tx);
+        drained.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    });
+    emit_phase(config, ScanPhase::Summarising);
+    config.order.sort(&mut reports);
+
+    let models = roots
+        .iter()
+        .zip(&layouts)
+        .flat_map(|(root, layout)| layout::group_reports(*layout, root, &reports))
+        .collect();
+    let orphans: Vec<_> = if config.find_orphans {
+        roots
+            .iter()
+            .flat_map(|root| layout::hf_orphans(root, &walked.symlinks, &reports))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let duplicates = dupes::find_duplicates(&reports, &orphans);
+    let reclaim = ReclaimSummary::new(&duplicates, &orphans);
+    let (hardlinks, real_bytes) = if config.hardlinks {
+        let (groups, real) = hardlinks::find_hardlinks(&reports);
+        (groups, Some(real))
+    } else {
+        (Vec::new(), None)
+    };
+    let layout = match layouts.split_first() {
+        Some((first, rest)) if rest.iter().all(|l| l == first) => *first,
+        _ => Layout::Auto,
+    };
+    emit_phase(config, ScanPhase::Done);
+    Ok(ScanSummary {
+        report: ScanReport {
+            cache: first.clone(),
+            roots: if roots.len() > 1 { roots.clone() } else { Vec::new() },
+            total_files: reports.len(),
+            total_bytes: reports.iter().map(|r| r.size).sum(),
+            hash_algorithm: config.algorithm,
+            hash_encoding: config.encoding,
+            files: reports,
+            models,
+            orphans,
+            duplicates,
+            symlink_issues: Vec::new(),
+            size_collisions: Vec::new(),
+            walk_errors: walked.errors,
+            hardlinks,
+            real_bytes,
+            sample: None,
+            limited_from,
+            provenance: None,
+        },
+        reclaim,
+        layout,
+        cancelled: cancel.is_cancelled(),
+        tally: None,
+    })
+}
+
+/// Reports from `scan_cache_iter` in completion order, as workers finish
+/// them. Only a few per worker are buffered, so hashing waits for a slow
+/// consumer; dropping the iterator stops the scan once the files in
+/// progress are done.
+pub struct ScanIter {
+    config: Arc<ScanConfig>,
+    reports: Receiver<FileReport>,
+    worker: Option<Receiver<std::thread::Result<()>>>,
+    files: usize,
+    walk_errors: Vec<WalkError>,
+}
+
+impl ScanIter {
+    /// Files queued for hashing; archives count once, though each member
+    /// is reported separately.
+    pub fn files(&self) -> usize {
+        self.files
+    }
+
+    /// Directories the walk could not read; their files are not queued.
+    pub fn walk_errors(&self) -> &[WalkError] {
+        &self.walk_errors
+    }
+}
+
+impl Iterator for ScanIter {
+    type Item = Result<FileReport>;
+
+    /// The next report to finish, or once the last has, an error if the
+    /// scan's thread panicked.
+    fn next(&mut self) -> Option<Result<FileReport>> {
+        if let Ok(mut r) = self.reports.recv() {
+            complete(&self.config, &mut r);
+            return Some(Ok(r));
+        }
+        let worker = self.worker.take()?;
+        let finished = worker.recv();
+        emit_phase(&self.config, ScanPhase::Done);
+        let panic = match finished {
+            Ok(outcome) => outcome.err()?,
+            Err(_) => return Some(Err(anyhow::anyhow!("scan worker never ran"))),
+        };
+        let why = panic
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("no message");
+        Some(Err(anyhow::anyhow!("scan worker panicked: {}", why)))
+    }
+}
+
+/// Walk the caches in `config` like `scan_cache`, then hash them in the
+/// background and hand back each report as it completes, so a caller can
+/// process and drop them instead of holding every one. Reports come in
+/// completion order, not `config.order`, and no summary is built. A
+/// cancelled token is noticed as each file finishes.
+pub fn scan_cache_iter(config: &ScanConfig, cancel: &CancellationToken) -> Result<ScanIter> {
+    let Planned {
+        walked,
+        files,
+        work,
+        mounts,
+        ..
+    } = plan(config, cancel)?;
+    let config = Arc::new(config.clone());
+    let (tx, rx) = crossbeam_channel::bounded(rayon::current_num_threads() * 4);
+    let queued = files.len();
+    let (done_tx, done) = crossbeam_channel::bounded(1);
+    let produce = {
+        let config = Arc::clone(&config);
+        let cancel = cancel.clone();
+        move || {
+            let outcome = std::panic::catch_unwind(AssertUnwindSafe(move || {
+                let worker = worker_options(&config, mounts.as_ref());
+                // stops on the caller's token, or once the iterator is dropped
+                let stop = CancellationToken::new();
+                if cancel.is_cancelled() {
+                    stop.cancel();
+                }
+                process_files(&files, work, &worker, &stop, |r| {
+                    if tx.send(r).is_err() || cancel.is_cancelled() {
+                        stop.cancel();
+                    }
+                });
+            }));
+            let _ = done_tx.send(outcome);
+        }
+    };
+    // hash in the caller's pool, unless the caller is that pool's only
+    // thread and would block it reading the reports
+    if rayon::current_thread_index().is_some() && rayon::current_num_threads() == 1 {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .context("Failed to start the scan's thread pool")?
+            .spawn(produce);
+    } else {
+        rayon::spawn(produce);
+    }
+    Ok(ScanIter {
+        config,
+        reports: rx,
+        worker: Some(done),
+        files: queued,
+        walk_errors: walked.errors,
+    })
+}
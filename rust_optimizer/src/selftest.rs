@@ -0,0 +1,171 @@
+//! The `selftest` subcommand: a small fixture cache with known contents,
+//! scanned end to end, so walk, hash and aggregation can be checked on any
+//! machine without a real model cache.
+
+use crate::bench::synthetic_data;
+use crate::encoding::HashEncoding;
+use crate::hashmode::{HashAlgorithm, HashMode};
+use crate::manifest::Manifest;
+use crate::scan::{scan_cache, CancellationToken, ScanConfig};
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+/// Manifest fingerprint of the fixture. It changes only when the fixture, the
+/// hash or the manifest format does, and each of those needs a deliberate
+/// update here.
+pub const EXPECTED_FINGERPRINT: &str =
+    "f94c7b6c2309b8b6cdc85c2fd570b8276bd70391d092bbb3558a43cd6750f35b";
+
+/// One file of the fixture; `None` contents mark the symlink.
+struct FixtureFile {
+    path: &'static str,
+    contents: Option<Vec<u8>>,
+}
+
+fn fixture() -> Vec<FixtureFile> {
+    let weights = synthetic_data((1 << 20) + 3, 1);
+    vec![
+        // spans several BLAKE3 chunks and ends mid-word
+        FixtureFile {
+            path: "weights.bin",
+            contents: Some(weights.clone()),
+        },
+        // byte-identical to weights.bin: the one duplicate pair
+        FixtureFile {
+            path: "copy/weights.bin",
+            contents: Some(weights),
+        },
+        FixtureFile {
+            path: "nested/dir/tokenizer.json",
+            contents: Some(synthetic_data(65_536, 2)),
+        },
+        FixtureFile {
+            path: "config.json",
+            contents: Some(b"{\"model_type\": \"selftest\"}\n".to_vec()),
+        },
+        FixtureFile {
+            path: "empty",
+            contents: Some(Vec::new()),
+        },
+        // never followed by the walk, so never counted or hashed
+        FixtureFile {
+            path: "link-to-config",
+            contents: None,
+        },
+    ]
+}
+
+/// Write the fixture under `dir`, which must not exist yet.
+pub fn create_fixture(dir: &Path) -> Result<()> {
+    std::fs::create_dir(dir).with_context(|| format!("creating {:?}", dir))?;
+    for file in fixture() {
+        let path = dir.join(file.path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).with_context(|| format!("creating {:?}", parent))?;
+        }
+        match &file.contents {
+            Some(contents) => {
+                std::fs::write(&path, contents).with_context(|| format!("writing {:?}", path))?
+            }
+            None => symlink("config.json", &path)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn symlink(target: &str, link: &Path) -> Result<()> {
+    std::os::unix::fs::symlink(target, link).with_context(|| format!("creating {:?}", link))
+}
+
+// creating symlinks needs extra privileges on Windows; the walk ignores them anyway
+#[cfg(not(unix))]
+fn symlink(_target: &str, _link: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// One expectation and what the scan produced.
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl Check {
+    fn new(name: &'static str, expected: impl ToString, actual: impl ToString) -> Self {
+        Self {
+            name,
+            expected: expected.to_string(),
+            actual: actual.to_string(),
+        }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.expected == self.actual
+    }
+}
+
+/// Scan the fixture at `dir` (from `create_fixture`) and compare counts,
+/// per-file hashes, duplicate groups and the manifest fingerprint.
+pub fn run(dir: &Path) -> Result<Vec<Check>> {
+    let config = ScanConfig {
+        // every file was only just written
+        incomplete: None,
+        ..ScanConfig::new(dir)
+    };
+    let summary = scan_cache(&config, &CancellationToken::new())?;
+    let report = &summary.report;
+    let files: Vec<(PathBuf, Vec<u8>)> = fixture()
+        .into_iter()
+        .filter_map(|f| Some((dir.join(f.path), f.contents?)))
+        .collect();
+
+    let mut checks = vec![
+        Check::new("files found", files.len(), report.total_files),
+        Check::new(
+            "bytes found",
+            files.iter().map(|(_, c)| c.len() as u64).sum::<u64>(),
+            report.total_bytes,
+        ),
+    ];
+    let mismatched: Vec<String> = files
+        .iter()
+        .filter(|(path, contents)| {
+            let expected = blake3::hash(contents).to_hex().to_string();
+            !report
+                .files
+                .iter()
+                .any(|r| &r.path == path && r.hash_str.as_deref() == Some(expected.as_str()))
+        })
+        .map(|(path, _)| path.display().to_string())
+        .collect();
+    checks.push(Check::new("files with a wrong or missing hash", "", mismatched.join(", ")));
+    let groups: Vec<String> = report
+        .duplicates
+        .iter()
+        .map(|g| {
+            let paths: Vec<String> = g
+                .paths
+                .iter()
+                .map(|p| p.strip_prefix(dir).unwrap_or(p).display().to_string())
+                .collect();
+            paths.join(" = ")
+        })
+        .collect();
+    let expected_group = Path::new("copy").join("weights.bin").display().to_string();
+    checks.push(Check::new(
+        "duplicate groups",
+        format!("{} = weights.bin", expected_group),
+        groups.join("; "),
+    ));
+    checks.push(Check::new(
+        "reclaimable duplicate bytes",
+        (1 << 20) + 3,
+        summary.reclaim.duplicate_bytes,
+    ));
+    let (algorithm, mode) = (HashAlgorithm::Blake3, HashMode::Plain);
+    let manifest = Manifest::build(dir, &report.files, algorithm, &mode, HashEncoding::Hex);
+    checks.push(Check::new("manifest fingerprint", EXPECTED_FINGERPRINT, manifest.fingerprint));
+    Ok(checks)
+}
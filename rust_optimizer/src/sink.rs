@@ -0,0 +1,832 @@
+//! Output destinations for scan results. The aggregator feeds every sink each
+//! file as it completes, then hands over the ordered, post-processed summary.
+
+use crate::checksums;
+use crate::docformat::DocFormat;
+use crate::dupes::{DuplicateGroup, ReclaimSummary};
+use crate::hashmode::HashAlgorithm;
+use crate::layout::{Layout, OrphanBlob};
+use crate::report::{
+    fs_type_totals, human_bytes, FileReport, ReportOrder, ScanReport, SizeFormat,
+};
+use crate::tally::{Tally, FIRST_FILES};
+use crate::symlinks::LinkProblem;
+use crate::table::{new_table, short_hash, size_cell};
+use anyhow::{Context, Result};
+use comfy_table::Cell;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Everything known once the scan and its post-passes are done.
+pub struct ScanSummary {
+    /// Files in the requested order, plus models, orphans and duplicates
+    pub report: ScanReport,
+    pub reclaim: ReclaimSummary,
+    pub layout: Layout,
+    /// The scan was stopped early; `report` covers only the files finished by then
+    pub cancelled: bool,
+    /// Totals streamed from every report as it arrived. When set, `report.files`
+    /// may hold only the reports the post-passes need (see the CLI's retention);
+    /// when absent, the human summary tallies `report.files` itself
+    pub tally: Option<Tally>,
+}
+
+pub trait ReportSink: Send {
+    /// Called for each file as its result arrives, in completion order.
+    fn emit(&mut self, _report: &FileReport) -> Result<()> {
+        Ok(())
+    }
+
+    /// Whether `emit` writes anything. Only then are reports passed to the
+    /// sink one at a time as they arrive; otherwise it sees them in `finish`.
+    fn streams(&self) -> bool {
+        false
+    }
+
+    /// Called once after the scan. Ordered outputs write everything here.
+    fn finish(&mut self, summary: &ScanSummary) -> Result<()>;
+}
+
+type Out = Box<dyn Write + Send>;
+
+fn stdout() -> Out {
+    Box::new(BufWriter::new(std::io::stdout()))
+}
+
+/// Whether `path` is the regular file or pipe this process's stdout goes to,
+/// e.g. `/dev/stdout` under a redirect. A sink writing there would interleave
+/// with whatever `--format` prints.
+pub fn is_stdout(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::fd::AsFd;
+        use std::os::unix::fs::{FileTypeExt, MetadataExt};
+        let out = std::io::stdout().as_fd().try_clone_to_owned().map(File::from);
+        if let (Ok(out), Ok(target)) = (out.and_then(|f| f.metadata()), std::fs::metadata(path)) {
+            // terminals and /dev/null take interleaved writes without harm
+            let shared = out.is_file() || out.file_type().is_fifo();
+            return shared && out.dev() == target.dev() && out.ino() == target.ino();
+        }
+    }
+    let _ = path;
+    false
+}
+
+fn create(path: &Path) -> Result<Out> {
+    let f = File::create(path).with_context(|| format!("creating {:?}", path))?;
+    Ok(Box::new(BufWriter::new(f)))
+}
+
+/// Discards everything.
+pub struct NoopSink;
+
+impl ReportSink for NoopSink {
+    fn finish(&mut self, _summary: &ScanSummary) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Forwards to several sinks in turn.
+pub struct Tee(pub Vec<Box<dyn ReportSink>>);
+
+impl ReportSink for Tee {
+    fn emit(&mut self, report: &FileReport) -> Result<()> {
+        self.0.iter_mut().try_for_each(|s| s.emit(report))
+    }
+
+    fn streams(&self) -> bool {
+        self.0.iter().any(|s| s.streams())
+    }
+
+    fn finish(&mut self, summary: &ScanSummary) -> Result<()> {
+        self.0.iter_mut().try_for_each(|s| s.finish(summary))
+    }
+}
+
+/// The pretty-printed `--output` document.
+pub struct JsonSink {
+    out: Out,
+    dest: String,
+}
+
+impl JsonSink {
+    /// `dest` names the destination in error messages.
+    pub fn new(out: Out, dest: impl Into<String>) -> Self {
+        Self {
+            out,
+            dest: dest.into(),
+        }
+    }
+
+    pub fn stdout() -> Self {
+        Self::new(stdout(), "stdout")
+    }
+
+    pub fn create(path: &Path) -> Result<Self> {
+        Ok(Self::new(create(path)?, format!("{:?}", path)))
+    }
+}
+
+impl ReportSink for JsonSink {
+    fn finish(&mut self, summary: &ScanSummary) -> Result<()> {
+        serde_json::to_writer_pretty(&mut self.out, &summary.report.document())
+            .with_context(|| format!("writing JSON report to {}", self.dest))?;
+        writeln!(self.out)?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// The `--output` document as YAML or TOML on stdout.
+pub struct DocumentSink {
+    out: Out,
+    format: DocFormat,
+}
+
+impl DocumentSink {
+    pub fn stdout(format: DocFormat) -> Self {
+        Self {
+            out: stdout(),
+            format,
+        }
+    }
+}
+
+impl ReportSink for DocumentSink {
+    fn finish(&mut self, summary: &ScanSummary) -> Result<()> {
+        let text = self
+            .format
+            .render(&summary.report.document())
+            .with_context(|| format!("writing {} report to stdout", self.format.name()))?;
+        self.out.write_all(text.as_bytes())?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// One JSON object per file, streamed in completion order unless
+/// `in_report_order`.
+pub struct NdjsonSink {
+    out: Out,
+    in_report_order: bool,
+}
+
+impl NdjsonSink {
+    pub fn new(out: Out) -> Self {
+        Self {
+            out,
+            in_report_order: false,
+        }
+    }
+
+    pub fn stdout() -> Self {
+        Self::new(stdout())
+    }
+
+    /// Write every record once the scan ends, in report order, so two runs
+    /// over the same files print the same lines.
+    pub fn in_report_order(self) -> Self {
+        Self {
+            in_report_order: true,
+            ..self
+        }
+    }
+
+    fn write(&mut self, report: &FileReport) -> Result<()> {
+        serde_json::to_writer(&mut self.out, report).context("writing NDJSON record")?;
+        writeln!(self.out)?;
+        Ok(())
+    }
+}
+
+impl ReportSink for NdjsonSink {
+    fn emit(&mut self, report: &FileReport) -> Result<()> {
+        if !self.in_report_order {
+            self.write(report)?;
+        }
+        Ok(())
+    }
+
+    fn streams(&self) -> bool {
+        !self.in_report_order
+    }
+
+    fn finish(&mut self, summary: &ScanSummary) -> Result<()> {
+        if self.in_report_order {
+            summary.report.files.iter().try_for_each(|r| self.write(r))?;
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// A header row and one row per file, in report order; with several `--hash`
+/// algorithms, a `hash_<algorithm>` column each after the fixed ones.
+pub struct CsvSink {
+    out: Out,
+}
+
+impl CsvSink {
+    pub fn new(out: Out) -> Self {
+        Self { out }
+    }
+
+    pub fn stdout() -> Self {
+        Self::new(stdout())
+    }
+}
+
+/// Quote a field when it contains a delimiter, quote or line break (RFC 4180).
+fn csv_field(s: &str) -> Cow<'_, str> {
+    if s.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", s.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(s)
+    }
+}
+
+impl ReportSink for CsvSink {
+    fn finish(&mut self, summary: &ScanSummary) -> Result<()> {
+        let files = &summary.report.files;
+        let extra: BTreeSet<HashAlgorithm> =
+            files.iter().flat_map(|r| r.hashes.iter().flat_map(|h| h.keys().copied())).collect();
+        write!(self.out, "path,size,status,hash,signature,xor64,xor64_source,elapsed_ms,error")?;
+        for algorithm in &extra {
+            write!(self.out, ",hash_{}", algorithm.name())?;
+        }
+        writeln!(self.out)?;
+        for r in files {
+            write!(
+                self.out,
+                "{},{},{},{},{},{},{},{},{}",
+                csv_field(&r.path.to_string_lossy()),
+                r.size,
+                r.status.as_str(),
+                r.hash_str.as_deref().unwrap_or_default(),
+                r.signature.as_deref().unwrap_or_default(),
+                r.xor64.map(|x| format!("{:016x}", x)).unwrap_or_default(),
+                r.xor64_source.map(|s| s.as_str()).unwrap_or_default(),
+                r.elapsed_ms,
+                csv_field(r.error.as_deref().unwrap_or_default())
+            )?;
+            for algorithm in &extra {
+                let hash = r.hashes.as_ref().and_then(|h| h.get(algorithm));
+                write!(self.out, ",{}", hash.map(String::as_str).unwrap_or_default())?;
+            }
+            writeln!(self.out)?;
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// `<hex>  <path>` lines, interchangeable with b3sum/sha256sum.
+pub struct ChecksumsSink {
+    out: Out,
+}
+
+impl ChecksumsSink {
+    pub fn stdout() -> Self {
+        Self { out: stdout() }
+    }
+}
+
+impl ReportSink for ChecksumsSink {
+    fn finish(&mut self, summary: &ScanSummary) -> Result<()> {
+        // always hex, so b3sum and --check read it whatever --hash-encoding
+        let encoding = summary.report.hash_encoding;
+        for r in &summary.report.files {
+            if let Some(hash) = &r.hash_str {
+                let hex = encoding.to_hex(hash);
+                writeln!(self.out, "{}", checksums::format_line(&hex, &r.path))?;
+            }
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// `<hash>\t<path>` lines, each written as its file completes; files
+/// without a hash are left out. A tab, line break or backslash in a path is
+/// written as `\t`, `\n`, `\r` or `\\`, so every file is one line.
+pub struct HashPathSink {
+    out: Out,
+}
+
+impl HashPathSink {
+    pub fn new(out: Out) -> Self {
+        Self { out }
+    }
+
+    /// Straight to the line-buffered stdout, so a pipeline reading it gets
+    /// every line as soon as the file is hashed.
+    pub fn stdout() -> Self {
+        Self::new(Box::new(std::io::stdout()))
+    }
+}
+
+impl ReportSink for HashPathSink {
+    fn emit(&mut self, report: &FileReport) -> Result<()> {
+        let Some(hash) = &report.hash_str else {
+            return Ok(());
+        };
+        let name = report.path.to_string_lossy();
+        if name.contains(['\\', '\t', '\n', '\r']) {
+            let escaped = name
+                .replace('\\', "\\\\")
+                .replace('\t', "\\t")
+                .replace('\n', "\\n")
+                .replace('\r', "\\r");
+            writeln!(self.out, "{}\t{}", hash, escaped)?;
+        } else {
+            writeln!(self.out, "{}\t{}", hash, name)?;
+        }
+        Ok(())
+    }
+
+    fn streams(&self) -> bool {
+        true
+    }
+
+    fn finish(&mut self, _summary: &ScanSummary) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Stdout document for `--reclaim-report json`.
+pub struct ReclaimJsonSink;
+
+#[derive(Serialize)]
+struct ReclaimReport<'a> {
+    #[serde(flatten)]
+    summary: ReclaimSummary,
+    duplicates: &'a [DuplicateGroup],
+    orphans: &'a [OrphanBlob],
+}
+
+impl ReportSink for ReclaimJsonSink {
+    fn finish(&mut self, summary: &ScanSummary) -> Result<()> {
+        let doc = ReclaimReport {
+            summary: summary.reclaim,
+            duplicates: &summary.report.duplicates,
+            orphans: &summary.report.orphans,
+        };
+        let mut out = stdout();
+        serde_json::to_writer_pretty(&mut out, &doc).context("writing reclaim report")?;
+        writeln!(out)?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+/// The human-readable summary tables on stdout.
+pub struct HumanSummary {
+    pub order: ReportOrder,
+    /// Length of the slowest-files table; 0 leaves it out
+    pub slowest: usize,
+    /// Print the orphan section even when it is empty
+    pub show_orphans: bool,
+    /// Print the symlink check section even when it is empty
+    pub show_symlinks: bool,
+    /// Print the size-collision section even when it is empty
+    pub show_size_collisions: bool,
+    /// Print the hardlink section even when it is empty
+    pub show_hardlinks: bool,
+    /// Print the suspicious-file section even when it is empty
+    pub show_suspicious: bool,
+    /// Print the content-mismatch section even when it is empty
+    pub show_mismatches: bool,
+    /// Print the PyTorch checkpoint section even when it is empty
+    pub show_pytorch: bool,
+    /// Print mean and percentile file sizes
+    pub stats: bool,
+    /// Print directory sizes this many levels deep
+    pub tree_depth: Option<usize>,
+    pub sizes: SizeFormat,
+    pub color: bool,
+}
+
+impl ReportSink for HumanSummary {
+    fn finish(&mut self, summary: &ScanSummary) -> Result<()> {
+        let (color, sizes) = (self.color, self.sizes);
+        let own;
+        let tally = match &summary.tally {
+            Some(tally) => tally,
+            None => {
+                let mut tally = Tally::new(self.order, self.slowest, self.stats);
+                if let Some(depth) = self.tree_depth {
+                    let report = &summary.report;
+                    let roots = if report.roots.is_empty() {
+                        std::slice::from_ref(&report.cache)
+                    } else {
+                        &report.roots[..]
+                    };
+                    tally = tally.with_tree(roots, depth);
+                }
+                summary.report.files.iter().for_each(|r| tally.add(r));
+                own = tally;
+                &own
+            }
+        };
+        let (total_files, total_bytes) = (tally.files, tally.bytes);
+        println!("\n--- Summary ---");
+        println!("Processed files: {}", total_files);
+        println!("Total bytes processed: {}", human_bytes(total_bytes, sizes));
+        if let Some(real) = summary.report.real_bytes {
+            println!(
+                "Real disk usage: {} (hard links counted once, {} less)",
+                human_bytes(real as u128, sizes),
+                human_bytes(total_bytes.saturating_sub(real as u128), sizes)
+            );
+        }
+        if let Some(sample) = &summary.report.sample {
+            println!(
+                "Sample: {:.1}% of files (seed {}), {} of {}; whole cache estimated at ~{}",
+                sample.fraction * 100.0,
+                sample.seed,
+                total_files,
+                sample.population_files,
+                human_bytes(sample.estimated_bytes(total_files, total_bytes as u64) as u128, sizes)
+            );
+        }
+        if let Some(eligible) = summary.report.limited_from {
+            println!("Limited: the first {} of {} files (--limit)", total_files, eligible);
+        }
+        if tally.warmed_files > 0 {
+            println!(
+                "Warmed {} files, {}, no hashing",
+                tally.warmed_files,
+                human_bytes(tally.warmed_bytes, sizes)
+            );
+        }
+        if tally.errored > 0 {
+            println!("Errored files: {}", tally.errored);
+        }
+        if tally.partially_hashed > 0 {
+            println!(
+                "Partially hashed files (read error partway; see bytes_ok): {}",
+                tally.partially_hashed
+            );
+        }
+        if tally.hole_files > 0 {
+            println!(
+                "Sparse holes hashed without reading: {} across {} files",
+                human_bytes(tally.hole_bytes, sizes),
+                tally.hole_files
+            );
+        }
+        if let Some(ratio) = tally.hit_ratio() {
+            println!(
+                "Page cache hits: {:.1}% of {} was already resident ({} files measured)",
+                ratio * 100.0,
+                human_bytes(tally.measured_bytes, sizes),
+                tally.measured_files
+            );
+        }
+        if tally.vanished > 0 {
+            println!("Vanished files (deleted during the scan): {}", tally.vanished);
+        }
+        // throughput over summed worker time, not wall time, so it reflects
+        // per-file read+hash speed independent of the job count
+        let busy_ms = tally.busy_ms;
+        if let Some(per_sec) = (total_bytes * 1000).checked_div(busy_ms) {
+            println!(
+                "Throughput: {}/s ({:.2}s total processing time)",
+                human_bytes(per_sec, sizes),
+                busy_ms as f64 / 1000.0
+            );
+        }
+        if self.stats && tally.has_sizes() {
+            match tally.size_stats() {
+                Some(s) => println!(
+                    "File sizes: mean {}, p50 {}, p90 {}, p99 {}, min {}, max {}",
+                    human_bytes(s.mean.round() as u128, sizes),
+                    human_bytes(s.p50 as u128, sizes),
+                    human_bytes(s.p90 as u128, sizes),
+                    human_bytes(s.p99 as u128, sizes),
+                    human_bytes(s.min as u128, sizes),
+                    human_bytes(s.max as u128, sizes)
+                ),
+                None => println!("File sizes: no files"),
+            }
+        }
+        if total_files > 0 {
+            println!("\nFirst {} files ({}):", FIRST_FILES, self.order.describe());
+            let mut table = new_table(&["Size", "Path"], &[0], color);
+            for r in tally.first.sorted() {
+                table.add_row(vec![size_cell(r.size, sizes, color), Cell::new(r.path.display())]);
+            }
+            println!("{table}");
+
+            let roots = tally.root_totals();
+            if !roots.is_empty() {
+                println!("\nBy cache root:");
+                let mut table = new_table(&["Root", "Files", "Size"], &[1, 2], color);
+                for t in &roots {
+                    table.add_row(vec![
+                        Cell::new(t.root.display()),
+                        Cell::new(t.files),
+                        size_cell(t.bytes, sizes, color),
+                    ]);
+                }
+                println!("{table}");
+            }
+
+            let mounts = tally.mount_totals();
+            if !mounts.is_empty() {
+                println!("\nBy mount:");
+                // per-mount rates tell a fast tier from a slow one, which the
+                // overall throughput averages away
+                let timed = mounts.iter().any(|t| t.busy_ms.is_some());
+                let mut headers = vec!["Mount", "Type", "Files", "Size"];
+                if timed {
+                    headers.extend(["Busy", "Rate"]);
+                }
+                let mut table = new_table(&headers, &[2, 3, 4, 5], color);
+                for t in &mounts {
+                    let mut row = vec![
+                        Cell::new(t.mount.display()),
+                        Cell::new(&t.fs_type),
+                        Cell::new(t.files),
+                        size_cell(t.bytes, sizes, color),
+                    ];
+                    if timed {
+                        let busy = t.busy_ms.map(|ms| format!("{:.2}s", ms as f64 / 1000.0));
+                        let rate = t.rate().map(|r| format!("{}/s", human_bytes(r, sizes)));
+                        row.push(Cell::new(busy.as_deref().unwrap_or("-")));
+                        row.push(Cell::new(rate.as_deref().unwrap_or("-")));
+                    }
+                    table.add_row(row);
+                }
+                println!("{table}");
+                println!("\nBy filesystem type:");
+                let mut table = new_table(&["Type", "Mounts", "Files", "Size"], &[1, 2, 3], color);
+                for t in fs_type_totals(&mounts) {
+                    table.add_row(vec![
+                        Cell::new(&t.fs_type),
+                        Cell::new(t.mounts),
+                        Cell::new(t.files),
+                        size_cell(t.bytes, sizes, color),
+                    ]);
+                }
+                println!("{table}");
+            }
+
+            if let Some(tree) = &tally.tree {
+                println!("\nDirectory sizes:");
+                tree.render(sizes).iter().for_each(|line| println!("{line}"));
+            }
+
+            let extensions = tally.extension_totals();
+            println!("\nTop extensions by size:");
+            let mut table = new_table(&["Extension", "Files", "Size"], &[1, 2], color);
+            for e in extensions.iter().take(10) {
+                table.add_row(vec![
+                    Cell::new(&e.extension),
+                    Cell::new(e.files),
+                    size_cell(e.bytes, sizes, color),
+                ]);
+            }
+            println!("{table}");
+        }
+        let compressibility = tally.compressibility_totals();
+        if !compressibility.is_empty() {
+            let savings: u64 = compressibility.iter().map(|t| t.estimated_savings()).sum();
+            println!(
+                "\nCompressibility estimate ({} could be saved):",
+                human_bytes(savings as u128, sizes)
+            );
+            let header = ["Extension", "Files", "Size", "Ratio", "Savings"];
+            let mut table = new_table(&header, &[1, 2, 3, 4], color);
+            for t in compressibility.iter().take(10) {
+                table.add_row(vec![
+                    Cell::new(&t.extension),
+                    Cell::new(t.files),
+                    size_cell(t.bytes, sizes, color),
+                    Cell::new(format!(
+                        "{:.2}",
+                        t.estimated_compressed as f64 / t.bytes.max(1) as f64
+                    )),
+                    size_cell(t.estimated_savings(), sizes, color),
+                ]);
+            }
+            println!("{table}");
+        }
+        if self.slowest > 0 && total_files > 0 {
+            let by_time = tally.slowest.sorted();
+            println!("\nTop {} slowest files:", by_time.len());
+            let mut table = new_table(&["Time (ms)", "Size", "Path"], &[0, 1], color);
+            for r in by_time {
+                table.add_row(vec![
+                    Cell::new(r.elapsed_ms),
+                    size_cell(r.size, sizes, color),
+                    Cell::new(r.path.display()),
+                ]);
+            }
+            println!("{table}");
+        }
+
+        let models = &summary.report.models;
+        if !models.is_empty() {
+            match summary.layout {
+                // roots with different layouts leave it unresolved
+                Layout::Auto => println!("\nModels:"),
+                layout => println!("\nModels ({} layout):", layout.describe()),
+            }
+            let mut table = new_table(&["Size", "Model", "Revision", "Files"], &[0, 3], color);
+            for g in models {
+                let revision = match g.revisions.as_slice() {
+                    [] => String::new(),
+                    [only] => short_hash(only).to_string(),
+                    [first, rest @ ..] => format!("{} (+{} more)", short_hash(first), rest.len()),
+                };
+                table.add_row(vec![
+                    size_cell(g.bytes, sizes, color),
+                    Cell::new(&g.name),
+                    Cell::new(revision),
+                    Cell::new(g.files),
+                ]);
+            }
+            println!("{table}");
+        }
+
+        let orphans = &summary.report.orphans;
+        if self.show_orphans {
+            println!(
+                "\nOrphaned blobs: {} ({} reclaimable)",
+                orphans.len(),
+                human_bytes(summary.reclaim.orphan_bytes as u128, sizes)
+            );
+            if !orphans.is_empty() {
+                let mut table = new_table(&["Size", "Path"], &[0], color);
+                for o in orphans {
+                    table.add_row(vec![
+                        size_cell(o.size, sizes, color),
+                        Cell::new(o.path.display()),
+                    ]);
+                }
+                println!("{table}");
+            }
+        }
+
+        let issues = &summary.report.symlink_issues;
+        if self.show_symlinks {
+            println!("\nSymlink problems: {}", issues.len());
+            if !issues.is_empty() {
+                let mut table = new_table(&["Problem", "Link", "Target", "Fix"], &[], color);
+                for i in issues {
+                    let problem = match i.problem {
+                        LinkProblem::Broken => "broken",
+                        LinkProblem::Escapes => "outside cache",
+                    };
+                    table.add_row(vec![
+                        Cell::new(problem),
+                        Cell::new(i.link.display()),
+                        Cell::new(i.target.display()),
+                        Cell::new(i.fix.as_ref().map(|f| f.display().to_string()).unwrap_or_default()),
+                    ]);
+                }
+                println!("{table}");
+            }
+        }
+
+        let suspicious: Vec<&FileReport> =
+            tally.annotated.iter().filter(|r| r.suspicious.is_some()).collect();
+        if self.show_suspicious {
+            println!("\nSuspicious files (likely failed downloads): {}", suspicious.len());
+            if !suspicious.is_empty() {
+                let mut table = new_table(&["Size", "Reason", "Path"], &[0], color);
+                for r in &suspicious {
+                    table.add_row(vec![
+                        size_cell(r.size, sizes, color),
+                        Cell::new(r.suspicious.as_deref().unwrap_or_default()),
+                        Cell::new(r.path.display()),
+                    ]);
+                }
+                println!("{table}");
+            }
+        }
+
+        let mismatched: Vec<&FileReport> =
+            tally.annotated.iter().filter(|r| r.content_mismatch.is_some()).collect();
+        if self.show_mismatches {
+            println!("\nContent not matching the extension: {}", mismatched.len());
+            if !mismatched.is_empty() {
+                let mut table = new_table(&["Size", "Reason", "Path"], &[0], color);
+                for r in &mismatched {
+                    table.add_row(vec![
+                        size_cell(r.size, sizes, color),
+                        Cell::new(r.content_mismatch.as_deref().unwrap_or_default()),
+                        Cell::new(r.path.display()),
+                    ]);
+                }
+                println!("{table}");
+            }
+        }
+
+        let checkpoints: Vec<&FileReport> =
+            tally.annotated.iter().filter(|r| r.pytorch.is_some()).collect();
+        if self.show_pytorch {
+            println!("\nPyTorch checkpoints: {}", checkpoints.len());
+            if !checkpoints.is_empty() {
+                let mut table =
+                    new_table(&["Format", "Tensors", "Parameters", "Path"], &[1, 2], color);
+                for (r, info) in checkpoints.iter().filter_map(|r| Some((r, r.pytorch.as_ref()?))) {
+                    let (tensors, parameters) = match &info.error {
+                        Some(e) => (format!("unreadable: {}", e), String::new()),
+                        None => (info.tensors.len().to_string(), info.parameters().to_string()),
+                    };
+                    table.add_row(vec![
+                        Cell::new(format!("{:?}", info.format).to_lowercase()),
+                        Cell::new(tensors),
+                        Cell::new(parameters),
+                        Cell::new(r.path.display()),
+                    ]);
+                }
+                println!("{table}");
+            }
+        }
+
+        let collisions = &summary.report.size_collisions;
+        if self.show_size_collisions {
+            println!("\nSize collisions (same size, different contents): {}", collisions.len());
+            if !collisions.is_empty() {
+                let mut table = new_table(&["Size", "Hash", "Path"], &[0], color);
+                for c in collisions.iter().take(10) {
+                    for f in &c.files {
+                        table.add_row(vec![
+                            size_cell(c.size, sizes, color),
+                            Cell::new(short_hash(&f.hash_str)),
+                            Cell::new(f.path.display()),
+                        ]);
+                    }
+                }
+                println!("{table}");
+            }
+        }
+
+        let hardlinks = &summary.report.hardlinks;
+        if self.show_hardlinks {
+            let paths: usize = hardlinks.iter().map(|g| g.paths.len()).sum();
+            println!(
+                "\nHard-linked files: {} inode(s) reached by {} path(s)",
+                hardlinks.len(),
+                paths
+            );
+            if !hardlinks.is_empty() {
+                let mut table = new_table(&["Size", "Links", "Scanned", "Path"], &[0, 1, 2], color);
+                for g in hardlinks.iter().take(10) {
+                    for (i, path) in g.paths.iter().enumerate() {
+                        let first = i == 0;
+                        table.add_row(vec![
+                            if first { size_cell(g.size, sizes, color) } else { Cell::new("") },
+                            Cell::new(if first { g.nlink.to_string() } else { String::new() }),
+                            Cell::new(if first {
+                                g.paths.len().to_string()
+                            } else {
+                                String::new()
+                            }),
+                            Cell::new(path.display()),
+                        ]);
+                    }
+                }
+                println!("{table}");
+                if hardlinks.len() > 10 {
+                    println!("... and {} more inode(s)", hardlinks.len() - 10);
+                }
+            }
+        }
+
+        let duplicates = &summary.report.duplicates;
+        if !duplicates.is_empty() {
+            println!(
+                "\nDuplicate groups: {} ({} in extra copies)",
+                duplicates.len(),
+                human_bytes(summary.reclaim.duplicate_bytes as u128, sizes)
+            );
+            let mut table = new_table(&["Wasted", "Copies", "Hash", "Kept"], &[0, 1], color);
+            for g in duplicates.iter().take(10) {
+                table.add_row(vec![
+                    size_cell(g.wasted(), sizes, color),
+                    Cell::new(g.paths.len()),
+                    Cell::new(short_hash(&g.hash_str)),
+                    Cell::new(g.paths[0].display()),
+                ]);
+            }
+            println!("{table}");
+        }
+        let reclaim = &summary.reclaim;
+        println!(
+            "\nPotentially reclaimable: {} ({} from duplicates, {} from orphans).",
+            human_bytes(reclaim.total_bytes as u128, sizes),
+            human_bytes(reclaim.duplicate_bytes as u128, sizes),
+            human_bytes(reclaim.orphan_bytes as u128, sizes)
+        );
+        Ok(())
+    }
+}
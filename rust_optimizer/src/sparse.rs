@@ -0,0 +1,62 @@
+//! Allocated extents of sparse files, so `--sparse-aware` can skip reading
+//! holes. The hasher is still fed zeros for every hole, which is cheap next
+//! to faulting in gigabytes of zero pages, so digests match a dense read.
+
+use std::fs::{File, Metadata};
+use std::io;
+use std::ops::Range;
+
+/// Fewer blocks are allocated than the length needs, so the file has holes.
+#[cfg(unix)]
+pub fn is_sparse(meta: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    meta.blocks().saturating_mul(512) < meta.len()
+}
+
+#[cfg(not(unix))]
+pub fn is_sparse(_meta: &Metadata) -> bool {
+    false
+}
+
+/// The ranges of `start..end` that hold data, in order, found with
+/// `SEEK_DATA`/`SEEK_HOLE`. Filesystems without hole tracking report the
+/// whole file as data, which just means a full read.
+#[cfg(target_os = "linux")]
+pub fn data_extents(f: &File, start: u64, end: u64) -> io::Result<Vec<Range<u64>>> {
+    use std::os::fd::AsRawFd;
+    let seek = |pos: u64, whence| -> io::Result<Option<u64>> {
+        let pos = libc::off_t::try_from(pos).map_err(|_| io::ErrorKind::InvalidInput)?;
+        // SAFETY: lseek only moves this descriptor's offset, and every read
+        // seeks to its own start first
+        match unsafe { libc::lseek(f.as_raw_fd(), pos, whence) } {
+            // ENXIO: no data (or hole) at or after `pos`
+            -1 => match io::Error::last_os_error() {
+                e if e.raw_os_error() == Some(libc::ENXIO) => Ok(None),
+                e => Err(e),
+            },
+            found => Ok(Some(found as u64)),
+        }
+    };
+    let mut extents = Vec::new();
+    let mut pos = start;
+    while pos < end {
+        let Some(data) = seek(pos, libc::SEEK_DATA)?.filter(|&d| d < end) else {
+            break;
+        };
+        let hole = seek(data, libc::SEEK_HOLE)?.unwrap_or(end).min(end);
+        extents.push(data..hole);
+        pos = hole;
+    }
+    Ok(extents)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn data_extents(_f: &File, _start: u64, _end: u64) -> io::Result<Vec<Range<u64>>> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// Bytes of `start..end` outside every extent.
+pub fn hole_bytes(extents: &[Range<u64>], start: u64, end: u64) -> u64 {
+    let data: u64 = extents.iter().map(|e| e.end.min(end).saturating_sub(e.start.max(start))).sum();
+    (end - start).saturating_sub(data)
+}
@@ -0,0 +1,75 @@
+//! Spotting failed downloads: empty files, model files too small to hold
+//! anything, and safetensors files shorter than their own header says.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Smallest plausible size per model-weight extension. Anything below is a
+/// stub, an HTML error page or a Git LFS pointer rather than weights.
+const MIN_MODEL_BYTES: &[(&str, u64)] = &[
+    ("safetensors", 1024),
+    ("gguf", 1024),
+    ("bin", 1024),
+    ("pt", 1024),
+    ("pth", 1024),
+    ("ckpt", 1024),
+    ("onnx", 1024),
+    ("h5", 1024),
+    ("msgpack", 1024),
+];
+
+/// safetensors refuses headers over 100 MB; a bigger length is garbage
+pub(crate) const MAX_SAFETENSORS_HEADER: u64 = 100 << 20;
+
+/// Why `path` looks like a broken download, if it does.
+pub fn check(path: &Path, size: u64) -> Option<String> {
+    if size == 0 {
+        return Some("empty file".to_string());
+    }
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    if let Some(&(_, min)) = MIN_MODEL_BYTES.iter().find(|(e, _)| *e == ext) {
+        if size < min {
+            return Some(format!("only {} bytes for a .{} file", size, ext));
+        }
+    }
+    if ext == "safetensors" {
+        return match safetensors_expected_size(path) {
+            Ok(expected) if expected > size => Some(format!(
+                "truncated: header describes {} bytes, file has {}",
+                expected, size
+            )),
+            Ok(_) => None,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                Some("truncated inside its header".to_string())
+            }
+            Err(e) => Some(format!("unreadable safetensors header: {}", e)),
+        };
+    }
+    None
+}
+
+/// Size a complete safetensors file must have: the 8-byte length prefix, the
+/// JSON header, and tensor data up to the furthest `data_offsets` end.
+pub fn safetensors_expected_size(path: &Path) -> io::Result<u64> {
+    let mut f = File::open(path)?;
+    let mut prefix = [0u8; 8];
+    f.read_exact(&mut prefix)?;
+    let header_len = u64::from_le_bytes(prefix);
+    if header_len > MAX_SAFETENSORS_HEADER {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("header length {} is implausible", header_len),
+        ));
+    }
+    let mut header = vec![0u8; header_len as usize];
+    f.read_exact(&mut header)?;
+    let header: serde_json::Map<String, serde_json::Value> = serde_json::from_slice(&header)?;
+    let data_end = header
+        .iter()
+        .filter(|(name, _)| name.as_str() != "__metadata__")
+        .filter_map(|(_, tensor)| tensor.get("data_offsets")?.get(1)?.as_u64())
+        .max()
+        .unwrap_or(0);
+    Ok(8 + header_len + data_end)
+}
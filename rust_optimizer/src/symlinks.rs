@@ -0,0 +1,138 @@
+//! Validating HF snapshot symlinks and repointing the broken ones.
+
+use crate::layout::hf_repo_name;
+use crate::walk::Symlink;
+use anyhow::{Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+
+/// What is wrong with a symlink.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkProblem {
+    /// The target does not exist
+    Broken,
+    /// The target resolves outside the cache root
+    Escapes,
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema, Deserialize)]
+pub struct SymlinkIssue {
+    pub link: PathBuf,
+    /// The link's target as stored on disk
+    pub target: PathBuf,
+    pub problem: LinkProblem,
+    /// Relative target into the repo's `blobs/` holding the same content, if found
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fix: Option<PathBuf>,
+}
+
+/// Report every symlink under `root` that dangles or leaves the cache. HF
+/// names blobs after their hash and links keep that name as their last
+/// component, so a file in the repo's `blobs/` with that name, whose content
+/// really has that hash, is the fix.
+pub fn check_symlinks(root: &Path, symlinks: &[Symlink]) -> Vec<SymlinkIssue> {
+    let canonical_root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    let mut issues = Vec::new();
+    for s in symlinks {
+        let problem = match &s.target {
+            None => LinkProblem::Broken,
+            Some(t) if !t.starts_with(&canonical_root) => LinkProblem::Escapes,
+            Some(_) => continue,
+        };
+        let Ok(target) = fs::read_link(&s.link) else {
+            continue;
+        };
+        let fix = repo_blob_for(root, &s.link, &target);
+        issues.push(SymlinkIssue {
+            link: s.link.clone(),
+            target,
+            problem,
+            fix,
+        });
+    }
+    issues
+}
+
+/// `../../blobs/<name>`-style path from `link` to the repo blob named like
+/// `target`'s last component, when that blob exists and its content matches
+/// the name.
+fn repo_blob_for(root: &Path, link: &Path, target: &Path) -> Option<PathBuf> {
+    let rel = link.strip_prefix(root).ok()?;
+    let repo = rel.components().next()?.as_os_str();
+    hf_repo_name(&repo.to_string_lossy())?;
+    let blob_name = target.file_name()?;
+    // one `..` per directory between the repo and the link itself
+    let dirs = rel.parent()?.components().filter(|c| matches!(c, Component::Normal(_))).count();
+    let depth = dirs.checked_sub(1)?;
+    let blob = root.join(repo).join("blobs").join(blob_name);
+    if !blob.is_file() || !has_digest(&blob, &blob_name.to_string_lossy()).unwrap_or(false) {
+        return None;
+    }
+    let mut fix: PathBuf = std::iter::repeat_n("..", depth).collect();
+    fix.push("blobs");
+    fix.push(blob_name);
+    Some(fix)
+}
+
+/// Whether `blob` hashes to `name` the way HF names blobs: the SHA-256 of
+/// the content for LFS files (64 hex digits), the git blob SHA-1 for the
+/// rest (40). Any other name matches nothing.
+fn has_digest(blob: &Path, name: &str) -> Result<bool> {
+    use sha2::Digest;
+    if !name.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Ok(false);
+    }
+    let mut f = File::open(blob).with_context(|| format!("opening {:?}", blob))?;
+    let hex = match name.len() {
+        64 => {
+            let mut sha = sha2::Sha256::new();
+            io::copy(&mut f, &mut sha)?;
+            sha.finalize().iter().map(|b| format!("{:02x}", b)).collect::<String>()
+        }
+        40 => {
+            let mut sha = sha1_smol::Sha1::new();
+            sha.update(format!("blob {}\0", f.metadata()?.len()).as_bytes());
+            let mut buf = vec![0u8; 1 << 16];
+            loop {
+                match f.read(&mut buf)? {
+                    0 => break,
+                    n => sha.update(&buf[..n]),
+                }
+            }
+            sha.digest().to_string()
+        }
+        _ => return Ok(false),
+    };
+    Ok(hex.eq_ignore_ascii_case(name))
+}
+
+/// Repoint `issue.link` at `issue.fix` by creating the new link beside it and
+/// renaming it into place.
+pub fn apply_fix(issue: &SymlinkIssue) -> Result<()> {
+    let fix = issue.fix.as_ref().context("no blob with matching content")?;
+    let name = issue.link.file_name().context("symlink path has no file name")?;
+    let tmp = issue
+        .link
+        .with_file_name(format!(".{}.aivista-relink", name.to_string_lossy()));
+    let _ = fs::remove_file(&tmp);
+    create_file_symlink(fix, &tmp).with_context(|| format!("creating {:?}", tmp))?;
+    if let Err(e) = fs::rename(&tmp, &issue.link) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e).with_context(|| format!("replacing {:?}", issue.link));
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+pub fn create_file_symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+pub fn create_file_symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_file(target, link)
+}
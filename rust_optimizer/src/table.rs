@@ -0,0 +1,58 @@
+//! Aligned, optionally coloured tables for the human summary.
+
+use crate::report::{human_bytes, SizeFormat};
+use comfy_table::{presets, Cell, CellAlignment, Color, Table, TableComponent};
+use std::io::IsTerminal;
+
+/// Colour only when stdout is a terminal, `NO_COLOR` is unset or empty
+/// (<https://no-color.org>) and the user did not pass `--no-color`.
+pub fn use_color(no_color_flag: bool) -> bool {
+    !no_color_flag
+        && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+        && std::io::stdout().is_terminal()
+}
+
+/// A table with a header row, framed by horizontal rules. Columns listed in
+/// `right` (sizes, counts) are right-aligned.
+pub fn new_table(header: &[&str], right: &[usize], color: bool) -> Table {
+    let mut table = Table::new();
+    table
+        .load_preset(presets::UTF8_HORIZONTAL_ONLY)
+        .remove_style(TableComponent::HorizontalLines)
+        .remove_style(TableComponent::MiddleIntersections)
+        .remove_style(TableComponent::LeftBorderIntersections)
+        .remove_style(TableComponent::RightBorderIntersections);
+    if color {
+        table.enforce_styling();
+    } else {
+        // no escape codes and no terminal width probing when piped
+        table.force_no_tty();
+    }
+    table.set_header(header.iter().map(Cell::new));
+    for &i in right {
+        if let Some(column) = table.column_mut(i) {
+            column.set_cell_alignment(CellAlignment::Right);
+        }
+    }
+    table
+}
+
+/// Long commit and content hashes are cut to 12 characters for display.
+pub fn short_hash(hash: &str) -> &str {
+    hash.get(..12).unwrap_or(hash)
+}
+
+/// Human-readable size, coloured by order of magnitude when `color` is set.
+pub fn size_cell(bytes: u64, sizes: SizeFormat, color: bool) -> Cell {
+    let cell = Cell::new(human_bytes(bytes as u128, sizes));
+    if !color {
+        return cell;
+    }
+    let fg = match bytes {
+        b if b >= 1 << 30 => Color::Red,
+        b if b >= 1 << 20 => Color::Yellow,
+        b if b >= 1 << 10 => Color::Green,
+        _ => Color::DarkGrey,
+    };
+    cell.fg(fg)
+}
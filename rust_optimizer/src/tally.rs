@@ -0,0 +1,447 @@
+//! Streaming aggregates over scan results: everything the human summary
+//! prints, kept in memory bounded by the number of distinct extensions,
+//! roots and mounts rather than the number of files. The aggregator feeds
+//! it one report at a time, so a cache of ten million files need not keep
+//! ten million `FileReport`s just to print totals and a top ten.
+
+use crate::report::{
+    extension_of, CompressibilityTotal, ExtensionTotal, FileReport, FileStatus, MountTotal,
+    ReportOrder, RootTotal, SizeStats, SortKey,
+};
+use crate::tree::SizeTree;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BinaryHeap};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// A report ranked by `order`; the greatest is the last in that order.
+#[derive(Debug, Clone)]
+struct Ranked {
+    order: ReportOrder,
+    report: FileReport,
+}
+
+impl PartialEq for Ranked {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Ranked {}
+
+impl PartialOrd for Ranked {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Ranked {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.order.compare(&self.report, &other.report)
+    }
+}
+
+/// The first `n` reports in `order`, in a heap of at most `n` whose top is
+/// the one to evict next.
+#[derive(Debug, Clone)]
+pub struct TopN {
+    n: usize,
+    order: ReportOrder,
+    heap: BinaryHeap<Ranked>,
+}
+
+impl TopN {
+    pub fn new(n: usize, order: ReportOrder) -> Self {
+        Self {
+            n,
+            order,
+            heap: BinaryHeap::with_capacity(n + 1),
+        }
+    }
+
+    pub fn push(&mut self, report: &FileReport) {
+        if self.n == 0 {
+            return;
+        }
+        if self.heap.len() == self.n {
+            // most reports lose to the current last place; skip cloning those
+            let last = self.heap.peek().map(|r| &r.report);
+            if last.is_some_and(|last| self.order.compare(report, last) != Ordering::Less) {
+                return;
+            }
+            self.heap.pop();
+        }
+        self.heap.push(Ranked {
+            order: self.order,
+            report: report.clone(),
+        });
+    }
+
+    /// Keep the first `n` of both rankings.
+    pub fn merge(&mut self, other: TopN) {
+        other.heap.iter().for_each(|r| self.push(&r.report));
+    }
+
+    /// The kept reports, first in `order` first.
+    pub fn sorted(&self) -> Vec<&FileReport> {
+        let mut kept: Vec<&Ranked> = self.heap.iter().collect();
+        kept.sort();
+        kept.into_iter().map(|r| &r.report).collect()
+    }
+}
+
+/// Totals and rankings over every report added.
+#[derive(Debug, Clone)]
+pub struct Tally {
+    pub files: usize,
+    pub bytes: u128,
+    pub warmed_files: usize,
+    pub warmed_bytes: u128,
+    pub errored: usize,
+    pub vanished: usize,
+    /// Read errors partway through, from `--partial-on-error`
+    pub partially_hashed: usize,
+    pub hole_files: usize,
+    pub hole_bytes: u128,
+    pub measured_files: usize,
+    pub measured_bytes: u128,
+    resident_bytes: f64,
+    /// Summed worker time over every file
+    pub busy_ms: u128,
+    /// The first files in the summary's order
+    pub first: TopN,
+    pub slowest: TopN,
+    /// Files `--flag-suspicious`, `--detect-mismatch` or `--inspect-pytorch`
+    /// annotated; few by nature
+    pub annotated: Vec<FileReport>,
+    extensions: BTreeMap<String, (usize, u64)>,
+    compressibility: BTreeMap<String, CompressibilityTotal>,
+    roots: BTreeMap<PathBuf, (usize, u64)>,
+    mounts: BTreeMap<(PathBuf, String), (usize, u64)>,
+    /// When files on each mount were being processed, as milliseconds since
+    /// `origin`; kept only for files added with `add_finished`
+    mount_spans: BTreeMap<PathBuf, BusyTime>,
+    origin: Instant,
+    /// Every size, for `--stats` percentiles: eight bytes a file
+    sizes: Option<Vec<u64>>,
+    /// Directory sizes for `--tree`
+    pub tree: Option<SizeTree>,
+}
+
+/// Files the summary's first-files table lists.
+pub const FIRST_FILES: usize = 10;
+
+impl Tally {
+    /// `slowest` sizes the slowest-files ranking; `stats` keeps every size.
+    pub fn new(order: ReportOrder, slowest: usize, stats: bool) -> Self {
+        Self {
+            files: 0,
+            bytes: 0,
+            warmed_files: 0,
+            warmed_bytes: 0,
+            errored: 0,
+            vanished: 0,
+            partially_hashed: 0,
+            hole_files: 0,
+            hole_bytes: 0,
+            measured_files: 0,
+            measured_bytes: 0,
+            resident_bytes: 0.0,
+            busy_ms: 0,
+            first: TopN::new(FIRST_FILES, order),
+            slowest: TopN::new(slowest, ReportOrder::new(Some(SortKey::Elapsed), true)),
+            annotated: Vec::new(),
+            extensions: BTreeMap::new(),
+            compressibility: BTreeMap::new(),
+            roots: BTreeMap::new(),
+            mounts: BTreeMap::new(),
+            mount_spans: BTreeMap::new(),
+            origin: Instant::now(),
+            sizes: stats.then(Vec::new),
+            tree: None,
+        }
+    }
+
+    /// Also sum sizes per directory under `roots`, down to `depth` levels.
+    pub fn with_tree(mut self, roots: &[PathBuf], depth: usize) -> Self {
+        self.tree = Some(SizeTree::new(roots, depth));
+        self
+    }
+
+    /// A tally of `reports` all at once.
+    pub fn of(reports: &[FileReport], order: ReportOrder, slowest: usize, stats: bool) -> Self {
+        let mut tally = Self::new(order, slowest, stats);
+        reports.iter().for_each(|r| tally.add(r));
+        tally
+    }
+
+    pub fn add(&mut self, r: &FileReport) {
+        self.files += 1;
+        self.bytes += r.size as u128;
+        self.busy_ms += r.elapsed_ms;
+        match r.status {
+            FileStatus::Warmed => {
+                self.warmed_files += 1;
+                self.warmed_bytes += r.size as u128;
+            }
+            FileStatus::Errored => self.errored += 1,
+            FileStatus::Vanished => self.vanished += 1,
+            FileStatus::PartiallyHashed => self.partially_hashed += 1,
+            _ => {}
+        }
+        if let Some(holes) = r.hole_bytes.filter(|&h| h > 0) {
+            self.hole_files += 1;
+            self.hole_bytes += holes as u128;
+        }
+        if let Some(fraction) = r.resident {
+            let len = r.range.map_or(r.size, |[start, end]| end - start);
+            self.measured_files += 1;
+            self.measured_bytes += len as u128;
+            self.resident_bytes += len as f64 * fraction;
+        }
+        self.first.push(r);
+        self.slowest.push(r);
+        if r.suspicious.is_some() || r.content_mismatch.is_some() || r.pytorch.is_some() {
+            self.annotated.push(r.clone());
+        }
+
+        let ext = extension_of(r);
+        if let Some(ratio) = r.compress_ratio {
+            let total = self.compressibility.entry(ext.clone()).or_insert(CompressibilityTotal {
+                extension: ext.clone(),
+                files: 0,
+                bytes: 0,
+                estimated_compressed: 0,
+            });
+            total.files += 1;
+            total.bytes += r.size;
+            total.estimated_compressed += (r.size as f64 * ratio.min(1.0)) as u64;
+        }
+        let entry = self.extensions.entry(ext).or_default();
+        entry.0 += 1;
+        entry.1 += r.size;
+        if let Some(root) = &r.root {
+            let entry = self.roots.entry(root.clone()).or_default();
+            entry.0 += 1;
+            entry.1 += r.size;
+        }
+        if let Some(mount) = &r.mount {
+            let fs_type = r.fs_type.as_deref().unwrap_or("?").to_string();
+            let entry = self.mounts.entry((mount.clone(), fs_type)).or_default();
+            entry.0 += 1;
+            entry.1 += r.size;
+        }
+        if let Some(sizes) = &mut self.sizes {
+            sizes.push(r.size);
+        }
+        if let Some(tree) = &mut self.tree {
+            tree.add(&r.path, r.size);
+        }
+    }
+
+    /// Fold in `other`, a clone of this tally fed other reports, as if they
+    /// had been added here. Clones share `origin`, so mount busy times
+    /// measured on each still line up.
+    pub fn merge(&mut self, other: Tally) {
+        self.files += other.files;
+        self.bytes += other.bytes;
+        self.warmed_files += other.warmed_files;
+        self.warmed_bytes += other.warmed_bytes;
+        self.errored += other.errored;
+        self.vanished += other.vanished;
+        self.partially_hashed += other.partially_hashed;
+        self.hole_files += other.hole_files;
+        self.hole_bytes += other.hole_bytes;
+        self.measured_files += other.measured_files;
+        self.measured_bytes += other.measured_bytes;
+        self.resident_bytes += other.resident_bytes;
+        self.busy_ms += other.busy_ms;
+        self.first.merge(other.first);
+        self.slowest.merge(other.slowest);
+        self.annotated.extend(other.annotated);
+        for (ext, (files, bytes)) in other.extensions {
+            let entry = self.extensions.entry(ext).or_default();
+            entry.0 += files;
+            entry.1 += bytes;
+        }
+        for (ext, theirs) in other.compressibility {
+            let total = self.compressibility.entry(ext).or_insert(CompressibilityTotal {
+                files: 0,
+                bytes: 0,
+                estimated_compressed: 0,
+                ..theirs.clone()
+            });
+            total.files += theirs.files;
+            total.bytes += theirs.bytes;
+            total.estimated_compressed += theirs.estimated_compressed;
+        }
+        for (root, (files, bytes)) in other.roots {
+            let entry = self.roots.entry(root).or_default();
+            entry.0 += files;
+            entry.1 += bytes;
+        }
+        for (mount, (files, bytes)) in other.mounts {
+            let entry = self.mounts.entry(mount).or_default();
+            entry.0 += files;
+            entry.1 += bytes;
+        }
+        for (mount, spans) in other.mount_spans {
+            self.mount_spans.entry(mount).or_default().merge(spans);
+        }
+        if let (Some(sizes), Some(theirs)) = (&mut self.sizes, other.sizes) {
+            sizes.extend(theirs);
+        }
+        if let (Some(tree), Some(theirs)) = (&mut self.tree, other.tree) {
+            tree.merge(theirs);
+        }
+    }
+
+    /// `add` a report that came back from its worker at `finished`, so the
+    /// time its mount was busy can be measured.
+    pub fn add_finished(&mut self, r: &FileReport, finished: Instant) {
+        self.add(r);
+        if let Some(mount) = &r.mount {
+            let end = finished.saturating_duration_since(self.origin).as_millis() as u64;
+            let start = end.saturating_sub(r.elapsed_ms as u64);
+            self.mount_spans.entry(mount.clone()).or_default().insert(start, end);
+        }
+    }
+
+    /// Byte-weighted page-cache hit ratio of the measured files.
+    pub fn hit_ratio(&self) -> Option<f64> {
+        (self.measured_bytes > 0).then(|| self.resident_bytes / self.measured_bytes as f64)
+    }
+
+    /// `None` unless kept for `--stats`, or when there were no files.
+    pub fn size_stats(&self) -> Option<SizeStats> {
+        SizeStats::of(self.sizes.clone()?)
+    }
+
+    /// Whether `--stats` sizes were kept.
+    pub fn has_sizes(&self) -> bool {
+        self.sizes.is_some()
+    }
+
+    /// Totals per lowercased extension, largest first.
+    pub fn extension_totals(&self) -> Vec<ExtensionTotal> {
+        let mut totals: Vec<ExtensionTotal> = self
+            .extensions
+            .iter()
+            .map(|(extension, &(files, bytes))| ExtensionTotal {
+                extension: extension.clone(),
+                files,
+                bytes,
+            })
+            .collect();
+        // stable sort keeps ties in extension order
+        totals.sort_by_key(|t| std::cmp::Reverse(t.bytes));
+        totals
+    }
+
+    /// Per-extension compressibility of the probed files, most savings first.
+    pub fn compressibility_totals(&self) -> Vec<CompressibilityTotal> {
+        let mut totals: Vec<CompressibilityTotal> =
+            self.compressibility.values().cloned().collect();
+        totals.sort_by_key(|t| std::cmp::Reverse(t.estimated_savings()));
+        totals
+    }
+
+    /// Totals per `FileReport::root`, in root order.
+    pub fn root_totals(&self) -> Vec<RootTotal> {
+        self.roots
+            .iter()
+            .map(|(root, &(files, bytes))| RootTotal {
+                root: root.clone(),
+                files,
+                bytes,
+            })
+            .collect()
+    }
+
+    /// Totals per `FileReport::mount`, largest first.
+    pub fn mount_totals(&self) -> Vec<MountTotal> {
+        let mut totals: Vec<MountTotal> = self
+            .mounts
+            .iter()
+            .map(|((mount, fs_type), &(files, bytes))| MountTotal {
+                mount: mount.clone(),
+                fs_type: fs_type.clone(),
+                files,
+                bytes,
+                busy_ms: self.mount_spans.get(mount).map(BusyTime::total),
+            })
+            .collect();
+        totals.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.mount.cmp(&b.mount)));
+        totals
+    }
+}
+
+/// One partial aggregate per worker thread, so workers fold in their own
+/// reports without waiting on each other or on a single aggregator; merge
+/// them with `into_inner` once the workers are done.
+pub struct Shards<T>(Vec<Mutex<T>>);
+
+impl<T: Clone> Shards<T> {
+    /// A clone of `empty` for each of `workers` rayon threads, and one
+    /// shared by any other thread.
+    pub fn new(empty: T, workers: usize) -> Self {
+        Self((0..=workers).map(|_| Mutex::new(empty.clone())).collect())
+    }
+}
+
+impl<T> Shards<T> {
+    /// Run `f` on the calling thread's shard; only that thread locks it.
+    pub fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        let last = self.0.len() - 1;
+        let index = rayon::current_thread_index().map_or(last, |i| i.min(last));
+        let mut shard = self.0[index].lock().unwrap();
+        f(&mut shard)
+    }
+
+    pub fn into_inner(self) -> Vec<T> {
+        self.0
+            .into_iter()
+            .map(|m| m.into_inner().unwrap())
+            .collect()
+    }
+}
+
+/// Milliseconds covered by a set of spans, overlapping ones counted once.
+/// Spans are merged as they are inserted, so memory grows with the idle
+/// gaps between them, not with the files that made them.
+#[derive(Debug, Clone, Default)]
+pub struct BusyTime(BTreeMap<u64, u64>);
+
+impl BusyTime {
+    /// Add `[start, end)`. A gap of a millisecond or less, the resolution of
+    /// `elapsed_ms`, is counted as busy and closed.
+    pub fn insert(&mut self, mut start: u64, mut end: u64) {
+        if let Some((&s, &e)) = self.0.range(..=start).next_back() {
+            if e + 1 >= start {
+                start = s;
+                end = end.max(e);
+            }
+        }
+        let absorbed: Vec<u64> = self.0.range(start..=end + 1).map(|(&s, _)| s).collect();
+        for s in absorbed {
+            end = end.max(self.0.remove(&s).unwrap_or(end));
+        }
+        self.0.insert(start, end);
+    }
+
+    pub fn merge(&mut self, other: BusyTime) {
+        for (start, end) in other.0 {
+            self.insert(start, end);
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.0.iter().map(|(start, end)| end - start).sum()
+    }
+
+    /// Disjoint spans held.
+    pub fn spans(&self) -> usize {
+        self.0.len()
+    }
+}
@@ -0,0 +1,53 @@
+//! Wall-time breakdown of a scan by phase, printed by `--timing`.
+
+use std::time::{Duration, Instant};
+
+/// Phases in the order they ran, each with its wall time.
+#[derive(Debug, Default)]
+pub struct PhaseTimings {
+    phases: Vec<(&'static str, Duration)>,
+}
+
+impl PhaseTimings {
+    /// Run `f` and record how long it took under `name`.
+    pub fn time<T>(&mut self, name: &'static str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let out = f();
+        self.record(name, start.elapsed());
+        out
+    }
+
+    pub fn record(&mut self, name: &'static str, elapsed: Duration) {
+        self.phases.push((name, elapsed));
+    }
+
+    /// Print the table to stderr. `parallel` names the phase run on the
+    /// worker pool; everything else counts as serial. `busy` is the summed
+    /// per-file worker time and `aggregate` the aggregator's share of it.
+    pub fn print(&self, wall: Duration, parallel: &str, busy: Duration, aggregate: Duration, workers: usize) {
+        let secs = |d: Duration| d.as_secs_f64();
+        let share = |d: Duration| 100.0 * secs(d) / secs(wall).max(f64::EPSILON);
+        eprintln!("\nTiming:");
+        for (name, d) in &self.phases {
+            eprintln!("  {:<22} {:>9.3}s  {:>5.1}%", name, secs(*d), share(*d));
+        }
+        let parallel_wall: Duration = self
+            .phases
+            .iter()
+            .filter(|(name, _)| *name == parallel)
+            .map(|(_, d)| *d)
+            .sum();
+        let serial = wall.saturating_sub(parallel_wall);
+        eprintln!("  {:<22} {:>9.3}s", "worker busy (sum)", secs(busy));
+        eprintln!("  {:<22} {:>9.3}s", "aggregator busy", secs(aggregate));
+        eprintln!(
+            "  serial {:.3}s ({:.1}%), parallel {:.3}s ({:.1}%), {:.1}x effective of {} workers",
+            secs(serial),
+            share(serial),
+            secs(parallel_wall),
+            share(parallel_wall),
+            secs(busy) / secs(parallel_wall).max(f64::EPSILON),
+            workers
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! `--tree`: directory sizes summed up the hierarchy, printed as an indented
+//! tree like ncdu's. Directories below the depth limit are folded into their
+//! ancestor at the limit, so memory grows with the directories shown, not
+//! with the files scanned.
+
+use crate::report::{human_bytes, SizeFormat};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// Levels below each root shown by default.
+pub const DEFAULT_DEPTH: usize = 3;
+
+#[derive(Debug, Clone, Default)]
+struct Node {
+    bytes: u64,
+    files: usize,
+    children: BTreeMap<OsString, Node>,
+}
+
+impl Node {
+    fn merge(&mut self, other: Node) {
+        self.bytes += other.bytes;
+        self.files += other.files;
+        for (name, child) in other.children {
+            self.children.entry(name).or_default().merge(child);
+        }
+    }
+
+    /// Children largest first, ties by name.
+    fn sorted(&self) -> Vec<(&OsString, &Node)> {
+        let mut children: Vec<_> = self.children.iter().collect();
+        children.sort_by(|a, b| b.1.bytes.cmp(&a.1.bytes).then_with(|| a.0.cmp(b.0)));
+        children
+    }
+}
+
+/// Bytes and files per directory under each root, down to `depth` levels.
+#[derive(Debug, Clone)]
+pub struct SizeTree {
+    depth: usize,
+    roots: Vec<(PathBuf, Node)>,
+}
+
+impl SizeTree {
+    pub fn new(roots: &[PathBuf], depth: usize) -> Self {
+        Self {
+            depth,
+            roots: roots.iter().map(|root| (root.clone(), Node::default())).collect(),
+        }
+    }
+
+    /// Count a file of `size` bytes at `path` in every directory above it,
+    /// under the deepest root containing it; files outside every root are
+    /// not counted.
+    pub fn add(&mut self, path: &Path, size: u64) {
+        let Some((root, node)) = self
+            .roots
+            .iter_mut()
+            .filter(|(root, _)| path.starts_with(root))
+            .max_by_key(|(root, _)| root.components().count())
+        else {
+            return;
+        };
+        let dirs = path.strip_prefix(&*root).ok().and_then(Path::parent).unwrap_or(Path::new(""));
+        let mut node = node;
+        node.bytes += size;
+        node.files += 1;
+        for name in dirs.iter().take(self.depth) {
+            node = node.children.entry(name.to_os_string()).or_default();
+            node.bytes += size;
+            node.files += 1;
+        }
+    }
+
+    /// Fold in `other`, a tree over the same roots fed other files.
+    pub fn merge(&mut self, other: SizeTree) {
+        for ((_, mine), (_, theirs)) in self.roots.iter_mut().zip(other.roots) {
+            mine.merge(theirs);
+        }
+    }
+
+    /// The tree as lines: size, share of the parent directory, then the
+    /// name indented under its parent.
+    pub fn render(&self, sizes: SizeFormat) -> Vec<String> {
+        let mut lines = Vec::new();
+        for (root, node) in &self.roots {
+            lines.push(format!(
+                "{:>10} {:>6}  {} ({} files)",
+                human_bytes(node.bytes as u128, sizes),
+                "",
+                root.display(),
+                node.files
+            ));
+            render_children(node, "", sizes, &mut lines);
+        }
+        lines
+    }
+}
+
+fn render_children(parent: &Node, indent: &str, sizes: SizeFormat, lines: &mut Vec<String>) {
+    let children = parent.sorted();
+    let last = children.len().saturating_sub(1);
+    for (i, (name, node)) in children.into_iter().enumerate() {
+        let share = if parent.bytes > 0 {
+            node.bytes as f64 * 100.0 / parent.bytes as f64
+        } else {
+            0.0
+        };
+        let (branch, next) = if i == last { ("└── ", "    ") } else { ("├── ", "│   ") };
+        lines.push(format!(
+            "{:>10} {:>5.1}%  {}{}{}",
+            human_bytes(node.bytes as u128, sizes),
+            share,
+            indent,
+            branch,
+            Path::new(name).display()
+        ));
+        render_children(node, &format!("{}{}", indent, next), sizes, lines);
+    }
+}
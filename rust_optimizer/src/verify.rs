@@ -0,0 +1,102 @@
+//! Re-hashing files against expected digests, and the exit-code scheme CI
+//! can branch on.
+
+use crate::encoding::HashEncoding;
+use crate::process::{process_file, ProcessOptions};
+use rayon::prelude::*;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+/// Outcome of a run, ordered by severity; when several apply the highest wins.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verdict {
+    /// Everything verified (or, for a plain scan, every file was read)
+    #[default]
+    Ok,
+    /// A file's size or hash differs from what was expected
+    Mismatch,
+    /// An expected file does not exist
+    Missing,
+    /// A file exists but could not be read, or the run itself failed
+    IoError,
+    /// `--verify-url` could not fetch what to verify against
+    Network,
+}
+
+impl Verdict {
+    pub fn code(self) -> u8 {
+        match self {
+            Verdict::Ok => 0,
+            Verdict::Mismatch => 1,
+            Verdict::Missing => 2,
+            Verdict::IoError => 3,
+            Verdict::Network => 4,
+        }
+    }
+
+    pub fn exit_code(self) -> ExitCode {
+        ExitCode::from(self.code())
+    }
+
+    /// `Missing` when nothing is at `path` any more, else `IoError`.
+    pub fn for_unreadable(path: &Path) -> Verdict {
+        if path.symlink_metadata().is_err() {
+            Verdict::Missing
+        } else {
+            Verdict::IoError
+        }
+    }
+}
+
+/// What a file is expected to contain.
+#[derive(Debug, Clone)]
+pub struct Expected {
+    pub path: PathBuf,
+    /// Checked before hashing when known
+    pub size: Option<u64>,
+    pub hash_hex: String,
+}
+
+/// The verdict for one file, with the error when it could not be hashed.
+#[derive(Debug)]
+pub struct Checked {
+    pub verdict: Verdict,
+    pub error: Option<String>,
+}
+
+/// Hash every file in `expected` in parallel; results come back in the same order.
+pub fn verify_all(expected: &[Expected], opts: &ProcessOptions) -> Vec<Checked> {
+    expected.par_iter().map(|e| verify_one(e, opts)).collect()
+}
+
+fn verify_one(expected: &Expected, opts: &ProcessOptions) -> Checked {
+    // a size mismatch is conclusive without reading the file
+    if let (Some(size), Ok(meta)) = (expected.size, expected.path.metadata()) {
+        if meta.is_file() && meta.len() != size {
+            return Checked {
+                verdict: Verdict::Mismatch,
+                error: None,
+            };
+        }
+    }
+    // hash at whatever length the expected digest was recorded with
+    let opts = ProcessOptions {
+        digest_len: Some(expected.hash_hex.len() / 2),
+        encoding: HashEncoding::Hex,
+        ..*opts
+    };
+    match process_file(&expected.path, &opts) {
+        Ok(report) if report.hash_str.as_deref() == Some(expected.hash_hex.as_str()) => Checked {
+            verdict: Verdict::Ok,
+            error: None,
+        },
+        Ok(_) => Checked {
+            verdict: Verdict::Mismatch,
+            error: None,
+        },
+        Err(e) => Checked {
+            verdict: Verdict::for_unreadable(&expected.path),
+            error: Some(format!("{:#}", e)),
+        },
+    }
+}
@@ -0,0 +1,288 @@
+//! Collecting the files to scan from a cache directory.
+
+use anyhow::{Context, Result};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use rayon::prelude::*;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use walkdir::{DirEntry, WalkDir};
+
+/// Ignore file picked up automatically from the cache root.
+pub const IGNORE_FILE_NAME: &str = ".aivista-ignore";
+
+/// Suffixes left behind by interrupted or in-flight downloads.
+pub const DEFAULT_INCOMPLETE_SUFFIXES: &[&str] = &[".incomplete", ".tmp", ".part", ".lock"];
+
+/// Files modified more recently than this are assumed to still be written.
+pub const DEFAULT_SETTLE_SECS: u64 = 5;
+
+/// Recognises partially downloaded files by suffix or a very recent mtime.
+#[derive(Debug, Clone)]
+pub struct IncompleteFilter {
+    pub suffixes: Vec<String>,
+    pub settle: Duration,
+}
+
+impl Default for IncompleteFilter {
+    fn default() -> Self {
+        Self {
+            suffixes: DEFAULT_INCOMPLETE_SUFFIXES.iter().map(|s| s.to_string()).collect(),
+            settle: Duration::from_secs(DEFAULT_SETTLE_SECS),
+        }
+    }
+}
+
+impl IncompleteFilter {
+    fn matches(&self, entry: &DirEntry, now: SystemTime) -> bool {
+        let name = entry.file_name().to_string_lossy();
+        if self.suffixes.iter().any(|s| name.ends_with(s.as_str())) {
+            return true;
+        }
+        entry
+            .metadata()
+            .ok()
+            .and_then(|m| m.modified().ok())
+            .and_then(|mtime| now.duration_since(mtime).ok())
+            .is_some_and(|age| age < self.settle)
+    }
+}
+
+/// What the walk does with the symlinks it meets. Roots and listed files
+/// are always followed; this is only about links found inside a root.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SymlinkPolicy {
+    /// Hash the target's content under the link's path, and descend into
+    /// linked directories
+    Follow,
+    /// Leave symlinks out of the scan
+    #[default]
+    Skip,
+    /// List each symlink and where it points, without hashing the target
+    Record,
+}
+
+impl SymlinkPolicy {
+    /// Size of a walked path as the scan will report it: a recorded symlink
+    /// by its own length, anything else by its target's.
+    pub fn size_of(self, path: &Path) -> u64 {
+        let meta = match self {
+            SymlinkPolicy::Record => path.symlink_metadata(),
+            _ => path.metadata(),
+        };
+        meta.map(|m| m.len()).unwrap_or(0)
+    }
+}
+
+/// What to leave out of the walk.
+#[derive(Default)]
+pub struct WalkOptions {
+    pub ignore: Option<Gitignore>,
+    pub incomplete: Option<IncompleteFilter>,
+    /// Record every symlink met on the way, with its canonical target
+    pub resolve_symlinks: bool,
+    pub symlinks: SymlinkPolicy,
+}
+
+/// A symlink seen by the walk. `target` is `None` when it dangles.
+#[derive(Debug, Clone)]
+pub struct Symlink {
+    pub link: PathBuf,
+    pub target: Option<PathBuf>,
+}
+
+/// A path the walk could not enter or read, typically a directory without
+/// read permission; nothing below it is in the scan.
+#[derive(Debug, Clone, Serialize, JsonSchema, Deserialize)]
+pub struct WalkError {
+    pub path: PathBuf,
+    pub error: String,
+    /// OS error number, when there is one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub errno: Option<i32>,
+}
+
+impl WalkError {
+    fn new(root: &Path, err: &walkdir::Error) -> Self {
+        let io = err.io_error();
+        Self {
+            path: err.path().unwrap_or(root).to_path_buf(),
+            error: io.map_or_else(|| err.to_string(), |e| e.to_string()),
+            errno: io.and_then(|e| e.raw_os_error()),
+        }
+    }
+}
+
+/// Files selected by a walk plus counts of what was left out.
+#[derive(Default)]
+pub struct WalkOutcome {
+    pub files: Vec<PathBuf>,
+    pub skipped_incomplete: usize,
+    /// Directories and entries that could not be read, in path order
+    pub errors: Vec<WalkError>,
+    /// Symlinks found, when `resolve_symlinks` was set
+    pub symlinks: Vec<Symlink>,
+    /// Paths dropped by `dedup_canonical` as another path to a queued file
+    pub collapsed: usize,
+}
+
+impl WalkOutcome {
+    /// Fold another root's walk into this one. Files stay sorted, and a file
+    /// reached through two overlapping roots is kept once.
+    pub fn merge(&mut self, other: WalkOutcome) {
+        self.files.extend(other.files);
+        self.files.sort();
+        self.files.dedup();
+        self.skipped_incomplete += other.skipped_incomplete;
+        self.errors.extend(other.errors);
+        self.errors.sort_by(|a, b| a.path.cmp(&b.path));
+        self.symlinks.extend(other.symlinks);
+        self.symlinks.sort_by(|a, b| a.link.cmp(&b.link));
+        self.collapsed += other.collapsed;
+    }
+
+    /// Keep one path per canonical file, so a root given twice under
+    /// different spellings, or through a symlink into another root, is not
+    /// hashed twice. The first path in sorted order is kept as given, so
+    /// reports stay relative to their root. Paths that no longer resolve are
+    /// kept and left for the scan to report. Under `SymlinkPolicy::Record` a
+    /// link is an entry of its own, so it is not collapsed into its target.
+    pub fn dedup_canonical(&mut self, symlinks: SymlinkPolicy) {
+        let key = |p: &PathBuf| {
+            let is_link = p.symlink_metadata().is_ok_and(|m| m.file_type().is_symlink());
+            if symlinks == SymlinkPolicy::Record && is_link {
+                return Some(p.parent()?.canonicalize().ok()?.join(p.file_name()?));
+            }
+            p.canonicalize().ok()
+        };
+        let canonical: Vec<Option<PathBuf>> = self.files.par_iter().map(key).collect();
+        let mut seen = HashSet::with_capacity(self.files.len());
+        let before = self.files.len();
+        let mut canonical = canonical.into_iter();
+        self.files.retain(|_| match canonical.next().flatten() {
+            Some(c) => seen.insert(c),
+            None => true,
+        });
+        self.collapsed += before - self.files.len();
+    }
+}
+
+/// Build gitignore-style rules from `ignore_file`, or from `<root>/.aivista-ignore`
+/// when no file is given and one exists. Returns `None` when there are no rules.
+pub fn load_ignore(root: &Path, ignore_file: Option<&Path>) -> Result<Option<Gitignore>> {
+    let default_file = root.join(IGNORE_FILE_NAME);
+    let file = match ignore_file {
+        Some(f) => f,
+        None if default_file.is_file() => default_file.as_path(),
+        None => return Ok(None),
+    };
+    let mut builder = GitignoreBuilder::new(root);
+    if let Some(err) = builder.add(file) {
+        return Err(err).with_context(|| format!("reading ignore file {:?}", file));
+    }
+    let rules = builder
+        .build()
+        .with_context(|| format!("parsing ignore file {:?}", file))?;
+    Ok(Some(rules))
+}
+
+/// Read an explicit list of files from `source` (`-` for stdin): one path per
+/// line, or NDJSON objects with a `path` field. Blank lines are ignored.
+pub fn read_file_list(source: &Path) -> Result<Vec<PathBuf>> {
+    let text = if source == Path::new("-") {
+        std::io::read_to_string(std::io::stdin().lock()).context("reading file list from stdin")?
+    } else {
+        std::fs::read_to_string(source).with_context(|| format!("reading file list {:?}", source))?
+    };
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            let line = line.trim_end_matches('\r');
+            if !line.trim_start().starts_with('{') {
+                return Ok(PathBuf::from(line));
+            }
+            #[derive(serde::Deserialize)]
+            struct Entry {
+                path: PathBuf,
+            }
+            let entry: Entry = serde_json::from_str(line)
+                .with_context(|| format!("{:?} line {}: expected {{\"path\": ...}}", source, i + 1))?;
+            Ok(entry.path)
+        })
+        .collect()
+}
+
+/// Keep roughly `fraction` of `files`, chosen by a hash of each path keyed
+/// with `seed`: the same seed picks the same files on every run, whatever
+/// order they were listed in.
+pub fn sample_files(files: Vec<PathBuf>, fraction: f64, seed: u64) -> Vec<PathBuf> {
+    if fraction >= 1.0 {
+        return files;
+    }
+    let threshold = (fraction * u64::MAX as f64) as u64;
+    files
+        .into_iter()
+        .filter(|p| {
+            let mut hasher = blake3::Hasher::new_derive_key("aivista-cache-scan 2026-10-16 file sample v1");
+            hasher.update(&seed.to_le_bytes());
+            hasher.update(p.as_os_str().as_encoded_bytes());
+            let head: [u8; 8] = hasher.finalize().as_bytes()[..8].try_into().unwrap();
+            u64::from_le_bytes(head) < threshold
+        })
+        .collect()
+}
+
+/// Walk `root` and return every regular file, in sorted order. Ignored
+/// directories are pruned without descending, as git does, so a `!pattern`
+/// cannot re-include a file below an excluded directory.
+pub fn collect_files(root: &Path, opts: &WalkOptions) -> WalkOutcome {
+    let now = SystemTime::now();
+    let mut skipped_incomplete = 0;
+    let mut symlinks = Vec::new();
+    let mut errors = Vec::new();
+    let mut files: Vec<PathBuf> = WalkDir::new(root)
+        .follow_links(opts.symlinks == SymlinkPolicy::Follow)
+        .into_iter()
+        .filter_entry(|e| {
+            // never drop the root itself, whatever the patterns say
+            e.depth() == 0
+                || !opts
+                    .ignore
+                    .as_ref()
+                    .is_some_and(|gi| gi.matched(e.path(), e.file_type().is_dir()).is_ignore())
+        })
+        .filter_map(|e| e.map_err(|err| errors.push(WalkError::new(root, &err))).ok())
+        .filter(|e| {
+            if opts.resolve_symlinks && e.path_is_symlink() {
+                symlinks.push(Symlink {
+                    link: e.path().to_path_buf(),
+                    target: e.path().canonicalize().ok(),
+                });
+            }
+            // without following, a link's file type is the link's own
+            e.file_type().is_file()
+                || (opts.symlinks == SymlinkPolicy::Record && e.depth() > 0 && e.path_is_symlink())
+        })
+        .filter(|e| match &opts.incomplete {
+            Some(filter) if filter.matches(e, now) => {
+                skipped_incomplete += 1;
+                false
+            }
+            _ => true,
+        })
+        .map(|e| e.into_path())
+        .collect();
+    files.sort(); // deterministic order
+    symlinks.sort_by(|a, b| a.link.cmp(&b.link));
+    errors.sort_by(|a, b| a.path.cmp(&b.path));
+    WalkOutcome {
+        files,
+        skipped_incomplete,
+        errors,
+        symlinks,
+        collapsed: 0,
+    }
+}
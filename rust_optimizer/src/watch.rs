@@ -0,0 +1,287 @@
+//! `--watch`: stay running after the scan and re-hash files as they change.
+//! Events are debounced per path, since a download writes in bursts of
+//! thousands of small writes; a file is re-hashed once it has been quiet for
+//! the debounce interval, and compared against an index of the last hashes.
+
+use crate::process::{process_file, ProcessOptions};
+use crate::report::{FileReport, FileStatus};
+use crate::scan::CancellationToken;
+use anyhow::{Context, Result};
+use ignore::gitignore::Gitignore;
+use notify::event::{CreateKind, EventKind, ModifyKind};
+use notify::{RecursiveMode, Watcher};
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+use walkdir::WalkDir;
+
+/// Quiet time before a changed file is re-hashed.
+pub const DEFAULT_DEBOUNCE_MS: u64 = 500;
+
+/// Longest wait between checks of the cancellation token.
+const POLL: Duration = Duration::from_millis(200);
+
+/// What a re-hash found, relative to the index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Added {
+        path: PathBuf,
+        size: u64,
+        hash: Option<String>,
+    },
+    /// The size or hash differs from the indexed one
+    Changed {
+        path: PathBuf,
+        old_size: u64,
+        size: u64,
+        old_hash: Option<String>,
+        hash: Option<String>,
+    },
+    Removed {
+        path: PathBuf,
+        size: u64,
+    },
+    /// The file is there but could not be read; its index entry is kept
+    Failed {
+        path: PathBuf,
+        error: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    size: u64,
+    /// Hash, or `--sample-hash` signature; `None` when not hashed
+    hash: Option<String>,
+}
+
+impl Entry {
+    fn of(r: &FileReport) -> Self {
+        Self {
+            size: r.size,
+            hash: r.hash_str.clone().or_else(|| r.signature.clone()),
+        }
+    }
+}
+
+/// The last known size and hash of every watched file.
+#[derive(Debug, Default)]
+pub struct WatchIndex {
+    files: BTreeMap<PathBuf, Entry>,
+}
+
+impl WatchIndex {
+    /// Seed from a finished scan. Archive members and files that were gone
+    /// by the time they were opened are left out.
+    pub fn from_reports(reports: &[FileReport]) -> Self {
+        let files = reports
+            .iter()
+            .filter(|r| r.member.is_none() && r.status != FileStatus::Vanished)
+            .map(|r| (r.path.clone(), Entry::of(r)))
+            .collect();
+        Self { files }
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Re-hash `path` and update the index; `None` when nothing changed.
+    pub fn refresh_file(&mut self, path: &Path, opts: &ProcessOptions) -> Option<Change> {
+        let report = match process_file(path, opts) {
+            Ok(report) => report,
+            Err(e) if gone(&e) => return self.remove(path),
+            Err(e) => {
+                return Some(Change::Failed {
+                    path: path.to_path_buf(),
+                    error: format!("{:#}", e),
+                })
+            }
+        };
+        let entry = Entry::of(&report);
+        match self.files.insert(path.to_path_buf(), entry.clone()) {
+            None => Some(Change::Added {
+                path: path.to_path_buf(),
+                size: entry.size,
+                hash: entry.hash,
+            }),
+            Some(old) if old != entry => Some(Change::Changed {
+                path: path.to_path_buf(),
+                old_size: old.size,
+                size: entry.size,
+                old_hash: old.hash,
+                hash: entry.hash,
+            }),
+            Some(_) => None,
+        }
+    }
+
+    fn remove(&mut self, path: &Path) -> Option<Change> {
+        let old = self.files.remove(path)?;
+        Some(Change::Removed {
+            path: path.to_path_buf(),
+            size: old.size,
+        })
+    }
+
+    /// Drop `path` and, when it was a directory, everything under it.
+    pub fn remove_under(&mut self, path: &Path) -> Vec<Change> {
+        let under: Vec<PathBuf> = self
+            .files
+            .range(path.to_path_buf()..)
+            .map(|(p, _)| p)
+            .take_while(|p| p.starts_with(path))
+            .cloned()
+            .collect();
+        under.iter().filter_map(|p| self.remove(p)).collect()
+    }
+}
+
+fn gone(e: &anyhow::Error) -> bool {
+    e.chain()
+        .filter_map(|cause| cause.downcast_ref::<io::Error>())
+        .any(|io| io.kind() == io::ErrorKind::NotFound)
+}
+
+/// Collects event paths and hands each back once no event has touched it
+/// for `quiet`.
+#[derive(Debug)]
+pub struct Debouncer {
+    quiet: Duration,
+    pending: HashMap<PathBuf, Instant>,
+}
+
+impl Debouncer {
+    pub fn new(quiet: Duration) -> Self {
+        Self {
+            quiet,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Record an event on `path` at `now`, restarting its quiet period.
+    pub fn touch(&mut self, path: PathBuf, now: Instant) {
+        self.pending.insert(path, now);
+    }
+
+    /// Paths quiet since `now - quiet`, sorted; they are forgotten.
+    pub fn due(&mut self, now: Instant) -> Vec<PathBuf> {
+        let mut due: Vec<PathBuf> = self
+            .pending
+            .iter()
+            .filter(|(_, &last)| now.duration_since(last) >= self.quiet)
+            .map(|(p, _)| p.clone())
+            .collect();
+        due.iter().for_each(|p| {
+            self.pending.remove(p);
+        });
+        due.sort();
+        due
+    }
+
+    /// How long until the next path is due, if any is pending.
+    pub fn next_due(&self, now: Instant) -> Option<Duration> {
+        self.pending
+            .values()
+            .map(|&last| (last + self.quiet).saturating_duration_since(now))
+            .min()
+    }
+}
+
+/// What the watch leaves alone, as the walk did.
+#[derive(Default)]
+pub struct WatchOptions {
+    pub debounce: Duration,
+    /// Ignore rules of each root that has them
+    pub ignores: Vec<(PathBuf, Gitignore)>,
+    /// Partial-download suffixes; the finished file shows up under its own
+    /// name once the download renames it
+    pub incomplete_suffixes: Vec<String>,
+}
+
+impl WatchOptions {
+    fn skips(&self, path: &Path, is_dir: bool) -> bool {
+        let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+        if !is_dir && self.incomplete_suffixes.iter().any(|s| name.ends_with(s.as_str())) {
+            return true;
+        }
+        self.ignores.iter().any(|(root, rules)| {
+            path.starts_with(root)
+                && rules.matched_path_or_any_parents(path, is_dir).is_ignore()
+        })
+    }
+}
+
+/// Watch `roots` until `cancel` is set, re-hashing changed files with
+/// `process` and passing every difference from `index` to `on_change`.
+pub fn watch(
+    roots: &[PathBuf],
+    opts: &WatchOptions,
+    process: &ProcessOptions,
+    index: &mut WatchIndex,
+    cancel: &CancellationToken,
+    mut on_change: impl FnMut(&Change),
+) -> Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).context("starting the file watcher")?;
+    for root in roots {
+        watcher
+            .watch(root, RecursiveMode::Recursive)
+            .with_context(|| format!("watching {:?}", root))?;
+    }
+    let mut pending = Debouncer::new(opts.debounce);
+    while !cancel.is_cancelled() {
+        let wait = pending.next_due(Instant::now()).map_or(POLL, |d| d.min(POLL));
+        match rx.recv_timeout(wait) {
+            Ok(event) => {
+                let event = event.context("watching the cache")?;
+                let now = Instant::now();
+                if event.need_rescan() {
+                    // the kernel dropped events; walk everything again
+                    roots.iter().for_each(|r| pending.touch(r.clone(), now));
+                }
+                // a directory is walked only when it appears; other events on
+                // one (attributes, its own mtime) say nothing about its files
+                let new_dir = matches!(
+                    event.kind,
+                    EventKind::Create(CreateKind::Folder) | EventKind::Modify(ModifyKind::Name(_))
+                );
+                // reads and opens say nothing about contents
+                let relevant = !matches!(event.kind, EventKind::Access(_) | EventKind::Other);
+                for path in event.paths.into_iter().filter(|_| relevant) {
+                    let is_dir = path.is_dir();
+                    if (!is_dir || new_dir) && !opts.skips(&path, is_dir) {
+                        pending.touch(path, now);
+                    }
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => anyhow::bail!("the file watcher stopped"),
+        }
+        for path in pending.due(Instant::now()) {
+            if path.is_dir() {
+                let files = WalkDir::new(&path)
+                    .into_iter()
+                    .filter_entry(|e| {
+                        e.depth() == 0 || !opts.skips(e.path(), e.file_type().is_dir())
+                    })
+                    .filter_map(|e| e.ok())
+                    .filter(|e| e.file_type().is_file());
+                for file in files {
+                    index.refresh_file(file.path(), process).iter().for_each(&mut on_change);
+                }
+            } else if path.exists() {
+                index.refresh_file(&path, process).iter().for_each(&mut on_change);
+            } else {
+                index.remove_under(&path).iter().for_each(&mut on_change);
+            }
+        }
+    }
+    Ok(())
+}
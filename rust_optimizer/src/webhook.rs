@@ -0,0 +1,103 @@
+//! POSTing the finished report to an HTTP endpoint.
+
+use crate::sink::{ReportSink, ScanSummary};
+use anyhow::{Context, Result};
+use reqwest::blocking::Client;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::time::Duration;
+
+/// Per-attempt limit covering connect, upload and response.
+pub const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Tries in total; server errors (5xx) and transport failures are retried.
+pub const ATTEMPTS: u32 = 3;
+
+/// Wait before the first retry, doubled before each later one.
+const BACKOFF: Duration = Duration::from_secs(1);
+
+/// Sends the `--output` JSON document once the scan is done.
+pub struct WebhookSink {
+    client: Client,
+    url: String,
+    headers: HeaderMap,
+    required: bool,
+    backoff: Duration,
+}
+
+/// Parse `Name: value` strings, as given to `--webhook-header` and `--header`.
+pub fn header_map(headers: &[String]) -> Result<HeaderMap> {
+    let mut map = HeaderMap::new();
+    for h in headers {
+        let (name, value) =
+            h.split_once(':').with_context(|| format!("header {:?} is not `Name: value`", h))?;
+        let name = HeaderName::from_bytes(name.trim().as_bytes())
+            .with_context(|| format!("invalid header name in {:?}", h))?;
+        let value = HeaderValue::from_str(value.trim())
+            .with_context(|| format!("invalid header value in {:?}", h))?;
+        map.append(name, value);
+    }
+    Ok(map)
+}
+
+impl WebhookSink {
+    /// `headers` are `Name: value` strings. Unless `required`, a failed
+    /// delivery is logged and the scan still succeeds.
+    pub fn new(url: &str, headers: &[String], required: bool) -> Result<Self> {
+        let map = header_map(headers).context("invalid --webhook-header")?;
+        let client = Client::builder()
+            .timeout(WEBHOOK_TIMEOUT)
+            .build()
+            .context("building HTTP client")?;
+        Ok(Self {
+            client,
+            url: url.to_string(),
+            headers: map,
+            required,
+            backoff: BACKOFF,
+        })
+    }
+
+    /// Wait `backoff` before the first retry instead of a second.
+    pub fn with_backoff(self, backoff: Duration) -> Self {
+        Self { backoff, ..self }
+    }
+
+    fn post(&self, summary: &ScanSummary) -> Result<()> {
+        let mut attempt = 1;
+        loop {
+            let sent = self
+                .client
+                .post(&self.url)
+                .headers(self.headers.clone())
+                .json(&summary.report.document())
+                .send();
+            let err = match sent {
+                Ok(resp) if resp.status().is_success() => return Ok(()),
+                Ok(resp) if resp.status().is_server_error() => {
+                    anyhow::anyhow!("{} answered {}", self.url, resp.status())
+                }
+                Ok(resp) => anyhow::bail!("{} answered {}", self.url, resp.status()),
+                Err(e) => anyhow::Error::new(e).context(format!("posting to {}", self.url)),
+            };
+            if attempt == ATTEMPTS {
+                return Err(err.context(format!("giving up after {} attempts", ATTEMPTS)));
+            }
+            eprintln!("[WARN] Webhook attempt {} failed: {:#}; retrying", attempt, err);
+            std::thread::sleep(self.backoff * (1 << (attempt - 1)));
+            attempt += 1;
+        }
+    }
+}
+
+impl ReportSink for WebhookSink {
+    fn finish(&mut self, summary: &ScanSummary) -> Result<()> {
+        match self.post(summary) {
+            Ok(()) => Ok(()),
+            Err(e) if self.required => Err(e.context("--webhook-required delivery failed")),
+            Err(e) => {
+                eprintln!("[WARN] Webhook delivery failed: {:#}", e);
+                Ok(())
+            }
+        }
+    }
+}
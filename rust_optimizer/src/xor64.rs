@@ -0,0 +1,53 @@
+//! Host-side XOR64 checksum, shared by the CPU fallback and the GPU tests.
+
+/// Pack bytes into little-endian u64 words as uploaded to the GPU kernel.
+/// Every byte lands in exactly one word; a trailing partial word is zero-padded.
+pub fn pack_u64_le(bytes: &[u8]) -> Vec<u64> {
+    bytes.chunks(8).map(word_le).collect()
+}
+
+fn word_le(chunk: &[u8]) -> u64 {
+    let mut word = [0u8; 8];
+    word[..chunk.len()].copy_from_slice(chunk);
+    u64::from_le_bytes(word)
+}
+
+/// CPU reference for the GPU `xor_reduce` kernel: XOR of the input read as
+/// little-endian u64 words, with the final partial word zero-padded.
+pub fn xor64_cpu(bytes: &[u8]) -> u64 {
+    bytes.chunks(8).fold(0u64, |acc, chunk| acc ^ word_le(chunk))
+}
+
+/// Incremental [`xor64_cpu`] for data arriving in arbitrarily sized pieces.
+#[derive(Debug, Default, Clone)]
+pub struct Xor64Stream {
+    acc: u64,
+    pending: [u8; 8],
+    pending_len: usize,
+}
+
+impl Xor64Stream {
+    pub fn update(&mut self, mut bytes: &[u8]) {
+        // top up a word left partial by the previous piece first
+        if self.pending_len > 0 {
+            let take = (8 - self.pending_len).min(bytes.len());
+            self.pending[self.pending_len..self.pending_len + take].copy_from_slice(&bytes[..take]);
+            self.pending_len += take;
+            bytes = &bytes[take..];
+            if self.pending_len < 8 {
+                return;
+            }
+            self.acc ^= u64::from_le_bytes(self.pending);
+            self.pending_len = 0;
+        }
+        let words = bytes.chunks_exact(8);
+        let rest = words.remainder();
+        self.acc = words.fold(self.acc, |acc, w| acc ^ word_le(w));
+        self.pending[..rest.len()].copy_from_slice(rest);
+        self.pending_len = rest.len();
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.acc ^ word_le(&self.pending[..self.pending_len])
+    }
+}
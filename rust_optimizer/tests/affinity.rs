@@ -0,0 +1,10 @@
+//! Worker placement across NUMA nodes.
+
+use aivista_cache_scan::affinity::parse_cpulist;
+
+#[test]
+fn parses_kernel_cpu_lists() {
+    assert_eq!(parse_cpulist("0-3,8-9\n"), vec![0, 1, 2, 3, 8, 9]);
+    assert_eq!(parse_cpulist("5"), vec![5]);
+    assert!(parse_cpulist("").is_empty());
+}
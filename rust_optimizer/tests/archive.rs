@@ -0,0 +1,93 @@
+//! Zip members hashed in place, stored or deflated, without extraction.
+
+use aivista_cache_scan::archive::{hash_members, ArchiveKind};
+use aivista_cache_scan::process::ProcessOptions;
+use aivista_cache_scan::report::FileStatus;
+use flate2::write::DeflateEncoder;
+use std::io::Write;
+use std::path::Path;
+
+/// A zip of `(name, method, content)` members, written the way any zip
+/// tool would: local headers and data, then the central directory.
+fn zip(members: &[(&str, u16, &[u8])]) -> Vec<u8> {
+    let (mut out, mut central) = (Vec::new(), Vec::new());
+    for &(name, method, content) in members {
+        let data = match method {
+            8 => {
+                let mut enc = DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+                enc.write_all(content).unwrap();
+                enc.finish().unwrap()
+            }
+            _ => content.to_vec(),
+        };
+        let crc = crc32fast::hash(content);
+        let offset = out.len() as u32;
+        let mut fields = Vec::new();
+        fields.extend_from_slice(&method.to_le_bytes());
+        fields.extend_from_slice(&[0; 4]); // time, date
+        fields.extend_from_slice(&crc.to_le_bytes());
+        fields.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        fields.extend_from_slice(&(content.len() as u32).to_le_bytes());
+        fields.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        fields.extend_from_slice(&[0; 2]); // extra length
+
+        out.extend_from_slice(b"PK\x03\x04\x14\x00\x00\x00");
+        out.extend_from_slice(&fields);
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(&data);
+
+        central.extend_from_slice(b"PK\x01\x02\x14\x00\x14\x00\x00\x00");
+        central.extend_from_slice(&fields);
+        central.extend_from_slice(&[0; 10]); // comment length, disk, attributes
+        central.extend_from_slice(&offset.to_le_bytes());
+        central.extend_from_slice(name.as_bytes());
+    }
+    let directory = out.len() as u32;
+    out.extend_from_slice(&central);
+    out.extend_from_slice(b"PK\x05\x06\x00\x00\x00\x00");
+    out.extend_from_slice(&(members.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(members.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(central.len() as u32).to_le_bytes());
+    out.extend_from_slice(&directory.to_le_bytes());
+    out.extend_from_slice(&[0; 2]); // comment length
+    out
+}
+
+#[test]
+fn hashes_stored_and_deflated_members_and_flags_corrupt_ones() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("cache.zip");
+    let text = b"hello hello hello hello\n".repeat(1000);
+    let bytes = zip(&[
+        ("blobs/stored.bin", 0, b"raw bytes"),
+        ("blobs/", 0, b""),
+        ("text.txt", 8, &text),
+    ]);
+    std::fs::write(&path, &bytes).unwrap();
+    assert_eq!(ArchiveKind::detect(&path), Some(ArchiveKind::Zip));
+
+    let reports = hash_members(&path, ArchiveKind::Zip, &ProcessOptions::default());
+    let members: Vec<_> = reports.iter().map(|r| r.member.as_deref().unwrap()).collect();
+    assert_eq!(members, [Path::new("blobs/stored.bin"), Path::new("text.txt")]);
+    assert_eq!(reports[0].hash_str.as_deref(), Some(blake3::hash(b"raw bytes").to_hex().as_str()));
+    assert_eq!(reports[1].hash_str.as_deref(), Some(blake3::hash(&text).to_hex().as_str()));
+    assert_eq!(reports[1].size, text.len() as u64);
+    assert_eq!(reports[1].path, path.join("text.txt"));
+
+    // a flipped byte in the stored member fails its CRC; the other still hashes
+    let mut corrupt = bytes.clone();
+    let at = corrupt.windows(9).position(|w| w == b"raw bytes").unwrap();
+    corrupt[at] ^= 1;
+    std::fs::write(&path, &corrupt).unwrap();
+    let reports = hash_members(&path, ArchiveKind::Zip, &ProcessOptions::default());
+    assert_eq!(reports[0].status, FileStatus::Errored);
+    assert!(reports[0].error.as_deref().unwrap().contains("CRC-32"));
+    assert_eq!(reports[1].status, FileStatus::Hashed);
+
+    // without a central directory there is nothing to list
+    std::fs::write(&path, &bytes[..bytes.len() / 2]).unwrap();
+    let reports = hash_members(&path, ArchiveKind::Zip, &ProcessOptions::default());
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].member, None);
+    assert_eq!(reports[0].status, FileStatus::Errored);
+}
@@ -0,0 +1,77 @@
+//! `--max-mem` must cap the bytes mapped at once without stalling files
+//! bigger than the whole budget.
+
+use aivista_cache_scan::budget::MemoryBudget;
+use aivista_cache_scan::process::{process_file, ProcessOptions, ReaderMode};
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+#[test]
+fn in_flight_bytes_never_exceed_the_budget() {
+    let budget = MemoryBudget::new(100);
+    let (in_flight, peak) = (AtomicU64::new(0), AtomicU64::new(0));
+    std::thread::scope(|s| {
+        for worker in 0..8u64 {
+            let (budget, in_flight, peak) = (&budget, &in_flight, &peak);
+            s.spawn(move || {
+                for i in 0..50u64 {
+                    let bytes = (worker * 17 + i * 13) % 60 + 1;
+                    let _permit = budget.acquire(bytes);
+                    let now = in_flight.fetch_add(bytes, Ordering::SeqCst) + bytes;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    std::thread::sleep(Duration::from_micros(200));
+                    in_flight.fetch_sub(bytes, Ordering::SeqCst);
+                }
+            });
+        }
+    });
+    let peak = peak.into_inner();
+    assert!(peak <= 100, "{} bytes in flight", peak);
+    assert!(peak > 60, "workers never overlapped");
+}
+
+#[test]
+fn a_file_larger_than_the_budget_is_still_hashed() {
+    let data = vec![5u8; 64 << 10];
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    file.write_all(&data).unwrap();
+    file.flush().unwrap();
+
+    let budget = MemoryBudget::new(4096);
+    assert!(!budget.fits(data.len() as u64));
+    // a permit for everything else may be outstanding; the file is read instead
+    let _held = budget.acquire(4096);
+    let opts = ProcessOptions {
+        reader: ReaderMode::Mmap,
+        budget: Some(&budget),
+        ..Default::default()
+    };
+    let report = process_file(file.path(), &opts).expect("process_file");
+    assert_eq!(report.hash_str.unwrap(), blake3::hash(&data).to_hex().to_string());
+}
+
+#[test]
+fn a_file_truncated_while_waiting_on_the_budget_errors_instead_of_faulting() {
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    file.write_all(&[9u8; 8192]).unwrap();
+    file.flush().unwrap();
+
+    let budget = MemoryBudget::new(8192);
+    let held = budget.acquire(8192);
+    let opts = ProcessOptions {
+        reader: ReaderMode::Mmap,
+        budget: Some(&budget),
+        ..Default::default()
+    };
+    let result = std::thread::scope(|s| {
+        let worker = s.spawn(|| process_file(file.path(), &opts));
+        // the worker has sized the file and now waits for the held bytes
+        std::thread::sleep(Duration::from_millis(200));
+        file.as_file().set_len(100).unwrap();
+        drop(held);
+        worker.join().unwrap()
+    });
+    let err = result.expect_err("a shrunken file must not hash");
+    assert!(format!("{:#}", err).contains("shrank"), "{:#}", err);
+}
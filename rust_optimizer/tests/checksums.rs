@@ -0,0 +1,29 @@
+//! Checksum lines must stay interchangeable with b3sum/sha256sum.
+
+use aivista_cache_scan::checksums::{format_line, parse_line};
+use std::path::{Path, PathBuf};
+
+#[test]
+fn plain_and_escaped_names_round_trip() {
+    for name in ["models/a.bin", "dir with spaces/x", "back\\slash", "new\nline"] {
+        let line = format_line("abc123", Path::new(name));
+        assert_eq!(
+            parse_line(&line),
+            Some(("abc123".to_string(), PathBuf::from(name))),
+            "line {:?}",
+            line
+        );
+    }
+    assert_eq!(format_line("ff", Path::new("a\\b")), "\\ff  a\\\\b");
+}
+
+#[test]
+fn accepts_coreutils_variants() {
+    assert_eq!(
+        parse_line("ABCD *bin/file"),
+        Some(("abcd".to_string(), PathBuf::from("bin/file")))
+    );
+    assert_eq!(parse_line("abcd file"), None);
+    assert_eq!(parse_line("not-hex  file"), None);
+    assert_eq!(parse_line("abcd  "), None);
+}
@@ -0,0 +1,38 @@
+//! Work chunks must cover every file exactly once, in order, largest files first.
+
+use aivista_cache_scan::chunks::{plan, sort_largest_first, Chunking, MAX_CHUNK_FILES};
+
+fn covers(sizes: &[u64], chunks: &[std::ops::Range<usize>]) {
+    let flat: Vec<usize> = chunks.iter().flat_map(|r| r.clone()).collect();
+    assert_eq!(flat, (0..sizes.len()).collect::<Vec<_>>());
+}
+
+#[test]
+fn fixed_chunks_keep_the_requested_count() {
+    let sizes = vec![1; 300];
+    let chunks = plan(&sizes, Chunking::Fixed(128), 4);
+    covers(&sizes, &chunks);
+    assert_eq!(chunks.iter().map(|r| r.len()).collect::<Vec<_>>(), vec![128, 128, 44]);
+}
+
+#[test]
+fn size_aware_chunks_isolate_large_files() {
+    let mut sizes = vec![1024; 500];
+    for i in [10, 11, 12, 13] {
+        sizes[i] = 4 << 30;
+    }
+    let chunks = plan(&sizes, Chunking::SizeAware, 4);
+    covers(&sizes, &chunks);
+    for i in [10, 11, 12, 13] {
+        assert!(chunks.contains(&(i..i + 1)), "{:?}", chunks);
+    }
+    assert!(chunks.iter().all(|r| r.len() <= MAX_CHUNK_FILES));
+    assert!(plan(&[], Chunking::SizeAware, 4).is_empty());
+}
+
+#[test]
+fn largest_first_is_stable_for_equal_sizes() {
+    let mut items = vec![("a", 5), ("b", 9), ("c", 5), ("d", 1)];
+    sort_largest_first(&mut items);
+    assert_eq!(items, [("b", 9), ("a", 5), ("c", 5), ("d", 1)]);
+}
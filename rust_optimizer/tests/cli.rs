@@ -0,0 +1,33 @@
+//! Flag combinations the binary refuses before touching the cache.
+
+use std::process::Command;
+
+/// A cache holding two fresh copies of one file, scanned with `args`.
+fn run(args: &[&str]) -> (std::process::Output, tempfile::TempDir) {
+    let dir = tempfile::tempdir().unwrap();
+    for name in ["a.bin", "b.bin"] {
+        std::fs::write(dir.path().join(name), b"the same weights twice").unwrap();
+    }
+    let out = Command::new(env!("CARGO_BIN_EXE_aivista_cache_scan"))
+        .arg("--cache")
+        .arg(dir.path())
+        .args(["--dedup-action", "delete", "--confirm", "--settle-secs", "0"])
+        .args(args)
+        .output()
+        .unwrap();
+    (out, dir)
+}
+
+#[test]
+fn a_truncated_digest_cannot_drive_a_destructive_dedup() {
+    let (out, dir) = run(&["--hash-length", "8"]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("8-byte digest is too short"), "{}", stderr);
+    assert!(dir.path().join("a.bin").exists() && dir.path().join("b.bin").exists());
+
+    let (out, dir) = run(&["--hash-length", "8", "--paranoid"]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let left = ["a.bin", "b.bin"].iter().filter(|n| dir.path().join(n).exists()).count();
+    assert_eq!(left, 1);
+}
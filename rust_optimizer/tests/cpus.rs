@@ -0,0 +1,34 @@
+//! Default worker count under cgroup CPU quotas, and `--jobs` parsing.
+
+use aivista_cache_scan::cpus::{parse_cfs, parse_cpu_max, quota_files, Jobs};
+use std::path::Path;
+
+#[test]
+fn parses_quotas() {
+    assert_eq!(parse_cpu_max("200000 100000\n"), Some(2.0));
+    assert_eq!(parse_cpu_max("150000 100000"), Some(1.5));
+    assert_eq!(parse_cpu_max("max 100000\n"), None);
+    assert_eq!(parse_cfs("50000\n", "100000\n"), Some(0.5));
+    assert_eq!(parse_cfs("-1", "100000"), None);
+}
+
+#[test]
+fn looks_in_own_cgroup_then_root() {
+    let mount = Path::new("/sys/fs/cgroup");
+    let v2 = quota_files("0::/kubepods/pod1\n", mount);
+    assert_eq!(v2, [mount.join("kubepods/pod1/cpu.max"), mount.join("cpu.max")]);
+    let v1 = quota_files("4:memory:/x\n2:cpu,cpuacct:/docker/abc\n", mount);
+    assert_eq!(v1[0], mount.join("cpu/docker/abc/cpu.cfs_quota_us"));
+    assert!(v1.contains(&mount.join("cpu,cpuacct/cpu.cfs_quota_us")));
+    assert!(v1.iter().all(|f| !f.starts_with(mount.join("memory"))));
+}
+
+#[test]
+fn parses_jobs() {
+    assert_eq!("auto".parse::<Jobs>().unwrap(), Jobs::Auto);
+    assert_eq!("6".parse::<Jobs>().unwrap(), Jobs::Count(6));
+    assert_eq!(Jobs::Count(6).resolve(), 6);
+    assert!("0".parse::<Jobs>().is_err());
+    assert!("many".parse::<Jobs>().is_err());
+    assert!(Jobs::Auto.resolve() >= 1);
+}
@@ -0,0 +1,45 @@
+//! `--decompress`: a compressed file hashes as its content, and a damaged
+//! stream fails that file alone.
+
+use aivista_cache_scan::decompress::{Codec, Decompress};
+use aivista_cache_scan::process::{process_file, ProcessOptions};
+use std::io::Write;
+
+#[test]
+fn compressed_copies_hash_like_the_original() {
+    let dir = tempfile::tempdir().unwrap();
+    let content: Vec<u8> = (0..200_000u32).map(|i| (i * 31 % 251) as u8).collect();
+    let plain = dir.path().join("w.bin");
+    std::fs::write(&plain, &content).unwrap();
+    let gz = dir.path().join("w.bin.gz");
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+    encoder.write_all(&content).unwrap();
+    std::fs::write(&gz, encoder.finish().unwrap()).unwrap();
+    let zst = dir.path().join("w.bin.zst");
+    std::fs::write(&zst, zstd::bulk::compress(&content, 3).unwrap()).unwrap();
+
+    let auto = ProcessOptions {
+        decompress: Decompress::Auto,
+        ..Default::default()
+    };
+    let expected = process_file(&plain, &ProcessOptions::default()).unwrap();
+    assert_eq!(process_file(&plain, &auto).unwrap().decompressed, None);
+    for (path, codec) in [(&gz, Codec::Gzip), (&zst, Codec::Zstd)] {
+        let report = process_file(path, &auto).unwrap();
+        assert_eq!(report.hash_str, expected.hash_str);
+        assert_eq!(report.decompressed, Some(codec));
+        assert_eq!(report.logical_size, Some(content.len() as u64));
+        assert_eq!(report.size, std::fs::metadata(path).unwrap().len());
+    }
+
+    // forcing a codec decodes whatever the file is, so a plain file fails
+    let gzip = ProcessOptions {
+        decompress: Decompress::Gzip,
+        ..Default::default()
+    };
+    assert!(process_file(&plain, &gzip).is_err());
+    let truncated = dir.path().join("cut.gz");
+    std::fs::write(&truncated, &std::fs::read(&gz).unwrap()[..500]).unwrap();
+    let err = process_file(&truncated, &auto).unwrap_err();
+    assert!(format!("{:#}", err).contains("decompressing gzip stream"), "{:#}", err);
+}
@@ -0,0 +1,95 @@
+//! `--dedup-action` must leave one intact copy, link or remove the rest,
+//! and touch nothing without `--confirm`.
+#![cfg(unix)]
+
+use aivista_cache_scan::dedup::{apply, DedupAction, DedupStatus};
+use aivista_cache_scan::dupes::DuplicateGroup;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+const CONTENT: &[u8] = b"the same weights, three times over";
+
+/// A kept file and two copies of it in a fresh directory.
+fn copies() -> (tempfile::TempDir, DuplicateGroup) {
+    let dir = tempfile::tempdir().unwrap();
+    let paths: Vec<PathBuf> = ["kept.bin", "b.bin", "c.bin"].map(|n| dir.path().join(n)).into();
+    for p in &paths {
+        fs::write(p, CONTENT).unwrap();
+    }
+    let group = DuplicateGroup {
+        hash_str: blake3::hash(CONTENT).to_hex().to_string(),
+        size: CONTENT.len() as u64,
+        paths,
+    };
+    (dir, group)
+}
+
+fn names(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn each_action_keeps_one_copy_and_leaves_no_temporaries() {
+    for action in [DedupAction::Hardlink, DedupAction::Symlink, DedupAction::Delete] {
+        let (dir, group) = copies();
+        let outcomes = apply(std::slice::from_ref(&group), action, true, true);
+        assert_eq!(outcomes.len(), 2);
+        assert!(outcomes.iter().all(|o| matches!(o.result, Ok(DedupStatus::Applied))));
+
+        let kept = &group.paths[0];
+        assert_eq!(fs::read(kept).unwrap(), CONTENT, "{:?}", action);
+        for copy in &group.paths[1..] {
+            match action {
+                DedupAction::Hardlink => {
+                    let (a, b) = (fs::metadata(kept).unwrap(), fs::metadata(copy).unwrap());
+                    assert_eq!((a.dev(), a.ino()), (b.dev(), b.ino()));
+                }
+                DedupAction::Symlink => {
+                    let target = fs::read_link(copy).unwrap();
+                    assert_eq!(target, kept.canonicalize().unwrap());
+                    assert_eq!(fs::read(copy).unwrap(), CONTENT);
+                }
+                _ => assert!(!copy.exists()),
+            }
+        }
+        let expected = match action {
+            DedupAction::Delete => vec!["kept.bin"],
+            _ => vec!["b.bin", "c.bin", "kept.bin"],
+        };
+        assert_eq!(names(dir.path()), expected, "{:?}", action);
+    }
+}
+
+#[test]
+fn without_confirm_nothing_changes() {
+    let (dir, group) = copies();
+    let inodes = |g: &DuplicateGroup| -> Vec<u64> {
+        g.paths.iter().map(|p| fs::symlink_metadata(p).unwrap().ino()).collect()
+    };
+    let before = inodes(&group);
+    for action in [DedupAction::Hardlink, DedupAction::Symlink, DedupAction::Delete] {
+        let outcomes = apply(std::slice::from_ref(&group), action, false, false);
+        assert!(outcomes.iter().all(|o| matches!(o.result, Ok(DedupStatus::Planned))));
+    }
+    assert_eq!(inodes(&group), before);
+    assert_eq!(names(dir.path()), ["b.bin", "c.bin", "kept.bin"]);
+}
+
+#[test]
+fn paranoid_refuses_a_copy_that_differs() {
+    let (dir, group) = copies();
+    let mut changed = CONTENT.to_vec();
+    changed[0] ^= 1;
+    fs::write(&group.paths[1], &changed).unwrap();
+    let outcomes = apply(std::slice::from_ref(&group), DedupAction::Delete, true, true);
+    assert!(outcomes[0].result.is_err());
+    assert!(matches!(outcomes[1].result, Ok(DedupStatus::Applied)));
+    assert_eq!(fs::read(&group.paths[1]).unwrap(), changed);
+    assert_eq!(names(dir.path()), ["b.bin", "kept.bin"]);
+}
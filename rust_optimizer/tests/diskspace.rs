@@ -0,0 +1,40 @@
+//! `--min-free-bytes`: free space read from `statvfs` and the refusal below
+//! the threshold.
+#![cfg(unix)]
+
+use aivista_cache_scan::diskspace::DiskSpace;
+use aivista_cache_scan::report::SizeFormat;
+use std::path::Path;
+
+fn statvfs(frsize: u64, bsize: u64, blocks: u64, bavail: u64) -> libc::statvfs {
+    // SAFETY: statvfs is plain integers, for which all zeroes is valid
+    let mut st: libc::statvfs = unsafe { std::mem::zeroed() };
+    st.f_frsize = frsize as _;
+    st.f_bsize = bsize as _;
+    st.f_blocks = blocks as _;
+    st.f_bavail = bavail as _;
+    st
+}
+
+#[test]
+fn space_is_counted_in_fragments() {
+    let space = DiskSpace::from_statvfs(&statvfs(4096, 1 << 20, 1000, 10));
+    assert_eq!((space.total, space.available), (4096 * 1000, 4096 * 10));
+    // a zero fragment size falls back to the block size
+    let space = DiskSpace::from_statvfs(&statvfs(0, 512, 8, 2));
+    assert_eq!((space.total, space.available), (4096, 1024));
+}
+
+#[test]
+fn below_the_minimum_is_refused() {
+    let space = DiskSpace::from_statvfs(&statvfs(4096, 4096, 1000, 10));
+    let path = Path::new("/cache");
+    let sizes = SizeFormat::default();
+    assert!(space.require(path, 40960, sizes).is_ok());
+    assert!(space.require(path, 0, sizes).is_ok());
+    let err = space.require(path, 40961, sizes).unwrap_err().to_string();
+    assert_eq!(
+        err,
+        "only 40 KiB free on the filesystem holding \"/cache\", below the 40.00 KiB minimum"
+    );
+}
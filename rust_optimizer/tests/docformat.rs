@@ -0,0 +1,68 @@
+//! The report document written as YAML and TOML reads back as the same
+//! values, structs in field order. (`json!` objects sort their keys.)
+
+use aivista_cache_scan::docformat::DocFormat;
+use serde_json::{json, Value};
+
+#[derive(serde::Serialize)]
+struct Doc {
+    version: u32,
+    summary: Value,
+    files: Vec<Value>,
+}
+
+fn doc() -> Doc {
+    Doc {
+        version: 2,
+        summary: json!({ "bytes": 1536, "hit_ratio": 1.0, "skipped": null }),
+        files: vec![
+            json!({ "path": "/c/a \"b\".bin", "size": 1024, "hash": null }),
+            json!({ "path": "/c/z", "size": 512, "tags": ["x", "y"], "pt": { "ok": true } }),
+            // keys and strings another type would claim if written bare
+            json!({
+                "null": "null", "true": "true", "1.0": "1.0", "~": "~", "": "",
+                "path": "del\u{7f} tab\t nl\n bell\u{7} #: - ", "[x]": "'quoted'"
+            }),
+        ],
+    }
+}
+
+/// `value` with every null map value left out, as TOML writes it.
+fn without_nulls(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(k, v)| (k, without_nulls(v)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(without_nulls).collect()),
+        other => other,
+    }
+}
+
+#[test]
+fn yaml_reads_back_the_same_document() {
+    let yaml = DocFormat::Yaml.render(&doc()).unwrap();
+    let parsed: Value = serde_yaml::from_str(&yaml).unwrap();
+    assert_eq!(parsed, serde_json::to_value(doc()).unwrap());
+    assert!(yaml.starts_with("version: 2\nsummary:\n"), "{}", yaml);
+    assert!(yaml.ends_with('\n'));
+}
+
+#[test]
+fn toml_reads_back_the_same_document_without_nulls() {
+    let toml = DocFormat::Toml.render(&doc()).unwrap();
+    let parsed: Value = toml::from_str(&toml).unwrap();
+    assert_eq!(parsed, without_nulls(serde_json::to_value(doc()).unwrap()));
+    assert!(toml.starts_with("version = 2\n"), "{}", toml);
+    assert!(toml.find("[summary]") < toml.find("[[files]]"));
+    assert!(!toml.contains("skipped"));
+}
+
+#[test]
+fn toml_rejects_what_it_cannot_hold() {
+    assert!(DocFormat::Toml.render(&json!({ "a": [1, null] })).is_err());
+    assert!(DocFormat::Toml.render(&json!({ "big": u64::MAX })).is_err());
+    assert!(DocFormat::Toml.render(&json!([1, 2])).is_err());
+}
@@ -0,0 +1,55 @@
+//! `--hash-encoding` must round-trip every digest length and agree with the
+//! RFC 4648 test vectors (lowercase, unpadded).
+
+use aivista_cache_scan::encoding::HashEncoding;
+
+const ALL: [HashEncoding; 3] = [HashEncoding::Hex, HashEncoding::Base32, HashEncoding::Base64url];
+
+#[test]
+fn round_trips_every_length() {
+    for len in 0..=64 {
+        let bytes: Vec<u8> = (0..len as u8).map(|i| i.wrapping_mul(37) ^ 0xa5).collect();
+        for encoding in ALL {
+            let text = encoding.encode(&bytes);
+            assert_eq!(encoding.decode(&text), Some(bytes.clone()), "{:?} {}", encoding, len);
+            let hex = HashEncoding::Hex.encode(&bytes);
+            assert_eq!(encoding.to_hex(&encoding.from_hex(&hex)), hex);
+        }
+    }
+}
+
+#[test]
+fn matches_rfc_4648_vectors() {
+    let vectors = [
+        ("", "", ""),
+        ("f", "my", "Zg"),
+        ("fo", "mzxq", "Zm8"),
+        ("foo", "mzxw6", "Zm9v"),
+        ("foob", "mzxw6yq", "Zm9vYg"),
+        ("fooba", "mzxw6ytb", "Zm9vYmE"),
+        ("foobar", "mzxw6ytboi", "Zm9vYmFy"),
+    ];
+    for (plain, base32, base64url) in vectors {
+        assert_eq!(HashEncoding::Base32.encode(plain.as_bytes()), base32);
+        assert_eq!(HashEncoding::Base64url.encode(plain.as_bytes()), base64url);
+    }
+    // the URL-safe alphabet, and upper-case base32 as other tools print it
+    assert_eq!(HashEncoding::Base64url.encode(&[0xfb, 0xff]), "-_8");
+    assert_eq!(HashEncoding::Base32.decode("MZXW6YTBOI"), Some(b"foobar".to_vec()));
+    assert_eq!(HashEncoding::Base64url.encode(&[0u8; 32]).len(), 43);
+    assert_eq!(HashEncoding::Base32.encode(&[0u8; 32]).len(), 52);
+}
+
+#[test]
+fn rejects_malformed_text() {
+    assert_eq!(HashEncoding::Hex.decode("abc"), None);
+    assert_eq!(HashEncoding::Hex.decode("zz"), None);
+    // no byte string encodes to 1 or 3 base32 characters, or 1 base64 one
+    assert_eq!(HashEncoding::Base32.decode("m"), None);
+    assert_eq!(HashEncoding::Base32.decode("mzx"), None);
+    assert_eq!(HashEncoding::Base64url.decode("Z"), None);
+    // non-zero padding bits, and characters from the other alphabets
+    assert_eq!(HashEncoding::Base32.decode("mz"), None);
+    assert_eq!(HashEncoding::Base64url.decode("Zm9+"), None);
+    assert_eq!(HashEncoding::Base32.decode("m1"), None);
+}
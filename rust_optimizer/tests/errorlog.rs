@@ -0,0 +1,43 @@
+//! `--error-log` lists only errored files, one parseable object per line,
+//! with the OS error number kept through `anyhow` context.
+
+use aivista_cache_scan::errorlog::{self, ErrorEntry, ErrorLog};
+use aivista_cache_scan::report::FileReport;
+use anyhow::Context;
+
+fn report(json: &str) -> FileReport {
+    serde_json::from_str(json).unwrap()
+}
+
+#[test]
+fn errno_survives_context() {
+    let err = std::fs::File::open("/definitely/not/here")
+        .context("opening")
+        .context("processing file")
+        .unwrap_err();
+    assert_eq!(errorlog::errno(&err), Some(libc::ENOENT));
+    assert_eq!(errorlog::errno(&anyhow::anyhow!("no io here")), None);
+}
+
+#[test]
+fn writes_one_line_per_errored_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("errors.ndjson");
+    let reports = [
+        report(r#"{"path":"/c/ok","size":1,"elapsed_ms":0}"#),
+        report(
+            r#"{"path":"/c/bad","size":0,"elapsed_ms":0,"status":"errored",
+                "error":"Permission denied","errno":13}"#,
+        ),
+        report(r#"{"path":"/c/gone","size":0,"elapsed_ms":0,"status":"vanished"}"#),
+    ];
+    let count = ErrorLog::create(&path).unwrap().write(&reports).unwrap();
+    assert_eq!(count, 1);
+    let text = std::fs::read_to_string(&path).unwrap();
+    let entries: Vec<ErrorEntry> =
+        text.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].path.to_str(), Some("/c/bad"));
+    assert_eq!(entries[0].errno, Some(13));
+    assert_eq!(entries[0].error, "Permission denied");
+}
@@ -0,0 +1,20 @@
+//! `--exec` substitutes whole arguments and never goes through a shell.
+
+use aivista_cache_scan::exec::ExecTemplate;
+use std::ffi::OsStr;
+use std::path::Path;
+
+#[test]
+fn placeholders_fill_arguments_without_resplitting() {
+    let template = ExecTemplate::parse("upload --to 'my bucket' {path} id={hash} {size}B {x}")
+        .unwrap();
+    let cmd = template.command(Path::new("/c/a b; rm -rf $(x).bin"), "ab12", 42);
+    assert_eq!(cmd.get_program(), "upload");
+    let args: Vec<&OsStr> = cmd.get_args().collect();
+    assert_eq!(
+        args,
+        ["--to", "my bucket", "/c/a b; rm -rf $(x).bin", "id=ab12", "42B", "{x}"]
+    );
+    assert!(ExecTemplate::parse("").is_err());
+    assert!(ExecTemplate::parse("echo 'unterminated").is_err());
+}
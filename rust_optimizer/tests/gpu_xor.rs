@@ -0,0 +1,79 @@
+//! Checks the OpenCL `xor_reduce` kernel against the CPU reference reduction.
+//! Run with `cargo test --features gpu`; skips when no OpenCL device exists.
+#![cfg(feature = "gpu")]
+
+use aivista_cache_scan::gpu::GpuContext;
+use aivista_cache_scan::xor64::xor64_cpu;
+use rayon::prelude::*;
+
+/// Deterministic xorshift64* byte stream so failures are reproducible.
+fn pseudo_random_bytes(len: usize, mut seed: u64) -> Vec<u8> {
+    let mut out = Vec::with_capacity(len);
+    while out.len() < len {
+        seed ^= seed >> 12;
+        seed ^= seed << 25;
+        seed ^= seed >> 27;
+        let word = seed.wrapping_mul(0x2545_f491_4f6c_dd1d).to_le_bytes();
+        let take = (len - out.len()).min(8);
+        out.extend_from_slice(&word[..take]);
+    }
+    out
+}
+
+#[test]
+fn gpu_xor_matches_cpu_reference() {
+    let ctx = match GpuContext::try_new() {
+        Ok(ctx) => ctx,
+        Err(e) => {
+            eprintln!("skipping GPU XOR test: no usable OpenCL device ({:#})", e);
+            return;
+        }
+    };
+
+    // empty, sub-word, word-aligned, off-by-one around word boundaries, and
+    // sizes large enough to exercise the strided kernel loop
+    let lengths = [
+        0usize, 1, 3, 7, 8, 9, 15, 16, 17, 63, 64, 65, 1023, 4096, 65_537, 1 << 20,
+    ];
+    for (i, &len) in lengths.iter().enumerate() {
+        let bytes = pseudo_random_bytes(len, 0x9e37_79b9_7f4a_7c15 ^ i as u64);
+        let gpu = ctx
+            .xor64_for_file(&bytes)
+            .unwrap_or_else(|e| panic!("GPU reduction failed for len {}: {:#}", len, e));
+        assert_eq!(gpu, xor64_cpu(&bytes), "GPU/CPU mismatch for len {}", len);
+    }
+}
+
+#[test]
+fn concurrent_workers_use_their_own_queues() {
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+    let ctx = match pool.install(GpuContext::try_new) {
+        Ok(ctx) => ctx,
+        Err(e) => {
+            eprintln!("skipping GPU queue test: no usable OpenCL device ({:#})", e);
+            return;
+        }
+    };
+    pool.install(|| {
+        (0..64u64).into_par_iter().for_each(|i| {
+            let bytes = pseudo_random_bytes(4096 + i as usize * 13, i);
+            assert_eq!(ctx.xor64_for_file(&bytes).unwrap(), xor64_cpu(&bytes), "input {}", i);
+        });
+    });
+}
+
+#[test]
+fn chunked_launches_match_one_launch() {
+    let ctx = match GpuContext::try_new() {
+        Ok(ctx) => ctx.with_max_chunk(4096),
+        Err(e) => {
+            eprintln!("skipping GPU chunk test: no usable OpenCL device ({:#})", e);
+            return;
+        }
+    };
+    // one chunk, exactly two, and several with a short padded tail
+    for (i, &len) in [4096usize, 8192, 65_541].iter().enumerate() {
+        let bytes = pseudo_random_bytes(len, 0x51ed ^ i as u64);
+        assert_eq!(ctx.xor64_for_file(&bytes).unwrap(), xor64_cpu(&bytes), "len {}", len);
+    }
+}
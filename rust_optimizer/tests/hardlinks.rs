@@ -0,0 +1,33 @@
+//! Hard links: grouped by inode and counted once in the real disk usage.
+#![cfg(unix)]
+
+use aivista_cache_scan::scan::{scan_cache, CancellationToken, ScanConfig};
+use std::fs;
+
+#[test]
+fn links_to_one_inode_are_grouped_and_counted_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    fs::write(root.join("blob"), vec![7u8; 1000]).unwrap();
+    fs::hard_link(root.join("blob"), root.join("a.bin")).unwrap();
+    fs::hard_link(root.join("blob"), root.join("b.bin")).unwrap();
+    fs::write(root.join("single.bin"), vec![1u8; 300]).unwrap();
+    // a link from outside the scan still counts towards nlink
+    let outside = tempfile::tempdir_in(root.parent().unwrap()).unwrap();
+    fs::hard_link(root.join("single.bin"), outside.path().join("elsewhere")).unwrap();
+
+    let config = ScanConfig {
+        incomplete: None,
+        hardlinks: true,
+        ..ScanConfig::new(root)
+    };
+    let report = scan_cache(&config, &CancellationToken::new()).unwrap().report;
+    assert_eq!(report.total_bytes, 3300);
+    assert_eq!(report.real_bytes, Some(1300));
+    assert_eq!(report.hardlinks.len(), 2);
+    let blob = &report.hardlinks[0];
+    assert_eq!((blob.size, blob.nlink, blob.overcounted()), (1000, 3, 2000));
+    assert_eq!(blob.paths, [root.join("a.bin"), root.join("b.bin"), root.join("blob")]);
+    let single = &report.hardlinks[1];
+    assert_eq!((single.nlink, single.paths.len(), single.overcounted()), (2, 1, 0));
+}
@@ -0,0 +1,114 @@
+//! Keyed and derive-key hashing must match BLAKE3's own functions, including
+//! when the digest is assembled window by window; the quick algorithms must
+//! match their reference crates, alone or alongside BLAKE3.
+
+use aivista_cache_scan::hashmode::{HashAlgorithm, HashMode};
+use aivista_cache_scan::process::{digest_hex, process_file, ProcessOptions, ReaderMode};
+use aivista_cache_scan::resume::hash_windowed;
+use std::io::Write;
+
+const KEY_HEX: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+fn key() -> [u8; 32] {
+    std::array::from_fn(|i| i as u8)
+}
+
+#[test]
+fn process_file_uses_the_mode() {
+    let data: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    file.write_all(&data).unwrap();
+    file.flush().unwrap();
+
+    let hash_with = |hash_mode| {
+        let opts = ProcessOptions {
+            hash_mode,
+            ..Default::default()
+        };
+        process_file(file.path(), &opts).unwrap().hash_str.unwrap()
+    };
+    let keyed = HashMode::keyed_from_hex(KEY_HEX).unwrap();
+    assert_eq!(hash_with(keyed), blake3::keyed_hash(&key(), &data).to_hex().to_string());
+    assert_eq!(
+        hash_with(HashMode::derive_key("aivista test context")),
+        blake3::Hasher::new_derive_key("aivista test context")
+            .update(&data)
+            .finalize()
+            .to_hex()
+            .to_string()
+    );
+    assert_ne!(hash_with(keyed), hash_with(HashMode::Plain));
+}
+
+#[test]
+fn windowed_keyed_hash_matches_one_shot() {
+    let data: Vec<u8> = (0..(5 * 2048 + 77)).map(|i: u32| (i * 13 % 256) as u8).collect();
+    let mode = HashMode::Keyed(key());
+    let output = hash_windowed(
+        &mode,
+        data.len() as u64,
+        2048,
+        0,
+        Vec::new(),
+        |hasher, from, to| {
+            hasher.update(&data[from as usize..to as usize]);
+            Ok(())
+        },
+        |_, _| Ok(()),
+    )
+    .unwrap();
+    assert_eq!(digest_hex(output, 32), blake3::keyed_hash(&key(), &data).to_hex().to_string());
+}
+
+#[test]
+fn malformed_keys_are_rejected() {
+    assert!(HashMode::keyed_from_hex("abcd").is_err());
+    assert!(HashMode::keyed_from_hex(&"zz".repeat(32)).is_err());
+}
+
+#[test]
+fn quick_algorithms_match_their_crates_with_either_reader() {
+    let data: Vec<u8> = (0..(2 << 20) + 5).map(|i: u32| (i * 29 % 256) as u8).collect();
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    file.write_all(&data).unwrap();
+    file.flush().unwrap();
+    let expected = [
+        (HashAlgorithm::Crc32, format!("{:08x}", crc32fast::hash(&data))),
+        (HashAlgorithm::Xxh3_128, format!("{:032x}", xxhash_rust::xxh3::xxh3_128(&data))),
+    ];
+    for (algorithm, hex) in expected {
+        for reader in [ReaderMode::Mmap, ReaderMode::Read] {
+            let opts = ProcessOptions {
+                reader,
+                algorithm,
+                ..Default::default()
+            };
+            let report = process_file(file.path(), &opts).unwrap();
+            assert_eq!(report.hash_str.as_deref(), Some(hex.as_str()), "{:?}", algorithm);
+        }
+    }
+}
+
+#[test]
+fn extra_algorithms_share_one_pass() {
+    let data: Vec<u8> = (0..(2 << 20) + 5).map(|i: u32| (i * 31 % 256) as u8).collect();
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    file.write_all(&data).unwrap();
+    file.flush().unwrap();
+    let blake3 = blake3::hash(&data).to_hex().to_string();
+    let xxh3 = format!("{:032x}", xxhash_rust::xxh3::xxh3_128(&data));
+    for reader in [ReaderMode::Mmap, ReaderMode::Read] {
+        let opts = ProcessOptions {
+            reader,
+            extra_algorithms: &[HashAlgorithm::Xxh3_128],
+            ..Default::default()
+        };
+        let report = process_file(file.path(), &opts).unwrap();
+        assert_eq!(report.hash_str.as_deref(), Some(blake3.as_str()));
+        let hashes = report.hashes.unwrap();
+        assert_eq!(hashes[&HashAlgorithm::Blake3], blake3);
+        assert_eq!(hashes[&HashAlgorithm::Xxh3_128], xxh3);
+    }
+    let single = process_file(file.path(), &ProcessOptions::default()).unwrap();
+    assert!(single.hashes.is_none());
+}
@@ -0,0 +1,54 @@
+//! `--sqlite` appends a scan row and its file rows per run, and will not
+//! touch a database from a newer schema.
+
+use aivista_cache_scan::encoding::HashEncoding;
+use aivista_cache_scan::hashmode::{HashAlgorithm, HashMode};
+use aivista_cache_scan::history::{self, SqliteSink};
+use aivista_cache_scan::scan::{scan_cache, CancellationToken, ScanConfig};
+
+#[test]
+fn appends_one_scan_per_run() {
+    let dir = tempfile::tempdir().unwrap();
+    let cache = dir.path().join("cache");
+    std::fs::create_dir(&cache).unwrap();
+    std::fs::write(cache.join("a"), b"abc").unwrap();
+    std::fs::write(cache.join("b"), b"defg").unwrap();
+    let config = ScanConfig {
+        incomplete: None,
+        ..ScanConfig::new(&cache)
+    };
+    let summary = scan_cache(&config, &CancellationToken::new()).unwrap();
+    let db = dir.path().join("history.db");
+    for expected_id in 1..=2 {
+        let mut sink = SqliteSink::open(
+            &db,
+            &cache,
+            HashAlgorithm::Blake3,
+            HashMode::Plain,
+            HashEncoding::Hex,
+        )
+        .unwrap();
+        assert_eq!(sink.record(&summary).unwrap(), expected_id);
+    }
+
+    let conn = history::open(&db).unwrap();
+    let (scans, bytes): (i64, i64) = conn
+        .query_row("SELECT count(*), sum(total_bytes) FROM scans", [], |r| {
+            Ok((r.get(0)?, r.get(1)?))
+        })
+        .unwrap();
+    assert_eq!((scans, bytes), (2, 14));
+    let hash: String = conn
+        .query_row(
+            "SELECT hash FROM files WHERE scan_id = 2 AND path LIKE '%/a'",
+            [],
+            |r| r.get(0),
+        )
+        .unwrap();
+    assert_eq!(hash, blake3::hash(b"abc").to_hex().as_str());
+
+    conn.pragma_update(None, "user_version", 99).unwrap();
+    drop(conn);
+    let err = history::open(&db).unwrap_err();
+    assert!(format!("{:#}", err).contains("newer"), "{:#}", err);
+}
@@ -0,0 +1,81 @@
+//! An index updated file by file must fingerprint exactly as a manifest
+//! built from scratch over the same files.
+
+use aivista_cache_scan::encoding::HashEncoding;
+use aivista_cache_scan::hashmode::{HashAlgorithm, HashMode};
+use aivista_cache_scan::index::IndexState;
+use aivista_cache_scan::manifest::{Manifest, ManifestEntry};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+fn full(files: &BTreeMap<String, u64>, mode: &HashMode) -> String {
+    let entries: Vec<ManifestEntry> = files
+        .iter()
+        .map(|(path, &size)| ManifestEntry {
+            path: path.clone(),
+            size,
+            hash: format!("{:064x}", size),
+        })
+        .collect();
+    aivista_cache_scan::manifest::fingerprint(&entries, mode, HashEncoding::Hex)
+}
+
+#[test]
+fn incremental_fingerprint_matches_a_full_recompute() {
+    let mode = HashMode::derive_key("index test");
+    let index = IndexState::new(HashAlgorithm::Blake3, mode, HashEncoding::Hex);
+    let mut files = BTreeMap::new();
+    // inserts out of order, overwrites and removals, checked after each step
+    for step in 0u64..60 {
+        let path = format!("m{}/w{}.bin", step % 7, (step * 13) % 11);
+        if step % 5 == 4 {
+            assert_eq!(index.remove(&path), files.remove(&path).is_some());
+        } else {
+            let changed = files.insert(path.clone(), step) != Some(step);
+            assert_eq!(index.update_index(&path, step, &format!("{:064x}", step)), changed);
+        }
+        assert_eq!(index.fingerprint(), full(&files, &mode), "after step {}", step);
+    }
+    assert_eq!(index.len(), files.len());
+
+    let manifest = index.to_manifest();
+    manifest.check(HashAlgorithm::Blake3, &mode).unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let saved = dir.path().join("index.json");
+    index.save(&saved).unwrap();
+    let resumed = IndexState::load(&saved, HashAlgorithm::Blake3, mode).unwrap();
+    assert_eq!(resumed.fingerprint(), manifest.fingerprint);
+    assert!(IndexState::load(&saved, HashAlgorithm::Blake3, HashMode::Plain).is_err());
+    assert_eq!(Manifest::load(&saved).unwrap(), manifest);
+}
+
+#[test]
+fn concurrent_updates_land_in_one_consistent_index() {
+    let index = Arc::new(IndexState::new(
+        HashAlgorithm::Blake3,
+        HashMode::Plain,
+        HashEncoding::Hex,
+    ));
+    let threads: Vec<_> = (0..4u64)
+        .map(|t| {
+            let index = Arc::clone(&index);
+            std::thread::spawn(move || {
+                for i in 0..50 {
+                    let size = t * 100 + i;
+                    index.update_index(&format!("t{}/{}", t, i), size, &format!("{:064x}", size));
+                    // readers in between see some consistent generation
+                    index.fingerprint();
+                }
+            })
+        })
+        .collect();
+    threads.into_iter().for_each(|t| t.join().unwrap());
+
+    let files: BTreeMap<String, u64> = (0..4u64)
+        .flat_map(|t| (0..50).map(move |i| (format!("t{}/{}", t, i), t * 100 + i)))
+        .collect();
+    assert_eq!(index.fingerprint(), full(&files, &HashMode::Plain));
+    let manifest = index.to_manifest();
+    assert_eq!(manifest.files.len(), 200);
+    manifest.check(HashAlgorithm::Blake3, &HashMode::Plain).unwrap();
+}
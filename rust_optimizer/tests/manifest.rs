@@ -0,0 +1,59 @@
+//! The manifest must not depend on where the cache lives or the order files
+//! finished hashing in.
+
+use aivista_cache_scan::encoding::HashEncoding;
+use aivista_cache_scan::hashmode::{HashAlgorithm, HashMode};
+use aivista_cache_scan::manifest::{short_id, Manifest};
+use aivista_cache_scan::report::FileReport;
+use std::path::{Path, PathBuf};
+
+fn hashed(root: &str, rel: &str, size: u64) -> FileReport {
+    FileReport {
+        hash_str: Some(format!("{:064x}", size)),
+        elapsed_ms: size as u128,
+        ..FileReport::new(Path::new(root).join(rel), size)
+    }
+}
+
+#[test]
+fn identical_caches_give_identical_manifests() {
+    let a = vec![hashed("/srv/a", "m/w.bin", 3), hashed("/srv/a", "cfg.json", 1)];
+    let b = vec![hashed("/mnt/b", "cfg.json", 1), hashed("/mnt/b", "m/w.bin", 3)];
+    let (alg, mode, hex) = (HashAlgorithm::Blake3, HashMode::Plain, HashEncoding::Hex);
+    let ma = Manifest::build(Path::new("/srv/a"), &a, alg, &mode, hex);
+    let mb = Manifest::build(Path::new("/mnt/b"), &b, alg, &mode, hex);
+    assert_eq!(ma, mb);
+    assert_eq!(ma.files[1].path, "m/w.bin");
+    assert_eq!(ma.expected(Path::new("/x"))[1].path, PathBuf::from("/x/m/w.bin"));
+    ma.check(HashAlgorithm::Blake3, &HashMode::Plain).unwrap();
+    assert!(ma.check(HashAlgorithm::Blake3, &HashMode::derive_key("other")).is_err());
+    assert!(ma.check(HashAlgorithm::Crc32, &HashMode::Plain).is_err());
+    assert_eq!(short_id(&ma.fingerprint), &ma.fingerprint[..7]);
+
+    let mut edited = ma.clone();
+    edited.files[0].size += 1;
+    assert!(edited.check(HashAlgorithm::Blake3, &HashMode::Plain).is_err());
+}
+
+#[test]
+fn encodings_change_only_how_hashes_are_written() {
+    let hex_reports = vec![hashed("/c", "a.bin", 5), hashed("/c", "b.bin", 7)];
+    let (alg, mode) = (HashAlgorithm::Blake3, HashMode::Plain);
+    let hex = Manifest::build(Path::new("/c"), &hex_reports, alg, &mode, HashEncoding::Hex);
+    for encoding in [HashEncoding::Base32, HashEncoding::Base64url] {
+        let reports: Vec<FileReport> = hex_reports
+            .iter()
+            .map(|r| FileReport {
+                hash_str: r.hash_str.as_deref().map(|h| encoding.from_hex(h)),
+                ..hashed("/c", r.path.strip_prefix("/c").unwrap().to_str().unwrap(), r.size)
+            })
+            .collect();
+        let m = Manifest::build(Path::new("/c"), &reports, alg, &mode, encoding);
+        m.check(alg, &mode).unwrap();
+        assert_eq!(encoding.to_hex(&m.fingerprint), hex.fingerprint);
+        assert_eq!(m.expected(Path::new("/c"))[0].hash_hex, hex.files[0].hash);
+        // the encoding is recorded, so the manifest reads back the same
+        let text = serde_json::to_string(&m).unwrap();
+        assert_eq!(serde_json::from_str::<Manifest>(&text).unwrap(), m);
+    }
+}
@@ -0,0 +1,43 @@
+//! Files whose content contradicts their extension.
+
+use aivista_cache_scan::mismatch::{check, Content};
+use std::path::Path;
+
+fn write(dir: &Path, name: &str, bytes: &[u8]) -> (std::path::PathBuf, u64) {
+    let path = dir.join(name);
+    std::fs::write(&path, bytes).unwrap();
+    (path, bytes.len() as u64)
+}
+
+#[test]
+fn sniffs_signatures() {
+    let header = br#"{"w":{"dtype":"U8","shape":[2],"data_offsets":[0,2]}}"#;
+    let mut st = (header.len() as u64).to_le_bytes().to_vec();
+    st.extend_from_slice(header);
+    st.extend_from_slice(&[1, 2]);
+    assert_eq!(Content::sniff(&st, st.len() as u64), Some(Content::Safetensors));
+    // a header running past the end of the file is not a safetensors header
+    assert_eq!(Content::sniff(&st, 20), None);
+    assert_eq!(Content::sniff(b"GGUF\x03\0\0\0", 8), Some(Content::Gguf));
+    assert_eq!(Content::sniff(b"\x1f\x8b\x08", 3), Some(Content::Gzip));
+    assert_eq!(Content::sniff(b"\xef\xbb\xbf  [1, 2]", 12), Some(Content::Json));
+    assert_eq!(Content::sniff(b"\n<!DOCTYPE html><p>", 19), Some(Content::Html));
+}
+
+#[test]
+fn flags_only_contradictions() {
+    let dir = tempfile::tempdir().unwrap();
+    let (page, len) = write(dir.path(), "model.safetensors", b"<html><body>502</body></html>");
+    assert_eq!(check(&page, len).as_deref(), Some(".safetensors file holds HTML"));
+    let pointer = b"version https://git-lfs.github.com/spec/v1\noid sha256:ab\nsize 9\n";
+    let (lfs, len) = write(dir.path(), "w.GGUF", pointer);
+    assert_eq!(check(&lfs, len).as_deref(), Some(".gguf file holds a Git LFS pointer"));
+    let (gguf, len) = write(dir.path(), "x.gguf", b"not a model");
+    assert_eq!(check(&gguf, len).as_deref(), Some(".gguf file has no GGUF signature"));
+
+    let (json, len) = write(dir.path(), "config.json", b"{\"a\": 1}");
+    assert_eq!(check(&json, len), None);
+    // extensions used for anything are left alone
+    let (bin, len) = write(dir.path(), "pytorch_model.bin", b"<html>");
+    assert_eq!(check(&bin, len), None);
+}
@@ -0,0 +1,39 @@
+//! Files held open for writing by another process, found through /proc.
+#![cfg(target_os = "linux")]
+
+use aivista_cache_scan::openfiles::OpenForWrite;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+#[test]
+fn finds_files_another_process_is_writing() {
+    let dir = tempfile::tempdir().unwrap();
+    let writing = dir.path().join("model.safetensors");
+    let reading = dir.path().join("config.json");
+    std::fs::write(&writing, b"partial").unwrap();
+    std::fs::write(&reading, b"{}").unwrap();
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg("exec sleep 30 3>>\"$1\" 4<\"$2\"")
+        .arg("sh")
+        .arg(&writing)
+        .arg(&reading)
+        .spawn()
+        .unwrap();
+
+    // the descriptors are opened only once the shell runs
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let open = loop {
+        let open = OpenForWrite::scan().expect("/proc is readable");
+        if open.writer(&writing).is_some() || Instant::now() > deadline {
+            break open;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    };
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    let writer = open.writer(&writing).expect("the writer was found");
+    assert_eq!(writer.pid, child.id());
+    assert!(open.writer(&reading).is_none());
+}
@@ -0,0 +1,37 @@
+//! The non-TTY status line: counts, sizes, rate and a coarse ETA; progress-bar templates.
+
+use aivista_cache_scan::progress::{bar_style, status_line, FILES_TEMPLATE};
+use aivista_cache_scan::report::Units;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::time::Duration;
+
+#[test]
+fn status_line_reports_rate_and_eta() {
+    let gib = 1 << 30;
+    // 10 GiB in 100 s leaves 30 GiB for another 300 s
+    let line = status_line(
+        Duration::from_secs(100),
+        (4210, 10000),
+        (10 * gib, 40 * gib),
+        45.0 * (1 << 20) as f64,
+        Units::Iec,
+    );
+    assert_eq!(line, "[01:40] 4210/10000 files, 10.00 GiB/40.00 GiB, 45.00 MiB/s, ETA 5m");
+
+    let done = status_line(Duration::from_secs(5), (3, 3), (10, 10), 0.0, Units::Si);
+    assert!(done.ends_with("ETA 0s"), "{}", done);
+    let stalled = status_line(Duration::from_secs(5), (0, 3), (0, 10), 0.0, Units::Si);
+    assert!(stalled.ends_with("ETA ?"), "{}", stalled);
+}
+
+#[test]
+fn invalid_templates_fall_back_instead_of_panicking() {
+    assert!(ProgressStyle::with_template("{pos:>x}").is_err());
+    let bar = ProgressBar::hidden();
+    for template in ["{pos:>x}", "{wide_msg} {pos}/{len}"] {
+        bar.set_style(bar_style(Some(template), FILES_TEMPLATE, "--progress-template"));
+        bar.tick();
+    }
+    bar.set_style(bar_style(None, "{pos:>x}", "--progress-template"));
+    bar.tick();
+}
@@ -0,0 +1,142 @@
+//! `--inspect-pytorch` reads tensor shapes from a checkpoint's pickle index
+//! by hand-assembled opcodes, and refuses anything that would run code.
+
+use aivista_cache_scan::pytorch::{self, PytorchFormat, PytorchInfo, TensorInfo};
+use std::io::Write;
+
+/// The three header pickles of a legacy `torch.save`: magic number,
+/// protocol version 1001 and an empty system-info dict.
+const LEGACY_HEADER: &[u8] = b"\x80\x02\x8a\x0a\x6c\xfc\x9c\x46\xf9\x20\x6a\xa8\x50\x19.\
+                               \x80\x02M\xe9\x03.\
+                               \x80\x02}.";
+
+fn short_str(s: &str) -> Vec<u8> {
+    [&[0x8c, s.len() as u8][..], s.as_bytes()].concat()
+}
+
+/// `_rebuild_tensor_v2(PersId(("storage", torch.<storage>, key, "cpu", n)), 0, shape, ...)`
+fn tensor(storage: &str, key: &str, shape: &[u8]) -> Vec<u8> {
+    let mut p = b"ctorch._utils\n_rebuild_tensor_v2\n((".to_vec();
+    p.extend(short_str("storage"));
+    p.extend(format!("ctorch\n{}\n", storage).as_bytes());
+    p.extend(short_str(key));
+    p.extend(short_str("cpu"));
+    p.extend(b"K\x06t\x94QK\x00(");
+    for &d in shape {
+        p.extend([b'K', d]);
+    }
+    p.extend(b"t)\x89ccollections\nOrderedDict\n)Rt\x94R");
+    p
+}
+
+fn legacy_file(index: &[u8]) -> tempfile::NamedTempFile {
+    let file = tempfile::NamedTempFile::new().unwrap();
+    let mut bytes = LEGACY_HEADER.to_vec();
+    bytes.extend(index);
+    // storage keys and tensor data follow the index and are never read
+    bytes.extend(b"\x80\x02]q\x00.");
+    bytes.extend([0u8; 64]);
+    std::fs::write(file.path(), bytes).unwrap();
+    file
+}
+
+#[test]
+fn lists_tensors_of_a_legacy_checkpoint() {
+    let mut index = b"\x80\x02}q\x00(".to_vec();
+    index.extend(short_str("model"));
+    index.extend(b"ccollections\nOrderedDict\n)R(");
+    index.extend(short_str("weight"));
+    index.extend(tensor("HalfStorage", "0", &[2, 3]));
+    index.extend(short_str("bias"));
+    index.extend(tensor("FloatStorage", "1", &[3]));
+    index.extend(b"u");
+    index.extend(short_str("epoch"));
+    index.extend(b"K\x07u.");
+    let info = pytorch::inspect(legacy_file(&index).path()).unwrap();
+    assert_eq!(info.format, PytorchFormat::Legacy);
+    assert_eq!(info.error, None);
+    let tensors: Vec<(&str, &str, &[u64])> = info
+        .tensors
+        .iter()
+        .map(|t| (t.name.as_str(), t.dtype.as_str(), t.shape.as_slice()))
+        .collect();
+    assert_eq!(
+        tensors,
+        [("model.weight", "float16", &[2, 3][..]), ("model.bias", "float32", &[3][..])]
+    );
+    assert_eq!(info.parameters(), 9);
+}
+
+#[test]
+fn lists_tensors_of_a_zip_checkpoint() {
+    let mut index = b"\x80\x02}q\x00(".to_vec();
+    index.extend(short_str("weight"));
+    index.extend(tensor("BFloat16Storage", "0", &[4, 2]));
+    index.extend(b"u.");
+    let file = tempfile::NamedTempFile::new().unwrap();
+    let mut zip = zip::ZipWriter::new(file.reopen().unwrap());
+    let stored = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Stored);
+    zip.start_file("archive/data.pkl", stored).unwrap();
+    zip.write_all(&index).unwrap();
+    zip.start_file("archive/data/0", stored).unwrap();
+    zip.write_all(&[0u8; 16]).unwrap();
+    zip.finish().unwrap();
+
+    let info = pytorch::inspect(file.path()).unwrap();
+    assert_eq!(info.format, PytorchFormat::Zip);
+    assert_eq!(info.error, None);
+    assert_eq!(info.tensors[0].name, "weight");
+    assert_eq!(info.tensors[0].dtype, "bfloat16");
+    assert_eq!(info.parameters(), 8);
+}
+
+#[test]
+fn refuses_pickles_outside_the_torch_subset() {
+    // os.system("true"), spelled with the protocol-0 STRING opcode
+    let info = pytorch::inspect(legacy_file(b"cos\nsystem\n(S'true'\ntR.").path()).unwrap();
+    assert!(info.tensors.is_empty());
+    assert!(info.error.unwrap().contains("unsupported pickle opcode"));
+}
+
+#[test]
+fn gives_up_on_an_index_sharing_one_dict_exponentially() {
+    // each dict holds the previous one under two keys: 2^64 key paths
+    let mut index = b"\x80\x02}q\x00".to_vec();
+    for level in 1..=64u8 {
+        index.extend(b"}(");
+        for key in ["a", "b"] {
+            index.extend(short_str(key));
+            index.extend([b'h', level - 1]);
+        }
+        index.extend([b'u', b'q', level]);
+    }
+    index.push(b'.');
+    let info = pytorch::inspect(legacy_file(&index).path()).unwrap();
+    assert!(info.tensors.is_empty());
+    assert!(info.error.unwrap().contains("shared"));
+}
+
+#[test]
+fn parameter_count_saturates_on_absurd_shapes() {
+    let huge = TensorInfo {
+        name: "w".into(),
+        dtype: "float32".into(),
+        shape: vec![u64::MAX, 2],
+    };
+    let info = PytorchInfo {
+        format: PytorchFormat::Legacy,
+        tensors: vec![huge.clone(), huge],
+        error: None,
+    };
+    assert_eq!(info.parameters(), u64::MAX);
+}
+
+#[test]
+fn ignores_other_files() {
+    let file = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(file.path(), b"PK\x03\x04 not a checkpoint, not even a zip").unwrap();
+    assert_eq!(pytorch::inspect(file.path()), None);
+    std::fs::write(file.path(), [0u8; 4096]).unwrap();
+    assert_eq!(pytorch::inspect(file.path()), None);
+}
@@ -0,0 +1,212 @@
+//! Every `--reader` mode must produce the same digest and checksum.
+
+use aivista_cache_scan::hashmode::HashMode;
+use aivista_cache_scan::process::{process_file, ByteRange, ProcessOptions, ReaderMode};
+use aivista_cache_scan::report::FileStatus;
+use aivista_cache_scan::xor64::Xor64Stream;
+use aivista_cache_scan::xor64::xor64_cpu;
+use std::io::Write;
+
+fn hash_with(path: &std::path::Path, reader: ReaderMode) -> (Option<String>, Option<u64>) {
+    let opts = ProcessOptions {
+        reader,
+        use_gpu: true,
+        ..Default::default()
+    };
+    let report = process_file(path, &opts).expect("process_file");
+    (report.hash_str, report.xor64)
+}
+
+#[test]
+fn mmap_and_read_agree() {
+    // spans several read buffers and ends mid-word
+    let data: Vec<u8> = (0..(3 << 20) + 13).map(|i: u32| (i * 7 % 253) as u8).collect();
+    for len in [0, 1, 4097, data.len()] {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(&data[..len]).unwrap();
+        file.flush().unwrap();
+
+        let mapped = hash_with(file.path(), ReaderMode::Mmap);
+        let read = hash_with(file.path(), ReaderMode::Read);
+        assert_eq!(mapped, read, "len {}", len);
+        assert_eq!(mapped.0.unwrap(), blake3::hash(&data[..len]).to_hex().to_string());
+        assert_eq!(mapped.1, Some(xor64_cpu(&data[..len])));
+    }
+}
+
+#[test]
+fn populated_mapping_hashes_the_same() {
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    file.write_all(&[3u8; 100_000]).unwrap();
+    file.flush().unwrap();
+    let opts = ProcessOptions {
+        reader: ReaderMode::Mmap,
+        populate: true,
+        ..Default::default()
+    };
+    let report = process_file(file.path(), &opts).expect("process_file");
+    assert_eq!(report.hash_str, hash_with(file.path(), ReaderMode::Mmap).0);
+}
+
+#[test]
+fn warm_only_skips_hashing() {
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    file.write_all(&[7u8; 10_000]).unwrap();
+    file.flush().unwrap();
+    for reader in [ReaderMode::Mmap, ReaderMode::Read] {
+        let opts = ProcessOptions {
+            reader,
+            use_gpu: true,
+            warm_only: true,
+            ..Default::default()
+        };
+        let report = process_file(file.path(), &opts).expect("process_file");
+        assert_eq!(report.status, FileStatus::Warmed);
+        assert_eq!(report.size, 10_000);
+        assert_eq!(report.hash_str, None);
+        assert_eq!(report.xor64, None);
+    }
+}
+
+#[test]
+fn streaming_xor_matches_one_shot() {
+    let data: Vec<u8> = (0u8..=200).collect();
+    for split in [0, 1, 3, 8, 13, 200] {
+        let mut x = Xor64Stream::default();
+        x.update(&data[..split]);
+        x.update(&data[split..]);
+        assert_eq!(x.finish(), xor64_cpu(&data), "split {}", split);
+    }
+}
+
+#[test]
+fn range_hashes_only_its_span() {
+    let data: Vec<u8> = (0..20_000u32).map(|i| (i * 31 % 251) as u8).collect();
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    file.write_all(&data).unwrap();
+    file.flush().unwrap();
+    // the second span runs past the end and is cut short there
+    for (range, span) in [("4097:5000", 4097..9097), ("19000:5000", 19000..20_000)] {
+        for reader in [ReaderMode::Mmap, ReaderMode::Read] {
+            let opts = ProcessOptions {
+                reader,
+                use_gpu: true,
+                range: Some(ByteRange::parse(range).unwrap()),
+                ..Default::default()
+            };
+            let report = process_file(file.path(), &opts).expect("process_file");
+            let expected = blake3::hash(&data[span.clone()]).to_hex().to_string();
+            assert_eq!(report.hash_str, Some(expected), "{} with {:?}", range, reader);
+            assert_eq!(report.xor64, Some(xor64_cpu(&data[span.clone()])));
+            assert_eq!(report.range, Some([span.start as u64, span.end as u64]));
+        }
+    }
+
+    let opts = ProcessOptions {
+        range: Some(ByteRange::parse("20000:1").unwrap()),
+        ..Default::default()
+    };
+    let report = process_file(file.path(), &opts).expect("process_file");
+    assert_eq!(report.status, FileStatus::Skipped);
+    assert!(report.skip_reason.unwrap().contains("20000"));
+}
+
+#[test]
+fn sparse_aware_matches_a_dense_read() {
+    use std::io::{Seek, SeekFrom};
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    let len = 8u64 << 20;
+    file.as_file().set_len(len).unwrap();
+    for offset in [0, 3 << 20, len - 100] {
+        file.seek(SeekFrom::Start(offset)).unwrap();
+        file.write_all(&[0xab; 100]).unwrap();
+    }
+    file.flush().unwrap();
+    let dense = std::fs::read(file.path()).unwrap();
+
+    let opts = ProcessOptions {
+        reader: ReaderMode::Mmap,
+        use_gpu: true,
+        sparse_aware: true,
+        ..Default::default()
+    };
+    let report = process_file(file.path(), &opts).expect("process_file");
+    assert_eq!(report.hash_str, Some(blake3::hash(&dense).to_hex().to_string()));
+    assert_eq!(report.xor64, Some(xor64_cpu(&dense)));
+    // filesystems without holes (or non-Linux) fall back to a full read
+    if let Some(holes) = report.hole_bytes {
+        assert!(holes <= len - 300);
+    }
+}
+
+#[test]
+fn parallel_hash_matches_serial_digest() {
+    // several BLAKE3 subtrees deep, ending mid-chunk
+    let data: Vec<u8> = (0..(9 << 20) + 1537).map(|i: u32| (i * 13 % 241) as u8).collect();
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    file.write_all(&data).unwrap();
+    file.flush().unwrap();
+    let key = [7u8; 32];
+    for (hash_mode, expected) in [
+        (HashMode::Plain, blake3::hash(&data)),
+        (HashMode::Keyed(key), blake3::keyed_hash(&key, &data)),
+    ] {
+        let opts = ProcessOptions {
+            reader: ReaderMode::Mmap,
+            hash_mode,
+            parallel_threshold: Some(1 << 20),
+            ..Default::default()
+        };
+        let report = process_file(file.path(), &opts).expect("process_file");
+        assert_eq!(report.hash_str, Some(expected.to_hex().to_string()));
+    }
+    let opts = ProcessOptions {
+        reader: ReaderMode::Mmap,
+        range: Some(ByteRange::parse("1025:5000000").unwrap()),
+        parallel_threshold: Some(0),
+        ..Default::default()
+    };
+    let report = process_file(file.path(), &opts).expect("process_file");
+    let expected = blake3::hash(&data[1025..5_001_025]).to_hex().to_string();
+    assert_eq!(report.hash_str, Some(expected));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn residency_is_measured_before_reading() {
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    file.write_all(&vec![1u8; 1 << 20]).unwrap();
+    file.flush().unwrap();
+    let opts = ProcessOptions {
+        reader: ReaderMode::Read,
+        measure_residency: true,
+        ..Default::default()
+    };
+    // just written, so still in the page cache
+    let report = process_file(file.path(), &opts).expect("process_file");
+    let resident = report.resident.expect("measured");
+    assert!((0.0..=1.0).contains(&resident));
+    assert!(resident > 0.5, "{}", resident);
+}
+
+#[test]
+fn partial_on_error_keeps_the_hash_of_what_was_read() {
+    // sysfs reports 4096 bytes for a few bytes of text, so reading stops short
+    // just as it would on a bad block
+    let path = std::path::Path::new("/sys/devices/system/cpu/online");
+    let Ok(content) = std::fs::read(path) else {
+        return;
+    };
+    assert!(process_file(path, &ProcessOptions::default()).is_err());
+
+    let opts = ProcessOptions {
+        partial_on_error: true,
+        ..Default::default()
+    };
+    let report = process_file(path, &opts).expect("process_file");
+    assert_eq!(report.status, FileStatus::PartiallyHashed);
+    assert_eq!(report.hash_str, None);
+    assert_eq!(report.bytes_ok, Some(content.len() as u64));
+    assert_eq!(report.partial_hash.unwrap(), blake3::hash(&content).to_hex().to_string());
+    assert!(report.error.unwrap().contains("shrank"));
+}
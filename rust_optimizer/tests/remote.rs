@@ -0,0 +1,71 @@
+//! `--verify-url` fetching: ETag revalidation against the local copy, and
+//! failures reported as errors rather than served from it.
+
+use aivista_cache_scan::remote::fetch;
+use reqwest::header::HeaderMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::thread::JoinHandle;
+
+/// Answer one request per entry of `responses` (status line and body, with
+/// `"ETag"` always set); the request heads come back from the join handle.
+fn serve(responses: Vec<(&'static str, &'static str)>) -> (String, JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/manifest.json", listener.local_addr().unwrap());
+    let handle = std::thread::spawn(move || {
+        let mut heads = Vec::new();
+        for (status, body) in responses {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut head = String::new();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                head.push_str(&line.to_ascii_lowercase());
+            }
+            heads.push(head);
+            write!(
+                stream,
+                "HTTP/1.1 {}\r\nETag: \"v1\"\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            )
+            .unwrap();
+        }
+        heads
+    });
+    (url, handle)
+}
+
+#[test]
+fn unchanged_manifest_comes_from_the_local_copy() {
+    let cache = tempfile::tempdir().unwrap();
+    let (url, server) = serve(vec![("200 OK", "{\"a\":1}"), ("304 Not Modified", "")]);
+    let mut headers = HeaderMap::new();
+    headers.insert("authorization", "Bearer t".parse().unwrap());
+
+    let first = fetch(&url, headers.clone(), Some(cache.path())).unwrap();
+    assert!(!first.not_modified);
+    let second = fetch(&url, headers, Some(cache.path())).unwrap();
+    assert!(second.not_modified);
+    assert_eq!(second.text, "{\"a\":1}");
+
+    let heads = server.join().unwrap();
+    assert!(heads.iter().all(|h| h.contains("authorization: bearer t")));
+    assert!(!heads[0].contains("if-none-match"));
+    assert!(heads[1].contains("if-none-match: \"v1\""));
+}
+
+#[test]
+fn failed_fetch_is_an_error_even_with_a_local_copy() {
+    let cache = tempfile::tempdir().unwrap();
+    let (url, server) = serve(vec![("200 OK", "{}"), ("503 Service Unavailable", "")]);
+    fetch(&url, HeaderMap::new(), Some(cache.path())).unwrap();
+    let err = fetch(&url, HeaderMap::new(), Some(cache.path())).unwrap_err();
+    assert!(format!("{:#}", err).contains("503"));
+    server.join().unwrap();
+}
@@ -0,0 +1,262 @@
+//! `scan_cache` as a library call: run to completion, cancelled, racing deletions,
+//! overlapping roots, symlinks, walk errors, --limit, reproducible output and streaming.
+
+use aivista_cache_scan::report::FileStatus;
+use aivista_cache_scan::scan::{
+    scan_cache, scan_cache_iter, CancellationToken, ProgressCallback, ProgressEvent, ScanConfig,
+    ScanPhase,
+};
+use aivista_cache_scan::walk::SymlinkPolicy;
+use std::sync::{Arc, Mutex};
+
+fn cache_with_files(n: usize) -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    for i in 0..n {
+        std::fs::write(dir.path().join(format!("f{}.bin", i)), vec![i as u8; 100 + i]).unwrap();
+    }
+    dir
+}
+
+fn config(dir: &tempfile::TempDir) -> ScanConfig {
+    ScanConfig {
+        // the files were only just written
+        incomplete: None,
+        ..ScanConfig::new(dir.path())
+    }
+}
+
+#[test]
+fn scans_every_file() {
+    let dir = cache_with_files(5);
+    let summary = scan_cache(&config(&dir), &CancellationToken::new()).unwrap();
+    assert!(!summary.cancelled);
+    assert_eq!(summary.report.total_files, 5);
+    assert!(summary.report.files.iter().all(|r| r.hash_str.is_some()));
+}
+
+#[test]
+fn cancelled_scan_returns_partial_summary() {
+    let dir = cache_with_files(50);
+    let cancel = CancellationToken::new();
+    cancel.cancel();
+    let summary = scan_cache(&config(&dir), &cancel).unwrap();
+    assert!(summary.cancelled);
+    assert!(summary.report.files.len() < 50);
+    assert_eq!(summary.report.total_files, summary.report.files.len());
+}
+
+#[test]
+fn progress_callback_sees_every_file_and_phase() {
+    let dir = cache_with_files(4);
+    let seen = Arc::new(Mutex::new((Vec::new(), 0usize, 0u64)));
+    let sink = seen.clone();
+    let config = ScanConfig {
+        progress_callback: Some(ProgressCallback::new(move |event| {
+            let mut seen = sink.lock().unwrap();
+            match event {
+                ProgressEvent::PhaseChanged(phase) => seen.0.push(phase),
+                ProgressEvent::FileStarted { .. } => seen.1 += 1,
+                ProgressEvent::FileCompleted { size, .. } => seen.2 += size,
+            }
+        })),
+        ..config(&dir)
+    };
+    scan_cache(&config, &CancellationToken::new()).unwrap();
+    let seen = seen.lock().unwrap();
+    let hashing = ScanPhase::Hashing {
+        files: 4,
+        bytes: 406,
+    };
+    assert_eq!(seen.0, [ScanPhase::Walking, hashing, ScanPhase::Summarising, ScanPhase::Done]);
+    assert_eq!((seen.1, seen.2), (4, 406));
+}
+
+#[test]
+fn file_deleted_after_the_walk_is_vanished_not_errored() {
+    let dir = cache_with_files(3);
+    let config = ScanConfig {
+        // delete f1 just as a worker is about to open it
+        progress_callback: Some(ProgressCallback::new(|event| {
+            if let ProgressEvent::FileStarted { path } = event {
+                if path.ends_with("f1.bin") {
+                    std::fs::remove_file(path).unwrap();
+                }
+            }
+        })),
+        ..config(&dir)
+    };
+    let summary = scan_cache(&config, &CancellationToken::new()).unwrap();
+    let gone = summary.report.files.iter().find(|r| r.path.ends_with("f1.bin")).unwrap();
+    assert_eq!(gone.status, FileStatus::Vanished);
+    assert_eq!(gone.error, None);
+    assert_eq!(summary.report.files.iter().filter(|r| r.hash_str.is_some()).count(), 2);
+}
+
+#[cfg(unix)]
+#[test]
+fn overlapping_roots_count_each_file_once() {
+    let dir = cache_with_files(3);
+    let sub = dir.path().join("sub");
+    std::fs::create_dir(&sub).unwrap();
+    std::fs::write(sub.join("g.bin"), b"inner").unwrap();
+    let outside = tempfile::tempdir().unwrap();
+    let link = outside.path().join("link");
+    std::os::unix::fs::symlink(&sub, &link).unwrap();
+    let roots = vec![dir.path().to_path_buf(), link];
+
+    let config = ScanConfig {
+        roots: roots.clone(),
+        ..config(&dir)
+    };
+    let summary = scan_cache(&config, &CancellationToken::new()).unwrap();
+    assert_eq!(summary.report.total_files, 4);
+
+    let config = ScanConfig {
+        roots,
+        canonicalize: false,
+        ..config
+    };
+    let summary = scan_cache(&config, &CancellationToken::new()).unwrap();
+    assert_eq!(summary.report.total_files, 5);
+}
+
+#[cfg(unix)]
+#[test]
+fn symlink_policy_decides_whether_links_are_hashed() {
+    let dir = cache_with_files(1);
+    let snapshot = dir.path().join("snapshot");
+    std::fs::create_dir(&snapshot).unwrap();
+    std::os::unix::fs::symlink("../f0.bin", snapshot.join("model.bin")).unwrap();
+    let scan = |symlinks, canonicalize| {
+        let config = ScanConfig {
+            symlinks,
+            canonicalize,
+            ..config(&dir)
+        };
+        scan_cache(&config, &CancellationToken::new()).unwrap().report.files
+    };
+
+    assert_eq!(scan(SymlinkPolicy::Skip, false).len(), 1);
+    // followed, the link reaches the blob again; only canonicalizing drops it
+    assert_eq!(scan(SymlinkPolicy::Follow, true).len(), 1);
+    let followed = scan(SymlinkPolicy::Follow, false);
+    assert_eq!(followed.len(), 2);
+    assert_eq!(followed[0].hash_str, followed[1].hash_str);
+
+    let recorded = scan(SymlinkPolicy::Record, true);
+    let link = recorded.iter().find(|r| r.status == FileStatus::Symlink).unwrap();
+    assert_eq!(link.symlink_target.as_deref(), Some(std::path::Path::new("../f0.bin")));
+    assert_eq!(link.hash_str, None);
+    assert_eq!(recorded.len(), 2);
+}
+
+#[cfg(unix)]
+#[test]
+fn unwalkable_paths_are_reported_not_dropped() {
+    let dir = cache_with_files(2);
+    let sub = dir.path().join("sub");
+    std::fs::create_dir(&sub).unwrap();
+    // followed, the link leads back into the directory holding it
+    std::os::unix::fs::symlink("..", sub.join("up")).unwrap();
+    let config = ScanConfig {
+        symlinks: SymlinkPolicy::Follow,
+        ..config(&dir)
+    };
+    let report = scan_cache(&config, &CancellationToken::new()).unwrap().report;
+    assert_eq!(report.total_files, 2);
+    assert_eq!(report.walk_errors.len(), 1);
+    assert_eq!(report.walk_errors[0].path, sub.join("up"));
+    assert!(report.walk_errors[0].error.contains("loop"), "{}", report.walk_errors[0].error);
+}
+
+#[test]
+fn limit_keeps_the_first_files_in_processing_order() {
+    let dir = cache_with_files(10);
+    let config = ScanConfig {
+        limit: Some(3),
+        ..config(&dir)
+    };
+    let report = scan_cache(&config, &CancellationToken::new()).unwrap().report;
+    assert_eq!(report.limited_from, Some(10));
+    // largest first: f9, f8 and f7
+    let mut sizes: Vec<u64> = report.files.iter().map(|r| r.size).collect();
+    sizes.sort();
+    assert_eq!(sizes, [107, 108, 109]);
+}
+
+#[test]
+fn reproducible_scans_serialize_identically() {
+    let dir = tempfile::tempdir().unwrap();
+    // many equal sizes, so order rests on the path tie-break, not on timing
+    for i in 0..64 {
+        let sub = dir.path().join(format!("m{}", i % 4));
+        std::fs::create_dir_all(&sub).unwrap();
+        std::fs::write(sub.join(format!("w{}.bin", i)), vec![i as u8; 4096 * (i % 3)]).unwrap();
+    }
+    let config = ScanConfig {
+        reproducible: true,
+        ..config(&dir)
+    };
+    let run = || {
+        let summary = scan_cache(&config, &CancellationToken::new()).unwrap();
+        serde_json::to_string_pretty(&summary.report).unwrap()
+    };
+    let first = run();
+    for _ in 0..3 {
+        assert_eq!(run(), first);
+    }
+}
+
+#[test]
+fn iterator_streams_every_report_and_stops_when_dropped() {
+    let dir = cache_with_files(20);
+    let iter = scan_cache_iter(&config(&dir), &CancellationToken::new()).unwrap();
+    assert_eq!(iter.files(), 20);
+    let mut paths: Vec<_> = iter.map(|r| r.unwrap().path).collect();
+    paths.sort();
+    paths.dedup();
+    assert_eq!(paths.len(), 20);
+
+    let dir = cache_with_files(200);
+    let mut iter = scan_cache_iter(&config(&dir), &CancellationToken::new()).unwrap();
+    assert!(iter.next().unwrap().is_ok());
+    // the workers see the closed channel and wind down instead of blocking
+    drop(iter);
+}
+
+#[test]
+fn files_are_hashed_in_the_callers_pool() {
+    let dir = cache_with_files(8);
+    let threads = Arc::new(Mutex::new(Vec::new()));
+    let sink = threads.clone();
+    let config = ScanConfig {
+        progress_callback: Some(ProgressCallback::new(move |event| {
+            if let ProgressEvent::FileStarted { .. } = event {
+                let name = std::thread::current().name().map(String::from);
+                sink.lock().unwrap().push(name);
+            }
+        })),
+        ..config(&dir)
+    };
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(2)
+        .thread_name(|i| format!("scan-pool-{}", i))
+        .build()
+        .unwrap();
+    pool.install(|| {
+        scan_cache(&config, &CancellationToken::new()).unwrap();
+        let iter = scan_cache_iter(&config, &CancellationToken::new()).unwrap();
+        assert_eq!(iter.map(Result::unwrap).count(), 8);
+    });
+    let names = std::mem::take(&mut *threads.lock().unwrap());
+    assert_eq!(names.len(), 16);
+    assert!(names.iter().all(|t| t.as_deref().is_some_and(|t| t.starts_with("scan-pool-"))));
+
+    // a caller that is its pool's only thread still gets every report
+    let single = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+    let streamed = single.install(|| {
+        let iter = scan_cache_iter(&config, &CancellationToken::new()).unwrap();
+        iter.map(Result::unwrap).count()
+    });
+    assert_eq!(streamed, 8);
+}
@@ -0,0 +1,124 @@
+//! The `--output` envelope, checked against the schema `schema` prints, and
+//! reports of the old and future schema versions.
+
+use aivista_cache_scan::hashmode::HashAlgorithm;
+use aivista_cache_scan::provenance::Provenance;
+use aivista_cache_scan::report::{report_schema, ScanReport, SCHEMA_VERSION};
+use aivista_cache_scan::scan::{scan_cache, CancellationToken, ScanConfig};
+use aivista_cache_scan::walk::SymlinkPolicy;
+use serde_json::{json, Value};
+
+/// The schema `node` stands for, following a `$ref` into `$defs`.
+fn resolve<'a>(schema: &'a Value, node: &'a Value) -> &'a Value {
+    match node.get("$ref").and_then(Value::as_str) {
+        Some(r) => &schema["$defs"][r.trim_start_matches("#/$defs/")],
+        None => node,
+    }
+}
+
+/// Every key of `value` must be declared and every required key present;
+/// enough to catch a field added to the serde types but not to the schema.
+fn conforms(schema: &Value, node: &Value, value: &Value, at: &str) {
+    let node = resolve(schema, node);
+    // an `Option` field: the value's own schema or null
+    if let Some(alternatives) = node["anyOf"].as_array() {
+        let some = alternatives.iter().find(|n| n["type"] != "null").unwrap();
+        return conforms(schema, some, value, at);
+    }
+    match value {
+        // a map: every value follows one schema, keys are checked by name
+        Value::Object(map) if node.get("additionalProperties").is_some() => {
+            // an enum's names, bare or each with its own description
+            let names = resolve(schema, &node["propertyNames"]);
+            let enumerated = names["enum"].as_array().into_iter().flatten();
+            let described = names["oneOf"].as_array().into_iter().flatten().map(|n| &n["const"]);
+            let names: Vec<&Value> = enumerated.chain(described).collect();
+            for (key, v) in map {
+                assert!(names.contains(&&json!(key)), "{}.{} is not an allowed key", at, key);
+                conforms(schema, &node["additionalProperties"], v, &format!("{}.{}", at, key));
+            }
+        }
+        Value::Object(map) => {
+            let props = node["properties"].as_object().unwrap_or_else(|| panic!("{}", at));
+            for key in map.keys() {
+                assert!(props.contains_key(key), "{}.{} is not in the schema", at, key);
+            }
+            for key in node["required"].as_array().into_iter().flatten() {
+                let key = key.as_str().unwrap();
+                assert!(map.contains_key(key), "{}.{} is required", at, key);
+            }
+            for (key, v) in map {
+                conforms(schema, &props[key], v, &format!("{}.{}", at, key));
+            }
+        }
+        Value::Array(items) if node.get("items").is_some() => {
+            items.iter().for_each(|v| conforms(schema, &node["items"], v, &format!("{}[]", at)))
+        }
+        _ => {}
+    }
+}
+
+#[test]
+fn checked_in_schema_matches_the_report_types() {
+    let generated = serde_json::to_string_pretty(&report_schema()).unwrap() + "\n";
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/schema/report.schema.json");
+    let checked_in = std::fs::read_to_string(path).unwrap();
+    assert!(
+        generated == checked_in,
+        "schema/report.schema.json is out of date; regenerate it with \
+         `cargo run -- schema > schema/report.schema.json`"
+    );
+}
+
+#[test]
+fn scan_document_matches_the_schema() {
+    let dir = tempfile::tempdir().unwrap();
+    for name in ["a.bin", "b.bin"] {
+        std::fs::write(dir.path().join(name), b"same bytes").unwrap();
+    }
+    std::fs::create_dir(dir.path().join("sub")).unwrap();
+    // a link back up gives the walk an error to report
+    #[cfg(unix)]
+    std::os::unix::fs::symlink("..", dir.path().join("sub/up")).unwrap();
+    let config = ScanConfig {
+        incomplete: None,
+        extra_algorithms: vec![HashAlgorithm::Xxh3_128],
+        symlinks: SymlinkPolicy::Follow,
+        canonicalize: false,
+        ..ScanConfig::new(dir.path())
+    };
+    let mut summary = scan_cache(&config, &CancellationToken::new()).unwrap();
+    summary.report.provenance = Some(Provenance::collect(2, false, true));
+    let document = serde_json::to_value(summary.report.document()).unwrap();
+    assert_eq!(document["schema_version"], SCHEMA_VERSION);
+    assert!(!document["summary"]["duplicates"].as_array().unwrap().is_empty());
+    #[cfg(unix)]
+    assert!(!document["summary"]["walk_errors"].as_array().unwrap().is_empty());
+    assert!(document["summary"].get("files").is_none());
+    assert_eq!(document["files"][0]["hashes"].as_object().unwrap().len(), 2);
+    assert_eq!(document["summary"]["provenance"]["threads"], 2);
+
+    let schema = report_schema();
+    assert_eq!(schema["properties"]["schema_version"]["const"], SCHEMA_VERSION);
+    conforms(&schema, &schema, &document, "$");
+
+    let back = ScanReport::from_json(document).unwrap();
+    assert_eq!(back.files.len(), summary.report.files.len());
+}
+
+#[test]
+fn reads_flat_reports_and_refuses_newer_ones() {
+    let flat = json!({
+        "cache": "/c",
+        "total_files": 1,
+        "total_bytes": 3,
+        "files": [{"path": "/c/a", "size": 3, "hash_hex": "ab", "xor64": null,
+                   "xor64_source": null, "elapsed_ms": 0}]
+    });
+    let report = ScanReport::from_json(flat).unwrap();
+    assert_eq!(report.files[0].hash_str.as_deref(), Some("ab"));
+
+    let future = json!({"schema_version": SCHEMA_VERSION + 1, "summary": {}, "files": []});
+    let err = ScanReport::from_json(future).unwrap_err().to_string();
+    assert!(err.contains("newer"), "{}", err);
+}
@@ -0,0 +1,15 @@
+//! The `selftest` fixture must pass its own checks, so a change that breaks
+//! the walk, hashing or aggregation fails here as well as in the field.
+
+use aivista_cache_scan::selftest::{create_fixture, run};
+
+#[test]
+fn fixture_passes_every_check() {
+    let parent = tempfile::tempdir().unwrap();
+    let dir = parent.path().join("fixture");
+    create_fixture(&dir).unwrap();
+    for check in run(&dir).unwrap() {
+        let (name, expected, actual) = (check.name, &check.expected, &check.actual);
+        assert!(check.passed(), "{}: expected {:?}, got {:?}", name, expected, actual);
+    }
+}
@@ -0,0 +1,156 @@
+//! Sinks write through any `Write`, so their output can be captured here.
+
+use aivista_cache_scan::dupes::ReclaimSummary;
+use aivista_cache_scan::layout::Layout;
+use aivista_cache_scan::report::{FileReport, FileStatus, ScanReport};
+use aivista_cache_scan::sink::{
+    CsvSink, HashPathSink, JsonSink, NdjsonSink, ReportSink, ScanSummary, Tee,
+};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Captured {
+    fn text(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+fn file(path: &str, status: FileStatus, error: Option<&str>) -> FileReport {
+    FileReport {
+        hash_str: (status == FileStatus::Hashed).then(|| "ab".repeat(32)),
+        elapsed_ms: 1,
+        status,
+        error: error.map(str::to_string),
+        ..FileReport::new(path, 3)
+    }
+}
+
+fn summary(files: Vec<FileReport>) -> ScanSummary {
+    ScanSummary {
+        report: ScanReport {
+            cache: PathBuf::from("cache"),
+            roots: Vec::new(),
+            total_files: files.len(),
+            total_bytes: files.iter().map(|f| f.size).sum(),
+            hash_algorithm: Default::default(),
+            hash_encoding: Default::default(),
+            files,
+            models: Vec::new(),
+            orphans: Vec::new(),
+            duplicates: Vec::new(),
+            symlink_issues: Vec::new(),
+            size_collisions: Vec::new(),
+            walk_errors: Vec::new(),
+            hardlinks: Vec::new(),
+            real_bytes: None,
+            sample: None,
+            limited_from: None,
+            provenance: None,
+        },
+        reclaim: ReclaimSummary::default(),
+        layout: Layout::Raw,
+        cancelled: false,
+        tally: None,
+    }
+}
+
+#[test]
+fn csv_quotes_awkward_fields() {
+    let out = Captured::default();
+    let mut sink = CsvSink::new(Box::new(out.clone()));
+    let files = vec![
+        file("a,b.bin", FileStatus::Hashed, None),
+        file("gone", FileStatus::Errored, Some("said \"no\"")),
+    ];
+    sink.finish(&summary(files)).unwrap();
+    let text = out.text();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines[0], "path,size,status,hash,signature,xor64,xor64_source,elapsed_ms,error");
+    assert_eq!(lines[1], format!("\"a,b.bin\",3,hashed,{},,,,1,", "ab".repeat(32)));
+    assert_eq!(lines[2], "gone,3,errored,,,,,1,\"said \"\"no\"\"\"");
+}
+
+#[test]
+fn ndjson_streams_each_report_on_emit() {
+    let out = Captured::default();
+    let mut sink = NdjsonSink::new(Box::new(out.clone()));
+    assert!(sink.streams());
+    sink.emit(&file("x", FileStatus::Hashed, None)).unwrap();
+    assert_eq!(out.text().lines().count(), 1);
+    sink.emit(&file("y", FileStatus::Skipped, None)).unwrap();
+    sink.finish(&summary(Vec::new())).unwrap();
+    let parsed: Vec<FileReport> = out
+        .text()
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(parsed[1].path, PathBuf::from("y"));
+    assert_eq!(parsed[1].status, FileStatus::Skipped);
+}
+
+#[test]
+fn hash_path_writes_one_line_per_hashed_file_as_it_arrives() {
+    let out = Captured::default();
+    let mut sink = HashPathSink::new(Box::new(out.clone()));
+    assert!(sink.streams());
+    sink.emit(&file("b/x.bin", FileStatus::Hashed, None)).unwrap();
+    assert_eq!(out.text(), format!("{}\tb/x.bin\n", "ab".repeat(32)));
+    sink.emit(&file("gone", FileStatus::Errored, Some("no"))).unwrap();
+    sink.emit(&file("tab\there\\", FileStatus::Hashed, None)).unwrap();
+    sink.finish(&summary(Vec::new())).unwrap();
+    let lines: Vec<String> = out.text().lines().map(String::from).collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[1], format!("{}\ttab\\there\\\\", "ab".repeat(32)));
+}
+
+#[test]
+fn ndjson_in_report_order_waits_for_the_summary() {
+    let out = Captured::default();
+    let mut sink = NdjsonSink::new(Box::new(out.clone())).in_report_order();
+    assert!(!sink.streams());
+    let files = vec![file("a", FileStatus::Hashed, None), file("b", FileStatus::Hashed, None)];
+    sink.emit(&files[1]).unwrap();
+    sink.emit(&files[0]).unwrap();
+    assert!(out.text().is_empty());
+    sink.finish(&summary(files)).unwrap();
+    let paths: Vec<PathBuf> = out
+        .text()
+        .lines()
+        .map(|l| serde_json::from_str::<FileReport>(l).unwrap().path)
+        .collect();
+    assert_eq!(paths, [PathBuf::from("a"), PathBuf::from("b")]);
+}
+
+#[test]
+fn tee_keeps_each_sink_output_whole() {
+    let (stdout, report) = (Captured::default(), Captured::default());
+    let mut tee = Tee(vec![
+        Box::new(NdjsonSink::new(Box::new(stdout.clone()))),
+        Box::new(JsonSink::new(Box::new(report.clone()), "report")),
+    ]);
+    assert!(tee.streams());
+    assert!(!Tee(vec![Box::new(JsonSink::new(Box::new(report.clone()), "report"))]).streams());
+    let files = vec![file("x", FileStatus::Hashed, None), file("y", FileStatus::Hashed, None)];
+    for f in &files {
+        tee.emit(f).unwrap();
+    }
+    tee.finish(&summary(files)).unwrap();
+    assert_eq!(stdout.text().lines().count(), 2);
+    let parsed = ScanReport::from_reader(report.text().as_bytes()).unwrap();
+    assert_eq!(parsed.files.len(), 2);
+}
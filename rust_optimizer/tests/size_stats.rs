@@ -0,0 +1,23 @@
+//! `--stats` percentiles are nearest-rank, so small scans report real sizes.
+
+use aivista_cache_scan::report::SizeStats;
+
+#[test]
+fn empty_and_single_file() {
+    assert_eq!(SizeStats::of(Vec::new()), None);
+    let one = SizeStats::of(vec![42]).unwrap();
+    assert_eq!((one.min, one.p50, one.p90, one.p99, one.max), (42, 42, 42, 42, 42));
+    assert_eq!(one.mean, 42.0);
+}
+
+#[test]
+fn percentiles_use_nearest_rank() {
+    // 1..=100, shuffled
+    let sizes: Vec<u64> = (0..100u64).map(|i| i * 37 % 100 + 1).collect();
+    let s = SizeStats::of(sizes).unwrap();
+    assert_eq!((s.min, s.p50, s.p90, s.p99, s.max), (1, 50, 90, 99, 100));
+    assert_eq!(s.mean, 50.5);
+
+    let s = SizeStats::of(vec![10, 1000, 20]).unwrap();
+    assert_eq!((s.p50, s.p90, s.p99), (20, 1000, 1000));
+}
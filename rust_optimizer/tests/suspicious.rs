@@ -0,0 +1,39 @@
+//! Failed downloads: empty, stub-sized and truncated safetensors files.
+
+use aivista_cache_scan::suspicious::check;
+use std::path::Path;
+
+/// A safetensors file whose one tensor claims `data_len` bytes, holding `have` of them.
+fn safetensors(dir: &Path, data_len: u64, have: usize) -> std::path::PathBuf {
+    let header = format!(
+        r#"{{"__metadata__":{{}},"w":{{"dtype":"U8","shape":[{}],"data_offsets":[0,{}]}}}}"#,
+        data_len, data_len
+    );
+    let mut bytes = (header.len() as u64).to_le_bytes().to_vec();
+    bytes.extend_from_slice(header.as_bytes());
+    bytes.extend(std::iter::repeat_n(7u8, have));
+    let path = dir.join(format!("model-{}-{}.safetensors", data_len, have));
+    std::fs::write(&path, bytes).unwrap();
+    path
+}
+
+fn size(path: &Path) -> u64 {
+    path.metadata().unwrap().len()
+}
+
+#[test]
+fn flags_truncated_safetensors() {
+    let dir = tempfile::tempdir().unwrap();
+    let whole = safetensors(dir.path(), 4096, 4096);
+    assert_eq!(check(&whole, size(&whole)), None);
+    let cut = safetensors(dir.path(), 4096, 3000);
+    let reason = check(&cut, size(&cut)).unwrap();
+    assert!(reason.starts_with("truncated"), "{}", reason);
+}
+
+#[test]
+fn flags_empty_and_stub_files() {
+    assert_eq!(check(Path::new("x/config.json"), 0).as_deref(), Some("empty file"));
+    assert!(check(Path::new("x/pytorch_model.bin"), 130).is_some());
+    assert_eq!(check(Path::new("x/config.json"), 130), None);
+}
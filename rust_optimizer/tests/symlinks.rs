@@ -0,0 +1,91 @@
+//! Broken HF snapshot links: repointed at the repo's blob only when that
+//! blob's content has the hash the link names.
+#![cfg(unix)]
+
+use aivista_cache_scan::symlinks::{apply_fix, check_symlinks, LinkProblem};
+use aivista_cache_scan::walk::Symlink;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+
+const WEIGHTS: &[u8] = b"safetensors bytes";
+
+fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// A link at `link` (relative to the root) pointing into a cache that moved.
+fn dangling(root: &Path, link: &str, blob: &str) -> Symlink {
+    let link = root.join(link);
+    fs::create_dir_all(link.parent().unwrap()).unwrap();
+    symlink(Path::new("/old/cache/models--org--m/blobs").join(blob), &link).unwrap();
+    Symlink { link, target: None }
+}
+
+#[test]
+fn broken_link_is_repointed_at_the_blob_with_its_hash() {
+    let root = tempfile::tempdir().unwrap();
+    let blob = sha256_hex(WEIGHTS);
+    fs::create_dir_all(root.path().join("models--org--m/blobs")).unwrap();
+    fs::write(root.path().join("models--org--m/blobs").join(&blob), WEIGHTS).unwrap();
+    let link = dangling(root.path(), "models--org--m/snapshots/rev/model.safetensors", &blob);
+
+    let issues = check_symlinks(root.path(), std::slice::from_ref(&link));
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].problem, LinkProblem::Broken);
+    let fix: PathBuf = ["..", "..", "blobs", &blob].iter().collect();
+    assert_eq!(issues[0].fix.as_ref(), Some(&fix));
+
+    apply_fix(&issues[0]).unwrap();
+    assert_eq!(fs::read_link(&link.link).unwrap(), fix);
+    assert_eq!(fs::read(&link.link).unwrap(), WEIGHTS);
+    let names: Vec<_> = fs::read_dir(link.link.parent().unwrap()).unwrap().collect();
+    assert_eq!(names.len(), 1, "temporary link left behind");
+}
+
+#[test]
+fn blob_whose_content_does_not_match_its_name_is_no_fix() {
+    let root = tempfile::tempdir().unwrap();
+    let blob = sha256_hex(WEIGHTS);
+    fs::create_dir_all(root.path().join("models--org--m/blobs")).unwrap();
+    fs::write(root.path().join("models--org--m/blobs").join(&blob), b"truncated").unwrap();
+    let link = dangling(root.path(), "models--org--m/snapshots/rev/model.safetensors", &blob);
+
+    let issues = check_symlinks(root.path(), &[link]);
+    assert_eq!(issues[0].fix, None);
+    assert!(apply_fix(&issues[0]).is_err());
+}
+
+#[test]
+fn git_blob_names_are_checked_as_sha1() {
+    let root = tempfile::tempdir().unwrap();
+    let config = b"{\"hidden\": 4}\n";
+    let mut sha = sha1_smol::Sha1::new();
+    sha.update(format!("blob {}\0", config.len()).as_bytes());
+    sha.update(config);
+    let blob = sha.digest().to_string();
+    fs::create_dir_all(root.path().join("models--org--m/blobs")).unwrap();
+    fs::write(root.path().join("models--org--m/blobs").join(&blob), config).unwrap();
+    let link = dangling(root.path(), "models--org--m/snapshots/rev/config.json", &blob);
+
+    let issues = check_symlinks(root.path(), &[link]);
+    assert!(issues[0].fix.is_some());
+}
+
+#[test]
+fn links_at_the_repo_level_do_not_underflow() {
+    let root = tempfile::tempdir().unwrap();
+    let blob = sha256_hex(WEIGHTS);
+    fs::create_dir_all(root.path().join("models--org--m/blobs")).unwrap();
+    fs::write(root.path().join("models--org--m/blobs").join(&blob), WEIGHTS).unwrap();
+    // a repo directory that is itself a dangling link
+    let repo = dangling(root.path(), "models--org--gone", &blob);
+    // a link directly inside the repo, beside blobs/
+    let stray = dangling(root.path(), "models--org--m/stray", &blob);
+
+    let issues = check_symlinks(root.path(), &[repo, stray]);
+    assert_eq!(issues.len(), 2);
+    assert_eq!(issues[0].fix, None);
+    assert_eq!(issues[1].fix, Some(Path::new("blobs").join(&blob)));
+}
@@ -0,0 +1,110 @@
+//! The streaming tally must print what a sort of every report would.
+
+use aivista_cache_scan::report::{FileReport, FileStatus, ReportOrder, SortKey};
+use aivista_cache_scan::tally::{BusyTime, Tally, FIRST_FILES};
+use std::path::{Path, PathBuf};
+
+fn file(i: u64) -> FileReport {
+    FileReport {
+        elapsed_ms: (i as u128 * 104_729) % 53,
+        status: if i.is_multiple_of(10) { FileStatus::Errored } else { FileStatus::Warmed },
+        // sizes repeat so ties fall back to path order
+        ..FileReport::new(format!("f{:03}.bin", i), (i * 7919) % 37)
+    }
+}
+
+#[test]
+fn top_n_matches_a_full_sort() {
+    let reports: Vec<FileReport> = (0..200).map(file).collect();
+    for order in [
+        ReportOrder::new(None, true),
+        ReportOrder::new(Some(SortKey::Path), false),
+        ReportOrder::new(Some(SortKey::Elapsed), true),
+    ] {
+        let tally = Tally::of(&reports, order, 5, false);
+        let mut sorted = reports.clone();
+        order.sort(&mut sorted);
+        let first: Vec<_> = tally.first.sorted().into_iter().map(|r| &r.path).collect();
+        let expected: Vec<_> = sorted.iter().take(FIRST_FILES).map(|r| &r.path).collect();
+        assert_eq!(first, expected);
+    }
+    let tally = Tally::of(&reports, ReportOrder::new(None, true), 5, true);
+    let mut by_time = reports.clone();
+    ReportOrder::new(Some(SortKey::Elapsed), true).sort(&mut by_time);
+    let slowest: Vec<_> = tally.slowest.sorted().into_iter().map(|r| &r.path).collect();
+    let expected: Vec<_> = by_time.iter().take(5).map(|r| &r.path).collect();
+    assert_eq!(slowest, expected);
+
+    assert_eq!(tally.files, 200);
+    assert_eq!(tally.errored, 20);
+    assert_eq!(tally.warmed_files, 180);
+    assert_eq!(tally.bytes, reports.iter().map(|r| r.size as u128).sum::<u128>());
+    assert_eq!(tally.size_stats().unwrap().files, 200);
+}
+
+#[test]
+fn overlapping_files_on_a_mount_count_their_time_once() {
+    let mut tally = Tally::new(ReportOrder::new(None, true), 0, false);
+    let start = std::time::Instant::now();
+    let at = |ms| start + std::time::Duration::from_millis(ms);
+    // two workers on /fast for 100..200 and 150..300, one on /slow
+    for (i, mount, elapsed, finished) in
+        [(0, "/fast", 100, 200), (1, "/fast", 150, 300), (2, "/slow", 400, 400)]
+    {
+        let mut r = file(i);
+        r.size = 1000;
+        r.elapsed_ms = elapsed;
+        r.mount = Some(PathBuf::from(mount));
+        tally.add_finished(&r, at(finished));
+    }
+    let mounts = tally.mount_totals();
+    let fast = mounts.iter().find(|t| t.mount == Path::new("/fast")).unwrap();
+    let slow = mounts.iter().find(|t| t.mount == Path::new("/slow")).unwrap();
+    assert_eq!(fast.busy_ms, Some(200));
+    assert_eq!(fast.rate(), Some(10_000));
+    assert_eq!(slow.busy_ms, Some(400));
+    assert_eq!(slow.rate(), Some(2_500));
+}
+
+#[test]
+fn busy_time_merges_spans_as_they_arrive() {
+    let mut busy = BusyTime::default();
+    // a thousand back-to-back files, a millisecond apart at most, out of order
+    for i in (0..1000u64).rev() {
+        busy.insert(i * 10 + i % 2, i * 10 + 10);
+    }
+    assert_eq!((busy.spans(), busy.total()), (1, 10_000));
+
+    busy.insert(20_000, 20_050);
+    busy.insert(20_100, 20_200);
+    assert_eq!((busy.spans(), busy.total()), (3, 10_150));
+    // one long file covers the gap and everything inside it
+    busy.insert(9_000, 20_150);
+    assert_eq!((busy.spans(), busy.total()), (1, 20_200));
+}
+
+#[test]
+fn merged_shards_match_one_tally() {
+    let reports: Vec<FileReport> = (0..200).map(file).collect();
+    let order = ReportOrder::new(Some(SortKey::Size), true);
+    let whole = Tally::of(&reports, order, 5, true);
+
+    // reports dealt unevenly across three workers, the way rayon might
+    let mut shards = [0, 1, 2].map(|_| Tally::new(order, 5, true));
+    for (i, r) in reports.iter().enumerate() {
+        shards[(i * i) % 3].add(r);
+    }
+    let [mut merged, b, c] = shards;
+    merged.merge(b);
+    merged.merge(c);
+
+    assert_eq!(merged.files, whole.files);
+    assert_eq!(merged.bytes, whole.bytes);
+    assert_eq!(merged.errored, whole.errored);
+    assert_eq!(merged.extension_totals(), whole.extension_totals());
+    assert_eq!(merged.size_stats(), whole.size_stats());
+    let paths = |t: &Tally| -> Vec<PathBuf> {
+        t.first.sorted().into_iter().chain(t.slowest.sorted()).map(|r| r.path.clone()).collect()
+    };
+    assert_eq!(paths(&merged), paths(&whole));
+}
@@ -0,0 +1,26 @@
+//! Directory sizes summed up the tree and cut off at the depth limit.
+
+use aivista_cache_scan::report::Units;
+use aivista_cache_scan::tree::SizeTree;
+use std::path::{Path, PathBuf};
+
+#[test]
+fn sums_up_the_hierarchy_largest_first() {
+    let mut tree = SizeTree::new(&[PathBuf::from("/c")], 2);
+    tree.add(Path::new("/c/models--a/blobs/x"), 600);
+    tree.add(Path::new("/c/models--a/snapshots/r1/deep/y"), 200);
+    tree.add(Path::new("/c/models--b/blobs/z"), 1000);
+    tree.add(Path::new("/c/README"), 200);
+    // outside every root
+    tree.add(Path::new("/elsewhere/w"), 5);
+    let lines = tree.render(Units::Si.into());
+    let expected = [
+        "      2 kB         /c (4 files)",
+        "      1 kB  50.0%  ├── models--b",
+        "      1 kB 100.0%  │   └── blobs",
+        "     800 B  40.0%  └── models--a",
+        "     600 B  75.0%      ├── blobs",
+        "     200 B  25.0%      └── snapshots",
+    ];
+    assert_eq!(lines, expected);
+}
@@ -0,0 +1,38 @@
+//! Size formatting must switch units exactly at the base, with labels that
+//! match it, and print only the decimals that carry information.
+
+use aivista_cache_scan::report::{human_bytes, SizeFormat, Units};
+
+#[test]
+fn iec_steps_at_1024() {
+    let iec = Units::Iec.into();
+    assert_eq!(human_bytes(999, iec), "999 B");
+    assert_eq!(human_bytes(1000, iec), "1000 B");
+    assert_eq!(human_bytes(1023, iec), "1023 B");
+    assert_eq!(human_bytes(1024, iec), "1 KiB");
+    assert_eq!(human_bytes(3 << 30, iec), "3 GiB");
+}
+
+#[test]
+fn si_steps_at_1000() {
+    let si = Units::Si.into();
+    assert_eq!(human_bytes(999, si), "999 B");
+    assert_eq!(human_bytes(1000, si), "1 kB");
+    assert_eq!(human_bytes(1023, si), "1.02 kB");
+    assert_eq!(human_bytes(1024, si), "1.02 kB");
+    assert_eq!(human_bytes(1_000_000, si), "1 MB");
+}
+
+#[test]
+fn exact_values_drop_zeros_and_rounded_ones_keep_them() {
+    let two = SizeFormat::default();
+    assert_eq!(human_bytes(1536, two), "1.5 KiB");
+    assert_eq!(human_bytes(1537, two), "1.50 KiB");
+    assert_eq!(human_bytes((4 << 30) + 1, two), "4.00 GiB");
+    assert_eq!(human_bytes(1_100_000, SizeFormat::new(Units::Si, 2)), "1.1 MB");
+
+    assert_eq!(human_bytes(1_234_567, SizeFormat::new(Units::Si, 3)), "1.235 MB");
+    assert_eq!(human_bytes(1_234_567, SizeFormat::new(Units::Si, 0)), "1 MB");
+    assert_eq!(human_bytes(1536, SizeFormat::new(Units::Iec, 0)), "2 KiB");
+    assert_eq!(human_bytes(1536, SizeFormat::new(Units::Iec, 4)), "1.5 KiB");
+}
@@ -0,0 +1,59 @@
+//! The watch index and debouncer, without a live watcher.
+
+use aivista_cache_scan::process::{process_file, ProcessOptions};
+use aivista_cache_scan::watch::{Change, Debouncer, WatchIndex};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+#[test]
+fn debouncer_waits_for_a_quiet_path() {
+    let start = Instant::now();
+    let quiet = Duration::from_millis(100);
+    let mut pending = Debouncer::new(quiet);
+    pending.touch(PathBuf::from("b"), start);
+    pending.touch(PathBuf::from("a"), start);
+    // a burst keeps pushing `a` back
+    pending.touch(PathBuf::from("a"), start + Duration::from_millis(80));
+    assert!(pending.due(start + Duration::from_millis(50)).is_empty());
+    assert_eq!(pending.next_due(start + Duration::from_millis(50)), Some(quiet / 2));
+    assert_eq!(pending.due(start + quiet), vec![PathBuf::from("b")]);
+    assert_eq!(pending.due(start + Duration::from_millis(180)), vec![PathBuf::from("a")]);
+    assert_eq!(pending.next_due(start), None);
+}
+
+#[test]
+fn index_reports_additions_changes_and_removals() {
+    let dir = tempfile::tempdir().unwrap();
+    let opts = ProcessOptions::default();
+    let kept = dir.path().join("kept.bin");
+    std::fs::write(&kept, b"same").unwrap();
+    let mut index = WatchIndex::from_reports(&[process_file(&kept, &opts).unwrap()]);
+
+    // a touch that leaves the contents alone is no change
+    assert_eq!(index.refresh_file(&kept, &opts), None);
+
+    std::fs::write(&kept, b"different").unwrap();
+    match index.refresh_file(&kept, &opts) {
+        Some(Change::Changed { old_size: 4, size: 9, old_hash, hash, .. }) => {
+            assert_ne!(old_hash, hash)
+        }
+        other => panic!("expected a change, got {:?}", other),
+    }
+
+    let sub = dir.path().join("sub");
+    std::fs::create_dir(&sub).unwrap();
+    std::fs::write(sub.join("new.bin"), b"new").unwrap();
+    assert!(matches!(
+        index.refresh_file(&sub.join("new.bin"), &opts),
+        Some(Change::Added { size: 3, hash: Some(_), .. })
+    ));
+    assert_eq!(index.len(), 2);
+
+    std::fs::remove_dir_all(&sub).unwrap();
+    let removed = index.remove_under(&sub);
+    assert_eq!(removed, vec![Change::Removed { path: sub.join("new.bin"), size: 3 }]);
+    // a file deleted before its re-hash reads as removed, not as an error
+    std::fs::remove_file(&kept).unwrap();
+    assert!(matches!(index.refresh_file(&kept, &opts), Some(Change::Removed { .. })));
+    assert!(index.is_empty());
+}
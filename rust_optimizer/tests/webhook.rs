@@ -0,0 +1,104 @@
+//! Webhook delivery: server errors are retried with backoff, up to
+//! `ATTEMPTS` tries; client errors are not.
+
+use aivista_cache_scan::dupes::ReclaimSummary;
+use aivista_cache_scan::layout::Layout;
+use aivista_cache_scan::report::ScanReport;
+use aivista_cache_scan::sink::{ReportSink, ScanSummary};
+use aivista_cache_scan::webhook::{WebhookSink, ATTEMPTS};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Answer one POST per status line in `statuses`, reading each request
+/// whole first; the join handle gives back how many arrived.
+fn serve(statuses: Vec<&'static str>) -> (String, JoinHandle<usize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let handle = std::thread::spawn(move || {
+        let mut served = 0;
+        for status in statuses {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+            reader.read_exact(&mut vec![0; length]).unwrap();
+            write!(stream, "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status)
+                .unwrap();
+            served += 1;
+        }
+        served
+    });
+    (url, handle)
+}
+
+fn summary() -> ScanSummary {
+    ScanSummary {
+        report: ScanReport {
+            cache: "cache".into(),
+            roots: Vec::new(),
+            total_files: 0,
+            total_bytes: 0,
+            hash_algorithm: Default::default(),
+            hash_encoding: Default::default(),
+            files: Vec::new(),
+            models: Vec::new(),
+            orphans: Vec::new(),
+            duplicates: Vec::new(),
+            symlink_issues: Vec::new(),
+            size_collisions: Vec::new(),
+            walk_errors: Vec::new(),
+            hardlinks: Vec::new(),
+            real_bytes: None,
+            sample: None,
+            limited_from: None,
+            provenance: None,
+        },
+        reclaim: ReclaimSummary::default(),
+        layout: Layout::Raw,
+        cancelled: false,
+        tally: None,
+    }
+}
+
+fn sink(url: &str) -> WebhookSink {
+    WebhookSink::new(url, &[], true).unwrap().with_backoff(Duration::from_millis(20))
+}
+
+#[test]
+fn server_errors_are_retried_until_one_succeeds() {
+    let (url, server) =
+        serve(vec!["503 Service Unavailable", "500 Internal Server Error", "200 OK"]);
+    let start = Instant::now();
+    sink(&url).finish(&summary()).unwrap();
+    assert_eq!(server.join().unwrap(), 3);
+    // 20 ms, then 40 ms
+    assert!(start.elapsed() >= Duration::from_millis(60));
+}
+
+#[test]
+fn delivery_gives_up_after_every_attempt_fails() {
+    let (url, server) = serve(vec!["503 Service Unavailable"; ATTEMPTS as usize]);
+    let err = sink(&url).finish(&summary()).unwrap_err();
+    assert_eq!(server.join().unwrap(), ATTEMPTS as usize);
+    assert!(format!("{:#}", err).contains(&format!("giving up after {} attempts", ATTEMPTS)));
+}
+
+#[test]
+fn client_errors_are_not_retried() {
+    let (url, server) = serve(vec!["401 Unauthorized"]);
+    assert!(sink(&url).finish(&summary()).is_err());
+    assert_eq!(server.join().unwrap(), 1);
+}
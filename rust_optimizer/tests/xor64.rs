@@ -0,0 +1,34 @@
+//! Pins the host-side u64 packing shared by the GPU upload and the CPU reference.
+
+use aivista_cache_scan::xor64::{pack_u64_le, xor64_cpu};
+
+const LENGTHS: [usize; 7] = [0, 1, 7, 8, 9, 15, 16];
+
+#[test]
+fn every_byte_lands_in_exactly_one_word() {
+    for len in LENGTHS {
+        assert_eq!(pack_u64_le(&vec![0u8; len]).len(), len.div_ceil(8), "len {}", len);
+        for i in 0..len {
+            // a single marked byte must show up once, in word i/8 at lane i%8
+            let mut bytes = vec![0u8; len];
+            bytes[i] = 0xA5;
+            let words = pack_u64_le(&bytes);
+            for (w, word) in words.iter().enumerate() {
+                let expected = if w == i / 8 { 0xA5u64 << (8 * (i % 8)) } else { 0 };
+                assert_eq!(*word, expected, "len {} byte {} word {}", len, i, w);
+            }
+        }
+    }
+}
+
+#[test]
+fn cpu_reduction_matches_packed_words() {
+    for len in LENGTHS {
+        let bytes: Vec<u8> = (1..=len as u8).collect();
+        let folded = pack_u64_le(&bytes).into_iter().fold(0, |acc, w| acc ^ w);
+        assert_eq!(xor64_cpu(&bytes), folded, "len {}", len);
+    }
+    assert_eq!(xor64_cpu(&[]), 0);
+    assert_eq!(xor64_cpu(&[0x01]), 0x01);
+    assert_eq!(xor64_cpu(&[0, 0, 0, 0, 0, 0, 0, 0, 0x02]), 0x02);
+}